@@ -1,9 +1,40 @@
 use crate::prelude::*;
 
 #[derive(serde::Deserialize, Default, Debug)]
+#[serde(default)]
 pub struct Config {
     aliases:
         std::collections::HashMap<std::path::PathBuf, crate::parse::ast::Exe>,
+    rewrites: Vec<Rewrite>,
+    named_dirs: std::collections::HashMap<String, std::path::PathBuf>,
+    correct_paths: bool,
+    autocd: bool,
+    pipefail: bool,
+    runner: Option<std::path::PathBuf>,
+    check_for_updates: bool,
+    greeting: Option<String>,
+    no_greeting: bool,
+    max_fps: Option<u32>,
+    max_output_memory: Option<usize>,
+    placeholder: Option<String>,
+    scrollback: Option<usize>,
+    history_sync: Option<std::path::PathBuf>,
+    locale: Option<String>,
+    time_format: Option<String>,
+    date_format: Option<String>,
+    twelve_hour: bool,
+    duration_format: crate::format::DurationFormat,
+    theme: crate::shell::Theme,
+    color_scheme: crate::shell::ColorScheme,
+    cache_daemon: bool,
+    status_bar: bool,
+    notify_after: Option<u64>,
+    notify_method: crate::shell::NotifyMethod,
+    prompt: PromptTemplates,
+    messages: std::collections::HashMap<
+        String,
+        std::collections::HashMap<String, String>,
+    >,
 }
 
 impl Config {
@@ -22,4 +53,179 @@ impl Config {
     ) -> Option<&crate::parse::ast::Exe> {
         self.aliases.get(path)
     }
+
+    pub fn rewrites(&self) -> &[Rewrite] {
+        &self.rewrites
+    }
+
+    pub fn runner(&self) -> Option<&std::path::Path> {
+        self.runner.as_deref()
+    }
+
+    pub fn check_for_updates(&self) -> bool {
+        self.check_for_updates
+    }
+
+    // None means the greeting should not be shown at all, Some(None) means
+    // the default greeting should be used
+    pub fn greeting(&self) -> Option<Option<&str>> {
+        if self.no_greeting {
+            None
+        } else {
+            Some(self.greeting.as_deref())
+        }
+    }
+
+    pub fn frame_duration(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(1) / self.max_fps.unwrap_or(60).max(1)
+    }
+
+    // in bytes
+    pub fn max_output_memory(&self) -> Option<usize> {
+        self.max_output_memory
+    }
+
+    // shown in the readline when it is empty. setting it to an empty string
+    // disables it.
+    pub fn placeholder(&self) -> String {
+        self.placeholder.clone().unwrap_or_else(|| {
+            crate::msg::get(crate::msg::Message::Placeholder, &[])
+        })
+    }
+
+    // in lines, for each entry
+    pub fn scrollback(&self) -> usize {
+        self.scrollback.unwrap_or(1000)
+    }
+
+    // a directory (usually a git checkout) to share the history through
+    pub fn history_sync(&self) -> Option<&std::path::Path> {
+        self.history_sync.as_deref()
+    }
+
+    // see shell::prompt for the template format
+    pub fn prompt_left(&self) -> Option<&str> {
+        self.prompt.left.as_deref()
+    }
+
+    pub fn prompt_right(&self) -> Option<&str> {
+        self.prompt.right.as_deref()
+    }
+
+    // share git status and the like with other nbsh instances through a
+    // background process
+    pub fn cache_daemon(&self) -> bool {
+        self.cache_daemon
+    }
+
+    // an extra row above the input showing things like whether an escape
+    // is pending, which would otherwise be invisible
+    pub fn status_bar(&self) -> bool {
+        self.status_bar
+    }
+
+    // in seconds. commands which take at least this long send a desktop
+    // notification when they finish while the terminal isn't focused.
+    pub fn notify_after(&self) -> Option<std::time::Duration> {
+        self.notify_after.map(std::time::Duration::from_secs)
+    }
+
+    pub fn notify_method(&self) -> crate::shell::NotifyMethod {
+        self.notify_method
+    }
+
+    // strftime-like, see format::strftime for the supported specifiers
+    pub fn time_format(&self) -> Option<&str> {
+        self.time_format.as_deref()
+    }
+
+    // shown in front of the time for things from a previous day
+    pub fn date_format(&self) -> Option<&str> {
+        self.date_format.as_deref()
+    }
+
+    // only used when time_format isn't set
+    pub fn twelve_hour(&self) -> bool {
+        self.twelve_hour
+    }
+
+    pub fn duration_format(&self) -> crate::format::DurationFormat {
+        self.duration_format
+    }
+
+    pub fn theme(&self) -> crate::shell::Theme {
+        self.theme
+    }
+
+    // the scheme to start with, it can be changed later with the colors
+    // builtin
+    pub fn color_scheme(&self) -> crate::shell::ColorScheme {
+        self.color_scheme
+    }
+
+    // overrides the locale from the environment
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    // translated messages for a locale, keyed by message name
+    pub fn messages(
+        &self,
+        locale: &str,
+    ) -> Option<&std::collections::HashMap<String, String>> {
+        self.messages.get(locale)
+    }
+
+    pub fn correct_paths(&self) -> bool {
+        self.correct_paths
+    }
+
+    // typing just the name of a directory changes into it
+    pub fn autocd(&self) -> bool {
+        self.autocd
+    }
+
+    // a pipeline fails if any part of it fails, not just the last command
+    pub fn pipefail(&self) -> bool {
+        self.pipefail
+    }
+
+    // named directories set at runtime (via hash -d) take precedence over
+    // the ones from the config file
+    pub fn export_named_dirs(&self) {
+        for (name, dir) in &self.named_dirs {
+            let var = Env::named_dir_var(name);
+            if std::env::var_os(&var).is_none() {
+                std::env::set_var(var, dir);
+            }
+        }
+    }
+}
+
+// commands which start with `when` have that part replaced with `run`, for
+// instance to run `pacman -S` under sudo. unlike aliases, these can match on
+// arguments as well as the executable. running a command via the command
+// builtin skips both.
+#[derive(serde::Deserialize, Debug)]
+pub struct Rewrite {
+    when: crate::parse::ast::Exe,
+    run: crate::parse::ast::Exe,
+}
+
+impl Rewrite {
+    pub fn when(&self) -> &crate::parse::ast::Exe {
+        &self.when
+    }
+
+    pub fn run(&self) -> &crate::parse::ast::Exe {
+        &self.run
+    }
+}
+
+// the templates for either side of the status line above the input
+#[derive(serde::Deserialize, Default, Debug)]
+#[serde(default)]
+struct PromptTemplates {
+    left: Option<String>,
+    right: Option<String>,
 }
@@ -7,10 +7,79 @@ pub fn config_file() -> std::path::PathBuf {
     config_dir().join("config.toml")
 }
 
+// sourced by login shells, before the rc file
+pub fn profile_file() -> std::path::PathBuf {
+    config_dir().join("profile.nbsh")
+}
+
+// sourced by every interactive shell
+pub fn rc_file() -> std::path::PathBuf {
+    config_dir().join("rc.nbsh")
+}
+
 pub fn history_file() -> std::path::PathBuf {
     data_dir().join("history")
 }
 
+// the directories visited, for jumping back to them with z
+pub fn jump_file() -> std::path::PathBuf {
+    data_dir().join("dirs")
+}
+
+// the hashes of the .nbsh-env files which have been allowed to run
+pub fn allowed_envs_file() -> std::path::PathBuf {
+    data_dir().join("allowed-envs")
+}
+
+pub fn sessions_dir() -> std::path::PathBuf {
+    data_dir().join("sessions")
+}
+
+// temporary files, namespaced by the pid of the shell which created them
+pub fn tmp_dir() -> std::path::PathBuf {
+    cache_dir().join("tmp")
+}
+
+// the process group of a running entry in the given shell (or the status of
+// a finished one), numbered the same way as in the entry header, so that
+// kill and wait can find it with %N
+pub fn job_file(session: &str, job: usize) -> std::path::PathBuf {
+    tmp_dir().join(session).join(format!("job-{}", job))
+}
+
+// the numbers of every entry in the given shell which has a job file
+pub fn jobs(session: &str) -> Vec<usize> {
+    std::fs::read_dir(tmp_dir().join(session)).map_or_else(
+        |_| vec![],
+        |entries| {
+            let mut jobs: Vec<usize> = entries
+                .filter_map(|entry| {
+                    entry
+                        .ok()?
+                        .file_name()
+                        .to_str()?
+                        .strip_prefix("job-")?
+                        .parse()
+                        .ok()
+                })
+                .collect();
+            jobs.sort_unstable();
+            jobs
+        },
+    )
+}
+
+// the commands that !N refers to in the given shell, which include the ones
+// run in it that haven't been saved to the history file yet
+pub fn history_list_file(session: &str) -> std::path::PathBuf {
+    tmp_dir().join(session).join("history")
+}
+
+// not every system has a runtime dir (it comes from XDG_RUNTIME_DIR)
+pub fn runtime_dir() -> Option<std::path::PathBuf> {
+    PROJECT_DIRS.runtime_dir().map(std::path::Path::to_path_buf)
+}
+
 fn config_dir() -> std::path::PathBuf {
     PROJECT_DIRS.config_dir().to_path_buf()
 }
@@ -18,3 +87,7 @@ fn config_dir() -> std::path::PathBuf {
 fn data_dir() -> std::path::PathBuf {
     PROJECT_DIRS.data_dir().to_path_buf()
 }
+
+fn cache_dir() -> std::path::PathBuf {
+    PROJECT_DIRS.cache_dir().to_path_buf()
+}
@@ -9,32 +9,34 @@ pub enum Env {
 pub struct V0 {
     pwd: std::path::PathBuf,
     vars: std::collections::HashMap<std::ffi::OsString, std::ffi::OsString>,
+    locals: std::collections::HashMap<std::ffi::OsString, std::ffi::OsString>,
 }
 
 const __NBSH_IDX: &str = "__NBSH_IDX";
 const __NBSH_LATEST_STATUS: &str = "__NBSH_LATEST_STATUS";
+const __NBSH_PIPESTATUS: &str = "__NBSH_PIPESTATUS";
 const __NBSH_PREV_PWD: &str = "__NBSH_PREV_PWD";
+const __NBSH_DIR_PREFIX: &str = "__NBSH_DIR_";
+const __NBSH_LOCAL_PREFIX: &str = "__NBSH_LOCAL_";
+const __NBSH_ARG_PREFIX: &str = "__NBSH_ARG_";
+const __NBSH_ARGC: &str = "__NBSH_ARGC";
+const __NBSH_COLOR_SCHEME: &str = "__NBSH_COLOR_SCHEME";
 
 impl Env {
     pub fn new() -> Result<Self> {
         let pwd = std::env::current_dir()?;
-        Ok(Self::V0(V0 {
-            pwd: pwd.clone(),
-            vars: std::env::vars_os()
-                .chain(Self::defaults(pwd).into_iter())
-                .collect(),
-        }))
+        let (vars, locals) = Self::split_locals(
+            std::env::vars_os().chain(Self::defaults().into_iter()),
+        );
+        Ok(Self::V0(V0 { pwd, vars, locals }))
     }
 
     pub fn new_from_env() -> Result<Self> {
         let pwd = std::env::current_dir()?;
-        Ok(Self::V0(V0 {
-            pwd: pwd.clone(),
-            vars: Self::defaults(pwd)
-                .into_iter()
-                .chain(std::env::vars_os())
-                .collect(),
-        }))
+        let (vars, locals) = Self::split_locals(
+            Self::defaults().into_iter().chain(std::env::vars_os()),
+        );
+        Ok(Self::V0(V0 { pwd, vars, locals }))
     }
 
     pub fn pwd(&self) -> &std::path::Path {
@@ -46,13 +48,30 @@ impl Env {
     pub fn var(&self, k: &str) -> Option<String> {
         match self {
             Self::V0(env) => self.special_var(k).or_else(|| {
-                env.vars
-                    .get(std::ffi::OsStr::new(k))
+                let k = std::ffi::OsStr::new(k);
+                env.locals
+                    .get(k)
+                    .or_else(|| env.vars.get(k))
                     .map(|v| v.to_str().unwrap().to_string())
             }),
         }
     }
 
+    // internal variables are left out, since they aren't meant to be used
+    // directly
+    pub fn var_names(&self) -> Vec<String> {
+        match self {
+            Self::V0(env) => env
+                .vars
+                .keys()
+                .chain(env.locals.keys())
+                .filter_map(|k| k.to_str())
+                .filter(|k| !k.starts_with("__NBSH_"))
+                .map(std::string::ToString::to_string)
+                .collect(),
+        }
+    }
+
     pub fn set_var<
         K: Into<std::ffi::OsString>,
         V: Into<std::ffi::OsString>,
@@ -68,6 +87,31 @@ impl Env {
         }
     }
 
+    // like set_var, but creates a shell-local variable unless the variable
+    // has already been exported (and isn't shadowed by a local)
+    pub fn set_shell_var(&mut self, k: &str, v: &str) {
+        match self {
+            Self::V0(env) => {
+                let k = std::ffi::OsString::from(k);
+                if !env.locals.contains_key(&k) && env.vars.contains_key(&k) {
+                    env.vars.insert(k, v.into());
+                } else {
+                    env.locals.insert(k, v.into());
+                }
+            }
+        }
+    }
+
+    pub fn remove_var(&mut self, k: &str) {
+        match self {
+            Self::V0(env) => {
+                let k = std::ffi::OsStr::new(k);
+                env.vars.remove(k);
+                env.locals.remove(k);
+            }
+        }
+    }
+
     pub fn idx(&self) -> usize {
         self.var(__NBSH_IDX).unwrap().parse().unwrap()
     }
@@ -77,7 +121,7 @@ impl Env {
     }
 
     pub fn latest_status(&self) -> std::process::ExitStatus {
-        std::process::ExitStatus::from_raw(
+        crate::status::from_raw(
             self.var(__NBSH_LATEST_STATUS).unwrap().parse().unwrap(),
         )
     }
@@ -85,28 +129,149 @@ impl Env {
     pub fn set_status(&mut self, status: std::process::ExitStatus) {
         self.set_var(
             __NBSH_LATEST_STATUS,
-            format!(
-                "{}",
-                (status.code().unwrap_or(0) << 8)
-                    | status.signal().unwrap_or(0)
-            ),
+            format!("{}", crate::status::to_raw(status)),
+        );
+    }
+
+    // $0 followed by the positional parameters. these are only kept in
+    // here rather than in the process environment, so that they aren't
+    // passed along to anything that gets run.
+    pub fn args(&self) -> Vec<String> {
+        let argc = self
+            .var(__NBSH_ARGC)
+            .and_then(|argc| argc.parse().ok())
+            .unwrap_or(0);
+        (0..argc)
+            .map(|i| {
+                self.var(&format!("{}{}", __NBSH_ARG_PREFIX, i))
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
+
+    pub fn set_args(&mut self, args: &[String]) {
+        self.set_var(__NBSH_ARGC, args.len().to_string());
+        for (i, arg) in args.iter().enumerate() {
+            self.set_var(format!("{}{}", __NBSH_ARG_PREFIX, i), arg);
+        }
+    }
+
+    // the status of each command in the most recent pipeline, as a space
+    // separated list of codes, since there are no arrays
+    pub fn pipestatus(&self) -> String {
+        self.var(__NBSH_PIPESTATUS).unwrap_or_default()
+    }
+
+    pub fn set_pipestatus(&mut self, statuses: &[std::process::ExitStatus]) {
+        self.set_var(
+            __NBSH_PIPESTATUS,
+            statuses
+                .iter()
+                .map(|status| crate::status::code(*status).to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
         );
     }
 
-    pub fn prev_pwd(&self) -> std::path::PathBuf {
-        std::path::PathBuf::from(self.var(__NBSH_PREV_PWD).unwrap())
+    pub fn prev_pwd(&self) -> Option<std::path::PathBuf> {
+        self.var(__NBSH_PREV_PWD).map(std::path::PathBuf::from)
     }
 
-    pub fn set_prev_pwd(&mut self, prev_pwd: std::path::PathBuf) {
+    fn set_prev_pwd(&mut self, prev_pwd: &std::path::Path) {
         self.set_var(__NBSH_PREV_PWD, prev_pwd);
     }
 
+    // an unknown name (from an older or newer nbsh) falls back to the
+    // default rather than failing
+    pub fn color_scheme(&self) -> crate::shell::ColorScheme {
+        self.var(__NBSH_COLOR_SCHEME)
+            .and_then(|name| name.parse().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_color_scheme(&mut self, scheme: crate::shell::ColorScheme) {
+        self.set_var(__NBSH_COLOR_SCHEME, scheme.name());
+    }
+
+    // for the colors builtin, which changes the process environment
+    pub fn color_scheme_var() -> &'static str {
+        __NBSH_COLOR_SCHEME
+    }
+
+    pub fn named_dir(&self, name: &str) -> Option<std::path::PathBuf> {
+        self.var(&Self::named_dir_var(name))
+            .map(std::path::PathBuf::from)
+    }
+
+    pub fn named_dirs(&self) -> Vec<(String, std::path::PathBuf)> {
+        match self {
+            Self::V0(env) => {
+                let mut dirs: Vec<_> = env
+                    .vars
+                    .iter()
+                    .filter_map(|(k, v)| {
+                        k.to_str()
+                            .and_then(|k| k.strip_prefix(__NBSH_DIR_PREFIX))
+                            .map(|name| (name.to_string(), v.into()))
+                    })
+                    .collect();
+                dirs.sort();
+                dirs
+            }
+        }
+    }
+
+    pub fn set_named_dir(&mut self, name: &str, dir: &std::path::Path) {
+        self.set_var(Self::named_dir_var(name), dir);
+    }
+
+    // named directories are stored in the environment so that they are
+    // preserved across Env::update calls and passed along to nested shells
+    pub fn named_dir_var(name: &str) -> String {
+        format!("{}{}", __NBSH_DIR_PREFIX, name)
+    }
+
+    // shell-local variables are stored in the process environment too (so
+    // that builtins can set them and so that they survive Env::update), just
+    // under a prefixed name
+    pub fn local_var(name: &str) -> String {
+        format!("{}{}", __NBSH_LOCAL_PREFIX, name)
+    }
+
+    // this is for running the runner process, which is still part of the
+    // shell, so it needs to see the local variables too
     pub fn apply(&self, cmd: &mut pty_process::Command) {
         match self {
             Self::V0(env) => {
                 cmd.current_dir(&env.pwd);
                 cmd.env_clear();
-                cmd.envs(env.vars.iter());
+                // each runner gets its own arguments
+                cmd.envs(env.vars.iter().filter(|(k, _)| {
+                    k.to_str().map_or(true, |k| {
+                        k != __NBSH_ARGC && !k.starts_with(__NBSH_ARG_PREFIX)
+                    })
+                }));
+                cmd.envs(env.locals.iter().map(|(k, v)| {
+                    let mut local =
+                        std::ffi::OsString::from(__NBSH_LOCAL_PREFIX);
+                    local.push(k);
+                    (local, v)
+                }));
+            }
+        }
+    }
+
+    // this is for running actual commands, which should only see exported
+    // variables
+    pub fn apply_exported(&self, cmd: &mut tokio::process::Command) {
+        match self {
+            Self::V0(env) => {
+                for k in env.locals.keys() {
+                    let mut local =
+                        std::ffi::OsString::from(__NBSH_LOCAL_PREFIX);
+                    local.push(k);
+                    cmd.env_remove(local);
+                }
             }
         }
     }
@@ -114,38 +279,66 @@ impl Env {
     pub fn update(&mut self) -> Result<()> {
         let idx = self.idx();
         let status = self.latest_status();
+        let pwd = self.pwd().to_path_buf();
         let prev_pwd = self.prev_pwd();
+        let args = self.args();
+        let pipestatus = self.pipestatus();
         *self = Self::new()?;
         self.set_idx(idx);
         self.set_status(status);
-        self.set_prev_pwd(prev_pwd);
+        self.set_var(__NBSH_PIPESTATUS, pipestatus);
+        self.set_args(&args);
+        // this is the only place the previous directory is tracked, so that
+        // any way of changing directories (not just the cd builtin) is
+        // picked up by cd -
+        if self.pwd() != pwd {
+            self.set_prev_pwd(&pwd);
+        } else if let Some(prev_pwd) = prev_pwd {
+            self.set_prev_pwd(&prev_pwd);
+        }
         Ok(())
     }
 
+    fn split_locals(
+        vars: impl Iterator<Item = (std::ffi::OsString, std::ffi::OsString)>,
+    ) -> (
+        std::collections::HashMap<std::ffi::OsString, std::ffi::OsString>,
+        std::collections::HashMap<std::ffi::OsString, std::ffi::OsString>,
+    ) {
+        let mut exported = std::collections::HashMap::new();
+        let mut locals = std::collections::HashMap::new();
+        for (k, v) in vars {
+            if let Some(name) =
+                k.as_bytes().strip_prefix(__NBSH_LOCAL_PREFIX.as_bytes())
+            {
+                locals.insert(std::ffi::OsStr::from_bytes(name).into(), v);
+            } else {
+                exported.insert(k, v);
+            }
+        }
+        (exported, locals)
+    }
+
     fn special_var(&self, k: &str) -> Option<String> {
         Some(match k {
             "$" => crate::info::pid(),
-            "?" => {
-                let status = self.latest_status();
-                status
-                    .signal()
-                    .map_or_else(
-                        || status.code().unwrap(),
-                        |signal| signal + 128,
-                    )
-                    .to_string()
+            "?" => crate::status::code(self.latest_status()).to_string(),
+            "#" => self.args().len().saturating_sub(1).to_string(),
+            "pipestatus" => self.pipestatus(),
+            "*" | "@" => self.args().get(1..).unwrap_or_default().join(" "),
+            _ => {
+                let n: usize = k.parse().ok()?;
+                // unset positional parameters are empty rather than falling
+                // back to the environment
+                self.args().get(n).cloned().unwrap_or_default()
             }
-            _ => return None,
         })
     }
 
-    fn defaults(
-        pwd: std::path::PathBuf,
-    ) -> [(std::ffi::OsString, std::ffi::OsString); 3] {
+    fn defaults() -> [(std::ffi::OsString, std::ffi::OsString); 2] {
         [
             (__NBSH_IDX.into(), "0".into()),
             (__NBSH_LATEST_STATUS.into(), "0".into()),
-            (__NBSH_PREV_PWD.into(), pwd.into()),
         ]
     }
 }
@@ -10,35 +10,221 @@ pub fn path(path: &std::path::Path) -> String {
     path
 }
 
+// command lines can span multiple lines, but everywhere they are displayed
+// only has room for one
+pub fn command_line(cmdline: &str) -> std::borrow::Cow<str> {
+    let mut lines = cmdline.lines();
+    let first = lines.next().unwrap_or("");
+    let rest = lines.count();
+    if rest == 0 {
+        std::borrow::Cow::Borrowed(first)
+    } else {
+        std::borrow::Cow::Owned(format!(
+            "{} ... (+{} line{})",
+            first,
+            rest,
+            if rest == 1 { "" } else { "s" }
+        ))
+    }
+}
+
+// fixed width, to fit in the entry headers. a * after the signal name means
+// that it dumped core.
 pub fn exit_status(status: std::process::ExitStatus) -> String {
     status.signal().map_or_else(
         || format!("{:03}  ", status.code().unwrap()),
         |sig| {
+            let core = if status.core_dumped() { '*' } else { ' ' };
             nix::sys::signal::Signal::try_from(sig).map_or_else(
-                |_| format!("SIG{} ", sig),
-                |sig| format!("{:4} ", &sig.as_str()[3..]),
+                |_| format!("SIG{}{}", sig, core),
+                |sig| format!("{:4}{}", &sig.as_str()[3..], core),
             )
         },
     )
 }
 
-pub fn time(time: time::OffsetDateTime) -> String {
-    let format = if time::OffsetDateTime::now_utc() - time
-        > std::time::Duration::from_secs(60 * 60 * 24)
-    {
-        time::format_description::parse(
-            "[year]-[month]-[day] [hour]:[minute]:[second]",
+// the longer form, for places that have room to explain it. 126 and 127 are
+// what shells (including the runner) exit with when a command couldn't be
+// run at all.
+pub fn exit_status_description(status: std::process::ExitStatus) -> String {
+    if let Some(sig) = status.signal() {
+        format!(
+            "killed by {}{}",
+            nix::sys::signal::Signal::try_from(sig).map_or_else(
+                |_| format!("signal {}", sig),
+                |sig| sig.as_str().to_string(),
+            ),
+            if status.core_dumped() {
+                " (core dumped)"
+            } else {
+                ""
+            }
         )
-        .unwrap()
     } else {
-        time::format_description::parse("[hour]:[minute]:[second]").unwrap()
+        let code = status.code().unwrap();
+        format!(
+            "exited with code {}{}",
+            code,
+            match code {
+                126 => " (not executable)",
+                127 => " (command not found)",
+                _ => "",
+            }
+        )
+    }
+}
+
+// how durations are displayed
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DurationFormat {
+    // like 1.234s or 2m5s, using the largest unit that fits
+    Compact,
+    // like 0:02:05.123, or 0:02:05 while it is still going
+    Clock,
+}
+
+impl Default for DurationFormat {
+    fn default() -> Self {
+        Self::Compact
+    }
+}
+
+// the time formats are stored in the syntax of the time crate, translated
+// from the strftime-like ones in the config
+struct Settings {
+    time: String,
+    date: String,
+    duration: DurationFormat,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            time: "[hour]:[minute]:[second]".to_string(),
+            date: "[year]-[month]-[day]".to_string(),
+            duration: DurationFormat::default(),
+        }
+    }
+}
+
+impl Settings {
+    fn load(config: &crate::config::Config) -> Result<Self> {
+        let default_time = if config.twelve_hour() {
+            "%I:%M:%S %p"
+        } else {
+            "%H:%M:%S"
+        };
+        Ok(Self {
+            time: strftime(config.time_format().unwrap_or(default_time))?,
+            date: strftime(config.date_format().unwrap_or("%Y-%m-%d"))?,
+            duration: config.duration_format(),
+        })
+    }
+}
+
+static SETTINGS: once_cell::sync::OnceCell<Settings> =
+    once_cell::sync::OnceCell::new();
+
+// like msg::init, this should be called once at startup. if the formats in
+// the config are invalid, the defaults are used instead and the error is
+// returned so that it can be shown.
+pub fn init(config: &crate::config::Config) -> Result<()> {
+    let (settings, res) = match Settings::load(config) {
+        Ok(settings) => (settings, Ok(())),
+        Err(e) => (Settings::default(), Err(e)),
+    };
+    #[allow(clippy::let_underscore_drop)]
+    let _ = SETTINGS.set(settings);
+    res
+}
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(Settings::default)
+}
+
+// translates the strftime specifiers that make sense for displaying a
+// timestamp into a time format description
+fn strftime(fmt: &str) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = fmt.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            // literal brackets need to be escaped by doubling them
+            if c == '[' {
+                out.push('[');
+            }
+            out.push(c);
+            continue;
+        }
+        let spec = chars
+            .next()
+            .ok_or_else(|| anyhow!("time format ends with %"))?;
+        out.push_str(match spec {
+            'Y' => "[year]",
+            'y' => "[year repr:last_two]",
+            'm' => "[month]",
+            'b' | 'h' => "[month repr:short]",
+            'B' => "[month repr:long]",
+            'd' => "[day]",
+            'e' => "[day padding:space]",
+            'a' => "[weekday repr:short]",
+            'A' => "[weekday]",
+            'H' => "[hour]",
+            'k' => "[hour padding:space]",
+            'I' => "[hour repr:12]",
+            'l' => "[hour repr:12 padding:space]",
+            'M' => "[minute]",
+            'S' => "[second]",
+            'p' => "[period]",
+            'P' => "[period case:lower]",
+            'F' => "[year]-[month]-[day]",
+            'T' => "[hour]:[minute]:[second]",
+            'R' => "[hour]:[minute]",
+            '%' => "%",
+            _ => {
+                return Err(anyhow!(
+                    "unknown time format specifier %{}",
+                    spec
+                ))
+            }
+        });
+    }
+    // make sure the result is something the time crate understands
+    time::format_description::parse(&out)?;
+    Ok(out)
+}
+
+// the date is included if the time is from a previous day
+pub fn time(time: time::OffsetDateTime) -> String {
+    let settings = settings();
+    let today = time::OffsetDateTime::now_utc()
+        .to_offset(time.offset())
+        .date();
+    let format = if time.date() == today {
+        std::borrow::Cow::Borrowed(settings.time.as_str())
+    } else {
+        std::borrow::Cow::Owned(format!(
+            "{} {}",
+            settings.date, settings.time
+        ))
     };
-    time.format(&format).unwrap()
+    time.format(&time::format_description::parse(&format).unwrap())
+        .unwrap()
 }
 
 pub fn duration(dur: std::time::Duration) -> String {
     let secs = dur.as_secs();
     let nanos = dur.subsec_nanos();
+    if settings().duration == DurationFormat::Clock {
+        return format!(
+            "{}:{:02}:{:02}.{:03}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            nanos / 1_000_000
+        );
+    }
     if secs > 60 {
         let mins = secs / 60;
         let secs = secs - mins * 60;
@@ -56,6 +242,20 @@ pub fn duration(dur: std::time::Duration) -> String {
     }
 }
 
+// for durations that are still going, which are only redrawn once a second
+pub fn elapsed(dur: std::time::Duration) -> String {
+    let secs = dur.as_secs();
+    if settings().duration == DurationFormat::Clock {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 60 * 60 {
+        format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
 pub fn io_error(e: &std::io::Error) -> String {
     let mut s = format!("{}", e);
     if e.raw_os_error().is_some() {
@@ -64,3 +264,63 @@ pub fn io_error(e: &std::io::Error) -> String {
     }
     s
 }
+
+#[test]
+fn test_exit_status() {
+    assert_eq!(exit_status(crate::status::exited(1)), "001  ");
+    assert_eq!(exit_status(crate::status::signaled(9)), "KILL ");
+    let core = crate::status::from_raw(0x80 | 11);
+    assert_eq!(exit_status(core), "SEGV*");
+    assert_eq!(
+        exit_status_description(crate::status::exited(127)),
+        "exited with code 127 (command not found)"
+    );
+    assert_eq!(
+        exit_status_description(crate::status::success()),
+        "exited with code 0"
+    );
+    assert_eq!(
+        exit_status_description(core),
+        "killed by SIGSEGV (core dumped)"
+    );
+    assert_eq!(
+        exit_status_description(crate::status::signaled(15)),
+        "killed by SIGTERM"
+    );
+}
+
+#[test]
+fn test_elapsed() {
+    let secs = std::time::Duration::from_secs;
+    assert_eq!(elapsed(std::time::Duration::from_millis(999)), "0s");
+    assert_eq!(elapsed(secs(59)), "59s");
+    assert_eq!(elapsed(secs(65)), "1m05s");
+    assert_eq!(elapsed(secs(3600 + 61)), "1h01m01s");
+}
+
+#[test]
+fn test_strftime() {
+    assert_eq!(strftime("%H:%M:%S").unwrap(), "[hour]:[minute]:[second]");
+    assert_eq!(
+        strftime("%b %e, %l:%M %p").unwrap(),
+        "[month repr:short] [day padding:space], \
+         [hour repr:12 padding:space]:[minute] [period]"
+    );
+    assert_eq!(
+        strftime("[%T] 100%%").unwrap(),
+        "[[[hour]:[minute]:[second]] 100%"
+    );
+    assert!(strftime("%Q").is_err());
+    assert!(strftime("%H%").is_err());
+}
+
+#[test]
+fn test_command_line() {
+    assert_eq!(command_line("ls"), "ls");
+    assert_eq!(command_line("ls\n"), "ls");
+    assert_eq!(command_line("ls\npwd"), "ls ... (+1 line)");
+    assert_eq!(
+        command_line("for i in 1 2 3\n  echo $i\nend"),
+        "for i in 1 2 3 ... (+2 lines)"
+    );
+}
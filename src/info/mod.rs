@@ -0,0 +1,167 @@
+use crate::prelude::*;
+
+pub mod update;
+
+pub fn user() -> Result<String> {
+    Ok(users::get_current_username()
+        .ok_or_else(|| anyhow!("couldn't get username"))?
+        .to_string_lossy()
+        .into_owned())
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn prompt_char() -> Result<String> {
+    if users::get_current_uid() == 0 {
+        Ok("#".into())
+    } else {
+        Ok("$".into())
+    }
+}
+
+pub fn hostname() -> Result<String> {
+    let mut hostname = hostname::get()?.to_string_lossy().into_owned();
+    if let Some(idx) = hostname.find('.') {
+        hostname.truncate(idx);
+    }
+    Ok(hostname)
+}
+
+#[allow(clippy::unnecessary_wraps)]
+pub fn time(offset: time::UtcOffset) -> Result<String> {
+    Ok(crate::format::time(
+        time::OffsetDateTime::now_utc().to_offset(offset),
+    ))
+}
+
+// how many nbsh instances we are running inside of (zero if this is the
+// outermost instance)
+pub fn depth() -> usize {
+    std::env::var("NBSH_DEPTH")
+        .ok()
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(0)
+}
+
+// used when the terminal size can't be determined, such as when there is no
+// terminal at all
+pub fn default_size() -> (u16, u16) {
+    let var = |name| {
+        std::env::var(name)
+            .ok()
+            .and_then(|val| val.parse().ok())
+            .filter(|&val| val > 0)
+    };
+    (var("LINES").unwrap_or(24), var("COLUMNS").unwrap_or(80))
+}
+
+pub fn pid() -> String {
+    nix::unistd::getpid().to_string()
+}
+
+pub fn running(pid: i32) -> bool {
+    // signal 0 only checks whether the process exists. EPERM means that it
+    // exists but belongs to someone else.
+    !matches!(
+        nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None),
+        Err(nix::errno::Errno::ESRCH)
+    )
+}
+
+static RUNNER_OVERRIDE: once_cell::sync::OnceCell<std::path::PathBuf> =
+    once_cell::sync::OnceCell::new();
+
+// the identity of the binary we were started from, so that we can tell if it
+// has been upgraded or removed out from under us
+static STARTUP_EXE: once_cell::sync::Lazy<Option<(u64, u64)>> =
+    once_cell::sync::Lazy::new(|| {
+        std::env::current_exe()
+            .and_then(std::fs::metadata)
+            .ok()
+            .map(|metadata| {
+                use std::os::unix::fs::MetadataExt as _;
+                (metadata.dev(), metadata.ino())
+            })
+    });
+
+pub fn set_runner(path: std::path::PathBuf) -> Result<()> {
+    let metadata = std::fs::metadata(&path).map_err(|e| {
+        anyhow!(
+            "invalid runner {}: {}",
+            path.display(),
+            crate::format::io_error(&e)
+        )
+    })?;
+    if !metadata.is_file() {
+        return Err(anyhow!("invalid runner {}: not a file", path.display()));
+    }
+    RUNNER_OVERRIDE
+        .set(path)
+        .map_err(|_| anyhow!("runner already set"))
+}
+
+pub fn record_startup_exe() {
+    once_cell::sync::Lazy::force(&STARTUP_EXE);
+}
+
+// the path to use to spawn new runner processes - these need to be running
+// the exact same version of nbsh as we are, since the protocol between the
+// shell and the runner isn't stable
+pub fn current_exe() -> Result<std::path::PathBuf> {
+    if let Some(path) = RUNNER_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
+    running_exe()
+}
+
+// /proc/self/exe always refers to the binary that is actually running, even
+// if the file on disk has since been replaced or deleted
+#[cfg(target_os = "linux")]
+#[allow(clippy::unnecessary_wraps)]
+fn running_exe() -> Result<std::path::PathBuf> {
+    Ok("/proc/self/exe".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn running_exe() -> Result<std::path::PathBuf> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    let path = std::env::current_exe()?;
+    let current = std::fs::metadata(&path)
+        .ok()
+        .map(|metadata| (metadata.dev(), metadata.ino()));
+    if current.is_none() || current != *STARTUP_EXE {
+        return Err(anyhow!(
+            "the nbsh binary at {} has been replaced or removed since this \
+             shell was started, please restart nbsh",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
+
+// the time crate is currently unable to get the local offset on unix due to
+// soundness concerns, so we have to do it manually/:
+//
+// https://github.com/time-rs/time/issues/380
+pub fn get_offset() -> time::UtcOffset {
+    let offset_str =
+        std::process::Command::new("date").args(&["+%:z"]).output();
+    if let Ok(offset_str) = offset_str {
+        let offset_str = String::from_utf8(offset_str.stdout).unwrap();
+        time::UtcOffset::parse(
+            offset_str.trim(),
+            &time::format_description::parse("[offset_hour]:[offset_minute]")
+                .unwrap(),
+        )
+        .unwrap_or(time::UtcOffset::UTC)
+    } else {
+        time::UtcOffset::UTC
+    }
+}
+
+#[test]
+fn test_running() {
+    assert!(running(nix::unistd::getpid().as_raw()));
+    // pids are never allowed to get this large on linux
+    assert!(!running(i32::MAX));
+}
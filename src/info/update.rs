@@ -0,0 +1,60 @@
+use crate::prelude::*;
+
+const CRATE_URL: &str = "https://crates.io/api/v1/crates/nbsh";
+
+pub fn current_version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+// returns the newest released version if it is newer than the running one.
+// this shells out to curl rather than pulling in an http client, and any
+// failure (including not having network access) is treated as there being
+// no update available.
+pub async fn check() -> Result<Option<String>> {
+    let output = tokio::process::Command::new("curl")
+        .args(&["--silent", "--fail", "--max-time", "5", CRATE_URL])
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(anyhow!("couldn't fetch {}", CRATE_URL));
+    }
+    let body = String::from_utf8_lossy(&output.stdout);
+    let latest = max_version(&body)
+        .ok_or_else(|| anyhow!("couldn't find version in response"))?;
+    if parse_version(latest) > parse_version(current_version()) {
+        Ok(Some(latest.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn max_version(body: &str) -> Option<&str> {
+    let key = "\"max_version\":\"";
+    let start = body.find(key)? + key.len();
+    let len = body[start..].find('"')?;
+    Some(&body[start..start + len])
+}
+
+fn parse_version(version: &str) -> Vec<u64> {
+    version
+        .split(&['-', '+'][..])
+        .next()
+        .unwrap_or("")
+        .split('.')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect()
+}
+
+#[test]
+fn test_version() {
+    assert_eq!(
+        max_version(r#"{"crate":{"id":"nbsh","max_version":"0.2.1"}}"#),
+        Some("0.2.1")
+    );
+    assert_eq!(max_version(r#"{"errors":[]}"#), None);
+    assert!(parse_version("0.10.0") > parse_version("0.9.3"));
+    assert_eq!(parse_version("1.0.0-rc.1"), parse_version("1.0.0"));
+    assert!(parse_version("0.1.1") > parse_version("0.1.0"));
+}
@@ -21,10 +21,12 @@ mod dirs;
 mod env;
 mod format;
 mod info;
+mod msg;
 mod parse;
 mod prelude;
 mod runner;
 mod shell;
+mod status;
 
 use prelude::*;
 
@@ -32,17 +34,124 @@ use clap::Parser as _;
 
 #[derive(clap::Parser)]
 #[clap(about = "NoteBook SHell")]
-struct Opt {
+#[clap(setting = clap::AppSettings::TrailingVarArg)]
+#[clap(group = clap::ArgGroup::new("run")
+    .multiple(true)
+    .args(&["command", "args"]))]
+pub struct Opt {
     #[clap(short = 'c')]
     command: Option<String>,
 
+    /// A script to run instead of starting the shell, followed by its
+    /// arguments. With -c, these are $0 and the arguments for the command
+    /// instead.
+    #[clap(value_name = "ARGS")]
+    args: Vec<String>,
+
     #[clap(long)]
     status_fd: Option<std::os::unix::io::RawFd>,
+
+    /// With -c or a script, write a JSON record of what was run and how it
+    /// exited once it is done
+    #[clap(long, requires = "run")]
+    json: bool,
+
+    /// The file descriptor to write the --json record to (stdout by
+    /// default)
+    #[clap(long, value_name = "FD", requires = "json")]
+    json_fd: Option<std::os::unix::io::RawFd>,
+
+    /// With --json, include the output of the commands in the record
+    /// rather than passing it through
+    #[clap(long, requires = "json")]
+    capture: bool,
+
+    /// With -c or a script, exit with 1 for any error in nbsh itself,
+    /// rather than 2 for syntax errors, 126 for commands that couldn't be
+    /// executed, and 127 for commands that weren't found
+    #[clap(long, requires = "run")]
+    simple_exit_codes: bool,
+
+    /// Start as a login shell, with a fresh environment and running the
+    /// profile before the rc file. This is also the default when started
+    /// with a name beginning with -, as login does.
+    #[clap(short = 'l', long)]
+    login: bool,
+
+    /// Don't run the rc file at startup
+    #[clap(long)]
+    norc: bool,
+
+    /// Only allow navigating and viewing, not running or typing into
+    /// commands
+    #[clap(long)]
+    read_only: bool,
+
+    /// Don't show the greeting at startup
+    #[clap(long)]
+    no_greeting: bool,
+
+    /// Start with a guided tour of the interface
+    #[clap(long)]
+    tutorial: bool,
+
+    /// Save the history as a named session when exiting
+    #[clap(long, value_name = "NAME")]
+    session: Option<String>,
+
+    /// Restore the history from a session saved with --session
+    #[clap(long, value_name = "NAME")]
+    restore: Option<String>,
+
+    /// Write a session saved with --session (given with --restore) to a
+    /// Markdown or HTML file, depending on its extension, and exit
+    #[clap(long, value_name = "FILE", requires = "restore")]
+    export: Option<std::path::PathBuf>,
+
+    /// Disable git status tracking, update checks, and anything else that
+    /// runs in the background
+    #[clap(long)]
+    safe: bool,
+
+    #[clap(long, hide = true)]
+    cache_daemon: bool,
+
+    /// Print the version and exit
+    #[clap(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also check whether a newer release is available
+    #[clap(long, requires = "version")]
+    check_update: bool,
 }
 
 #[tokio::main]
-async fn async_main(opt: Opt) -> Result<i32> {
-    if let Some(command) = opt.command {
+async fn async_main(mut opt: Opt) -> Result<i32> {
+    if opt.version {
+        print!("nbsh {}", info::update::current_version());
+        if opt.check_update {
+            match info::update::check().await {
+                Ok(Some(version)) => {
+                    print!(" (version {} is available)", version);
+                }
+                Ok(None) => print!(" (up to date)"),
+                Err(e) => print!(" (couldn't check for updates: {})", e),
+            }
+        }
+        println!();
+        return Ok(0);
+    }
+
+    let mut args = std::mem::take(&mut opt.args);
+    if opt.command.is_none() && !args.is_empty() {
+        opt.command =
+            Some(std::fs::read_to_string(&args[0]).map_err(|e| {
+                anyhow!("{}: {}", crate::format::io_error(&e), args[0])
+            })?);
+    } else if opt.command.is_some() && args.is_empty() {
+        args.push("nbsh".to_string());
+    }
+    if let Some(command) = opt.command.take() {
         let mut shell_write = opt.status_fd.and_then(|fd| {
             nix::sys::stat::fstat(fd).ok().map(|_| {
                 // Safety: we don't create File instances for or read/write
@@ -51,13 +160,29 @@ async fn async_main(opt: Opt) -> Result<i32> {
             })
         });
 
-        return runner::main(command, &mut shell_write).await;
+        let reporter = if opt.json {
+            Some(runner::Reporter::new(
+                opt.json_fd.unwrap_or(1),
+                opt.capture,
+            )?)
+        } else {
+            None
+        };
+
+        return runner::main(
+            command,
+            &mut shell_write,
+            reporter,
+            opt.simple_exit_codes,
+            args,
+        )
+        .await;
     }
 
     #[cfg(nbsh_tokio_console)]
     console_subscriber::init();
 
-    shell::main().await
+    shell::main(opt).await
 }
 
 fn main() {
@@ -66,7 +191,10 @@ fn main() {
             std::process::exit(code);
         }
         Err(e) => {
-            eprintln!("nbsh: {}", e);
+            eprintln!(
+                "{}",
+                crate::msg::get(crate::msg::Message::Error, &[&e])
+            );
             std::process::exit(1);
         }
     };
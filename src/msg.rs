@@ -0,0 +1,287 @@
+use crate::prelude::*;
+
+// all user facing text goes through here, so that it can be translated via
+// the config file without needing to touch the code that displays it. each
+// message is looked up by key in the catalog for the current locale (first
+// the full locale like "pt_BR", then just the language), falling back to the
+// builtin english text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Error,
+    BinaryData,
+    Evicted,
+    FailedToRun,
+    FailedToReload,
+    Greeting,
+    UpdateAvailable,
+    LastCommandFailed,
+    FinishedWhileAway,
+    FinishedWhileAwayOne,
+    WaitingForInput,
+    StillRunning,
+    Reruns,
+    Search,
+    FailedSearch,
+    Jump,
+    FailedJump,
+    Env,
+    EnvEdit,
+    Palette,
+    PaletteRerun,
+    PaletteKill,
+    PaletteFullscreen,
+    PaletteInspect,
+    PaletteExport,
+    PaletteJump,
+    PaletteEnv,
+    PaletteReadOnly,
+    PaletteColors,
+    ColorScheme,
+    NoEnvChanges,
+    NoExitEnv,
+    DirEnvLoaded,
+    DirEnvBlocked,
+    DirEnvFailed,
+    CommandFinished,
+    Exported,
+    ExportFailed,
+    SyncFailed,
+    CleanupFailed,
+    TutorialDone,
+    CorrectPath,
+    Placeholder,
+    NoLinks,
+    SelectedLink,
+    OpenFailed,
+    SafeMode,
+    InvalidPrompt,
+    InvalidTimeFormat,
+    StatusEscape,
+    StatusScrolling,
+    StatusFocus,
+    StatusRunning,
+    Time,
+}
+
+impl Message {
+    fn key(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::BinaryData => "binary-data",
+            Self::Evicted => "evicted",
+            Self::FailedToRun => "failed-to-run",
+            Self::FailedToReload => "failed-to-reload",
+            Self::Greeting => "greeting",
+            Self::UpdateAvailable => "update-available",
+            Self::LastCommandFailed => "last-command-failed",
+            Self::FinishedWhileAway => "finished-while-away",
+            Self::FinishedWhileAwayOne => "finished-while-away-one",
+            Self::WaitingForInput => "waiting-for-input",
+            Self::StillRunning => "still-running",
+            Self::Reruns => "reruns",
+            Self::Search => "search",
+            Self::FailedSearch => "failed-search",
+            Self::Jump => "jump",
+            Self::FailedJump => "failed-jump",
+            Self::Env => "env",
+            Self::EnvEdit => "env-edit",
+            Self::Palette => "palette",
+            Self::PaletteRerun => "palette-rerun",
+            Self::PaletteKill => "palette-kill",
+            Self::PaletteFullscreen => "palette-fullscreen",
+            Self::PaletteInspect => "palette-inspect",
+            Self::PaletteExport => "palette-export",
+            Self::PaletteJump => "palette-jump",
+            Self::PaletteEnv => "palette-env",
+            Self::PaletteReadOnly => "palette-read-only",
+            Self::PaletteColors => "palette-colors",
+            Self::ColorScheme => "color-scheme",
+            Self::NoEnvChanges => "no-env-changes",
+            Self::NoExitEnv => "no-exit-env",
+            Self::DirEnvLoaded => "dir-env-loaded",
+            Self::DirEnvBlocked => "dir-env-blocked",
+            Self::DirEnvFailed => "dir-env-failed",
+            Self::CommandFinished => "command-finished",
+            Self::Exported => "exported",
+            Self::ExportFailed => "export-failed",
+            Self::SyncFailed => "sync-failed",
+            Self::CleanupFailed => "cleanup-failed",
+            Self::TutorialDone => "tutorial-done",
+            Self::CorrectPath => "correct-path",
+            Self::Placeholder => "placeholder",
+            Self::NoLinks => "no-links",
+            Self::SelectedLink => "selected-link",
+            Self::OpenFailed => "open-failed",
+            Self::SafeMode => "safe-mode",
+            Self::InvalidPrompt => "invalid-prompt",
+            Self::InvalidTimeFormat => "invalid-time-format",
+            Self::StatusEscape => "status-escape",
+            Self::StatusScrolling => "status-scrolling",
+            Self::StatusFocus => "status-focus",
+            Self::StatusRunning => "status-running",
+            Self::Time => "time",
+        }
+    }
+
+    // each {} is replaced by the next argument
+    fn english(self) -> &'static str {
+        match self {
+            Self::Error => "nbsh: {}",
+            Self::BinaryData => {
+                "This appears to be binary data. Fullscreen \
+                                 this entry to view anyway."
+            }
+            Self::Evicted => "evicted — press enter to reload",
+            Self::FailedToRun => "nbsh: failed to run command: {}",
+            Self::FailedToReload => "nbsh: failed to reload output: {}",
+            Self::Greeting => {
+                "nbsh {}\npress ctrl-e for navigation mode, ctrl-d to exit"
+            }
+            Self::UpdateAvailable => "nbsh {} is available",
+            Self::LastCommandFailed => {
+                "the last command failed, press up to look at it"
+            }
+            Self::FinishedWhileAway => {
+                "{} commands finished while you were away"
+            }
+            Self::FinishedWhileAwayOne => {
+                "{} command finished while you were away"
+            }
+            Self::WaitingForInput => {
+                "entry {} is waiting for input, press ctrl-e w to jump to it"
+            }
+            Self::StillRunning => "entry {} is still running",
+            Self::Reruns => "(alt-enter reruns {})",
+            Self::Search => "(search) ",
+            Self::FailedSearch => "(failed search) ",
+            Self::Jump => "(jump) ",
+            Self::FailedJump => "(failed jump) ",
+            Self::Env => "(env) ",
+            Self::EnvEdit => "(edit {}) ",
+            Self::Palette => "(command) ",
+            Self::PaletteRerun => "rerun the focused entry",
+            Self::PaletteKill => "kill the focused entry",
+            Self::PaletteFullscreen => {
+                "toggle fullscreen for the focused entry"
+            }
+            Self::PaletteInspect => "inspect the focused entry",
+            Self::PaletteExport => "export the session as html",
+            Self::PaletteJump => "cd to a recently visited directory",
+            Self::PaletteEnv => "edit the environment",
+            Self::PaletteReadOnly => "toggle read-only mode",
+            Self::PaletteColors => "switch to the next color scheme",
+            Self::ColorScheme => "color scheme: {}",
+            Self::NoEnvChanges => {
+                "this command didn't change the environment or directory"
+            }
+            Self::NoExitEnv => {
+                "the environment this command exited with isn't known"
+            }
+            Self::DirEnvLoaded => "loaded {}",
+            Self::DirEnvBlocked => {
+                "{} hasn't been allowed yet, press ctrl-e A to load it"
+            }
+            Self::DirEnvFailed => "couldn't load {}: {}",
+            Self::CommandFinished => "{} {} after {}",
+            Self::Exported => "exported history to {}",
+            Self::ExportFailed => "couldn't export history: {}",
+            Self::SyncFailed => "couldn't sync history: {}",
+            Self::CleanupFailed => {
+                "couldn't clean up old temporary files: {}"
+            }
+            Self::TutorialDone => {
+                "that's the tour! press ctrl-d to exit when you're done"
+            }
+            Self::CorrectPath => "nbsh: correct '{}' to '{}'? [y/N] ",
+            Self::Placeholder => {
+                "type a command, or press ctrl-e to browse the history"
+            }
+            Self::NoLinks => "no links found in this entry",
+            Self::SelectedLink => "{} (press U to open)",
+            Self::OpenFailed => "couldn't open {}: {}",
+            Self::SafeMode => "not available in safe mode",
+            Self::InvalidPrompt => "invalid prompt in config: {}",
+            Self::InvalidTimeFormat => "invalid time format in config: {}",
+            Self::StatusEscape => "ESC",
+            Self::StatusScrolling => "SCROLL",
+            Self::StatusFocus => "entry {}",
+            Self::StatusRunning => "{} running",
+            Self::Time => "real {}  user {}  sys {}",
+        }
+    }
+}
+
+static CATALOG: once_cell::sync::OnceCell<
+    std::collections::HashMap<String, String>,
+> = once_cell::sync::OnceCell::new();
+
+// picks the catalog to use out of the ones in the config. should be called
+// once at startup, before anything is displayed.
+pub fn init(config: &crate::config::Config) {
+    let locale = config
+        .locale()
+        .map_or_else(env_locale, |locale| Some(locale.to_string()));
+    let catalog = locale
+        .as_deref()
+        .and_then(|locale| {
+            config.messages(locale).or_else(|| {
+                config.messages(locale.split('_').next().unwrap())
+            })
+        })
+        .cloned()
+        .unwrap_or_default();
+    #[allow(clippy::let_underscore_drop)]
+    let _ = CATALOG.set(catalog);
+}
+
+pub fn get(msg: Message, args: &[&dyn std::fmt::Display]) -> String {
+    let template = CATALOG
+        .get()
+        .and_then(|catalog| catalog.get(msg.key()))
+        .map_or(msg.english(), String::as_str);
+    fill(template, args)
+}
+
+// the locale that messages should be displayed in, using the same
+// precedence as setlocale
+fn env_locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|locale| !locale.is_empty())
+        .map(|locale| {
+            // strip off the encoding and modifier, like in en_US.UTF-8@euro
+            locale
+                .split(|c| c == '.' || c == '@')
+                .next()
+                .unwrap()
+                .to_string()
+        })
+}
+
+fn fill(template: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut args = args.iter();
+    let mut out = String::new();
+    let mut pieces = template.split("{}");
+    out.push_str(pieces.next().unwrap());
+    for piece in pieces {
+        // a translation with more placeholders than arguments shouldn't
+        // be able to crash anything
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(piece);
+    }
+    out
+}
+
+#[test]
+fn test_fill() {
+    assert_eq!(fill("no args", &[]), "no args");
+    assert_eq!(fill("entry {} is running", &[&3]), "entry 3 is running");
+    assert_eq!(
+        fill("correct '{}' to '{}'?", &[&"sl", &"ls"]),
+        "correct 'sl' to 'ls'?"
+    );
+    assert_eq!(fill("{} and {}", &[&1]), "1 and ");
+}
@@ -13,21 +13,47 @@ pub struct Commands {
 
 impl Commands {
     pub fn parse(full_cmd: &str) -> Result<Self, super::Error> {
-        Ok(Self::build_ast(
-            Shell::parse(Rule::line, full_cmd)
-                .map_err(|e| super::Error::new(full_cmd.to_string(), e))?
-                .next()
-                .unwrap()
-                .into_inner()
-                .next()
-                .unwrap(),
-        ))
+        Ok(Shell::parse(Rule::line, full_cmd)
+            .map_err(|e| super::Error::new(full_cmd.to_string(), e))?
+            .next()
+            .unwrap()
+            .into_inner()
+            .find(|pair| pair.as_rule() == Rule::commands)
+            .map_or_else(|| Self { commands: vec![] }, Self::build_ast))
     }
 
     pub fn commands(&self) -> &[Command] {
         &self.commands
     }
 
+    // the names of every command that would be run, as long as they are all
+    // known without running anything. this is None if any of them come from
+    // an expansion, or if there are any blocks, since those affect the rest
+    // of the command line in ways that a plain list of commands doesn't.
+    pub fn exe_names(&self) -> Option<Vec<String>> {
+        let mut names = vec![];
+        for command in &self.commands {
+            if let Command::Pipeline(pipeline) = command {
+                for exe in &pipeline.exes {
+                    names.push(exe.literal_name()?);
+                }
+            } else {
+                return None;
+            }
+        }
+        Some(names)
+    }
+
+    // an indented description of how the command line was parsed, one line
+    // per node, for showing to the user
+    pub fn tree(&self) -> Vec<String> {
+        let mut lines = vec![];
+        for command in &self.commands {
+            command.tree(0, &mut lines);
+        }
+        lines
+    }
+
     fn build_ast(commands: pest::iterators::Pair<Rule>) -> Self {
         assert!(matches!(commands.as_rule(), Rule::commands));
         Self {
@@ -44,9 +70,41 @@ pub enum Command {
     For(String, Vec<Word>),
     Else(Option<Pipeline>),
     End,
+    Break,
+    Continue,
 }
 
 impl Command {
+    fn tree(&self, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        match self {
+            Self::Pipeline(pipeline) => pipeline.tree(depth, lines),
+            Self::If(pipeline) => {
+                lines.push(format!("{}if", indent));
+                pipeline.tree(depth + 1, lines);
+            }
+            Self::While(pipeline) => {
+                lines.push(format!("{}while", indent));
+                pipeline.tree(depth + 1, lines);
+            }
+            Self::For(var, words) => {
+                lines.push(format!("{}for {}", indent, var));
+                for word in words {
+                    lines.push(format!("{}  in {}", indent, word.describe()));
+                }
+            }
+            Self::Else(pipeline) => {
+                lines.push(format!("{}else", indent));
+                if let Some(pipeline) = pipeline {
+                    pipeline.tree(depth + 1, lines);
+                }
+            }
+            Self::End => lines.push(format!("{}end", indent)),
+            Self::Break => lines.push(format!("{}break", indent)),
+            Self::Continue => lines.push(format!("{}continue", indent)),
+        }
+    }
+
     fn build_ast(command: pest::iterators::Pair<Rule>) -> Self {
         assert!(matches!(command.as_rule(), Rule::command));
         let next = command.into_inner().next().unwrap();
@@ -75,6 +133,8 @@ impl Command {
                         ty.into_inner().next().map(Pipeline::build_ast),
                     ),
                     Rule::control_end => Self::End,
+                    Rule::control_break => Self::Break,
+                    Rule::control_continue => Self::Continue,
                     _ => unreachable!(),
                 }
             }
@@ -87,6 +147,8 @@ impl Command {
 pub struct Pipeline {
     exes: Vec<Exe>,
     span: (usize, usize),
+    time: bool,
+    negate: bool,
 }
 
 impl Pipeline {
@@ -106,12 +168,43 @@ impl Pipeline {
         self.span
     }
 
+    // whether the pipeline was prefixed with time
+    pub fn time(&self) -> bool {
+        self.time
+    }
+
+    // whether the pipeline was prefixed with !, inverting its status
+    pub fn negate(&self) -> bool {
+        self.negate
+    }
+
+    fn tree(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!(
+            "{}{}{}pipeline",
+            "  ".repeat(depth),
+            if self.time { "timed " } else { "" },
+            if self.negate { "negated " } else { "" },
+        ));
+        for exe in &self.exes {
+            exe.tree(depth + 1, lines);
+        }
+    }
+
     fn build_ast(pipeline: pest::iterators::Pair<Rule>) -> Self {
         assert!(matches!(pipeline.as_rule(), Rule::pipeline));
         let span = (pipeline.as_span().start(), pipeline.as_span().end());
+        let mut inner = pipeline.into_inner().peekable();
+        let time = inner
+            .next_if(|pair| matches!(pair.as_rule(), Rule::time))
+            .is_some();
+        let negate = inner
+            .next_if(|pair| matches!(pair.as_rule(), Rule::negate))
+            .is_some();
         Self {
-            exes: pipeline.into_inner().map(Exe::build_ast).collect(),
+            exes: inner.map(Exe::build_ast).collect(),
             span,
+            time,
+            negate,
         }
     }
 }
@@ -121,17 +214,25 @@ pub struct Exe {
     exe: Word,
     args: Vec<Word>,
     redirects: Vec<Redirect>,
+    assignments: Vec<Assignment>,
 }
 
 impl Exe {
+    // the command name, if it doesn't need any expansion
+    pub fn literal_name(&self) -> Option<String> {
+        self.exe.literal()
+    }
+
     pub async fn eval(self, env: &Env) -> Result<super::Exe> {
-        let exe = self.exe.eval(env).await?;
-        assert_eq!(exe.len(), 1); // TODO
-        let exe = &exe[0];
-        Ok(super::Exe {
-            exe: std::path::PathBuf::from(exe),
-            args: self
-                .args
+        // an expansion in the command name can split into several words,
+        // in which case the rest of them become arguments
+        let mut args = self.exe.eval(env).await?;
+        if args.is_empty() {
+            anyhow::bail!("command name expanded to nothing");
+        }
+        let exe = args.remove(0);
+        args.extend(
+            self.args
                 .into_iter()
                 .map(|arg| async {
                     arg.eval(env).await.map(IntoIterator::into_iter)
@@ -140,8 +241,11 @@ impl Exe {
                 .try_collect::<Vec<_>>()
                 .await?
                 .into_iter()
-                .flatten()
-                .collect(),
+                .flatten(),
+        );
+        Ok(super::Exe {
+            exe: std::path::PathBuf::from(exe),
+            args,
             redirects: self
                 .redirects
                 .into_iter()
@@ -149,9 +253,39 @@ impl Exe {
                 .collect::<futures_util::stream::FuturesOrdered<_>>()
                 .try_collect()
                 .await?,
+            env: self
+                .assignments
+                .into_iter()
+                .map(|assignment| assignment.eval(env))
+                .collect::<futures_util::stream::FuturesOrdered<_>>()
+                .try_collect()
+                .await?,
         })
     }
 
+    fn tree(&self, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        lines.push(format!("{}command {}", indent, self.exe.describe()));
+        for assignment in &self.assignments {
+            lines.push(format!(
+                "{}  set {} = {}",
+                indent,
+                assignment.var,
+                assignment.value.describe()
+            ));
+        }
+        for arg in &self.args {
+            lines.push(format!("{}  arg {}", indent, arg.describe()));
+        }
+        for redirect in &self.redirects {
+            lines.push(format!(
+                "{}  redirect {}",
+                indent,
+                redirect.describe()
+            ));
+        }
+    }
+
     pub fn parse(s: &str) -> Result<Self, super::Error> {
         Ok(Self::build_ast(
             Shell::parse(Rule::exe, s)
@@ -189,9 +323,16 @@ impl Exe {
                     },
                 ],
                 redirects,
+                assignments: vec![],
             };
         }
-        let mut iter = pair.into_inner();
+        let mut iter = pair.into_inner().peekable();
+        let mut assignments = vec![];
+        while let Some(assignment) =
+            iter.next_if(|pair| matches!(pair.as_rule(), Rule::assignment))
+        {
+            assignments.push(Assignment::build_ast(assignment));
+        }
         let exe = iter.next().unwrap();
         let exe = match exe.as_rule() {
             Rule::word => Word::build_ast(exe),
@@ -211,6 +352,7 @@ impl Exe {
             exe,
             args,
             redirects,
+            assignments,
         }
     }
 }
@@ -251,7 +393,40 @@ pub struct Word {
 }
 
 impl Word {
+    fn literal(&self) -> Option<String> {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                WordPart::Bareword(s)
+                    if !s.starts_with('~')
+                        && !s.contains(&['*', '?', '['][..]) =>
+                {
+                    Some(s.as_str())
+                }
+                WordPart::DoubleQuoted(s) | WordPart::SingleQuoted(s) => {
+                    Some(s.as_str())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     pub async fn eval(self, env: &Env) -> Result<Vec<String>> {
+        let ifs = env.var("IFS").unwrap_or_else(|| DEFAULT_IFS.to_string());
+        self.expand(env, Some(&ifs)).await
+    }
+
+    // assignments and redirect targets are always a single word, so the
+    // results of expansions in them aren't split
+    async fn eval_unsplit(self, env: &Env) -> Result<Vec<String>> {
+        self.expand(env, None).await
+    }
+
+    async fn expand(
+        self,
+        env: &Env,
+        ifs: Option<&str>,
+    ) -> Result<Vec<String>> {
         let mut opts = glob::MatchOptions::new();
         opts.require_literal_separator = true;
         opts.require_literal_leading_dot = true;
@@ -287,9 +462,11 @@ impl Word {
 
         let mut expanded_words = vec![];
         for word in words {
-            let mut s = String::new();
-            let mut pat = String::new();
-            let mut is_glob = false;
+            // an empty alternative (as in {foo,}) is still a word
+            let mut fields = vec![Field {
+                present: word.is_empty(),
+                ..Field::default()
+            }];
             let initial_bareword = word
                 .get(0)
                 .map_or(false, |part| matches!(part, WordPart::Bareword(_)));
@@ -297,52 +474,50 @@ impl Word {
                 match part {
                     WordPart::Alternation(_) => unreachable!(),
                     WordPart::Bareword(_) => {
-                        let part = part.eval(env).await;
-                        s.push_str(&part);
-                        pat.push_str(&part);
-                        if part.contains(&['*', '?', '['][..]) {
-                            is_glob = true;
+                        let part = part.eval(env).await?;
+                        fields.last_mut().unwrap().push_bareword(&part);
+                    }
+                    WordPart::Substitution(_) | WordPart::Var(_) => {
+                        let part = part.eval(env).await?;
+                        if let Some(ifs) = ifs {
+                            split_fields(&mut fields, &part, ifs);
+                        } else {
+                            fields.last_mut().unwrap().push_quoted(&part);
                         }
                     }
-                    WordPart::Substitution(_)
-                    | WordPart::Var(_)
+                    WordPart::QuotedSubstitution(_)
+                    | WordPart::QuotedVar(_)
                     | WordPart::DoubleQuoted(_)
                     | WordPart::SingleQuoted(_) => {
-                        let part = part.eval(env).await;
-                        s.push_str(&part);
-                        pat.push_str(&glob::Pattern::escape(&part));
+                        let part = part.eval(env).await?;
+                        fields.last_mut().unwrap().push_quoted(&part);
                     }
                 }
             }
             if initial_bareword {
-                s = expand_home(&s)?;
-                pat = expand_home(&pat)?;
-            }
-            if is_glob {
-                let mut found = false;
-                for file in glob::glob_with(&pat, opts)? {
-                    let file = file?;
-                    let s = file.to_str().unwrap();
-                    if s == "."
-                        || s == ".."
-                        || s.ends_with("/.")
-                        || s.ends_with("/..")
-                    {
-                        continue;
-                    }
-                    found = true;
-                    expanded_words.push(s.to_string());
-                }
-                if !found {
-                    anyhow::bail!("no matches for {}", s);
-                }
-            } else {
-                expanded_words.push(s);
+                let field = &mut fields[0];
+                field.s = expand_home(&field.s, env)?;
+                field.pat = expand_home(&field.pat, env)?;
+            }
+            for field in fields {
+                field.expand(opts, &mut expanded_words)?;
             }
         }
         Ok(expanded_words)
     }
 
+    // each part of the word, labeled with how it will be expanded
+    fn describe(&self) -> String {
+        if self.parts.is_empty() {
+            return "''".to_string();
+        }
+        self.parts
+            .iter()
+            .map(WordPart::describe)
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+
     fn build_ast(pair: pest::iterators::Pair<Rule>) -> Self {
         assert!(matches!(
             pair.as_rule(),
@@ -354,42 +529,184 @@ impl Word {
     }
 }
 
+// what $IFS is treated as when it isn't set
+const DEFAULT_IFS: &str = " \t\n";
+
+// a single word in the result of expanding a word, along with the pattern
+// that it matches if it is a glob. quoted text and the results of expansions
+// are escaped in the pattern, so only literal glob characters are special.
+#[derive(Default)]
+struct Field {
+    s: String,
+    pat: String,
+    is_glob: bool,
+    // unquoted expansions which expand to nothing don't produce a word at
+    // all, unlike ""
+    present: bool,
+}
+
+impl Field {
+    fn push_bareword(&mut self, s: &str) {
+        self.s.push_str(s);
+        self.pat.push_str(s);
+        if s.contains(&['*', '?', '['][..]) {
+            self.is_glob = true;
+        }
+        self.present = true;
+    }
+
+    fn push_quoted(&mut self, s: &str) {
+        self.s.push_str(s);
+        self.pat.push_str(&glob::Pattern::escape(s));
+        self.present = true;
+    }
+
+    fn expand(
+        self,
+        opts: glob::MatchOptions,
+        words: &mut Vec<String>,
+    ) -> Result<()> {
+        if !self.present {
+            return Ok(());
+        }
+        // a lone [ (as for the test builtin) isn't a valid pattern, and is
+        // left alone like other shells do
+        if self.is_glob && glob::Pattern::new(&self.pat).is_ok() {
+            let mut found = false;
+            for file in glob::glob_with(&self.pat, opts)? {
+                let file = file?;
+                let s = file.to_str().unwrap();
+                if s == "."
+                    || s == ".."
+                    || s.ends_with("/.")
+                    || s.ends_with("/..")
+                {
+                    continue;
+                }
+                found = true;
+                words.push(s.to_string());
+            }
+            if !found {
+                anyhow::bail!("no matches for {}", self.s);
+            }
+        } else {
+            words.push(self.s);
+        }
+        Ok(())
+    }
+}
+
+// splits the result of an unquoted expansion on the characters in $IFS, the
+// way posix shells do. the text before the first separator joins onto the
+// end of the current field, and the text after the last one starts a field
+// that later parts of the word join onto. whitespace in $IFS is trimmed and
+// repeated whitespace only separates fields once, but every other character
+// in $IFS separates a field, even an empty one.
+fn split_fields(fields: &mut Vec<Field>, s: &str, ifs: &str) {
+    let is_space =
+        |c: &char| ifs.contains(*c) && matches!(c, ' ' | '\t' | '\n');
+    let is_delim = |c: &char| ifs.contains(*c) && !is_space(c);
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_space(&c) || is_delim(&c) {
+            let mut delim = is_delim(&c);
+            while chars.next_if(is_space).is_some() {}
+            if !delim && chars.next_if(is_delim).is_some() {
+                delim = true;
+                while chars.next_if(is_space).is_some() {}
+            }
+            let field = fields.last_mut().unwrap();
+            if delim {
+                field.present = true;
+            }
+            if field.present {
+                fields.push(Field::default());
+            }
+        } else {
+            fields
+                .last_mut()
+                .unwrap()
+                .push_quoted(c.encode_utf8(&mut [0; 4]));
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum WordPart {
     Alternation(Vec<Word>),
     Substitution(String),
     Var(String),
+    // expansions inside of double quotes, which aren't split into words
+    QuotedSubstitution(String),
+    QuotedVar(String),
     Bareword(String),
     DoubleQuoted(String),
     SingleQuoted(String),
 }
 
 impl WordPart {
-    async fn eval(self, env: &Env) -> String {
+    fn describe(&self) -> String {
         match self {
+            Self::Alternation(words) => format!(
+                "{{{}}}",
+                words
+                    .iter()
+                    .map(Word::describe)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Substitution(commands) => format!("$({})", commands),
+            Self::Var(name) => format!("${{{}}}", name),
+            Self::QuotedSubstitution(commands) => {
+                format!("\"$({})\"", commands)
+            }
+            Self::QuotedVar(name) => format!("\"${{{}}}\"", name),
+            Self::Bareword(s) => {
+                if s.contains(&['*', '?', '['][..]) {
+                    format!("glob {:?}", s)
+                } else {
+                    format!("{:?}", s)
+                }
+            }
+            Self::DoubleQuoted(s) | Self::SingleQuoted(s) => {
+                format!("{:?}", s)
+            }
+        }
+    }
+
+    // a substitution which can't be run at all (because it doesn't parse,
+    // or because the nbsh binary has been replaced since the shell started)
+    // needs to be reported rather than treated as empty output
+    async fn eval(self, env: &Env) -> Result<String> {
+        Ok(match self {
             Self::Alternation(_) => unreachable!(),
-            Self::Substitution(commands) => {
-                let mut cmd = tokio::process::Command::new(
-                    crate::info::current_exe().unwrap(),
-                );
-                cmd.args(&["-c", &commands]);
-                cmd.stdin(std::process::Stdio::inherit());
-                cmd.stderr(std::process::Stdio::inherit());
+            Self::Substitution(commands)
+            | Self::QuotedSubstitution(commands) => {
                 let mut out =
-                    String::from_utf8(cmd.output().await.unwrap().stdout)
-                        .unwrap();
+                    match crate::runner::substitute(&commands, env).await {
+                        Some(out) => out?,
+                        None => {
+                            let mut cmd = tokio::process::Command::new(
+                                crate::info::current_exe()?,
+                            );
+                            cmd.args(&["-c", &commands]);
+                            cmd.stdin(std::process::Stdio::inherit());
+                            cmd.stderr(std::process::Stdio::inherit());
+                            String::from_utf8(cmd.output().await?.stdout)?
+                        }
+                    };
                 if out.ends_with('\n') {
                     out.truncate(out.len() - 1);
                 }
                 out
             }
-            Self::Var(name) => {
+            Self::Var(name) | Self::QuotedVar(name) => {
                 env.var(&name).unwrap_or_else(|| "".to_string())
             }
             Self::Bareword(s)
             | Self::DoubleQuoted(s)
             | Self::SingleQuoted(s) => s,
-        }
+        })
     }
 
     fn build_ast(
@@ -399,23 +716,30 @@ impl WordPart {
             pair.as_rule(),
             Rule::word_part | Rule::alternation_word_part
         ));
-        pair.into_inner().map(|pair| match pair.as_rule() {
+        let quoted = pair.as_str().starts_with('"');
+        pair.into_inner().map(move |pair| match pair.as_rule() {
             Rule::substitution => {
                 let commands = pair.into_inner().next().unwrap();
                 assert!(matches!(commands.as_rule(), Rule::commands));
-                Self::Substitution(commands.as_str().to_string())
+                let commands = commands.as_str().to_string();
+                if quoted {
+                    Self::QuotedSubstitution(commands)
+                } else {
+                    Self::Substitution(commands)
+                }
             }
             Rule::var => {
                 let s = pair.as_str();
                 let inner = s.strip_prefix('$').unwrap();
-                Self::Var(
-                    inner
-                        .strip_prefix('{')
-                        .map_or(inner, |inner| {
-                            inner.strip_suffix('}').unwrap()
-                        })
-                        .to_string(),
-                )
+                let name = inner
+                    .strip_prefix('{')
+                    .map_or(inner, |inner| inner.strip_suffix('}').unwrap())
+                    .to_string();
+                if quoted {
+                    Self::QuotedVar(name)
+                } else {
+                    Self::Var(name)
+                }
             }
             Rule::bareword | Rule::alternation_bareword => {
                 Self::Bareword(strip_escape(pair.as_str()))
@@ -442,6 +766,10 @@ struct Redirect {
 }
 
 impl Redirect {
+    fn describe(&self) -> String {
+        format!("{} {} {}", self.from, self.dir, self.to.describe())
+    }
+
     fn build_ast(pair: pest::iterators::Pair<Rule>) -> Self {
         assert!(matches!(pair.as_rule(), Rule::redirect));
         let mut iter = pair.into_inner();
@@ -481,19 +809,19 @@ impl Redirect {
                 if let Some(fd) = s.strip_prefix('&') {
                     super::RedirectTarget::Fd(parse_fd(fd))
                 } else {
-                    let to = self.to.eval(env).await?;
+                    let to = self.to.eval_unsplit(env).await?;
                     assert_eq!(to.len(), 1); // TODO
                     let to = &to[0];
                     super::RedirectTarget::File(std::path::PathBuf::from(to))
                 }
             } else {
-                let to = self.to.eval(env).await?;
+                let to = self.to.eval_unsplit(env).await?;
                 assert_eq!(to.len(), 1); // TODO
                 let to = &to[0];
                 super::RedirectTarget::File(std::path::PathBuf::from(to))
             }
         } else {
-            let to = self.to.eval(env).await?;
+            let to = self.to.eval_unsplit(env).await?;
             assert_eq!(to.len(), 1); // TODO
             let to = &to[0];
             super::RedirectTarget::File(std::path::PathBuf::from(to))
@@ -506,6 +834,34 @@ impl Redirect {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Assignment {
+    var: String,
+    value: Word,
+}
+
+impl Assignment {
+    fn build_ast(pair: pest::iterators::Pair<Rule>) -> Self {
+        assert!(matches!(pair.as_rule(), Rule::assignment));
+        let mut iter = pair.into_inner();
+
+        let var = iter.next().unwrap();
+        assert!(matches!(var.as_rule(), Rule::assignment_var));
+        let value = iter
+            .next()
+            .map_or_else(|| Word { parts: vec![] }, Word::build_ast);
+
+        Self {
+            var: var.as_str().to_string(),
+            value,
+        }
+    }
+
+    async fn eval(self, env: &Env) -> Result<(String, String)> {
+        Ok((self.var, self.value.eval_unsplit(env).await?.join(" ")))
+    }
+}
+
 fn strip_escape(s: &str) -> String {
     let mut new = String::new();
     let mut escape = false;
@@ -553,7 +909,7 @@ fn parse_fd(s: &str) -> std::os::unix::io::RawFd {
     }
 }
 
-fn expand_home(dir: &str) -> Result<String> {
+pub fn expand_home(dir: &str, env: &Env) -> Result<String> {
     if dir.starts_with('~') {
         let path: std::path::PathBuf = dir.into();
         if let std::path::Component::Normal(prefix) =
@@ -565,7 +921,11 @@ fn expand_home(dir: &str) -> Result<String> {
             } else {
                 Some(std::ffi::OsStr::from_bytes(&prefix_bytes[1..]))
             };
-            if let Some(home) = home(name) {
+            let home = name
+                .and_then(std::ffi::OsStr::to_str)
+                .and_then(|name| env.named_dir(name))
+                .or_else(|| home(name));
+            if let Some(home) = home {
                 Ok(home
                     .join(path.strip_prefix(prefix).unwrap())
                     .to_str()
@@ -573,7 +933,7 @@ fn expand_home(dir: &str) -> Result<String> {
                     .to_string())
             } else {
                 anyhow::bail!(
-                    "no such user: {}",
+                    "no such user or named directory: {}",
                     name.map(std::ffi::OsStr::to_string_lossy)
                         .as_ref()
                         .unwrap_or(&std::borrow::Cow::Borrowed("(deleted)"))
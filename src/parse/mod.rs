@@ -16,6 +16,7 @@ pub struct Exe {
     exe: std::path::PathBuf,
     args: Vec<String>,
     redirects: Vec<Redirect>,
+    env: Vec<(String, String)>,
 }
 
 impl Exe {
@@ -27,23 +28,59 @@ impl Exe {
         &self.args
     }
 
+    pub fn args_mut(&mut self) -> &mut [String] {
+        &mut self.args
+    }
+
     pub fn append(&mut self, other: Self) {
         let Self {
             exe: _exe,
             args,
             redirects,
+            env,
         } = other;
         self.args.extend(args);
         self.redirects.extend(redirects);
+        self.env.extend(env);
+    }
+
+    // the rest of this command, if it starts with the same executable and
+    // arguments as the given command
+    pub fn strip_prefix(&self, prefix: &Self) -> Option<Self> {
+        if self.exe != prefix.exe || !self.args.starts_with(&prefix.args) {
+            return None;
+        }
+        Some(Self {
+            exe: self.exe.clone(),
+            args: self.args[prefix.args.len()..].to_vec(),
+            redirects: self.redirects.clone(),
+            env: self.env.clone(),
+        })
     }
 
     pub fn redirects(&self) -> &[Redirect] {
         &self.redirects
     }
 
+    // variables which should only be set for this command
+    pub fn env(&self) -> &[(String, String)] {
+        &self.env
+    }
+
+    pub fn set_exe(&mut self, exe: std::path::PathBuf) {
+        self.exe = exe;
+    }
+
     pub fn shift(&mut self) {
         self.exe = std::path::PathBuf::from(self.args.remove(0));
     }
+
+    // the inverse of shift, making the current executable the first
+    // argument to a new one
+    pub fn unshift(&mut self, exe: std::path::PathBuf) {
+        let prev = std::mem::replace(&mut self.exe, exe);
+        self.args.insert(0, prev.to_string_lossy().into_owned());
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -53,6 +90,16 @@ pub struct Redirect {
     pub dir: Direction,
 }
 
+impl std::fmt::Display for Redirect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ", self.from, self.dir)?;
+        match &self.to {
+            RedirectTarget::Fd(fd) => write!(f, "&{}", fd),
+            RedirectTarget::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub enum RedirectTarget {
     Fd(std::os::unix::io::RawFd),
@@ -66,6 +113,16 @@ pub enum Direction {
     Append,
 }
 
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::In => "<",
+            Self::Out => ">",
+            Self::Append => ">>",
+        })
+    }
+}
+
 impl Direction {
     pub fn open(
         self,
@@ -22,6 +22,8 @@ macro_rules! p {
         Pipeline {
             exes: vec![$($exes),*],
             span: $span,
+            time: false,
+            negate: false,
         }
     };
 }
@@ -40,6 +42,7 @@ macro_rules! e {
             exe: $word,
             args: vec![],
             redirects: vec![],
+            assignments: vec![],
         }
     };
     ($word:expr, $($args:expr),*) => {
@@ -47,6 +50,7 @@ macro_rules! e {
             exe: $word,
             args: vec![$($args),*],
             redirects: vec![],
+            assignments: vec![],
         }
     };
     ($word:expr ; $($redirects:expr),*) => {
@@ -54,6 +58,7 @@ macro_rules! e {
             exe: $word,
             args: vec![],
             redirects: vec![$($redirects),*],
+            assignments: vec![],
         }
     };
     ($word:expr, $($args:expr),* ; $($redirects:expr),*) => {
@@ -61,6 +66,7 @@ macro_rules! e {
             exe: $word,
             args: vec![$($args),*],
             redirects: vec![$($redirects),*],
+            assignments: vec![],
         }
     };
 }
@@ -71,6 +77,7 @@ macro_rules! ee {
             exe: std::path::PathBuf::from($exe.to_string()),
             args: vec![],
             redirects: vec![],
+            env: vec![],
         }
     };
     ($exe:expr, $($args:expr),*) => {
@@ -81,6 +88,7 @@ macro_rules! ee {
                 .map(|s| s.to_string())
                 .collect(),
             redirects: vec![],
+            env: vec![],
         }
     };
 }
@@ -120,6 +128,12 @@ macro_rules! wpv {
     };
 }
 
+macro_rules! wpqv {
+    ($var:literal) => {
+        WordPart::QuotedVar($var.to_string())
+    };
+}
+
 macro_rules! wpb {
     ($bareword:expr) => {
         WordPart::Bareword($bareword.to_string())
@@ -238,6 +252,40 @@ fn test_basic() {
     parse_eq!("foo \"\"", cs!(p!((0, 6), e!(w!("foo"), w!()))));
 }
 
+#[test]
+fn test_empty() {
+    let empty = Commands { commands: vec![] };
+    parse_eq!("", empty);
+    parse_eq!("  \n", empty);
+    parse_eq!("#!/usr/bin/env nbsh\n# nothing yet\n", empty);
+    parse_eq!(
+        "#!/usr/bin/env nbsh\nfoo\n",
+        cs!(p!((20, 23), e!(w!("foo"))))
+    );
+}
+
+#[test]
+fn test_time() {
+    let mut timed = p!((0, 14), e!(w!("foo")), e!(w!("bar")));
+    timed.time = true;
+    parse_eq!("time foo | bar", cs!(timed));
+    parse_eq!("time", cs!(p!((0, 4), e!(w!("time")))));
+    parse_eq!("timeout 5", cs!(p!((0, 9), e!(w!("timeout"), w!("5")))));
+    parse_eq!("foo time", cs!(p!((0, 8), e!(w!("foo"), w!("time")))));
+}
+
+#[test]
+fn test_negate() {
+    let mut negated = p!((0, 11), e!(w!("foo")), e!(w!("bar")));
+    negated.negate = true;
+    parse_eq!("! foo | bar", cs!(negated.clone()));
+    negated.span = (0, 16);
+    negated.time = true;
+    parse_eq!("time ! foo | bar", cs!(negated));
+    parse_eq!("!foo", cs!(p!((0, 4), e!(w!("!foo")))));
+    parse_eq!("foo !", cs!(p!((0, 5), e!(w!("foo"), w!("!")))));
+}
+
 #[test]
 fn test_whitespace() {
     parse_eq!("   foo    ", cs!(p!((3, 6), e!(w!("foo")))));
@@ -283,6 +331,26 @@ fn test_whitespace() {
     );
 }
 
+#[test]
+fn test_newlines() {
+    parse_eq!(
+        "foo\nbar baz\n",
+        cs!(
+            p!((0, 3), e!(w!("foo"))),
+            p!((4, 11), e!(w!("bar"), w!("baz")))
+        )
+    );
+    parse_eq!(
+        "foo # comment\n\n  bar",
+        cs!(p!((0, 3), e!(w!("foo"))), p!((17, 20), e!(w!("bar"))))
+    );
+    parse_eq!("foo |\nbar", cs!(p!((0, 9), e!(w!("foo")), e!(w!("bar")))));
+    parse_eq!(
+        "echo 'foo\nbar'",
+        cs!(p!((0, 15), e!(w!("echo"), w!(wps!("foo\nbar")))))
+    );
+}
+
 #[test]
 fn test_redirect() {
     parse_eq!(
@@ -317,6 +385,51 @@ fn test_redirect() {
     );
 }
 
+#[test]
+fn test_assignment() {
+    parse_eq!(
+        "FOO=bar baz",
+        cs!(p!(
+            (0, 11),
+            Exe {
+                exe: w!("baz"),
+                args: vec![],
+                redirects: vec![],
+                assignments: vec![Assignment {
+                    var: "FOO".to_string(),
+                    value: w!("bar"),
+                }],
+            }
+        ))
+    );
+    parse_eq!(
+        "A=1 B= C=\"x y\" cmd arg",
+        cs!(p!(
+            (0, 22),
+            Exe {
+                exe: w!("cmd"),
+                args: vec![w!("arg")],
+                redirects: vec![],
+                assignments: vec![
+                    Assignment {
+                        var: "A".to_string(),
+                        value: w!("1"),
+                    },
+                    Assignment {
+                        var: "B".to_string(),
+                        value: w!(),
+                    },
+                    Assignment {
+                        var: "C".to_string(),
+                        value: w!(wpd!("x y")),
+                    },
+                ],
+            }
+        ))
+    );
+    parse_eq!("foo A=1", cs!(p!((0, 7), e!(w!("foo"), w!("A=1")))));
+}
+
 #[test]
 fn test_escape() {
     parse_eq!("foo\\ bar", cs!(p!((0, 8), e!(w!("foo bar")))));
@@ -337,13 +450,13 @@ fn test_escape() {
 fn test_parts() {
     parse_eq!(
         "echo \"$HOME/bin\"",
-        cs!(p!((0, 16), e!(w!("echo"), w!(wpv!("HOME"), wpd!("/bin")))))
+        cs!(p!((0, 16), e!(w!("echo"), w!(wpqv!("HOME"), wpd!("/bin")))))
     );
     parse_eq!(
         "echo \"dir: $HOME/bin\"",
         cs!(p!(
             (0, 21),
-            e!(w!("echo"), w!(wpd!("dir: "), wpv!("HOME"), wpd!("/bin")))
+            e!(w!("echo"), w!(wpd!("dir: "), wpqv!("HOME"), wpd!("/bin")))
         ))
     );
     parse_eq!(
@@ -430,7 +543,7 @@ fn test_alternation() {
             e!(
                 w!("echo"),
                 w!(
-                    wpa!(w!(wpv!("foo")), w!(wpv!("HOME"), wpd!("/bin"))),
+                    wpa!(w!(wpv!("foo")), w!(wpqv!("HOME"), wpd!("/bin"))),
                     wpb!("."),
                     wpa!(w!(wps!("r"), wpd!("s")), w!("c"))
                 )
@@ -500,8 +613,135 @@ async fn test_eval_glob() {
     eval_fails!("echo *.{toml,doesnotexist}", env);
 }
 
+#[tokio::main]
+#[test]
+async fn test_eval_named_dir() {
+    let mut env = Env::new().unwrap();
+    env.set_named_dir("work", std::path::Path::new("/src/work"));
+
+    eval_eq!("echo ~work/foo", env, ep!(ee!("echo", "/src/work/foo")));
+    eval_eq!("echo '~work/foo'", env, ep!(ee!("echo", "~work/foo")));
+    eval_fails!("echo ~doesnotexist/foo", env);
+}
+
+#[tokio::main]
+#[test]
+async fn test_eval_split() {
+    let mut env = Env::new().unwrap();
+    env.set_var("foo", " a  b\tc\n");
+    env.set_var("empty", "");
+
+    eval_eq!("echo $foo", env, ep!(ee!("echo", "a", "b", "c")));
+    eval_eq!("echo \"$foo\"", env, ep!(ee!("echo", " a  b\tc\n")));
+    eval_eq!(
+        "echo x${foo}y",
+        env,
+        ep!(ee!("echo", "x", "a", "b", "c", "y"))
+    );
+    eval_eq!("echo $empty", env, ep!(ee!("echo")));
+    eval_eq!("echo \"$empty\"", env, ep!(ee!("echo", "")));
+    eval_eq!(
+        "FOO=$foo echo",
+        env,
+        ep!(super::super::Exe {
+            exe: "echo".into(),
+            args: vec![],
+            redirects: vec![],
+            env: vec![("FOO".to_string(), " a  b\tc\n".to_string())],
+        })
+    );
+
+    env.set_var("IFS", ": ");
+    env.set_var("foo", "a::b : c:");
+    eval_eq!("echo $foo", env, ep!(ee!("echo", "a", "", "b", "c")));
+    env.set_var("foo", ":a");
+    eval_eq!("echo $foo", env, ep!(ee!("echo", "", "a")));
+
+    env.set_var("IFS", "");
+    env.set_var("foo", "a b");
+    eval_eq!("echo $foo", env, ep!(ee!("echo", "a b")));
+}
+
 #[test]
 fn test_deserialize() {
     deserialize_eq!("foo", e!(w!("foo")));
     deserialize_eq!("foo bar baz", e!(w!("foo"), w!("bar"), w!("baz")));
 }
+
+#[test]
+fn test_tree() {
+    assert_eq!(
+        Commands::parse("FOO=1 ls -l *.rs 2>>log | wc $x\"y\"")
+            .unwrap()
+            .tree(),
+        vec![
+            "pipeline",
+            "  command \"ls\"",
+            "    set FOO = \"1\"",
+            "    arg \"-l\"",
+            "    arg glob \"*.rs\"",
+            "    redirect 2 >> \"log\"",
+            "  command \"wc\"",
+            "    arg ${x} + \"y\"",
+        ]
+    );
+    assert_eq!(
+        Commands::parse("for x in a{b,c}\necho $(pwd)\nend")
+            .unwrap()
+            .tree(),
+        vec![
+            "for x",
+            "  in \"a\" + {\"b\", \"c\"}",
+            "pipeline",
+            "  command \"echo\"",
+            "    arg $(pwd)",
+            "end",
+        ]
+    );
+    assert_eq!(
+        Commands::parse("while true\nif x; break; else; continue; end\nend")
+            .unwrap()
+            .tree(),
+        vec![
+            "while",
+            "  pipeline",
+            "    command \"true\"",
+            "if",
+            "  pipeline",
+            "    command \"x\"",
+            "break",
+            "else",
+            "continue",
+            "end",
+            "end",
+        ]
+    );
+    // only the whole word is a keyword
+    assert_eq!(
+        Commands::parse("breakfast").unwrap().tree(),
+        vec!["pipeline", "  command \"breakfast\""]
+    );
+}
+
+#[test]
+fn test_exe_names() {
+    assert_eq!(
+        Commands::parse("git rev-parse HEAD | tr a-z A-Z; 'ls' -l")
+            .unwrap()
+            .exe_names(),
+        Some(vec!["git".to_string(), "tr".to_string(), "ls".to_string()])
+    );
+    assert_eq!(
+        Commands::parse("a\"b\"'c' $(pwd)").unwrap().exe_names(),
+        Some(vec!["abc".to_string()])
+    );
+    assert_eq!(Commands::parse("$x foo").unwrap().exe_names(), None);
+    assert_eq!(Commands::parse("$(which ls)").unwrap().exe_names(), None);
+    assert_eq!(Commands::parse("l* foo").unwrap().exe_names(), None);
+    assert_eq!(Commands::parse("~/bin/foo").unwrap().exe_names(), None);
+    assert_eq!(Commands::parse("{a,b}").unwrap().exe_names(), None);
+    assert_eq!(
+        Commands::parse("if true; echo; end").unwrap().exe_names(),
+        None
+    );
+}
@@ -9,6 +9,7 @@ pub use futures_util::stream::TryStreamExt as _;
 pub use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
 
 pub use std::os::unix::ffi::{OsStrExt as _, OsStringExt as _};
+pub use std::os::unix::fs::PermissionsExt as _;
 pub use std::os::unix::io::{AsRawFd as _, FromRawFd as _, IntoRawFd as _};
 pub use std::os::unix::process::ExitStatusExt as _;
 pub use users::os::unix::UserExt as _;
@@ -2,6 +2,8 @@ use crate::runner::prelude::*;
 
 pub mod command;
 pub use command::{Child, Command, File, Io};
+mod printf;
+mod test;
 
 type Builtin = &'static (dyn for<'a> Fn(
     crate::parse::Exe,
@@ -17,24 +19,85 @@ static BUILTINS: once_cell::sync::Lazy<
 > = once_cell::sync::Lazy::new(|| {
     let mut builtins = std::collections::HashMap::new();
     builtins.insert("cd", &cd as Builtin);
+    builtins.insert("z", &jump);
+    builtins.insert("j", &jump);
     builtins.insert("set", &set);
     builtins.insert("unset", &unset);
+    builtins.insert("export", &export);
+    builtins.insert("local", &local);
     builtins.insert("echo", &echo);
     builtins.insert("read", &read);
     builtins.insert("and", &and);
     builtins.insert("or", &or);
     builtins.insert("command", &command);
     builtins.insert("builtin", &builtin);
+    builtins.insert("hash", &hash);
+    builtins.insert("history", &history);
+    builtins.insert("colors", &colors);
+    builtins.insert("type", &type_);
+    builtins.insert("test", &test);
+    builtins.insert("[", &test);
+    builtins.insert("exit", &exit);
+    builtins.insert("true", &true_);
+    builtins.insert("false", &false_);
+    builtins.insert("printf", &printf);
+    builtins.insert("source", &source);
+    builtins.insert(".", &source);
+    builtins.insert("kill", &kill);
+    builtins.insert("wait", &wait);
     builtins
 });
 
+pub fn is_builtin(exe: &std::path::Path) -> bool {
+    exe.to_str().map_or(false, |s| BUILTINS.contains_key(s))
+}
+
+pub fn is_source(exe: &std::path::Path) -> bool {
+    matches!(exe.to_str(), Some("source" | "."))
+}
+
+// cd can't fail to start the way that a binary can, so the runner checks
+// this before running it instead, so that it can offer a correction
+pub fn cd_dir_missing(exe: &crate::parse::Exe, env: &Env) -> bool {
+    exe.exe() == std::path::Path::new("cd")
+        && exe.args().get(0).map_or(false, |dir| {
+            !dir.is_empty()
+                && dir != "-"
+                && cdpath_dir(dir, env.var("CDPATH").as_deref()).is_none()
+                && !env.pwd().join(dir).exists()
+        })
+}
+
+// builtins which change the environment, the current directory, or whether
+// the runner keeps going, either directly or by running another builtin
+pub fn changes_state(exe: &str) -> bool {
+    matches!(
+        exe,
+        "cd" | "z"
+            | "j"
+            | "set"
+            | "unset"
+            | "export"
+            | "local"
+            | "read"
+            | "hash"
+            | "colors"
+            | "exit"
+            | "source"
+            | "."
+            | "and"
+            | "or"
+            | "builtin"
+    )
+}
+
 macro_rules! bail {
     ($cfg:expr, $exe:expr, $msg:expr $(,)?) => {
         $cfg.io().write_stderr(
             format!("{}: {}\n", $exe.exe().display(), $msg).as_bytes()
         )
         .unwrap();
-        return std::process::ExitStatus::from_raw(1 << 8);
+        return crate::status::failure();
     };
     ($cfg:expr, $exe:expr, $msg:expr, $($arg:tt)*) => {
         $cfg.io().write_stderr(
@@ -44,7 +107,7 @@ macro_rules! bail {
         $cfg.io().write_stderr(format!($msg, $($arg)*).as_bytes())
             .unwrap();
         $cfg.io().write_stderr(b"\n").unwrap();
-        return std::process::ExitStatus::from_raw(1 << 8);
+        return crate::status::failure();
     };
 }
 
@@ -57,12 +120,24 @@ fn cd(
 ) -> Result<command::Child> {
     let prev_pwd = env.prev_pwd();
     let home = env.var("HOME");
+    let cdpath = env.var("CDPATH");
     Ok(command::Child::new_task(move || {
+        let mut print = false;
         let dir = if let Some(dir) = exe.args().get(0) {
             if dir.is_empty() {
                 ".".to_string().into()
             } else if dir == "-" {
-                prev_pwd
+                print = true;
+                if let Some(prev_pwd) = prev_pwd {
+                    prev_pwd
+                } else {
+                    bail!(cfg, exe, "no previous directory");
+                }
+            } else if let Some(found) = cdpath_dir(dir, cdpath.as_deref()) {
+                // the directory isn't where it looks like it should be, so
+                // say where it actually was
+                print = true;
+                found
             } else {
                 dir.into()
             }
@@ -83,10 +158,128 @@ fn cd(
                 dir.display()
             );
         }
-        std::process::ExitStatus::from_raw(0)
+        if print {
+            if let Err(e) = cfg
+                .io()
+                .write_stdout(format!("{}\n", dir.display()).as_bytes())
+            {
+                bail!(cfg, exe, e);
+            }
+        }
+        crate::status::success()
+    }))
+}
+
+// changes to the most frecently visited directory matching all of the
+// given fragments, or with -l, lists the matching directories instead
+#[allow(clippy::unnecessary_wraps)]
+fn jump(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        let mut args = exe.args();
+        let list = args.first().map(String::as_str) == Some("-l");
+        if list {
+            args = &args[1..];
+        }
+        if args.is_empty() && !list {
+            bail!(
+                cfg,
+                exe,
+                "usage: {} [-l] fragment...",
+                exe.exe().display()
+            );
+        }
+
+        let mut dirs = crate::shell::dirs::Dirs::load();
+        dirs.retain_existing();
+        let matches =
+            dirs.matches(&args.join(" "), crate::shell::dirs::now());
+
+        if list {
+            // best match last, like z does, so that it is closest to the
+            // prompt
+            for (dir, score) in matches.iter().rev() {
+                if let Err(e) = cfg.io().write_stdout(
+                    format!("{:<10.2} {}\n", score, dir.display()).as_bytes(),
+                ) {
+                    bail!(cfg, exe, e);
+                }
+            }
+            return crate::status::success();
+        }
+
+        let dir = if let Some((dir, _)) = matches.first() {
+            dir
+        } else {
+            bail!(cfg, exe, "no matching directory: {}", args.join(" "));
+        };
+        if let Err(e) = std::env::set_current_dir(dir) {
+            bail!(
+                cfg,
+                exe,
+                "{}: {}",
+                crate::format::io_error(&e),
+                dir.display()
+            );
+        }
+        crate::status::success()
     }))
 }
 
+// looks for a relative directory under each of the directories in CDPATH in
+// turn, the way other shells do. an empty entry stands for the current
+// directory, which is where cd looks when nothing in CDPATH matches anyway.
+fn cdpath_dir(dir: &str, cdpath: Option<&str>) -> Option<std::path::PathBuf> {
+    let cdpath = cdpath?;
+    let path = std::path::Path::new(dir);
+    // paths starting with . or .. are explicitly relative to the current
+    // directory
+    if path.is_absolute()
+        || matches!(
+            path.components().next(),
+            Some(
+                std::path::Component::CurDir
+                    | std::path::Component::ParentDir
+            )
+        )
+    {
+        return None;
+    }
+    for base in cdpath.split(':') {
+        if base.is_empty() || base == "." {
+            if path.is_dir() {
+                return None;
+            }
+        } else {
+            let candidate = std::path::Path::new(base).join(path);
+            if candidate.is_dir() {
+                return Some(candidate);
+            }
+        }
+    }
+    None
+}
+
+#[test]
+fn test_cdpath_dir() {
+    let base = std::env::temp_dir()
+        .join(format!("nbsh-test-cdpath-{}", crate::info::pid()));
+    std::fs::create_dir_all(base.join("project")).unwrap();
+    let cdpath = format!("/nonexistent:{}", base.display());
+    assert_eq!(
+        cdpath_dir("project", Some(&cdpath)),
+        Some(base.join("project"))
+    );
+    assert_eq!(cdpath_dir("missing", Some(&cdpath)), None);
+    assert_eq!(cdpath_dir("./project", Some(&cdpath)), None);
+    assert_eq!(cdpath_dir(&base.display().to_string(), Some(&cdpath)), None);
+    assert_eq!(cdpath_dir("project", None), None);
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn set(
     exe: crate::parse::Exe,
@@ -105,8 +298,8 @@ fn set(
             bail!(cfg, exe, "usage: set key value");
         };
 
-        std::env::set_var(k, v);
-        std::process::ExitStatus::from_raw(0)
+        set_shell_var(k, v);
+        crate::status::success()
     }))
 }
 
@@ -124,10 +317,142 @@ fn unset(
         };
 
         std::env::remove_var(k);
-        std::process::ExitStatus::from_raw(0)
+        std::env::remove_var(Env::local_var(k));
+        crate::status::success()
     }))
 }
 
+#[allow(clippy::unnecessary_wraps)]
+fn export(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        if exe.args().is_empty() {
+            // variables aren't required to be utf8
+            let mut vars: Vec<_> = std::env::vars_os()
+                .filter(|(k, _)| !k.as_bytes().starts_with(b"__NBSH_"))
+                .collect();
+            vars.sort();
+            for (k, v) in vars {
+                if let Err(e) = cfg.io().write_stdout(
+                    &[k.as_bytes(), b"=", v.as_bytes(), b"\n"].concat(),
+                ) {
+                    bail!(cfg, exe, e);
+                }
+            }
+            return crate::status::success();
+        }
+
+        for arg in exe.args() {
+            let (k, v) = if let Some((k, v)) = arg.split_once('=') {
+                (k, Some(std::ffi::OsString::from(v)))
+            } else {
+                (arg.as_str(), std::env::var_os(Env::local_var(arg)))
+            };
+            if k.is_empty() {
+                bail!(cfg, exe, "usage: export key[=value]...");
+            }
+            std::env::remove_var(Env::local_var(k));
+            if let Some(v) = v {
+                std::env::set_var(k, v);
+            }
+        }
+        crate::status::success()
+    }))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn local(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        if exe.args().is_empty() {
+            bail!(cfg, exe, "usage: local key[=value]...");
+        }
+        let mut scopes = LOCAL_SCOPES.lock().unwrap();
+        let scope = if let Some(scope) = scopes.last_mut() {
+            scope
+        } else {
+            bail!(cfg, exe, "can only be used inside a block");
+        };
+        for arg in exe.args() {
+            let (k, v) = arg.split_once('=').unwrap_or((arg, ""));
+            if k.is_empty() {
+                bail!(cfg, exe, "usage: local key[=value]...");
+            }
+            // only the value from before the block started matters
+            if !scope.iter().any(|var| var.name == k) {
+                scope.push(SavedVar {
+                    name: k.to_string(),
+                    exported: std::env::var_os(k),
+                    local: std::env::var_os(Env::local_var(k)),
+                });
+            }
+            std::env::set_var(Env::local_var(k), v);
+        }
+        crate::status::success()
+    }))
+}
+
+// set by the exit builtin, and checked by the runner after each command so
+// that it knows to stop early. it is never cleared, since the runner exits
+// soon after, and sourced files need to stop their caller too.
+static EXIT_STATUS: once_cell::sync::Lazy<
+    std::sync::Mutex<Option<std::process::ExitStatus>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(None));
+
+pub fn exit_status() -> Option<std::process::ExitStatus> {
+    *EXIT_STATUS.lock().unwrap()
+}
+
+// the values that variables declared with local had before each currently
+// running block started. the runner pushes and pops these along with its
+// own frames, so that the values can be restored when the block ends.
+static LOCAL_SCOPES: once_cell::sync::Lazy<
+    std::sync::Mutex<Vec<Vec<SavedVar>>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(vec![]));
+
+struct SavedVar {
+    name: String,
+    exported: Option<std::ffi::OsString>,
+    local: Option<std::ffi::OsString>,
+}
+
+pub fn push_scope() {
+    LOCAL_SCOPES.lock().unwrap().push(vec![]);
+}
+
+pub fn pop_scope() {
+    let saved = LOCAL_SCOPES.lock().unwrap().pop().unwrap_or_default();
+    for var in saved {
+        let local = Env::local_var(&var.name);
+        match var.exported {
+            Some(v) => std::env::set_var(&var.name, v),
+            None => std::env::remove_var(&var.name),
+        }
+        match var.local {
+            Some(v) => std::env::set_var(local, v),
+            None => std::env::remove_var(local),
+        }
+    }
+}
+
+// variables are local to the shell unless they have been exported, in which
+// case assigning to them updates the exported value. local variables shadow
+// exported ones, though.
+pub fn set_shell_var(k: &str, v: &str) {
+    let local = Env::local_var(k);
+    if std::env::var_os(&local).is_none() && std::env::var_os(k).is_some() {
+        std::env::set_var(k, v);
+    } else {
+        std::env::set_var(local, v);
+    }
+}
+
 // clippy can't tell that the type is necessary
 #[allow(clippy::unnecessary_wraps)]
 // mostly just for testing and ensuring that builtins work, i'll likely remove
@@ -144,24 +469,137 @@ fn echo(
                     cfg.io()
                         .write_stderr(format!("echo: {}", e).as_bytes())
                         .unwrap();
-                    return std::process::ExitStatus::from_raw(1 << 8);
+                    return crate::status::failure();
                 }
             };
         }
-        let count = exe.args().len();
-        for (i, arg) in exe.args().iter().enumerate() {
-            write_stdout!(arg.as_bytes());
-            if i == count - 1 {
-                write_stdout!(b"\n");
+        let mut newline = true;
+        let mut escapes = false;
+        let mut args = exe.args();
+        while let Some(arg) = args.first() {
+            let flags = if let Some(flags) = arg.strip_prefix('-') {
+                flags
             } else {
+                break;
+            };
+            if flags.is_empty()
+                || !flags.chars().all(|c| matches!(c, 'n' | 'e' | 'E'))
+            {
+                break;
+            }
+            for flag in flags.chars() {
+                match flag {
+                    'n' => newline = false,
+                    'e' => escapes = true,
+                    'E' => escapes = false,
+                    _ => unreachable!(),
+                }
+            }
+            args = &args[1..];
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            if i > 0 {
                 write_stdout!(b" ");
             }
+            if escapes {
+                let (bytes, stop) = unescape(arg);
+                write_stdout!(&bytes);
+                if stop {
+                    return crate::status::success();
+                }
+            } else {
+                write_stdout!(arg.as_bytes());
+            }
+        }
+        if newline {
+            write_stdout!(b"\n");
         }
 
-        std::process::ExitStatus::from_raw(0)
+        crate::status::success()
     }))
 }
 
+// interprets backslash escapes the way echo -e does. the returned bool is
+// true if a \c was found, which means that no further output should be
+// produced.
+fn unescape(s: &str) -> (Vec<u8>, bool) {
+    let mut bytes = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => bytes.push(b'\\'),
+            Some('a') => bytes.push(b'\x07'),
+            Some('b') => bytes.push(b'\x08'),
+            Some('c') => return (bytes, true),
+            Some('e' | 'E') => bytes.push(b'\x1b'),
+            Some('f') => bytes.push(b'\x0c'),
+            Some('n') => bytes.push(b'\n'),
+            Some('r') => bytes.push(b'\r'),
+            Some('t') => bytes.push(b'\t'),
+            Some('v') => bytes.push(b'\x0b'),
+            Some('0') => {
+                let mut val = 0_u32;
+                for _ in 0..3 {
+                    if let Some(digit) =
+                        chars.peek().and_then(|c| c.to_digit(8))
+                    {
+                        val = val * 8 + digit;
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                bytes.push((val & 0xff).try_into().unwrap());
+            }
+            Some('x') => {
+                let mut val = 0_u32;
+                let mut found = false;
+                for _ in 0..2 {
+                    if let Some(digit) =
+                        chars.peek().and_then(|c| c.to_digit(16))
+                    {
+                        val = val * 16 + digit;
+                        found = true;
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if found {
+                    bytes.push(val.try_into().unwrap());
+                } else {
+                    bytes.extend(b"\\x");
+                }
+            }
+            Some(c) => {
+                bytes.push(b'\\');
+                let mut buf = [0; 4];
+                bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    (bytes, false)
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(unescape("foo"), (b"foo".to_vec(), false));
+    assert_eq!(unescape("a\\tb\\n"), (b"a\tb\n".to_vec(), false));
+    assert_eq!(unescape("\\0101\\x42"), (b"AB".to_vec(), false));
+    assert_eq!(unescape("\\0"), (b"\0".to_vec(), false));
+    assert_eq!(unescape("\\xzz"), (b"\\xzz".to_vec(), false));
+    assert_eq!(unescape("a\\cb"), (b"a".to_vec(), true));
+    assert_eq!(unescape("\\q\\"), (b"\\q\\".to_vec(), false));
+    assert_eq!(unescape("é"), ("é".as_bytes().to_vec(), false));
+}
+
 #[allow(clippy::unnecessary_wraps)]
 fn read(
     exe: crate::parse::Exe,
@@ -182,8 +620,315 @@ fn read(
             }
         };
 
-        std::env::set_var(var, val);
-        std::process::ExitStatus::from_raw(if done { 1 << 8 } else { 0 })
+        set_shell_var(var, &val);
+        if done {
+            crate::status::failure()
+        } else {
+            crate::status::success()
+        }
+    }))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn hash(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let named_dirs = env.named_dirs();
+    let dir = exe.args().get(1).and_then(|arg| arg.split_once('=')).map(
+        |(name, dir)| {
+            (
+                name.to_string(),
+                crate::parse::ast::expand_home(dir, env)
+                    .map(|dir| env.pwd().join(dir)),
+            )
+        },
+    );
+    Ok(command::Child::new_task(move || {
+        if exe.args().get(0).map(String::as_str) != Some("-d") {
+            bail!(cfg, exe, "usage: hash -d [name=dir]");
+        }
+        match dir {
+            Some((name, Ok(dir))) => {
+                if name.is_empty() {
+                    bail!(cfg, exe, "usage: hash -d [name=dir]");
+                }
+                std::env::set_var(Env::named_dir_var(&name), dir);
+            }
+            Some((_, Err(e))) => {
+                bail!(cfg, exe, e);
+            }
+            None => {
+                if exe.args().len() > 1 {
+                    bail!(cfg, exe, "usage: hash -d [name=dir]");
+                }
+                for (name, dir) in named_dirs {
+                    if let Err(e) = cfg.io().write_stdout(
+                        format!("{}={}\n", name, dir.display()).as_bytes(),
+                    ) {
+                        bail!(cfg, exe, e);
+                    }
+                }
+            }
+        }
+        crate::status::success()
+    }))
+}
+
+// only the history that has already been saved is shown, since the shell
+// writes out its own history when it exits
+#[allow(clippy::unnecessary_wraps)]
+fn history(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let session = env.var("NBSH_SESSION");
+    Ok(command::Child::new_task(move || {
+        let mut limit = None;
+        match exe.args().iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {}
+            ["-c"] => {
+                if let Err(e) = crate::shell::old_history::clear() {
+                    bail!(cfg, exe, crate::format::io_error(&e));
+                }
+                return crate::status::success();
+            }
+            ["-n", n] => {
+                if let Ok(n) = n.parse() {
+                    limit = Some(n);
+                } else {
+                    bail!(cfg, exe, "usage: history [-c | -n count]");
+                }
+            }
+            _ => {
+                bail!(cfg, exe, "usage: history [-c | -n count]");
+            }
+        }
+
+        // within a shell, this includes the commands that haven't been saved
+        // yet, so that the numbers are the same ones that !N uses
+        let cmds = match session
+            .as_deref()
+            .and_then(read_history_list)
+            .map_or_else(crate::shell::old_history::read_cmds, Ok)
+        {
+            Ok(cmds) => cmds,
+            Err(e) => {
+                bail!(cfg, exe, crate::format::io_error(&e));
+            }
+        };
+        let skip = limit.map_or(0, |limit| cmds.len().saturating_sub(limit));
+        // numbered from 1, to match !N
+        for (i, cmd) in cmds.iter().enumerate().skip(skip) {
+            if let Err(e) = cfg.io().write_stdout(
+                format!(
+                    "{:>5}  {}\n",
+                    i + 1,
+                    crate::format::command_line(cmd)
+                )
+                .as_bytes(),
+            ) {
+                bail!(cfg, exe, e);
+            }
+        }
+        crate::status::success()
+    }))
+}
+
+// the scheme is stored in the environment, so the shell picks it up once
+// this entry finishes
+#[allow(clippy::unnecessary_wraps)]
+fn colors(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let current = env.color_scheme();
+    Ok(command::Child::new_task(move || {
+        match exe.args().iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {
+                for scheme in crate::shell::ColorScheme::ALL {
+                    let marker = if *scheme == current { '*' } else { ' ' };
+                    if let Err(e) = cfg.io().write_stdout(
+                        format!("{} {}\n", marker, scheme.name()).as_bytes(),
+                    ) {
+                        bail!(cfg, exe, e);
+                    }
+                }
+            }
+            [name] => match name.parse::<crate::shell::ColorScheme>() {
+                Ok(scheme) => {
+                    std::env::set_var(Env::color_scheme_var(), scheme.name());
+                }
+                Err(e) => {
+                    bail!(cfg, exe, e);
+                }
+            },
+            _ => {
+                bail!(cfg, exe, "usage: colors [name]");
+            }
+        }
+        crate::status::success()
+    }))
+}
+
+// test expressions exit with 1 for false, and 2 for errors in the expression
+// itself
+#[allow(clippy::unnecessary_wraps)]
+fn test(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        let mut args = exe.args();
+        if exe.exe() == std::path::Path::new("[") {
+            if let Some(("]", rest)) =
+                args.split_last().map(|(last, rest)| (last.as_str(), rest))
+            {
+                args = rest;
+            } else {
+                cfg.io()
+                    .write_stderr(
+                        format!("{}: missing ]\n", exe.exe().display())
+                            .as_bytes(),
+                    )
+                    .unwrap();
+                return crate::status::exited(2);
+            }
+        }
+        match test::eval(args) {
+            Ok(true) => crate::status::success(),
+            Ok(false) => crate::status::failure(),
+            Err(e) => {
+                cfg.io()
+                    .write_stderr(
+                        format!("{}: {}\n", exe.exe().display(), e)
+                            .as_bytes(),
+                    )
+                    .unwrap();
+                crate::status::exited(2)
+            }
+        }
+    }))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn true_(
+    _exe: crate::parse::Exe,
+    _env: &Env,
+    _cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(crate::status::success))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn false_(
+    _exe: crate::parse::Exe,
+    _env: &Env,
+    _cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(crate::status::failure))
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn printf(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        let (fmt, args) = if let Some((fmt, args)) = exe.args().split_first()
+        {
+            (fmt, args)
+        } else {
+            bail!(cfg, exe, "usage: printf format [arguments...]");
+        };
+
+        let (out, err) = printf::format(fmt, args);
+        if let Err(e) = cfg.io().write_stdout(&out) {
+            bail!(cfg, exe, e);
+        }
+        if let Some(err) = err {
+            bail!(cfg, exe, err);
+        }
+        crate::status::success()
+    }))
+}
+
+// reports what each name would run as, checking in the same order that the
+// runner does: aliases, then builtins, then binaries in the PATH
+#[allow(clippy::unnecessary_wraps)]
+fn type_(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let env = env.clone();
+    Ok(command::Child::new_task(move || {
+        if exe.args().is_empty() {
+            bail!(cfg, exe, "usage: type name...");
+        }
+        let config = match crate::config::Config::load() {
+            Ok(config) => config,
+            Err(e) => {
+                bail!(cfg, exe, e);
+            }
+        };
+
+        let mut status = crate::status::success();
+        for name in exe.args() {
+            let path = std::path::Path::new(name);
+            let line = if let Some(alias) = config.alias_for(path) {
+                // expanded the same way the runner expands it, so that this
+                // shows what will actually run
+                let alias = match tokio::runtime::Handle::current()
+                    .block_on(alias.clone().eval(&env))
+                {
+                    Ok(alias) => alias,
+                    Err(e) => {
+                        bail!(cfg, exe, e);
+                    }
+                };
+                format!(
+                    "{} is an alias for {}",
+                    name,
+                    std::iter::once(alias.exe().display().to_string())
+                        .chain(alias.args().iter().cloned())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )
+            } else if is_builtin(path) {
+                format!("{} is a shell builtin", name)
+            } else if let Some(full) = crate::runner::find_executable(
+                path,
+                env.pwd(),
+                env.var("PATH").as_deref(),
+            ) {
+                format!("{} is {}", name, full.display())
+            } else {
+                cfg.io()
+                    .write_stderr(
+                        format!(
+                            "{}: {}: not found\n",
+                            exe.exe().display(),
+                            name
+                        )
+                        .as_bytes(),
+                    )
+                    .unwrap();
+                status = crate::status::failure();
+                continue;
+            };
+            if let Err(e) =
+                cfg.io().write_stdout(format!("{}\n", line).as_bytes())
+            {
+                bail!(cfg, exe, e);
+            }
+        }
+        status
     }))
 }
 
@@ -240,3 +985,291 @@ fn builtin(
     cfg.setup_command(&mut cmd);
     Ok(command::Child::new_wrapped(cmd.spawn(env)?))
 }
+
+// without an argument, exits with the status of the previous command. like
+// other shells, an argument which isn't a number still exits, but with a
+// status of 2.
+#[allow(clippy::unnecessary_wraps)]
+fn exit(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let latest = env.latest_status();
+    Ok(command::Child::new_task(move || {
+        let status = match exe
+            .args()
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()[..]
+        {
+            [] => latest,
+            [code] => code.parse().map_or_else(
+                |_| {
+                    cfg.io()
+                        .write_stderr(
+                            format!(
+                                "{}: numeric argument required: {}\n",
+                                exe.exe().display(),
+                                code
+                            )
+                            .as_bytes(),
+                        )
+                        .unwrap();
+                    crate::status::exited(2)
+                },
+                crate::status::exited,
+            ),
+            _ => {
+                bail!(cfg, exe, "usage: exit [code]");
+            }
+        };
+        *EXIT_STATUS.lock().unwrap() = Some(status);
+        status
+    }))
+}
+
+// the runner handles source itself when it is run on its own, so this only
+// runs when it is part of a larger pipeline, where it would have to run in a
+// separate process and so couldn't do anything useful
+#[allow(clippy::unnecessary_wraps)]
+fn source(
+    exe: crate::parse::Exe,
+    _env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    Ok(command::Child::new_task(move || {
+        bail!(cfg, exe, "can't be used in a pipeline");
+    }))
+}
+
+// sends a signal (TERM by default) to each of the given pids, or to the
+// process groups of the entries given as %N
+#[allow(clippy::unnecessary_wraps)]
+fn kill(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let session = env.var("NBSH_SESSION");
+    Ok(command::Child::new_task(move || {
+        let mut args = exe.args();
+        let mut signal = Some(nix::sys::signal::Signal::SIGTERM);
+        match args.first().map(String::as_str) {
+            Some("-l") => {
+                for signal in nix::sys::signal::Signal::iterator() {
+                    if let Err(e) = cfg.io().write_stdout(
+                        format!(
+                            "{}\n",
+                            signal.as_str().trim_start_matches("SIG")
+                        )
+                        .as_bytes(),
+                    ) {
+                        bail!(cfg, exe, e);
+                    }
+                }
+                return crate::status::success();
+            }
+            Some("-s") => {
+                signal = if let Some(signal) =
+                    args.get(1).and_then(|name| parse_signal(name))
+                {
+                    signal
+                } else {
+                    bail!(cfg, exe, "invalid signal");
+                };
+                args = &args[2..];
+            }
+            Some("--") => {
+                args = &args[1..];
+            }
+            Some(arg) => {
+                if let Some(name) = arg.strip_prefix('-') {
+                    signal = if let Some(signal) = parse_signal(name) {
+                        signal
+                    } else {
+                        bail!(cfg, exe, "invalid signal: {}", name);
+                    };
+                    args = &args[1..];
+                }
+            }
+            None => {}
+        }
+        if args.is_empty() {
+            bail!(cfg, exe, "usage: kill [-s signal | -signal] pid|%job...");
+        }
+
+        // like other shells, every target is tried even if some fail
+        let mut status = crate::status::success();
+        for arg in args {
+            let pid = if let Some(job) = arg.strip_prefix('%') {
+                job_pg(session.as_deref(), job)
+                    .map(super::sys::neg_pid)
+                    .ok_or_else(|| format!("no such job: {}", arg))
+            } else {
+                arg.parse()
+                    .map(nix::unistd::Pid::from_raw)
+                    .map_err(|_| format!("invalid pid: {}", arg))
+            };
+            let res = pid.and_then(|pid| {
+                nix::sys::signal::kill(pid, signal)
+                    .map_err(|e| format!("{}: {}", arg, e.desc()))
+            });
+            if let Err(e) = res {
+                cfg.io()
+                    .write_stderr(
+                        format!("{}: {}\n", exe.exe().display(), e)
+                            .as_bytes(),
+                    )
+                    .unwrap();
+                status = crate::status::failure();
+            }
+        }
+        status
+    }))
+}
+
+// accepts names with or without the SIG prefix, in any case, as well as
+// numbers. 0 doesn't send a signal, but still checks that the target exists.
+fn parse_signal(s: &str) -> Option<Option<nix::sys::signal::Signal>> {
+    if let Ok(n) = s.parse::<i32>() {
+        return if n == 0 {
+            Some(None)
+        } else {
+            nix::sys::signal::Signal::try_from(n).ok().map(Some)
+        };
+    }
+    let name = s.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+    name.parse().ok().map(Some)
+}
+
+// entries which have already finished don't have a process group anymore
+fn job_pg(session: Option<&str>, job: &str) -> Option<nix::unistd::Pid> {
+    read_job(session, job.parse().ok()?)?
+        .trim()
+        .parse()
+        .ok()
+        .map(nix::unistd::Pid::from_raw)
+}
+
+fn read_history_list(session: &str) -> Option<Vec<String>> {
+    let path = crate::dirs::history_list_file(session);
+    serde_json::from_str(&std::fs::read_to_string(path).ok()?).ok()
+}
+
+fn read_job(session: Option<&str>, job: usize) -> Option<String> {
+    let path = crate::dirs::job_file(session?, job);
+    std::fs::read_to_string(path).ok()
+}
+
+// waits for each of the entries given as %N to finish (or every earlier
+// entry, without arguments), exiting with the status of the last one.
+// pipelines can't be run in the background within a single command line, so
+// there is never anything else to wait for.
+#[allow(clippy::unnecessary_wraps)]
+fn wait(
+    exe: crate::parse::Exe,
+    env: &Env,
+    cfg: command::Cfg,
+) -> Result<command::Child> {
+    let session = env.var("NBSH_SESSION");
+    let current = env
+        .var("NBSH_ENTRY")
+        .and_then(|entry| entry.parse::<usize>().ok());
+    Ok(command::Child::new_task(move || {
+        let jobs = if exe.args().is_empty() {
+            // later entries could themselves be waiting for this one
+            session.as_deref().map_or_else(Vec::new, |session| {
+                crate::dirs::jobs(session)
+                    .into_iter()
+                    .filter(|&job| {
+                        current.map_or(true, |current| job < current)
+                    })
+                    .collect()
+            })
+        } else {
+            let mut jobs = vec![];
+            for arg in exe.args() {
+                let job = if let Some(job) =
+                    arg.strip_prefix('%').and_then(|job| job.parse().ok())
+                {
+                    job
+                } else {
+                    bail!(cfg, exe, "{}: not a job of this shell", arg);
+                };
+                // the entry running this can't finish until this does
+                if Some(job) == current {
+                    bail!(
+                        cfg,
+                        exe,
+                        "can't wait for the current entry: {}",
+                        arg
+                    );
+                }
+                jobs.push(job);
+            }
+            jobs
+        };
+        let mut status = crate::status::success();
+        for job in jobs {
+            match wait_job(session.as_deref(), job) {
+                Ok(job_status) => status = job_status,
+                Err(e) => {
+                    bail!(cfg, exe, e);
+                }
+            }
+        }
+        status
+    }))
+}
+
+// the entry is owned by the shell rather than by this process, so there is
+// nothing to actually wait on, and its job file is polled instead. nothing
+// will update that file once the shell is gone, so that stops the polling.
+fn wait_job(
+    session: Option<&str>,
+    job: usize,
+) -> Result<std::process::ExitStatus> {
+    let shell_pid = session.and_then(|session| session.parse().ok());
+    loop {
+        let contents = read_job(session, job)
+            .ok_or_else(|| anyhow!("no such job: %{}", job))?;
+        if let Some(raw) = contents
+            .strip_prefix("exited ")
+            .and_then(|raw| raw.trim().parse().ok())
+        {
+            return Ok(crate::status::from_raw(raw));
+        }
+        if !shell_pid.map_or(false, crate::info::running) {
+            return Err(anyhow!(
+                "shell for job %{} is no longer running",
+                job
+            ));
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_parse_signal() {
+    assert_eq!(
+        parse_signal("TERM"),
+        Some(Some(nix::sys::signal::Signal::SIGTERM))
+    );
+    assert_eq!(
+        parse_signal("sigint"),
+        Some(Some(nix::sys::signal::Signal::SIGINT))
+    );
+    assert_eq!(
+        parse_signal("9"),
+        Some(Some(nix::sys::signal::Signal::SIGKILL))
+    );
+    assert_eq!(parse_signal("0"), Some(None));
+    assert_eq!(parse_signal("NOPE"), None);
+    assert_eq!(parse_signal("999"), None);
+}
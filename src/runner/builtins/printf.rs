@@ -0,0 +1,264 @@
+// formats the arguments according to the format string the way printf(1)
+// does, including reusing the format string until all of the arguments have
+// been used. along with the output, returns the first error that was found,
+// since printf keeps going (treating the bad argument as 0) in that case.
+pub fn format(fmt: &str, args: &[String]) -> (Vec<u8>, Option<String>) {
+    let mut out = vec![];
+    let mut err = None;
+    let mut args = args.iter();
+    loop {
+        let mut consumed = false;
+        let mut literal = String::new();
+        let mut chars = fmt.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                literal.push(c);
+                // an escaped % is still a conversion, so make sure the
+                // backslash is handled separately
+                if c == '\\' {
+                    if let Some(c) = chars.next() {
+                        literal.push(c);
+                    }
+                }
+                continue;
+            }
+
+            let mut spec = Spec::default();
+            while let Some(&c) = chars.peek() {
+                match c {
+                    '-' => spec.left = true,
+                    '0' => spec.zero = true,
+                    '+' => spec.plus = true,
+                    ' ' => spec.space = true,
+                    '#' => spec.alt = true,
+                    _ => break,
+                }
+                chars.next();
+            }
+            spec.width = match digits(&mut chars) {
+                Ok(width) => width,
+                Err(e) => return (out, Some(e)),
+            };
+            if chars.peek() == Some(&'.') {
+                chars.next();
+                spec.precision = match digits(&mut chars) {
+                    Ok(precision) => Some(precision.unwrap_or(0)),
+                    Err(e) => return (out, Some(e)),
+                };
+            }
+            let conv = if let Some(conv) = chars.next() {
+                conv
+            } else {
+                return (out, Some("missing conversion".to_string()));
+            };
+            if conv == '%' {
+                literal.push('%');
+                continue;
+            }
+
+            if flush(&mut out, &mut literal) {
+                return (out, err);
+            }
+            let arg = args.next();
+            consumed |= arg.is_some();
+            let arg = arg.map_or("", String::as_str);
+            let formatted = match conv {
+                's' => spec.pad(
+                    spec.precision
+                        .map_or(arg, |precision| truncate(arg, precision))
+                        .to_string(),
+                    false,
+                ),
+                'b' => {
+                    let (bytes, stop) = super::unescape(arg);
+                    out.extend(bytes);
+                    if stop {
+                        return (out, err);
+                    }
+                    continue;
+                }
+                'c' => spec.pad(arg.chars().take(1).collect(), false),
+                'd' | 'i' | 'x' | 'X' | 'o' => {
+                    let n = number(arg).unwrap_or_else(|e| {
+                        err.get_or_insert(e);
+                        0
+                    });
+                    spec.int(conv, n)
+                }
+                _ => {
+                    return (
+                        out,
+                        Some(format!("invalid conversion: %{}", conv)),
+                    )
+                }
+            };
+            out.extend(formatted.as_bytes());
+        }
+        if flush(&mut out, &mut literal) {
+            return (out, err);
+        }
+        if !consumed || args.len() == 0 {
+            break;
+        }
+    }
+    (out, err)
+}
+
+// the flags are all independent of each other
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Default)]
+struct Spec {
+    left: bool,
+    zero: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    fn int(&self, conv: char, n: i64) -> String {
+        let s = match conv {
+            'x' => format!("{:x}", n),
+            'X' => format!("{:X}", n),
+            'o' => format!("{:o}", n),
+            _ => n.unsigned_abs().to_string(),
+        };
+        let s = match conv {
+            'x' if self.alt && n != 0 => format!("0x{}", s),
+            'X' if self.alt && n != 0 => format!("0X{}", s),
+            'o' if self.alt && n != 0 => format!("0{}", s),
+            'd' | 'i' if n < 0 => format!("-{}", s),
+            'd' | 'i' if self.plus => format!("+{}", s),
+            'd' | 'i' if self.space => format!(" {}", s),
+            _ => s,
+        };
+        self.pad(s, true)
+    }
+
+    fn pad(&self, s: String, numeric: bool) -> String {
+        let len = s.chars().count();
+        let width = self.width.unwrap_or(0);
+        if len >= width {
+            return s;
+        }
+        let fill = width - len;
+        if self.left {
+            format!("{}{}", s, " ".repeat(fill))
+        } else if self.zero && numeric {
+            // zeros go after the sign or prefix
+            let prefix_len = if s.starts_with("0x") || s.starts_with("0X") {
+                2
+            } else {
+                usize::from(s.starts_with(['-', '+', ' ']))
+            };
+            let (prefix, rest) = s.split_at(prefix_len);
+            format!("{}{}{}", prefix, "0".repeat(fill), rest)
+        } else {
+            format!("{}{}", " ".repeat(fill), s)
+        }
+    }
+}
+
+// returns true if a \c was found, meaning that printf should stop
+fn flush(out: &mut Vec<u8>, literal: &mut String) -> bool {
+    let (bytes, stop) = super::unescape(literal);
+    out.extend(bytes);
+    literal.clear();
+    stop
+}
+
+fn digits(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+) -> std::result::Result<Option<usize>, String> {
+    let mut n = None;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        n = Some(
+            n.unwrap_or(0usize)
+                .checked_mul(10)
+                .and_then(|n| n.checked_add(usize::try_from(digit).unwrap()))
+                .ok_or_else(|| {
+                    "invalid format: number too large".to_string()
+                })?,
+        );
+        chars.next();
+    }
+    Ok(n)
+}
+
+fn truncate(s: &str, chars: usize) -> &str {
+    s.char_indices().nth(chars).map_or(s, |(i, _)| &s[..i])
+}
+
+// a leading quote means the value of the character after it, as in posix
+fn number(s: &str) -> std::result::Result<i64, String> {
+    if let Some(c) =
+        s.strip_prefix(['\'', '"']).and_then(|s| s.chars().next())
+    {
+        return Ok(i64::from(u32::from(c)));
+    }
+    let trimmed = s.trim();
+    if trimmed.is_empty() {
+        return Ok(0);
+    }
+    let (negative, digits) = match trimmed.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, trimmed.strip_prefix('+').unwrap_or(trimmed)),
+    };
+    let n = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16)
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8)
+    } else {
+        digits.parse()
+    }
+    .map_err(|_| format!("invalid number: {}", s))?;
+    Ok(if negative { -n } else { n })
+}
+
+#[test]
+fn test_format() {
+    let format = |fmt: &str, args: &[&str]| {
+        let (out, err) = format(
+            fmt,
+            &args.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        );
+        (String::from_utf8(out).unwrap(), err)
+    };
+    assert_eq!(format("hello\\n", &[]), ("hello\n".to_string(), None));
+    assert_eq!(
+        format("%s=%d\\n", &["a", "1", "b", "2"]),
+        ("a=1\nb=2\n".to_string(), None)
+    );
+    assert_eq!(format("%s %s|", &["a"]), ("a |".to_string(), None));
+    assert_eq!(
+        format("%x %X %#x %o", &["255", "255", "255", "8"]),
+        ("ff FF 0xff 10".to_string(), None)
+    );
+    assert_eq!(
+        format("[%5s][%-5s][%.2s]", &["ab", "ab", "abc"]),
+        ("[   ab][ab   ][ab]".to_string(), None)
+    );
+    assert_eq!(
+        format("[%05d][%+d][%d]", &["-42", "7", "'A"]),
+        ("[-0042][+7][65]".to_string(), None)
+    );
+    assert_eq!(format("100%%", &[]), ("100%".to_string(), None));
+    assert_eq!(format("a\\cb%s", &["c"]), ("a".to_string(), None));
+    assert_eq!(
+        format("%d", &["x"]),
+        ("0".to_string(), Some("invalid number: x".to_string()))
+    );
+    assert!(format("%q", &[]).1.is_some());
+    assert_eq!(
+        format("%99999999999999999999d", &["1"]),
+        (
+            String::new(),
+            Some("invalid format: number too large".to_string())
+        )
+    );
+}
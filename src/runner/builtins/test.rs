@@ -0,0 +1,258 @@
+use crate::runner::prelude::*;
+
+// evaluates the arguments to test (or [, without the closing ]). errors are
+// returned as messages, since test reports them with a different exit
+// status than a false expression.
+pub fn eval(args: &[String]) -> std::result::Result<bool, String> {
+    let args: Vec<_> = args.iter().map(String::as_str).collect();
+    if args.is_empty() {
+        return Ok(false);
+    }
+    let mut parser = Parser {
+        args: &args,
+        pos: 0,
+    };
+    let res = parser.or()?;
+    if let Some(arg) = parser.peek() {
+        return Err(format!("unexpected argument: {}", arg));
+    }
+    Ok(res)
+}
+
+struct Parser<'a> {
+    args: &'a [&'a str],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.args.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&'a str> {
+        self.args.get(self.pos + offset).copied()
+    }
+
+    fn shift(&mut self) -> std::result::Result<&'a str, String> {
+        let arg = self.peek().ok_or_else(|| "argument expected".to_string());
+        self.pos += 1;
+        arg
+    }
+
+    // -o binds more loosely than -a, like in other implementations
+    fn or(&mut self) -> std::result::Result<bool, String> {
+        let mut res = self.and()?;
+        while self.peek() == Some("-o") {
+            self.pos += 1;
+            // both sides are always parsed, so that errors are still found
+            res = self.and()? || res;
+        }
+        Ok(res)
+    }
+
+    fn and(&mut self) -> std::result::Result<bool, String> {
+        let mut res = self.not()?;
+        while self.peek() == Some("-a") {
+            self.pos += 1;
+            res = self.not()? && res;
+        }
+        Ok(res)
+    }
+
+    fn not(&mut self) -> std::result::Result<bool, String> {
+        // a ! right before a binary operator is the left operand, as in
+        // test ! = !
+        if self.peek() == Some("!")
+            && !self.peek_at(1).map_or(false, is_binary)
+        {
+            self.pos += 1;
+            return Ok(!self.not()?);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> std::result::Result<bool, String> {
+        let arg = self.shift()?;
+        if let (Some(op), Some(_)) = (self.peek(), self.peek_at(1)) {
+            if is_binary(op) {
+                self.pos += 1;
+                return binary(arg, op, self.shift()?);
+            }
+        }
+        if arg == "(" && self.peek().is_some() {
+            let res = self.or()?;
+            if self.shift()? != ")" {
+                return Err("missing )".to_string());
+            }
+            return Ok(res);
+        }
+        // with nothing after it, an operator is just a non-empty string
+        if is_unary(arg) && self.peek().is_some() {
+            return Ok(unary(arg, self.shift()?));
+        }
+        Ok(!arg.is_empty())
+    }
+}
+
+fn is_unary(op: &str) -> bool {
+    matches!(
+        op,
+        "-b" | "-c"
+            | "-d"
+            | "-e"
+            | "-f"
+            | "-g"
+            | "-h"
+            | "-k"
+            | "-L"
+            | "-n"
+            | "-p"
+            | "-r"
+            | "-s"
+            | "-S"
+            | "-t"
+            | "-u"
+            | "-w"
+            | "-x"
+            | "-z"
+    )
+}
+
+fn is_binary(op: &str) -> bool {
+    matches!(
+        op,
+        "=" | "=="
+            | "!="
+            | "<"
+            | ">"
+            | "-eq"
+            | "-ne"
+            | "-lt"
+            | "-le"
+            | "-gt"
+            | "-ge"
+            | "-nt"
+            | "-ot"
+            | "-ef"
+    )
+}
+
+fn unary(op: &str, arg: &str) -> bool {
+    let path = std::path::Path::new(arg);
+    let access = |mode| nix::unistd::access(path, mode).is_ok();
+    match op {
+        "-n" => !arg.is_empty(),
+        "-z" => arg.is_empty(),
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-h" | "-L" => path
+            .symlink_metadata()
+            .map_or(false, |metadata| metadata.file_type().is_symlink()),
+        "-r" => access(nix::unistd::AccessFlags::R_OK),
+        "-w" => access(nix::unistd::AccessFlags::W_OK),
+        "-x" => access(nix::unistd::AccessFlags::X_OK),
+        "-s" => path.metadata().map_or(false, |metadata| metadata.len() > 0),
+        "-t" => arg
+            .parse()
+            .map_or(false, |fd| nix::unistd::isatty(fd).unwrap_or(false)),
+        _ => path.metadata().map_or(false, |metadata| {
+            let file_type = metadata.file_type();
+            let mode = metadata.permissions().mode();
+            match op {
+                "-b" => std::os::unix::fs::FileTypeExt::is_block_device(
+                    &file_type,
+                ),
+                "-c" => {
+                    std::os::unix::fs::FileTypeExt::is_char_device(&file_type)
+                }
+                "-p" => std::os::unix::fs::FileTypeExt::is_fifo(&file_type),
+                "-S" => std::os::unix::fs::FileTypeExt::is_socket(&file_type),
+                "-g" => mode & 0o2000 != 0,
+                "-u" => mode & 0o4000 != 0,
+                "-k" => mode & 0o1000 != 0,
+                _ => unreachable!(),
+            }
+        }),
+    }
+}
+
+fn binary(
+    left: &str,
+    op: &str,
+    right: &str,
+) -> std::result::Result<bool, String> {
+    Ok(match op {
+        "=" | "==" => left == right,
+        "!=" => left != right,
+        "<" => left < right,
+        ">" => left > right,
+        "-eq" => int(left)? == int(right)?,
+        "-ne" => int(left)? != int(right)?,
+        "-lt" => int(left)? < int(right)?,
+        "-le" => int(left)? <= int(right)?,
+        "-gt" => int(left)? > int(right)?,
+        "-ge" => int(left)? >= int(right)?,
+        // a file which doesn't exist is older than any file which does
+        "-nt" => match (mtime(left), mtime(right)) {
+            (Some(left), Some(right)) => left > right,
+            (left, right) => left.is_some() && right.is_none(),
+        },
+        "-ot" => match (mtime(left), mtime(right)) {
+            (Some(left), Some(right)) => left < right,
+            (left, right) => left.is_none() && right.is_some(),
+        },
+        "-ef" => match (std::fs::metadata(left), std::fs::metadata(right)) {
+            (Ok(left), Ok(right)) => {
+                std::os::unix::fs::MetadataExt::dev(&left)
+                    == std::os::unix::fs::MetadataExt::dev(&right)
+                    && std::os::unix::fs::MetadataExt::ino(&left)
+                        == std::os::unix::fs::MetadataExt::ino(&right)
+            }
+            _ => false,
+        },
+        _ => unreachable!(),
+    })
+}
+
+fn int(s: &str) -> std::result::Result<i64, String> {
+    s.trim()
+        .parse()
+        .map_err(|_| format!("integer expression expected: {}", s))
+}
+
+fn mtime(path: &str) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[test]
+fn test_eval() {
+    let eval = |args: &[&str]| {
+        eval(&args.iter().map(ToString::to_string).collect::<Vec<_>>())
+    };
+    assert_eq!(eval(&[]), Ok(false));
+    assert_eq!(eval(&[""]), Ok(false));
+    assert_eq!(eval(&["foo"]), Ok(true));
+    assert_eq!(eval(&["-f"]), Ok(true));
+    assert_eq!(eval(&["-n", ""]), Ok(false));
+    assert_eq!(eval(&["-z", ""]), Ok(true));
+    assert_eq!(eval(&["a", "=", "a"]), Ok(true));
+    assert_eq!(eval(&["a", "!=", "a"]), Ok(false));
+    assert_eq!(eval(&["!", "=", "!"]), Ok(true));
+    assert_eq!(eval(&["!", "a", "=", "b"]), Ok(true));
+    assert_eq!(eval(&["10", "-gt", "9"]), Ok(true));
+    assert_eq!(eval(&["10", "<", "9"]), Ok(true));
+    assert_eq!(eval(&[" 3", "-eq", "3"]), Ok(true));
+    assert!(eval(&["a", "-eq", "3"]).is_err());
+    assert_eq!(eval(&["a", "-a", "", "-o", "b"]), Ok(true));
+    assert_eq!(eval(&["a", "-a", "(", "", "-o", "b", ")"]), Ok(true));
+    assert_eq!(eval(&["", "-o", "b", "-a", ""]), Ok(false));
+    assert!(eval(&["(", "a"]).is_err());
+    assert!(eval(&["a", "b"]).is_err());
+    assert_eq!(eval(&["-d", "/"]), Ok(true));
+    assert_eq!(eval(&["-f", "/"]), Ok(false));
+    assert_eq!(eval(&["-e", "/nonexistent/nbsh-test"]), Ok(false));
+    assert_eq!(eval(&["/", "-ef", "/."]), Ok(true));
+}
@@ -29,6 +29,7 @@ impl Command {
         let redirects = exe.redirects().to_vec();
         let mut cmd = tokio::process::Command::new(exe.exe());
         cmd.args(exe.args());
+        cmd.envs(exe.env().iter().map(|(k, v)| (k, v)));
         Self {
             inner: Inner::Binary(cmd),
             exe: exe_path,
@@ -123,16 +124,13 @@ impl Command {
         );
         match inner {
             Inner::Binary(mut cmd) => {
+                env.apply_exported(&mut cmd);
                 // Safety: open, dup2, and close are async-signal-safe
                 // functions
                 unsafe { cmd.pre_exec(pre_exec) };
-                Ok(Child::Binary(cmd.spawn().map_err(|e| {
-                    anyhow!(
-                        "{}: {}",
-                        crate::format::io_error(&e),
-                        exe.display()
-                    )
-                })?))
+                Ok(Child::Binary(
+                    cmd.spawn().map_err(|e| SpawnError { exe, e })?,
+                ))
             }
             Inner::Builtin(mut cmd) => {
                 // Safety: open, dup2, and close are async-signal-safe
@@ -145,6 +143,37 @@ impl Command {
     }
 }
 
+// a command which couldn't be started at all, as opposed to one which ran
+// and failed
+#[derive(Debug)]
+pub struct SpawnError {
+    exe: std::path::PathBuf,
+    e: std::io::Error,
+}
+
+impl SpawnError {
+    pub fn kind(&self) -> std::io::ErrorKind {
+        self.e.kind()
+    }
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            crate::format::io_error(&self.e),
+            self.exe.display()
+        )
+    }
+}
+
+impl std::error::Error for SpawnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.e)
+    }
+}
+
 pub enum Inner {
     Binary(tokio::process::Command),
     Builtin(super::builtins::Command),
@@ -0,0 +1,164 @@
+use crate::runner::prelude::*;
+
+// the part of a command that a correction applies to
+pub enum Target {
+    Exe,
+    Arg(usize),
+}
+
+impl Target {
+    pub fn get(&self, exe: &crate::parse::Exe) -> String {
+        match self {
+            Self::Exe => exe.exe().to_string_lossy().into_owned(),
+            Self::Arg(i) => exe.args()[*i].clone(),
+        }
+    }
+
+    pub fn set(&self, exe: &mut crate::parse::Exe, path: String) {
+        match self {
+            Self::Exe => exe.set_exe(path.into()),
+            Self::Arg(i) => exe.args_mut()[*i] = path,
+        }
+    }
+}
+
+// corrections are only offered once a command has failed to start because
+// something didn't exist, since a missing path is otherwise just as likely
+// to be something that the command is about to create
+pub fn not_found(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<super::SpawnError>()
+        .map_or(false, |e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+// returns the parts of exe which look like paths that don't exist, along
+// with the closest existing path to each of them, if there is one. commands
+// found through the path aren't paths themselves.
+pub fn corrections(
+    exe: &crate::parse::Exe,
+    env: &Env,
+) -> Vec<(Target, String)> {
+    let is_cd = exe.exe() == std::path::Path::new("cd");
+    let command = exe
+        .exe()
+        .to_str()
+        .filter(|command| command.contains('/'))
+        .and_then(|command| correct_path(command, env))
+        .map(|path| (Target::Exe, path));
+    let args = exe
+        .args()
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| {
+            !arg.starts_with('-') && (is_cd || arg.contains('/'))
+        })
+        .filter_map(|(i, arg)| {
+            correct_path(arg, env).map(|path| (Target::Arg(i), path))
+        });
+    command.into_iter().chain(args).collect()
+}
+
+pub fn prompt(io: &super::builtins::Io, from: &str, to: &str) -> bool {
+    if io
+        .write_stderr(
+            crate::msg::get(crate::msg::Message::CorrectPath, &[&from, &to])
+                .as_bytes(),
+        )
+        .is_err()
+    {
+        return false;
+    }
+    io.read_line_stdin()
+        .map_or(false, |(line, _)| line.trim().eq_ignore_ascii_case("y"))
+}
+
+fn correct_path(arg: &str, env: &Env) -> Option<String> {
+    let full = env.pwd().join(arg);
+    if full.exists() {
+        return None;
+    }
+    let trailing_slash = arg.ends_with('/');
+    let arg = arg.trim_end_matches('/');
+    let (dir, name) = arg.rsplit_once('/').unwrap_or(("", arg));
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    let search_dir = if dir.is_empty() {
+        env.pwd().to_path_buf()
+    } else {
+        env.pwd().join(dir)
+    };
+
+    // only suggest corrections that are fairly close, since anything further
+    // away is more likely to be a new file than a typo
+    let max_distance = if name.chars().count() <= 3 { 1 } else { 2 };
+    let mut best: Option<(usize, String)> = None;
+    for entry in std::fs::read_dir(search_dir).ok()? {
+        let candidate = if let Some(candidate) = entry
+            .ok()
+            .and_then(|entry| entry.file_name().into_string().ok())
+        {
+            candidate
+        } else {
+            continue;
+        };
+        if candidate.starts_with('.') != name.starts_with('.') {
+            continue;
+        }
+        let distance = distance(name, &candidate);
+        if distance <= max_distance
+            && best.as_ref().map_or(true, |(best, _)| distance < *best)
+        {
+            best = Some((distance, candidate));
+        }
+    }
+
+    best.map(|(_, candidate)| {
+        let mut corrected = if dir.is_empty() {
+            candidate
+        } else {
+            format!("{}/{}", dir, candidate)
+        };
+        if trailing_slash {
+            corrected.push('/');
+        }
+        corrected
+    })
+}
+
+// damerau-levenshtein (optimal string alignment) distance, so that swapped
+// characters count as a single typo
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1]
+            {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[test]
+fn test_distance() {
+    assert_eq!(distance("foo", "foo"), 0);
+    assert_eq!(distance("foo", "fo"), 1);
+    assert_eq!(distance("foo", "fooo"), 1);
+    assert_eq!(distance("foo", "fob"), 1);
+    assert_eq!(distance("src", "scr"), 1);
+    assert_eq!(distance("Cargo.toml", "Crago.tmol"), 2);
+    assert_eq!(distance("", "abc"), 3);
+}
@@ -2,17 +2,62 @@ use crate::runner::prelude::*;
 
 mod builtins;
 mod command;
-pub use command::{Child, Command};
+pub use command::{Child, Command, SpawnError};
+mod correct;
 mod prelude;
+mod report;
+pub use report::Reporter;
 mod sys;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub enum Event {
     RunPipeline((usize, usize)),
     Suspend,
+    RequestInput,
+    Stopped,
+    Continued,
+    ProcessGroup(i32),
+    Chdir(std::path::PathBuf),
+    Timings(Vec<Timing>),
     Exit(Env),
 }
 
+// when each command in a pipeline started and stopped, relative to when the
+// pipeline itself started, along with what it actually ran as after
+// expansion, aliases, and corrections
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Timing {
+    exe: String,
+    args: Vec<String>,
+    redirects: Vec<String>,
+    start: std::time::Duration,
+    end: Option<std::time::Duration>,
+}
+
+impl Timing {
+    pub fn exe(&self) -> &str {
+        &self.exe
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn redirects(&self) -> &[String] {
+        &self.redirects
+    }
+
+    pub fn start(&self) -> std::time::Duration {
+        self.start
+    }
+
+    // None if the command never exited, for instance if waiting for it
+    // failed
+    pub fn end(&self) -> Option<std::time::Duration> {
+        self.end
+    }
+}
+
 struct Stack {
     frames: Vec<Frame>,
 }
@@ -22,11 +67,15 @@ impl Stack {
         Self { frames: vec![] }
     }
 
+    // each frame also has its own scope for local variables, which is
+    // restored when the frame is popped
     fn push(&mut self, frame: Frame) {
+        builtins::push_scope();
         self.frames.push(frame);
     }
 
     fn pop(&mut self) -> Frame {
+        builtins::pop_scope();
         self.frames.pop().unwrap()
     }
 
@@ -47,6 +96,45 @@ impl Stack {
         }
     }
 
+    // pops any frames inside of the innermost loop, and returns the pc of
+    // the end of that loop, which then either starts the next iteration or
+    // leaves the loop depending on the loop's frame
+    fn unwind_loop(
+        &mut self,
+        commands: &[crate::parse::ast::Command],
+        pc: usize,
+        name: &str,
+    ) -> Result<usize> {
+        if !self
+            .frames
+            .iter()
+            .any(|frame| matches!(frame, Frame::While(..) | Frame::For(..)))
+        {
+            return Err(anyhow!("{} used outside of a loop", name));
+        }
+        // the ends of the frames being popped still need to be skipped over
+        let mut depth = 0;
+        while matches!(self.top(), Some(Frame::If(..))) {
+            self.pop();
+            depth += 1;
+        }
+        for (i, command) in commands.iter().enumerate().skip(pc + 1) {
+            match command {
+                crate::parse::ast::Command::If(_)
+                | crate::parse::ast::Command::While(_)
+                | crate::parse::ast::Command::For(..) => depth += 1,
+                crate::parse::ast::Command::End => {
+                    if depth == 0 {
+                        return Ok(i);
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        Err(anyhow!("missing end"))
+    }
+
     fn should_execute(&self) -> bool {
         for frame in &self.frames {
             if matches!(
@@ -68,38 +156,174 @@ enum Frame {
     For(bool, usize, Vec<String>),
 }
 
+// the exit codes for errors in nbsh itself, as opposed to a command that ran
+// and failed. these follow the usual shell conventions, so that scripts
+// wrapping nbsh -c can tell the cases apart.
+const EXIT_ERROR: i32 = 1;
+const EXIT_PARSE_ERROR: i32 = 2;
+const EXIT_NOT_EXECUTABLE: i32 = 126;
+const EXIT_NOT_FOUND: i32 = 127;
+
+// with simple set, every error exits with the same code, like it used to
+pub fn error_code(e: &anyhow::Error, simple: bool) -> i32 {
+    if simple {
+        return EXIT_ERROR;
+    }
+    if e.downcast_ref::<crate::parse::Error>().is_some() {
+        return EXIT_PARSE_ERROR;
+    }
+    match e.downcast_ref::<SpawnError>().map(SpawnError::kind) {
+        Some(std::io::ErrorKind::NotFound) => EXIT_NOT_FOUND,
+        Some(std::io::ErrorKind::PermissionDenied) => EXIT_NOT_EXECUTABLE,
+        _ => EXIT_ERROR,
+    }
+}
+
+// only set in the runner, which is the only place that command substitutions
+// can run in-process
+static CONFIG: once_cell::sync::OnceCell<crate::config::Config> =
+    once_cell::sync::OnceCell::new();
+
 pub async fn main(
     commands: String,
     shell_write: &mut Option<tokio::fs::File>,
+    reporter: Option<Reporter>,
+    simple_exit_codes: bool,
+    args: Vec<String>,
 ) -> Result<i32> {
+    let config = CONFIG.get_or_try_init(crate::config::Config::load)?;
+    crate::msg::init(config);
+    // the shell already reports problems with the config
+    #[allow(clippy::let_underscore_drop)]
+    let _ = crate::format::init(config);
+    config.export_named_dirs();
+    if let Some(runner) = config.runner() {
+        crate::info::set_runner(runner.to_path_buf())?;
+    }
     let mut env = Env::new_from_env()?;
-    let config = crate::config::Config::load()?;
-    run_commands(commands, &mut env, &config, shell_write).await?;
+    env.set_args(&args);
+    let res =
+        run_commands(commands, &mut env, config, shell_write, false, None)
+            .await;
+    // things which read the report shouldn't need to also handle nbsh
+    // exiting without one
+    if let Some(reporter) = reporter {
+        reporter.finish(
+            res.as_ref().map_or_else(
+                |e| crate::status::exited(error_code(e, simple_exit_codes)),
+                |_| env.latest_status(),
+            ),
+            res.as_ref().err(),
+        )?;
+    }
+    if let Err(e) = res {
+        eprintln!("{}", crate::msg::get(crate::msg::Message::Error, &[&e]));
+        return Ok(error_code(&e, simple_exit_codes));
+    }
     let status = env.latest_status();
     write_event(shell_write, Event::Exit(env)).await?;
 
     if let Some(signal) = status.signal() {
         nix::sys::signal::raise(signal.try_into().unwrap())?;
     }
-    Ok(status.code().unwrap())
+    // raising the signal may not have killed us if it was ignored
+    Ok(crate::status::code(status))
+}
+
+// runs a command substitution in this process, rather than starting a new
+// copy of nbsh for it. the environment and current directory belong to the
+// whole process, so this is only done for substitutions which can't change
+// them, and anything else runs separately, like a subshell. returns None
+// when it has to run separately, which is always the case outside of the
+// runner.
+pub async fn substitute(commands: &str, env: &Env) -> Option<Result<String>> {
+    let config = CONFIG.get()?;
+    let names = match crate::parse::ast::Commands::parse(commands) {
+        Ok(parsed) => parsed.exe_names()?,
+        Err(e) => return Some(Err(e.into())),
+    };
+    if !names.iter().all(|name| in_process(name, env, config)) {
+        return None;
+    }
+    Some(capture_stdout(commands, env.clone(), config).await)
 }
 
+// aliases and rewrites can turn a command into anything, and so can autocd,
+// so only commands which won't be changed by those are known to be safe
+fn in_process(name: &str, env: &Env, config: &crate::config::Config) -> bool {
+    !builtins::changes_state(name)
+        && config.alias_for(std::path::Path::new(name)).is_none()
+        && !config.rewrites().iter().any(|rewrite| {
+            rewrite
+                .when()
+                .literal_name()
+                .map_or(true, |when| when == name)
+        })
+        && !(config.autocd() && env.pwd().join(name).is_dir())
+}
+
+async fn capture_stdout(
+    commands: &str,
+    mut env: Env,
+    config: &crate::config::Config,
+) -> Result<String> {
+    // the pipe is read in the background, so that large outputs can't fill
+    // it up
+    let (mut r, w) = sys::pipe()?;
+    let reader = std::thread::spawn(move || {
+        let mut buf = vec![];
+        r.read_to_end(&mut buf)?;
+        Ok::<_, std::io::Error>(buf)
+    });
+    // has to be boxed since substitutions can be nested
+    let res = run_commands(
+        commands.to_string(),
+        &mut env,
+        config,
+        &mut None,
+        true,
+        Some(&w),
+    )
+    .boxed_local()
+    .await;
+    // every command has exited by now, so this is the last copy of the write
+    // end of the pipe
+    drop(w);
+    let buf = reader.join().unwrap()?;
+    res?;
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+// stdout is fd 1 unless it is given, which is how command substitutions
+// capture their output
 async fn run_commands(
     commands: String,
     env: &mut Env,
     config: &crate::config::Config,
     shell_write: &mut Option<tokio::fs::File>,
+    sourced: bool,
+    stdout: Option<&std::fs::File>,
 ) -> Result<()> {
     let commands = crate::parse::ast::Commands::parse(&commands)?;
     let commands = commands.commands();
     let mut pc = 0;
     let mut stack = Stack::new();
     while pc < commands.len() {
+        if builtins::exit_status().is_some() {
+            break;
+        }
         match &commands[pc] {
             crate::parse::ast::Command::Pipeline(pipeline) => {
                 if stack.should_execute() {
-                    run_pipeline(pipeline.clone(), env, config, shell_write)
-                        .await?;
+                    run_pipeline(
+                        pipeline.clone(),
+                        env,
+                        config,
+                        shell_write,
+                        sourced,
+                        stdout,
+                    )
+                    .await?;
                 }
                 pc += 1;
             }
@@ -110,8 +334,15 @@ async fn run_commands(
                 }
                 if should {
                     let status = env.latest_status();
-                    run_pipeline(pipeline.clone(), env, config, shell_write)
-                        .await?;
+                    run_pipeline(
+                        pipeline.clone(),
+                        env,
+                        config,
+                        shell_write,
+                        sourced,
+                        stdout,
+                    )
+                    .await?;
                     if let Some(Frame::If(should, found)) = stack.top_mut() {
                         *should = env.latest_status().success();
                         if *should {
@@ -131,8 +362,15 @@ async fn run_commands(
                 }
                 if should {
                     let status = env.latest_status();
-                    run_pipeline(pipeline.clone(), env, config, shell_write)
-                        .await?;
+                    run_pipeline(
+                        pipeline.clone(),
+                        env,
+                        config,
+                        shell_write,
+                        sourced,
+                        stdout,
+                    )
+                    .await?;
                     if let Some(Frame::While(should, _)) = stack.top_mut() {
                         *should = env.latest_status().success();
                     } else {
@@ -175,8 +413,8 @@ async fn run_commands(
                             // XXX i really need to just pick one location and
                             // stick with it instead of trying to keep these
                             // in sync
-                            env.set_var(var, &val);
-                            std::env::set_var(var, &val);
+                            env.set_shell_var(var, &val);
+                            builtins::set_shell_var(var, &val);
                         }
                     } else {
                         unreachable!();
@@ -186,6 +424,7 @@ async fn run_commands(
             }
             crate::parse::ast::Command::Else(pipeline) => {
                 let mut top = stack.pop();
+                env.update()?;
                 if stack.should_execute() {
                     if let Frame::If(ref mut should, ref mut found) = top {
                         if *found {
@@ -197,6 +436,8 @@ async fn run_commands(
                                 env,
                                 config,
                                 shell_write,
+                                sourced,
+                                stdout,
                             )
                             .await?;
                             *should = env.latest_status().success();
@@ -218,6 +459,7 @@ async fn run_commands(
             crate::parse::ast::Command::End => match stack.top() {
                 Some(Frame::If(..)) => {
                     stack.pop();
+                    env.update()?;
                     pc += 1;
                 }
                 Some(
@@ -228,13 +470,41 @@ async fn run_commands(
                         pc = *start;
                     } else {
                         stack.pop();
+                        env.update()?;
                         pc += 1;
                     }
                 }
                 None => todo!(),
             },
+            crate::parse::ast::Command::Break => {
+                if stack.should_execute() {
+                    pc = stack.unwind_loop(commands, pc, "break")?;
+                    if let Some(
+                        Frame::While(should, _) | Frame::For(should, _, _),
+                    ) = stack.top_mut()
+                    {
+                        *should = false;
+                    }
+                    env.update()?;
+                } else {
+                    pc += 1;
+                }
+            }
+            crate::parse::ast::Command::Continue => {
+                if stack.should_execute() {
+                    pc = stack.unwind_loop(commands, pc, "continue")?;
+                    env.update()?;
+                } else {
+                    pc += 1;
+                }
+            }
         }
     }
+    // conditions restore the previous status after running, so the status
+    // that exit was given is kept separately
+    if let Some(status) = builtins::exit_status() {
+        env.set_status(status);
+    }
     Ok(())
 }
 
@@ -243,26 +513,39 @@ async fn run_pipeline(
     env: &mut Env,
     config: &crate::config::Config,
     shell_write: &mut Option<tokio::fs::File>,
+    sourced: bool,
+    stdout: Option<&std::fs::File>,
 ) -> Result<()> {
-    write_event(shell_write, Event::RunPipeline(pipeline.span())).await?;
-    // Safety: pipelines are run serially, so only one copy of these will ever
-    // exist at once. note that reusing a single copy of these at the top
-    // level would not be safe, because in the case of a command line like
-    // "echo foo; ls", we would pass the stdout fd to the ls process while it
-    // is still open here, and may still have data buffered.
-    let stdin = unsafe { std::fs::File::from_raw_fd(0) };
-    let stdout = unsafe { std::fs::File::from_raw_fd(1) };
-    let stderr = unsafe { std::fs::File::from_raw_fd(2) };
-    let mut io = builtins::Io::new();
-    io.set_stdin(stdin);
-    io.set_stdout(stdout);
-    io.set_stderr(stderr);
-
-    let pwd = env.pwd().to_path_buf();
+    // the spans in a sourced file don't refer to anything in the command
+    // line that the shell knows about
+    if !sourced {
+        write_event(shell_write, Event::RunPipeline(pipeline.span())).await?;
+    }
     let interactive = shell_write.is_some();
+    let time = pipeline.time();
+    let negate = pipeline.negate();
     let pipeline = pipeline.eval(env).await?;
     let mut exes: Vec<_> = pipeline.into_exes().collect();
     for exe in &mut exes {
+        // the result of a rewrite can be rewritten again, but each rewrite
+        // only applies once, so that they can't loop forever
+        let mut applied = vec![false; config.rewrites().len()];
+        'rewrite: loop {
+            for (i, rewrite) in config.rewrites().iter().enumerate() {
+                if applied[i] {
+                    continue;
+                }
+                let when = rewrite.when().clone().eval(env).await?;
+                if let Some(rest) = exe.strip_prefix(&when) {
+                    let mut new = rewrite.run().clone().eval(env).await?;
+                    new.append(rest);
+                    *exe = new;
+                    applied[i] = true;
+                    continue 'rewrite;
+                }
+            }
+            break;
+        }
         let mut seen = std::collections::HashSet::new();
         while let Some(alias) = config.alias_for(exe.exe()) {
             let mut new = alias.clone().eval(env).await?;
@@ -281,23 +564,243 @@ async fn run_pipeline(
             }
         }
     }
-    let cmds = exes
+    if config.autocd() && exes.len() == 1 && autocd(&exes[0], env) {
+        exes[0].unshift("cd".into());
+    }
+    // sourced files have to run in this process rather than in a child, so
+    // that they can change variables and the current directory. within a
+    // larger pipeline, the builtin of the same name reports an error instead.
+    if exes.len() == 1 && builtins::is_source(exes[0].exe()) {
+        return source(&exes[0], env, config, shell_write).await;
+    }
+    // Safety: pipelines are run serially, and command substitutions have all
+    // finished by this point, so only one copy of these will ever exist at
+    // once. note that reusing a single copy of these at the top level would
+    // not be safe, because in the case of a command line like "echo foo;
+    // ls", we would pass the stdout fd to the ls process while it is still
+    // open here, and may still have data buffered.
+    let mut io = builtins::Io::new();
+    io.set_stdin(unsafe { std::fs::File::from_raw_fd(0) });
+    if let Some(stdout) = stdout {
+        io.set_stdout(stdout.try_clone()?);
+    } else {
+        io.set_stdout(unsafe { std::fs::File::from_raw_fd(1) });
+    }
+    io.set_stderr(unsafe { std::fs::File::from_raw_fd(2) });
+    let mut pipeline_start = std::time::Instant::now();
+    let (user_start, sys_start) = sys::children_times();
+    // cd can't fail to start, so it isn't run yet if it would fail in the
+    // same way, so that the directory can be corrected first. if it isn't
+    // corrected, it still runs and reports the error itself.
+    let mut spawned = (exes.len() != 1
+        || !builtins::cd_dir_missing(&exes[0], env))
+    .then(|| spawn_children(commands(&exes, &io), env, interactive));
+    // only a single command can be safely retried, since otherwise the
+    // earlier parts of the pipeline would already be running
+    if interactive
+        && config.correct_paths()
+        && exes.len() == 1
+        && spawned.as_ref().map_or(
+            true,
+            |spawned| matches!(spawned, Err(e) if correct::not_found(e)),
+        )
+    {
+        let mut corrected = false;
+        for (target, path) in correct::corrections(&exes[0], env) {
+            write_event(shell_write, Event::RequestInput).await?;
+            if correct::prompt(&io, &target.get(&exes[0]), &path) {
+                target.set(&mut exes[0], path);
+                corrected = true;
+            }
+        }
+        if corrected {
+            spawned = None;
+        }
+    }
+    let (children, starts, pg) = if let Some(spawned) = spawned {
+        spawned?
+    } else {
+        pipeline_start = std::time::Instant::now();
+        spawn_children(commands(&exes, &io), env, interactive)?
+    };
+    let argv: Option<Vec<_>> = report::enabled().then(|| {
+        exes.iter()
+            .map(|exe| {
+                std::iter::once(exe.exe().display().to_string())
+                    .chain(exe.args().iter().cloned())
+                    .collect()
+            })
+            .collect()
+    });
+    let names: Vec<_> = exes
+        .iter()
+        .map(|exe| {
+            (
+                exe.exe().display().to_string(),
+                exe.args().to_vec(),
+                exe.redirects().iter().map(ToString::to_string).collect(),
+            )
+        })
+        .collect();
+    if let Some(pg) = pg {
+        write_event(shell_write, Event::ProcessGroup(pg.as_raw())).await?;
+    }
+    let mut ends = vec![None; children.len()];
+    let mut statuses = vec![None; children.len()];
+    let mut status =
+        wait_children(children, pg, &mut ends, &mut statuses, shell_write)
+            .await;
+    // a stage without a status means that waiting failed, and the error was
+    // already reported
+    let statuses: Vec<_> = statuses
         .into_iter()
-        .map(|exe| Command::new(exe, io.clone()))
+        .map(|status| status.unwrap_or_else(crate::status::failure))
+        .collect();
+    if config.pipefail() {
+        if let Some(failed) =
+            statuses.iter().rev().find(|status| !status.success())
+        {
+            status = *failed;
+        }
+    }
+    if negate {
+        status = if status.success() {
+            crate::status::failure()
+        } else {
+            crate::status::success()
+        };
+    }
+    // builtins run in this process, so only the time spent in actual child
+    // processes is counted
+    if time {
+        let (user, system) = sys::children_times();
+        eprintln!(
+            "{}",
+            crate::msg::get(
+                crate::msg::Message::Time,
+                &[
+                    &crate::format::duration(pipeline_start.elapsed()),
+                    &crate::format::duration(user.saturating_sub(user_start)),
+                    &crate::format::duration(
+                        system.saturating_sub(sys_start)
+                    ),
+                ]
+            )
+        );
+    }
+    if let Some(argv) = argv {
+        report::pipeline(argv, status, pipeline_start.elapsed());
+    }
+    let timings = names
+        .into_iter()
+        .zip(starts.into_iter().zip(ends.into_iter()))
+        .map(|((exe, args, redirects), (start, end))| Timing {
+            exe,
+            args,
+            redirects,
+            start: start - pipeline_start,
+            end: end.map(|end| end - pipeline_start),
+        })
         .collect();
-    let (children, pg) = spawn_children(cmds, env, interactive)?;
-    let status = wait_children(children, pg, shell_write).await;
+    write_event(shell_write, Event::Timings(timings)).await?;
     if interactive {
         sys::set_foreground_pg(nix::unistd::getpid())?;
     }
+    let pwd = env.pwd().to_path_buf();
     env.update()?;
     env.set_status(status);
+    env.set_pipestatus(&statuses);
     if env.pwd() != pwd {
-        env.set_prev_pwd(pwd);
+        write_event(shell_write, Event::Chdir(env.pwd().to_path_buf()))
+            .await?;
     }
     Ok(())
 }
 
+async fn source(
+    exe: &crate::parse::Exe,
+    env: &mut Env,
+    config: &crate::config::Config,
+    shell_write: &mut Option<tokio::fs::File>,
+) -> Result<()> {
+    let path = if let [path] = exe.args() {
+        env.pwd().join(path)
+    } else {
+        eprintln!(
+            "{}: usage: {} file",
+            exe.exe().display(),
+            exe.exe().display()
+        );
+        env.set_status(crate::status::failure());
+        return Ok(());
+    };
+    let commands = match std::fs::read_to_string(&path) {
+        Ok(commands) => commands,
+        Err(e) => {
+            eprintln!(
+                "{}: {}: {}",
+                exe.exe().display(),
+                crate::format::io_error(&e),
+                path.display()
+            );
+            env.set_status(crate::status::failure());
+            return Ok(());
+        }
+    };
+    // an empty file leaves the status alone otherwise
+    env.set_status(crate::status::success());
+    // run_commands ends up calling back into this function, so its future
+    // has to be boxed to give it a known size
+    run_commands(commands, env, config, shell_write, true, None)
+        .boxed_local()
+        .await
+}
+
+// a command line consisting of nothing but the name of a directory, as long
+// as there isn't also a command by that name
+fn autocd(exe: &crate::parse::Exe, env: &Env) -> bool {
+    if !exe.args().is_empty()
+        || !exe.redirects().is_empty()
+        || builtins::is_builtin(exe.exe())
+        || !env.pwd().join(exe.exe()).is_dir()
+    {
+        return false;
+    }
+    // anything with a slash in it is a path rather than a command name
+    if exe.exe().to_string_lossy().contains('/') {
+        return true;
+    }
+    find_executable(exe.exe(), env.pwd(), env.var("PATH").as_deref())
+        .is_none()
+}
+
+// where a binary will be found when it is run, searching the PATH the same
+// way that exec does. names with a slash in them are paths rather than names
+// to search for.
+pub fn find_executable(
+    exe: &std::path::Path,
+    pwd: &std::path::Path,
+    path: Option<&str>,
+) -> Option<std::path::PathBuf> {
+    if exe.to_string_lossy().contains('/') {
+        let full = pwd.join(exe);
+        return if is_executable(&full) {
+            Some(full)
+        } else {
+            None
+        };
+    }
+    std::env::split_paths(path?)
+        .map(|dir| dir.join(exe))
+        .find(|full| is_executable(full))
+}
+
+fn is_executable(path: &std::path::Path) -> bool {
+    std::fs::metadata(path).map_or(false, |metadata| {
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    })
+}
+
 async fn write_event(
     fh: &mut Option<tokio::fs::File>,
     event: Event,
@@ -309,11 +812,24 @@ async fn write_event(
     Ok(())
 }
 
+// the exes are kept around, in case they have to be corrected and spawned
+// again
+fn commands(exes: &[crate::parse::Exe], io: &builtins::Io) -> Vec<Command> {
+    exes.iter()
+        .cloned()
+        .map(|exe| Command::new(exe, io.clone()))
+        .collect()
+}
+
 fn spawn_children(
     mut cmds: Vec<Command>,
     env: &Env,
     interactive: bool,
-) -> Result<(Vec<Child>, Option<nix::unistd::Pid>)> {
+) -> Result<(
+    Vec<Child>,
+    Vec<std::time::Instant>,
+    Option<nix::unistd::Pid>,
+)> {
     for i in 0..(cmds.len() - 1) {
         let (r, w) = sys::pipe()?;
         cmds[i].stdout(w);
@@ -321,6 +837,7 @@ fn spawn_children(
     }
 
     let mut children = vec![];
+    let mut starts = vec![];
     let mut pg_pid = None;
     for mut cmd in cmds {
         // Safety: setpgid is an async-signal-safe function
@@ -331,6 +848,7 @@ fn spawn_children(
             });
         }
         let child = cmd.spawn(env)?;
+        starts.push(std::time::Instant::now());
         if let Some(id) = child.id() {
             let child_pid = sys::id_to_pid(id);
             sys::setpgid_parent(child_pid, pg_pid)?;
@@ -343,23 +861,28 @@ fn spawn_children(
         }
         children.push(child);
     }
-    Ok((children, pg_pid))
+    Ok((children, starts, pg_pid))
 }
 
 async fn wait_children(
     children: Vec<Child>,
     pg: Option<nix::unistd::Pid>,
+    ends: &mut [Option<std::time::Instant>],
+    statuses: &mut [Option<std::process::ExitStatus>],
     shell_write: &mut Option<tokio::fs::File>,
 ) -> std::process::ExitStatus {
     enum Res {
         Child(nix::Result<nix::sys::wait::WaitStatus>),
-        Builtin((Result<std::process::ExitStatus>, bool)),
+        Builtin((Result<std::process::ExitStatus>, usize)),
     }
 
     macro_rules! bail {
         ($e:expr) => {
-            eprintln!("nbsh: {}\n", $e);
-            return std::process::ExitStatus::from_raw(1 << 8);
+            eprintln!(
+                "{}\n",
+                crate::msg::get(crate::msg::Message::Error, &[&$e])
+            );
+            return crate::status::failure();
         };
     }
 
@@ -372,25 +895,23 @@ async fn wait_children(
         .partition(|(_, child)| child.id().is_some());
     let mut children: std::collections::HashMap<_, _> = children
         .into_iter()
-        .map(|(i, child)| {
-            (sys::id_to_pid(child.id().unwrap()), (child, i == count - 1))
-        })
+        .map(|(i, child)| (sys::id_to_pid(child.id().unwrap()), (child, i)))
         .collect();
     let mut builtin_count = builtins.len();
-    let builtins: futures_util::stream::FuturesUnordered<_> =
-        builtins
-            .into_iter()
-            .map(|(i, child)| async move {
-                (child.status().await, i == count - 1)
-            })
-            .collect();
+    let builtins: futures_util::stream::FuturesUnordered<_> = builtins
+        .into_iter()
+        .map(|(i, child)| async move { (child.status().await, i) })
+        .collect();
 
     let (wait_w, wait_r) = tokio::sync::mpsc::unbounded_channel();
     if let Some(pg) = pg {
         tokio::task::spawn_blocking(move || loop {
             let res = nix::sys::wait::waitpid(
                 sys::neg_pid(pg),
-                Some(nix::sys::wait::WaitPidFlag::WUNTRACED),
+                Some(
+                    nix::sys::wait::WaitPidFlag::WUNTRACED
+                        | nix::sys::wait::WaitPidFlag::WCONTINUED,
+                ),
             );
             match wait_w.send(res) {
                 Ok(_) => {}
@@ -420,15 +941,20 @@ async fn wait_children(
                     // branches because our waitpid call already collected the
                     // status
                     nix::sys::wait::WaitStatus::Exited(pid, code) => {
-                        let (_, last) = children.remove(&pid).unwrap();
-                        if last {
-                            final_status = Some(
-                                std::process::ExitStatus::from_raw(code << 8),
-                            );
+                        let (_, i) = children.remove(&pid).unwrap();
+                        ends[i] = Some(std::time::Instant::now());
+                        statuses[i] = Some(crate::status::exited(code));
+                        if i == count - 1 {
+                            final_status = statuses[i];
                         }
                     }
-                    nix::sys::wait::WaitStatus::Signaled(pid, signal, _) => {
-                        let (_, last) = children.remove(&pid).unwrap();
+                    nix::sys::wait::WaitStatus::Signaled(
+                        pid,
+                        signal,
+                        core_dumped,
+                    ) => {
+                        let (_, i) = children.remove(&pid).unwrap();
+                        ends[i] = Some(std::time::Instant::now());
                         if signal == nix::sys::signal::Signal::SIGINT {
                             if let Err(e) = nix::sys::signal::raise(
                                 nix::sys::signal::Signal::SIGINT,
@@ -439,11 +965,13 @@ async fn wait_children(
                         // this conversion is safe because the Signal enum is
                         // repr(i32)
                         #[allow(clippy::as_conversions)]
-                        if last {
-                            final_status =
-                                Some(std::process::ExitStatus::from_raw(
-                                    signal as i32,
-                                ));
+                        let status = crate::status::signaled_core(
+                            signal as i32,
+                            core_dumped,
+                        );
+                        statuses[i] = Some(status);
+                        if i == count - 1 {
+                            final_status = statuses[i];
                         }
                     }
                     nix::sys::wait::WaitStatus::Stopped(pid, signal) => {
@@ -459,6 +987,17 @@ async fn wait_children(
                             ) {
                                 bail!(e);
                             }
+                        } else if let Err(e) =
+                            write_event(shell_write, Event::Stopped).await
+                        {
+                            bail!(e);
+                        }
+                    }
+                    nix::sys::wait::WaitStatus::Continued(_) => {
+                        if let Err(e) =
+                            write_event(shell_write, Event::Continued).await
+                        {
+                            bail!(e);
                         }
                     }
                     _ => {}
@@ -467,7 +1006,8 @@ async fn wait_children(
             Res::Child(Err(e)) => {
                 bail!(e);
             }
-            Res::Builtin((Ok(status), last)) => {
+            Res::Builtin((Ok(status), i)) => {
+                ends[i] = Some(std::time::Instant::now());
                 // this conversion is safe because the Signal enum is
                 // repr(i32)
                 #[allow(clippy::as_conversions)]
@@ -480,7 +1020,8 @@ async fn wait_children(
                         bail!(e);
                     }
                 }
-                if last {
+                statuses[i] = Some(status);
+                if i == count - 1 {
                     final_status = Some(status);
                 }
                 builtin_count -= 1;
@@ -497,3 +1038,14 @@ async fn wait_children(
 
     final_status.unwrap()
 }
+
+#[test]
+fn test_error_code() {
+    let parse = anyhow::Error::from(
+        crate::parse::ast::Commands::parse("echo 'foo").unwrap_err(),
+    );
+    assert_eq!(error_code(&parse, false), EXIT_PARSE_ERROR);
+    assert_eq!(error_code(&parse, true), EXIT_ERROR);
+    let other = anyhow!("recursive alias found: ls");
+    assert_eq!(error_code(&other, false), EXIT_ERROR);
+}
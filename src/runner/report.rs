@@ -0,0 +1,143 @@
+use crate::runner::prelude::*;
+
+// the pipelines run so far, if a report was requested
+static PIPELINES: once_cell::sync::Lazy<
+    std::sync::Mutex<Option<Vec<Pipeline>>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(None));
+
+// the result of running nbsh -c with --json, for use by other programs
+#[derive(serde::Serialize)]
+struct Report {
+    pipelines: Vec<Pipeline>,
+    status: i32,
+    signal: Option<String>,
+    duration: f64,
+    stdout: Option<String>,
+    stderr: Option<String>,
+    // set if nbsh itself failed, like for a syntax error
+    error: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct Pipeline {
+    // one entry per command, after expansion, aliases, and corrections
+    argv: Vec<Vec<String>>,
+    status: i32,
+    duration: f64,
+}
+
+pub struct Reporter {
+    fd: std::os::unix::io::RawFd,
+    capture: Option<Capture>,
+    start: std::time::Instant,
+}
+
+impl Reporter {
+    pub fn new(fd: std::os::unix::io::RawFd, capture: bool) -> Result<Self> {
+        *PIPELINES.lock().unwrap() = Some(vec![]);
+        Ok(Self {
+            fd,
+            capture: if capture { Some(Capture::new()?) } else { None },
+            start: std::time::Instant::now(),
+        })
+    }
+
+    pub fn finish(
+        self,
+        status: std::process::ExitStatus,
+        error: Option<&anyhow::Error>,
+    ) -> Result<()> {
+        let (stdout, stderr) = if let Some(capture) = self.capture {
+            let (stdout, stderr) = capture.finish()?;
+            (Some(stdout), Some(stderr))
+        } else {
+            (None, None)
+        };
+        let report = Report {
+            pipelines: PIPELINES.lock().unwrap().take().unwrap_or_default(),
+            status: crate::status::code(status),
+            signal: status.signal().map(|sig| {
+                nix::sys::signal::Signal::try_from(sig).map_or_else(
+                    |_| sig.to_string(),
+                    |sig| sig.as_str().to_string(),
+                )
+            }),
+            duration: self.start.elapsed().as_secs_f64(),
+            stdout,
+            stderr,
+            error: error.map(ToString::to_string),
+        };
+        let mut line = serde_json::to_string(&report)?;
+        line.push('\n');
+        // Safety: the fd was given to us to write the report to, and the
+        // file is never dropped, so it won't be closed out from under anything
+        // else that might be using it
+        let mut fh = std::mem::ManuallyDrop::new(unsafe {
+            std::fs::File::from_raw_fd(self.fd)
+        });
+        fh.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub fn enabled() -> bool {
+    PIPELINES.lock().unwrap().is_some()
+}
+
+pub fn pipeline(
+    argv: Vec<Vec<String>>,
+    status: std::process::ExitStatus,
+    duration: std::time::Duration,
+) {
+    if let Some(pipelines) = &mut *PIPELINES.lock().unwrap() {
+        pipelines.push(Pipeline {
+            argv,
+            status: crate::status::code(status),
+            duration: duration.as_secs_f64(),
+        });
+    }
+}
+
+// points stdout and stderr at pipes, which are read in the background until
+// everything writing to them has exited
+struct Capture {
+    saved: [std::os::unix::io::RawFd; 2],
+    readers: [std::thread::JoinHandle<std::io::Result<Vec<u8>>>; 2],
+}
+
+impl Capture {
+    fn new() -> Result<Self> {
+        let mut saved = [0; 2];
+        let mut readers = vec![];
+        for (i, fd) in [1, 2].into_iter().enumerate() {
+            let (r, w) = super::sys::pipe()?;
+            saved[i] = nix::unistd::dup(fd)?;
+            nix::unistd::dup2(w.as_raw_fd(), fd)?;
+            readers.push(std::thread::spawn(move || {
+                let mut r = r;
+                let mut buf = vec![];
+                r.read_to_end(&mut buf)?;
+                Ok(buf)
+            }));
+        }
+        Ok(Self {
+            saved,
+            readers: readers.try_into().unwrap(),
+        })
+    }
+
+    fn finish(self) -> Result<(String, String)> {
+        // restoring the original fds closes our copies of the write ends of
+        // the pipes
+        for (i, fd) in [1, 2].into_iter().enumerate() {
+            nix::unistd::dup2(self.saved[i], fd)?;
+            nix::unistd::close(self.saved[i])?;
+        }
+        let [stdout, stderr] = self.readers;
+        let read = |reader: std::thread::JoinHandle<_>| -> Result<String> {
+            let buf: Vec<u8> = reader.join().unwrap()?;
+            Ok(String::from_utf8_lossy(&buf).into_owned())
+        };
+        Ok((read(stdout)?, read(stderr)?))
+    }
+}
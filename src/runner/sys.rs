@@ -13,11 +13,17 @@ pub fn pipe() -> Result<(std::fs::File, std::fs::File)> {
 }
 
 pub fn set_foreground_pg(pg: nix::unistd::Pid) -> Result<()> {
-    let pty = nix::fcntl::open(
+    let pty = match nix::fcntl::open(
         "/dev/tty",
         nix::fcntl::OFlag::empty(),
         nix::sys::stat::Mode::empty(),
-    )?;
+    ) {
+        Ok(pty) => pty,
+        // there is no controlling terminal (running from cron, ci, etc), so
+        // there is no foreground process group to manage
+        Err(nix::errno::Errno::ENXIO) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
 
     // if a background process calls tcsetpgrp, the kernel will send it
     // SIGTTOU which suspends it. if that background process is the session
@@ -70,6 +76,24 @@ pub fn setpgid_parent(
     Ok(())
 }
 
+// the cpu time used so far by all of the children that have been waited for,
+// as (user, system)
+pub fn children_times() -> (std::time::Duration, std::time::Duration) {
+    let mut usage = std::mem::MaybeUninit::<libc::rusage>::uninit();
+    // Safety: RUSAGE_CHILDREN is always valid, so getrusage can't fail, and
+    // it fills in the whole struct when it succeeds
+    let usage = unsafe {
+        libc::getrusage(libc::RUSAGE_CHILDREN, usage.as_mut_ptr());
+        usage.assume_init()
+    };
+    (timeval(usage.ru_utime), timeval(usage.ru_stime))
+}
+
+fn timeval(tv: libc::timeval) -> std::time::Duration {
+    std::time::Duration::from_secs(tv.tv_sec.try_into().unwrap_or(0))
+        + std::time::Duration::from_micros(tv.tv_usec.try_into().unwrap_or(0))
+}
+
 pub fn id_to_pid(id: u32) -> nix::unistd::Pid {
     nix::unistd::Pid::from_raw(id.try_into().unwrap())
 }
@@ -0,0 +1,251 @@
+use crate::shell::prelude::*;
+
+// the daemon exits once nothing has talked to it for this long
+const IDLE_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_secs(30 * 60);
+// git status can change without any nbsh instance noticing (if no instance
+// is currently in that repository), so don't trust it for too long
+const GIT_TTL: std::time::Duration = std::time::Duration::from_secs(5);
+// if the daemon can't answer this quickly, we're better off computing things
+// ourselves
+const CLIENT_TIMEOUT: std::time::Duration =
+    std::time::Duration::from_millis(200);
+
+static ENABLED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+static SPAWNED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+enum Request {
+    // refresh means that the caller knows that the repository has changed,
+    // so any cached info should be recomputed
+    GitInfo {
+        dir: std::path::PathBuf,
+        refresh: bool,
+    },
+    Executables {
+        path: String,
+    },
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+enum Response {
+    GitInfo(Option<super::inputs::GitInfo>),
+    Executables(Vec<String>),
+}
+
+// the daemon is only used once this has been called, which happens if it is
+// enabled in the config
+pub fn enable() {
+    ENABLED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+// each of these returns None if the daemon isn't available for whatever
+// reason, in which case the caller should just compute the value itself
+
+pub fn git_info(
+    dir: &std::path::Path,
+    refresh: bool,
+) -> Option<Option<super::inputs::GitInfo>> {
+    match query(&Request::GitInfo {
+        dir: dir.to_path_buf(),
+        refresh,
+    }) {
+        Some(Response::GitInfo(info)) => Some(info),
+        _ => None,
+    }
+}
+
+pub fn executables(path: &str) -> Option<Vec<String>> {
+    match query(&Request::Executables {
+        path: path.to_string(),
+    }) {
+        Some(Response::Executables(names)) => Some(names),
+        _ => None,
+    }
+}
+
+fn query(request: &Request) -> Option<Response> {
+    if !ENABLED.load(std::sync::atomic::Ordering::SeqCst) {
+        return None;
+    }
+    let path = socket_path()?;
+    let stream =
+        if let Ok(stream) = std::os::unix::net::UnixStream::connect(&path) {
+            stream
+        } else {
+            spawn_daemon();
+            return None;
+        };
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT)).ok()?;
+    let mut line = serde_json::to_string(request).ok()?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes()).ok()?;
+    let mut response = String::new();
+    std::io::BufRead::read_line(
+        &mut std::io::BufReader::new(&stream),
+        &mut response,
+    )
+    .ok()?;
+    serde_json::from_str(&response).ok()
+}
+
+// the protocol isn't stable between versions, so each version gets its own
+// daemon
+fn socket_path() -> Option<std::path::PathBuf> {
+    crate::dirs::runtime_dir().map(|dir| {
+        dir.join(format!(
+            "cache-{}.sock",
+            crate::info::update::current_version()
+        ))
+    })
+}
+
+fn spawn_daemon() {
+    if SPAWNED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return;
+    }
+    let exe = if let Ok(exe) = crate::info::current_exe() {
+        exe
+    } else {
+        return;
+    };
+    let mut cmd = std::process::Command::new(exe);
+    cmd.arg("--cache-daemon");
+    cmd.stdin(std::process::Stdio::null());
+    cmd.stdout(std::process::Stdio::null());
+    cmd.stderr(std::process::Stdio::null());
+    // Safety: setsid is async-signal-safe. this keeps the daemon from being
+    // killed along with the terminal that happened to start it.
+    unsafe {
+        std::os::unix::process::CommandExt::pre_exec(&mut cmd, || {
+            nix::unistd::setsid()?;
+            Ok(())
+        });
+    }
+    // the daemon is entirely optional, so there's not much to do if it
+    // fails to start other than keep computing things ourselves
+    #[allow(clippy::let_underscore_drop)]
+    let _ = cmd.spawn();
+}
+
+#[derive(Default)]
+struct State {
+    git: std::collections::HashMap<
+        std::path::PathBuf,
+        (std::time::Instant, Option<super::inputs::GitInfo>),
+    >,
+    // the mtimes of the directories in the path, so that we can tell when
+    // the cached list is out of date
+    executables: std::collections::HashMap<
+        String,
+        (Vec<Option<std::time::SystemTime>>, Vec<String>),
+    >,
+}
+
+pub async fn daemon() -> Result<i32> {
+    let path =
+        socket_path().ok_or_else(|| anyhow!("XDG_RUNTIME_DIR isn't set"))?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    if tokio::net::UnixStream::connect(&path).await.is_ok() {
+        // another instance won the race to start the daemon
+        return Ok(0);
+    }
+    // left over from a daemon which didn't shut down cleanly
+    std::fs::remove_file(&path).or_else(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            Ok(())
+        } else {
+            Err(e)
+        }
+    })?;
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+
+    let state = std::sync::Arc::new(std::sync::Mutex::new(State::default()));
+    loop {
+        tokio::select! {
+            stream = listener.accept() => {
+                let (stream, _) = stream?;
+                tokio::spawn(handle_client(stream, state.clone()));
+            }
+            _ = tokio::time::sleep(IDLE_TIMEOUT) => break,
+        }
+    }
+    std::fs::remove_file(&path)?;
+    Ok(0)
+}
+
+async fn handle_client(
+    stream: tokio::net::UnixStream,
+    state: std::sync::Arc<std::sync::Mutex<State>>,
+) {
+    let (read, mut write) = stream.into_split();
+    let mut lines =
+        tokio::io::AsyncBufReadExt::lines(tokio::io::BufReader::new(read));
+    while let Ok(Some(line)) = lines.next_line().await {
+        let request = if let Ok(request) = serde_json::from_str(&line) {
+            request
+        } else {
+            break;
+        };
+        let state = state.clone();
+        let response =
+            tokio::task::spawn_blocking(move || respond(request, &state))
+                .await
+                .unwrap();
+        let mut line = serde_json::to_string(&response).unwrap();
+        line.push('\n');
+        if write.write_all(line.as_bytes()).await.is_err() {
+            break;
+        }
+    }
+}
+
+// the lock isn't held while computing anything, since that can be slow
+fn respond(request: Request, state: &std::sync::Mutex<State>) -> Response {
+    match request {
+        Request::GitInfo { dir, refresh } => {
+            let cached = state.lock().unwrap().git.get(&dir).cloned();
+            if let Some((time, info)) = cached {
+                if !refresh && time.elapsed() < GIT_TTL {
+                    return Response::GitInfo(info);
+                }
+            }
+            let info = git2::Repository::discover(&dir)
+                .ok()
+                .map(|repo| super::inputs::GitInfo::new(&repo));
+            state
+                .lock()
+                .unwrap()
+                .git
+                .insert(dir, (std::time::Instant::now(), info.clone()));
+            Response::GitInfo(info)
+        }
+        Request::Executables { path } => {
+            let mtimes: Vec<_> = std::env::split_paths(&path)
+                .map(|dir| {
+                    std::fs::metadata(dir)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                })
+                .collect();
+            let cached =
+                state.lock().unwrap().executables.get(&path).cloned();
+            if let Some((cached_mtimes, names)) = cached {
+                if cached_mtimes == mtimes {
+                    return Response::Executables(names);
+                }
+            }
+            let names = super::completion::executables(&path);
+            state
+                .lock()
+                .unwrap()
+                .executables
+                .insert(path, (mtimes, names.clone()));
+            Response::Executables(names)
+        }
+    }
+}
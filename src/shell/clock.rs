@@ -0,0 +1,76 @@
+use crate::shell::prelude::*;
+
+// everything in the shell which needs to know the current time goes through
+// this, so that tests can control it. timers (the clock redraw and frame
+// scheduling) use tokio's time instead, which tests can control with
+// tokio::time::pause and tokio::time::advance.
+pub trait Clock: Send + Sync {
+    fn instant(&self) -> std::time::Instant;
+    fn time(&self) -> time::OffsetDateTime;
+}
+
+pub type Shared = std::sync::Arc<dyn Clock>;
+
+pub struct System;
+
+impl Clock for System {
+    fn instant(&self) -> std::time::Instant {
+        std::time::Instant::now()
+    }
+
+    fn time(&self) -> time::OffsetDateTime {
+        time::OffsetDateTime::now_utc()
+    }
+}
+
+// a clock which only moves when it is told to
+#[cfg(test)]
+pub struct Manual {
+    start_instant: std::time::Instant,
+    start_time: time::OffsetDateTime,
+    elapsed: std::sync::Mutex<std::time::Duration>,
+}
+
+#[cfg(test)]
+impl Manual {
+    pub fn new(start_time: time::OffsetDateTime) -> Self {
+        Self {
+            start_instant: std::time::Instant::now(),
+            start_time,
+            elapsed: std::sync::Mutex::new(std::time::Duration::ZERO),
+        }
+    }
+
+    pub fn advance(&self, duration: std::time::Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+    }
+}
+
+#[cfg(test)]
+impl Clock for Manual {
+    fn instant(&self) -> std::time::Instant {
+        self.start_instant + *self.elapsed.lock().unwrap()
+    }
+
+    fn time(&self) -> time::OffsetDateTime {
+        self.start_time + *self.elapsed.lock().unwrap()
+    }
+}
+
+#[test]
+fn test_manual() {
+    let clock = Manual::new(
+        time::macros::datetime!(2022-03-08 22:50:48).assume_utc(),
+    );
+    let instant = clock.instant();
+    assert_eq!(clock.instant(), instant);
+    clock.advance(std::time::Duration::from_secs(90));
+    assert_eq!(
+        clock.instant() - instant,
+        std::time::Duration::from_secs(90)
+    );
+    assert_eq!(
+        clock.time(),
+        time::macros::datetime!(2022-03-08 22:52:18).assume_utc()
+    );
+}
@@ -0,0 +1,73 @@
+use crate::shell::prelude::*;
+
+// the latest list of executables that the cache daemon gave us, and the
+// PATH that it was for. the daemon is only ever asked in the background,
+// since completion happens while the user is waiting for it, and the daemon
+// can be slow to answer (or not answer at all).
+static CACHED: once_cell::sync::Lazy<
+    std::sync::Mutex<Option<(String, Vec<String>)>>,
+> = once_cell::sync::Lazy::new(|| std::sync::Mutex::new(None));
+
+pub fn complete(prefix: &str, env: &Env) -> Vec<String> {
+    let path = if let Some(path) = env.var("PATH") {
+        path
+    } else {
+        return vec![];
+    };
+    refresh(&path);
+    let cached = CACHED
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|(cached_path, _)| *cached_path == path)
+        .map(|(_, names)| {
+            names
+                .iter()
+                .filter(|name| name.starts_with(prefix))
+                .cloned()
+                .collect()
+        });
+    cached.unwrap_or_else(|| list(&path, prefix))
+}
+
+// the next completion will see the result of this
+fn refresh(path: &str) {
+    let path = path.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Some(names) = crate::shell::cache::executables(&path) {
+            *CACHED.lock().unwrap() = Some((path, names));
+        }
+    });
+}
+
+// the executables in the given PATH which start with the prefix
+pub fn list(path: &str, prefix: &str) -> Vec<String> {
+    let mut names = vec![];
+    for dir in std::env::split_paths(&path) {
+        let entries = if let Ok(entries) = std::fs::read_dir(&dir) {
+            entries
+        } else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = if let Ok(name) = entry.file_name().into_string() {
+                name
+            } else {
+                continue;
+            };
+            if !name.starts_with(prefix) {
+                continue;
+            }
+            // follow symlinks, since most things in /usr/bin are
+            let executable =
+                std::fs::metadata(entry.path()).map_or(false, |metadata| {
+                    metadata.is_file()
+                        && metadata.permissions().mode() & 0o111 != 0
+                });
+            if executable {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
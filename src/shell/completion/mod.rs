@@ -0,0 +1,173 @@
+use crate::shell::prelude::*;
+
+mod exe;
+mod path;
+mod user;
+mod var;
+
+// every executable in the given PATH
+pub fn executables(path: &str) -> Vec<String> {
+    exe::list(path, "")
+}
+
+pub struct Completions {
+    start: usize,
+    candidates: Vec<String>,
+}
+
+impl Completions {
+    // the byte offset into the input where the word being completed starts
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn candidates(&self) -> &[String] {
+        &self.candidates
+    }
+
+    pub fn common_prefix(&self) -> &str {
+        let first = if let Some(first) = self.candidates.first() {
+            first
+        } else {
+            return "";
+        };
+        let mut len = first.len();
+        for candidate in &self.candidates[1..] {
+            len = first
+                .char_indices()
+                .zip(candidate.chars())
+                .find(|((_, a), b)| a != b)
+                .map_or(len.min(candidate.len()), |((i, _), _)| i)
+                .min(len);
+        }
+        &first[..len]
+    }
+}
+
+// input should be the contents of the readline up to the cursor
+pub fn complete(input: &str, env: &Env) -> Completions {
+    let start = word_start(input);
+    let word = &input[start..];
+    let mut candidates = if let Some(name) = word.strip_prefix('$') {
+        var::complete(name, env)
+            .into_iter()
+            .map(|name| format!("${}", name))
+            .collect()
+    } else if word.starts_with('~') && !word.contains('/') {
+        user::complete(&word[1..], env)
+    } else if is_command_position(&input[..start]) && !word.contains('/') {
+        exe::complete(&unescape(word), env)
+            .into_iter()
+            .map(|name| escape(&name))
+            .collect()
+    } else {
+        path::complete(&unescape(word), env)
+            .into_iter()
+            .map(|path| escape(&path))
+            .collect()
+    };
+    candidates.sort();
+    candidates.dedup();
+    Completions { start, candidates }
+}
+
+fn word_start(input: &str) -> usize {
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c.is_whitespace() || is_separator(c) {
+            start = i + c.len_utf8();
+        }
+    }
+    start
+}
+
+fn is_command_position(before: &str) -> bool {
+    let before = before.trim_end();
+    before.is_empty()
+        || before.ends_with(is_separator)
+        || before.ends_with("&&")
+        || before.ends_with("||")
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '|' | ';' | '(' | ')')
+}
+
+// these are the characters that can't appear unescaped in a bareword
+fn needs_escape(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            '|' | ';' | '"' | '\'' | '$' | '{' | '}' | '(' | ')' | '#' | '\\'
+        )
+}
+
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        if needs_escape(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(c) = chars.next() {
+                unescaped.push(c);
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+#[test]
+fn test_word_start() {
+    assert_eq!(word_start(""), 0);
+    assert_eq!(word_start("ls"), 0);
+    assert_eq!(word_start("ls "), 3);
+    assert_eq!(word_start("ls foo"), 3);
+    assert_eq!(word_start("ls foo\\ bar"), 3);
+    assert_eq!(word_start("ls|gr"), 3);
+    assert!(is_command_position(""));
+    assert!(is_command_position("ls | "));
+    assert!(is_command_position("true && "));
+    assert!(!is_command_position("ls "));
+}
+
+#[test]
+fn test_common_prefix() {
+    let completions = Completions {
+        start: 0,
+        candidates: vec!["foobar".to_string(), "foobaz".to_string()],
+    };
+    assert_eq!(completions.common_prefix(), "fooba");
+    let completions = Completions {
+        start: 0,
+        candidates: vec!["foo".to_string(), "foobar".to_string()],
+    };
+    assert_eq!(completions.common_prefix(), "foo");
+    let completions = Completions {
+        start: 0,
+        candidates: vec![],
+    };
+    assert_eq!(completions.common_prefix(), "");
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(escape("foo bar"), "foo\\ bar");
+    assert_eq!(unescape(&escape("a$b'c\\d")), "a$b'c\\d");
+}
@@ -0,0 +1,47 @@
+use crate::shell::prelude::*;
+
+pub fn complete(word: &str, env: &Env) -> Vec<String> {
+    let (dir, prefix) = word
+        .rsplit_once('/')
+        .map_or(("", word), |(dir, prefix)| (dir, prefix));
+    let dir_prefix = if word.contains('/') {
+        format!("{}/", dir)
+    } else {
+        "".to_string()
+    };
+    let full_dir = if word.starts_with('/') && dir.is_empty() {
+        std::path::PathBuf::from("/")
+    } else if let Ok(dir) = crate::parse::ast::expand_home(dir, env) {
+        env.pwd().join(dir)
+    } else {
+        return vec![];
+    };
+
+    let entries = if let Ok(entries) = std::fs::read_dir(&full_dir) {
+        entries
+    } else {
+        return vec![];
+    };
+    let mut paths = vec![];
+    for entry in entries.flatten() {
+        let name = if let Ok(name) = entry.file_name().into_string() {
+            name
+        } else {
+            continue;
+        };
+        if !name.starts_with(prefix)
+            || (name.starts_with('.') && !prefix.starts_with('.'))
+        {
+            continue;
+        }
+        let is_dir = std::fs::metadata(entry.path())
+            .map_or(false, |metadata| metadata.is_dir());
+        paths.push(format!(
+            "{}{}{}",
+            dir_prefix,
+            name,
+            if is_dir { "/" } else { "" }
+        ));
+    }
+    paths
+}
@@ -0,0 +1,32 @@
+use crate::shell::prelude::*;
+
+// completes both usernames and named directories, since both are valid
+// after a ~
+pub fn complete(prefix: &str, env: &Env) -> Vec<String> {
+    let mut names: Vec<_> = env
+        .named_dirs()
+        .into_iter()
+        .map(|(name, _)| name)
+        .chain(usernames())
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| format!("~{}/", name))
+        .collect();
+    if prefix.is_empty() {
+        names.push("~/".to_string());
+    }
+    names
+}
+
+fn usernames() -> Vec<String> {
+    std::fs::read_to_string("/etc/passwd").map_or_else(
+        |_| vec![],
+        |passwd| {
+            passwd
+                .lines()
+                .filter_map(|line| line.split(':').next())
+                .filter(|name| !name.is_empty() && !name.starts_with('#'))
+                .map(std::string::ToString::to_string)
+                .collect()
+        },
+    )
+}
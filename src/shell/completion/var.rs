@@ -0,0 +1,8 @@
+use crate::shell::prelude::*;
+
+pub fn complete(prefix: &str, env: &Env) -> Vec<String> {
+    env.var_names()
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .collect()
+}
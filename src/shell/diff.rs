@@ -0,0 +1,142 @@
+use crate::shell::prelude::*;
+
+pub struct Diff {
+    title: String,
+    lines: Vec<Line>,
+    scroll: usize,
+}
+
+impl Diff {
+    pub fn new(
+        from: &super::history::Entry,
+        to: &super::history::Entry,
+    ) -> Self {
+        let from_text = from.output_text();
+        let to_text = to.output_text();
+        Self {
+            title: format!(
+                "--- {}: {}\n+++ {}: {}",
+                from.idx() + 1,
+                crate::format::command_line(from.cmd()),
+                to.idx() + 1,
+                crate::format::command_line(to.cmd())
+            ),
+            lines: diff_lines(&lines(&from_text), &lines(&to_text)),
+            scroll: 0,
+        }
+    }
+
+    pub fn render(&self, out: &mut impl textmode::Textmode) {
+        let size = out.screen().size();
+        out.move_to(0, 0);
+        out.set_fgcolor(textmode::color::YELLOW);
+        for (i, line) in self.title.lines().enumerate() {
+            out.move_to(i.try_into().unwrap(), 0);
+            out.write_str(line);
+        }
+        out.reset_attributes();
+
+        let header_lines = self.title.lines().count();
+        let rows = usize::from(size.0).saturating_sub(header_lines);
+        for (i, line) in
+            self.lines.iter().skip(self.scroll).take(rows).enumerate()
+        {
+            out.move_to((header_lines + i).try_into().unwrap(), 0);
+            let (prefix, text) = match line {
+                Line::Same(text) => (" ", text),
+                Line::Removed(text) => {
+                    out.set_fgcolor(textmode::color::RED);
+                    ("-", text)
+                }
+                Line::Added(text) => {
+                    out.set_fgcolor(textmode::color::GREEN);
+                    ("+", text)
+                }
+            };
+            out.write_str(prefix);
+            out.write_str(text);
+            out.reset_attributes();
+        }
+        out.hide_cursor(true);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.scroll =
+            (self.scroll + lines).min(self.lines.len().saturating_sub(1));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Line {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+fn lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<_> = text.lines().collect();
+    while lines.last().map_or(false, |line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+// a basic longest common subsequence diff - entry outputs are limited to the
+// size of the terminal, so the quadratic behavior isn't a concern here
+fn diff_lines(from: &[&str], to: &[&str]) -> Vec<Line> {
+    let mut lcs = vec![vec![0_usize; to.len() + 1]; from.len() + 1];
+    for i in (0..from.len()).rev() {
+        for j in (0..to.len()).rev() {
+            lcs[i][j] = if from[i] == to[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < from.len() && j < to.len() {
+        if from[i] == to[j] {
+            lines.push(Line::Same(from[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            lines.push(Line::Removed(from[i].to_string()));
+            i += 1;
+        } else {
+            lines.push(Line::Added(to[j].to_string()));
+            j += 1;
+        }
+    }
+    lines.extend(
+        from[i..]
+            .iter()
+            .map(|line| Line::Removed((*line).to_string())),
+    );
+    lines.extend(to[j..].iter().map(|line| Line::Added((*line).to_string())));
+    lines
+}
+
+#[test]
+fn test_diff_lines() {
+    assert_eq!(
+        diff_lines(&["a", "b", "c"], &["a", "c", "d"]),
+        vec![
+            Line::Same("a".to_string()),
+            Line::Removed("b".to_string()),
+            Line::Same("c".to_string()),
+            Line::Added("d".to_string()),
+        ]
+    );
+    assert_eq!(diff_lines(&[], &["a"]), vec![Line::Added("a".to_string())]);
+    assert_eq!(
+        diff_lines(&["a"], &[]),
+        vec![Line::Removed("a".to_string())]
+    );
+}
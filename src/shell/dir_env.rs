@@ -0,0 +1,163 @@
+use crate::shell::prelude::*;
+
+// sets up the environment for the directory it is in and everything under it
+pub const FILE_NAME: &str = ".nbsh-env";
+
+// the variables set by the .nbsh-env file for the directory tree that the
+// shell is currently in. these files can contain anything, so they aren't
+// loaded until they have been allowed, and they have to be allowed again
+// whenever they change.
+#[derive(Default)]
+pub struct DirEnv {
+    active: Option<Active>,
+    // a file that was found but hasn't been allowed yet
+    blocked: Option<std::path::PathBuf>,
+    reload: bool,
+}
+
+struct Active {
+    file: std::path::PathBuf,
+    // the values the variables had before the file was loaded, or None if
+    // they weren't set, so that they can be put back when leaving the tree
+    saved: Vec<(String, Option<String>)>,
+}
+
+pub enum Update {
+    Loaded(std::path::PathBuf),
+    Blocked(std::path::PathBuf),
+}
+
+impl DirEnv {
+    pub fn reload_requested(&self) -> bool {
+        self.reload
+    }
+
+    // only the hash of the contents is recorded, so the file needs to be
+    // allowed again if it is edited afterwards
+    pub fn allow(&mut self) -> Result<()> {
+        let file = if let Some(file) = self.blocked.take() {
+            file
+        } else {
+            return Ok(());
+        };
+        let contents = std::fs::read_to_string(&file)?;
+        let path = crate::dirs::allowed_envs_file();
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let mut fh = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(fh, "{}", allow_line(&file, &contents))?;
+        self.reload = true;
+        Ok(())
+    }
+
+    pub async fn update(&mut self, env: &mut Env) -> Result<Option<Update>> {
+        let reload = std::mem::take(&mut self.reload);
+        let file = find(env.pwd());
+        if !reload
+            && file.as_deref()
+                == self.active.as_ref().map(|active| active.file.as_path())
+        {
+            return Ok(None);
+        }
+        if let Some(active) = self.active.take() {
+            for (k, v) in active.saved.into_iter().rev() {
+                match v {
+                    Some(v) => env.set_shell_var(&k, &v),
+                    None => env.remove_var(&k),
+                }
+            }
+        }
+        self.blocked = None;
+        let file = if let Some(file) = file {
+            file
+        } else {
+            return Ok(None);
+        };
+        let contents = std::fs::read_to_string(&file)?;
+        if !allowed(&file, &contents) {
+            self.blocked = Some(file.clone());
+            return Ok(Some(Update::Blocked(file)));
+        }
+        let mut active = Active {
+            file: file.clone(),
+            saved: vec![],
+        };
+        let res = load(&contents, env, &mut active.saved).await;
+        // whatever was set before an error still needs to be reverted
+        self.active = Some(active);
+        res?;
+        Ok(Some(Update::Loaded(file)))
+    }
+}
+
+fn find(dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|file| file.is_file())
+}
+
+// only set and export are allowed, since the file is applied by the shell
+// itself rather than by running it
+async fn load(
+    contents: &str,
+    env: &mut Env,
+    saved: &mut Vec<(String, Option<String>)>,
+) -> Result<()> {
+    let commands = crate::parse::ast::Commands::parse(contents)?;
+    for command in commands.commands() {
+        let pipeline =
+            if let crate::parse::ast::Command::Pipeline(pipeline) = command {
+                pipeline
+            } else {
+                return Err(anyhow!("only set and export are allowed"));
+            };
+        for exe in pipeline.clone().eval(env).await?.into_exes() {
+            let mut save = |k: &str, env: &Env| {
+                if !saved.iter().any(|(name, _)| name == k) {
+                    saved.push((k.to_string(), env.var(k)));
+                }
+            };
+            match (exe.exe().to_str(), exe.args()) {
+                (Some("set"), [k, v]) => {
+                    save(k, env);
+                    env.set_shell_var(k, v);
+                }
+                (Some("export"), args) => {
+                    for arg in args {
+                        let (k, v) =
+                            arg.split_once('=').ok_or_else(|| {
+                                anyhow!("export needs a value: {}", arg)
+                            })?;
+                        save(k, env);
+                        env.set_var(k, v);
+                    }
+                }
+                (Some("set"), _) => {
+                    return Err(anyhow!("usage: set key value"));
+                }
+                _ => return Err(anyhow!("only set and export are allowed")),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn allowed(file: &std::path::Path, contents: &str) -> bool {
+    let line = allow_line(file, contents);
+    std::fs::read_to_string(crate::dirs::allowed_envs_file())
+        .map_or(false, |allowed| allowed.lines().any(|l| l == line))
+}
+
+// this needs to be a cryptographic hash, since otherwise a file could be
+// written to match one that was already allowed
+fn allow_line(file: &std::path::Path, contents: &str) -> String {
+    use sha2::Digest as _;
+
+    format!(
+        "{:x} {}",
+        sha2::Sha256::digest(contents.as_bytes()),
+        file.display()
+    )
+}
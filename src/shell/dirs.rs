@@ -0,0 +1,228 @@
+use crate::shell::prelude::*;
+
+// once the ranks add up to more than this, they are all scaled down, so that
+// directories which haven't been used in a long time eventually drop out
+const MAX_TOTAL_RANK: f64 = 9000.0;
+const AGING: f64 = 0.99;
+
+const HOUR: i64 = 60 * 60;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+
+// the directories that have been visited, ranked by frecency in the same way
+// that z does it: each visit adds to the rank, and recent visits count for
+// more than old ones
+#[derive(Default, Debug)]
+pub struct Dirs {
+    entries: Vec<Dir>,
+}
+
+#[derive(Debug)]
+struct Dir {
+    path: std::path::PathBuf,
+    rank: f64,
+    // unix timestamp of the most recent visit
+    time: i64,
+}
+
+impl Dirs {
+    // a missing or unreadable file just means that nothing has been visited
+    // yet
+    pub fn load() -> Self {
+        std::fs::read_to_string(crate::dirs::jump_file())
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    // other shells may have recorded visits since this one last looked, so
+    // the file is read again right before adding to it
+    pub fn record(dir: &std::path::Path) -> std::io::Result<()> {
+        let mut dirs = Self::load();
+        dirs.visit(dir, now());
+        dirs.save()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = crate::dirs::jump_file();
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        // written to a separate file first, so that nothing ever sees it
+        // half written
+        let tmp = path.with_extension(crate::info::pid());
+        std::fs::write(&tmp, self.serialize())?;
+        std::fs::rename(&tmp, &path)
+    }
+
+    pub fn visit(&mut self, dir: &std::path::Path, now: i64) {
+        // the file has one directory per line
+        if dir.as_os_str().as_bytes().contains(&b'\n') {
+            return;
+        }
+        // aging happens first so that the directory being visited doesn't
+        // immediately get dropped
+        if self.entries.iter().map(|entry| entry.rank).sum::<f64>()
+            > MAX_TOTAL_RANK
+        {
+            for entry in &mut self.entries {
+                entry.rank *= AGING;
+            }
+            self.entries.retain(|entry| entry.rank >= 1.0);
+        }
+        if let Some(entry) =
+            self.entries.iter_mut().find(|entry| entry.path == dir)
+        {
+            entry.rank += 1.0;
+            entry.time = now;
+        } else {
+            self.entries.push(Dir {
+                path: dir.to_path_buf(),
+                rank: 1.0,
+                time: now,
+            });
+        }
+    }
+
+    // forgets about directories that have since been removed
+    pub fn retain_existing(&mut self) {
+        self.entries.retain(|entry| entry.path.is_dir());
+    }
+
+    // the directories which contain each of the given fragments in order,
+    // along with their scores, best first
+    pub fn matches(
+        &self,
+        query: &str,
+        now: i64,
+    ) -> Vec<(std::path::PathBuf, f64)> {
+        let fragments: Vec<_> = query.split_whitespace().collect();
+        let mut matches: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|entry| is_match(&entry.path, &fragments))
+            .map(|entry| (entry.path.clone(), entry.frecency(now)))
+            .collect();
+        matches.sort_by(|a, b| b.1.total_cmp(&a.1));
+        matches
+    }
+
+    fn parse(contents: &str) -> Self {
+        Self {
+            entries: contents
+                .lines()
+                .filter_map(|line| {
+                    // the path goes last, since it can contain anything
+                    let mut parts = line.splitn(3, '|');
+                    let rank = parts.next()?.parse().ok()?;
+                    let time = parts.next()?.parse().ok()?;
+                    let path = parts.next()?;
+                    Some(Dir {
+                        path: path.into(),
+                        rank,
+                        time,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn serialize(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| {
+                format!(
+                    "{}|{}|{}\n",
+                    entry.rank,
+                    entry.time,
+                    entry.path.display()
+                )
+            })
+            .collect()
+    }
+}
+
+impl Dir {
+    fn frecency(&self, now: i64) -> f64 {
+        let age = now - self.time;
+        if age < HOUR {
+            self.rank * 4.0
+        } else if age < DAY {
+            self.rank * 2.0
+        } else if age < WEEK {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+pub fn now() -> i64 {
+    time::OffsetDateTime::now_utc().unix_timestamp()
+}
+
+// fragments are matched case insensitively unless they contain an uppercase
+// letter, like smartcase in vim
+fn is_match(path: &std::path::Path, fragments: &[&str]) -> bool {
+    let path = path.to_string_lossy();
+    let lower = path.to_ascii_lowercase();
+    let mut pos = 0;
+    for fragment in fragments {
+        let haystack: &str =
+            if fragment.chars().any(|c| c.is_ascii_uppercase()) {
+                &path
+            } else {
+                &lower
+            };
+        if let Some(found) = haystack[pos..].find(fragment) {
+            pos += found + fragment.len();
+        } else {
+            return false;
+        }
+    }
+    true
+}
+
+#[test]
+fn test_matches() {
+    let mut dirs = Dirs::default();
+    dirs.visit(std::path::Path::new("/home/user/src/nbsh"), 0);
+    dirs.visit(std::path::Path::new("/home/user/src/Textmode"), 0);
+    dirs.visit(std::path::Path::new("/home/user/src/textmode"), 0);
+    dirs.visit(std::path::Path::new("/home/user/src/textmode"), 0);
+    dirs.visit(std::path::Path::new("/tmp"), WEEK);
+
+    let paths = |query: &str| -> Vec<_> {
+        dirs.matches(query, WEEK)
+            .into_iter()
+            .map(|(path, _)| path.display().to_string())
+            .collect()
+    };
+    // /tmp was only visited once, but much more recently
+    assert_eq!(
+        paths(""),
+        vec![
+            "/tmp",
+            "/home/user/src/textmode",
+            "/home/user/src/nbsh",
+            "/home/user/src/Textmode"
+        ]
+    );
+    assert_eq!(
+        paths("text"),
+        vec!["/home/user/src/textmode", "/home/user/src/Textmode"]
+    );
+    assert_eq!(paths("Text"), vec!["/home/user/src/Textmode"]);
+    assert_eq!(paths("src nb"), vec!["/home/user/src/nbsh"]);
+    assert!(paths("nb src").is_empty());
+}
+
+#[test]
+fn test_parse() {
+    let mut dirs = Dirs::default();
+    dirs.visit(std::path::Path::new("/a|b"), 10);
+    dirs.visit(std::path::Path::new("/c"), 20);
+    dirs.visit(std::path::Path::new("/c"), 30);
+    dirs.visit(std::path::Path::new("/bad\nname"), 30);
+    let serialized = dirs.serialize();
+    assert_eq!(serialized, "1|10|/a|b\n2|30|/c\n");
+    let parsed = Dirs::parse(&format!("{}garbage\n", serialized));
+    assert_eq!(parsed.serialize(), serialized);
+}
@@ -0,0 +1,156 @@
+use crate::shell::prelude::*;
+
+// set for each runner by the shell, rather than by anything it ran
+const IGNORED_VARS: &[&str] = &["NBSH_ENTRY"];
+
+// what running an entry changed: the environment and directory it started
+// with, compared to the ones the runner reported when it exited
+pub struct EnvDiff {
+    title: String,
+    lines: Option<Vec<Line>>,
+    scroll: usize,
+}
+
+impl EnvDiff {
+    pub fn new(entry: &super::history::Entry) -> Self {
+        Self {
+            title: format!(
+                "{}: {}",
+                entry.idx() + 1,
+                crate::format::command_line(entry.cmd())
+            ),
+            lines: entry
+                .exit_env()
+                .map(|exit_env| diff_envs(entry.env(), exit_env)),
+            scroll: 0,
+        }
+    }
+
+    pub fn render(&self, out: &mut impl textmode::Textmode) {
+        let size = out.screen().size();
+        out.move_to(0, 0);
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&self.title);
+        out.reset_attributes();
+
+        let lines = match &self.lines {
+            Some(lines) if !lines.is_empty() => lines,
+            lines => {
+                out.move_to(1, 0);
+                out.write_str(&crate::msg::get(
+                    if lines.is_some() {
+                        crate::msg::Message::NoEnvChanges
+                    } else {
+                        crate::msg::Message::NoExitEnv
+                    },
+                    &[],
+                ));
+                out.hide_cursor(true);
+                return;
+            }
+        };
+        let rows = usize::from(size.0).saturating_sub(1);
+        for (i, line) in lines.iter().skip(self.scroll).take(rows).enumerate()
+        {
+            out.move_to((i + 1).try_into().unwrap(), 0);
+            let text = match line {
+                Line::Directory(from, to) => {
+                    out.set_fgcolor(textmode::color::YELLOW);
+                    format!("cd {} -> {}", from, to)
+                }
+                Line::Removed(name, value) => {
+                    out.set_fgcolor(textmode::color::RED);
+                    format!(
+                        "-{}={}",
+                        name,
+                        super::env_editor::escape_control(value)
+                    )
+                }
+                Line::Added(name, value) => {
+                    out.set_fgcolor(textmode::color::GREEN);
+                    format!(
+                        "+{}={}",
+                        name,
+                        super::env_editor::escape_control(value)
+                    )
+                }
+            };
+            out.write_str(super::readline::truncate(
+                &text,
+                usize::from(size.1),
+            ));
+            out.reset_attributes();
+        }
+        out.hide_cursor(true);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.scroll = self.scroll.saturating_sub(lines);
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        let len = self.lines.as_ref().map_or(0, Vec::len);
+        self.scroll = (self.scroll + lines).min(len.saturating_sub(1));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Line {
+    Directory(String, String),
+    Removed(String, String),
+    Added(String, String),
+}
+
+// a changed variable shows up as its old value being removed and its new
+// value being added, like a changed line in a diff
+fn diff_envs(from: &Env, to: &Env) -> Vec<Line> {
+    let mut lines = vec![];
+    if from.pwd() != to.pwd() {
+        lines.push(Line::Directory(
+            crate::format::path(from.pwd()),
+            crate::format::path(to.pwd()),
+        ));
+    }
+    let mut names = from.var_names();
+    names.extend(to.var_names());
+    names.sort();
+    names.dedup();
+    for name in names {
+        if IGNORED_VARS.contains(&name.as_str()) {
+            continue;
+        }
+        let (old, new) = (from.var(&name), to.var(&name));
+        if old == new {
+            continue;
+        }
+        if let Some(old) = old {
+            lines.push(Line::Removed(name.clone(), old));
+        }
+        if let Some(new) = new {
+            lines.push(Line::Added(name, new));
+        }
+    }
+    lines
+}
+
+#[test]
+fn test_diff_envs() {
+    let mut from = Env::new().unwrap();
+    from.set_var("SAME", "1");
+    from.set_var("CHANGED", "old");
+    from.set_var("REMOVED", "gone");
+    let mut to = from.clone();
+    to.set_var("CHANGED", "new");
+    to.remove_var("REMOVED");
+    to.set_var("ADDED", "here");
+    to.set_var("NBSH_ENTRY", "3");
+    assert_eq!(
+        diff_envs(&from, &to),
+        vec![
+            Line::Added("ADDED".to_string(), "here".to_string()),
+            Line::Removed("CHANGED".to_string(), "old".to_string()),
+            Line::Added("CHANGED".to_string(), "new".to_string()),
+            Line::Removed("REMOVED".to_string(), "gone".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,159 @@
+use crate::shell::prelude::*;
+
+use unicode_width::UnicodeWidthStr as _;
+
+// lists the variables that the next entry will be run with, narrowing them
+// down as fragments of the name are typed. the selected variable can be
+// changed or removed, which only affects entries run after that.
+#[derive(Default)]
+pub struct EnvEditor {
+    query: String,
+    selected: usize,
+    // the name of the variable being edited, and its new value so far
+    editing: Option<(String, String)>,
+}
+
+impl EnvEditor {
+    pub fn editing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    pub fn add_input(&mut self, s: &str) {
+        if let Some((_, value)) = &mut self.editing {
+            value.push_str(s);
+        } else {
+            self.query.push_str(s);
+            self.selected = 0;
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some((_, value)) = &mut self.editing {
+            value.pop();
+        } else {
+            self.query.pop();
+            self.selected = 0;
+        }
+    }
+
+    pub fn select_next(&mut self, env: &Env) {
+        if self.editing.is_none()
+            && self.selected + 1 < self.matches(env).len()
+        {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        if self.editing.is_none() {
+            self.selected = self.selected.saturating_sub(1);
+        }
+    }
+
+    // editing starts from the current value, like editing a previous
+    // command does
+    pub fn start_editing(&mut self, env: &Env) {
+        if let Some(name) = self.matches(env).into_iter().nth(self.selected) {
+            let value = env.var(&name).unwrap_or_default();
+            self.editing = Some((name, value));
+        }
+    }
+
+    pub fn finish_editing(&mut self, env: &mut Env) {
+        if let Some((name, value)) = self.editing.take() {
+            env.set_shell_var(&name, &value);
+        }
+    }
+
+    pub fn cancel_editing(&mut self) {
+        self.editing = None;
+    }
+
+    pub fn delete(&mut self, env: &mut Env) {
+        if self.editing.is_some() {
+            return;
+        }
+        if let Some(name) = self.matches(env).into_iter().nth(self.selected) {
+            env.remove_var(&name);
+            self.selected =
+                self.selected.min(self.matches(env).len().saturating_sub(1));
+        }
+    }
+
+    pub fn render(&self, out: &mut impl textmode::Textmode, env: &Env) {
+        let size = out.screen().size();
+        let width = usize::from(size.1);
+        let matches = self.matches(env);
+
+        let rows = usize::from(size.0).saturating_sub(1);
+        let start = (self.selected + 1).saturating_sub(rows);
+        for (i, name) in matches.iter().enumerate().skip(start).take(rows) {
+            out.move_to((i - start).try_into().unwrap(), 0);
+            out.reset_attributes();
+            if i == self.selected {
+                out.set_bgcolor(env.color_scheme().highlight());
+            } else {
+                out.set_bgcolor(env.color_scheme().background());
+            }
+            out.write(b"\x1b[K");
+            let value = match &self.editing {
+                Some((editing, value)) if editing == name => value.clone(),
+                _ => env.var(name).unwrap_or_default(),
+            };
+            out.write_str(super::readline::truncate(
+                &format!("{}={}", name, escape_control(&value)),
+                width,
+            ));
+        }
+
+        out.move_to(size.0 - 1, 0);
+        out.reset_attributes();
+        let (prompt, input) = match &self.editing {
+            Some((name, value)) => (
+                crate::msg::get(crate::msg::Message::EnvEdit, &[name]),
+                value,
+            ),
+            None => {
+                (crate::msg::get(crate::msg::Message::Env, &[]), &self.query)
+            }
+        };
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&prompt);
+        out.reset_attributes();
+        let input = super::readline::truncate(
+            input,
+            width.saturating_sub(prompt.width()),
+        );
+        out.write_str(input);
+        out.move_to(
+            size.0 - 1,
+            (prompt.width() + input.width()).try_into().unwrap(),
+        );
+    }
+
+    fn matches(&self, env: &Env) -> Vec<String> {
+        let query = self.query.to_lowercase();
+        let mut names: Vec<_> = env
+            .var_names()
+            .into_iter()
+            .filter(|name| name.to_lowercase().contains(&query))
+            .collect();
+        // a local variable can shadow an exported one of the same name
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+// values can contain newlines and such, which would break up the list
+pub fn escape_control(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
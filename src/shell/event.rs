@@ -3,13 +3,24 @@ use crate::prelude::*;
 #[derive(Debug)]
 pub enum Event {
     Key(textmode::Key),
+    // the raw contents of a bracketed paste
+    Paste(Vec<u8>),
     Resize((u16, u16)),
-    PtyOutput,
+    Focus(bool),
+    PtyOutput(usize),
+    // only produced by the reader, to report every entry that produced
+    // output since the last time this event was handled
+    PtyOutputs(std::collections::BTreeSet<usize>),
     ChildRunPipeline(usize, (usize, usize)),
     ChildSuspend(usize),
+    ChildRequestInput(usize),
+    ChildStopped(usize, bool),
     ChildExit(usize, super::history::ExitInfo, Option<Env>),
-    GitInfo(Option<super::inputs::GitInfo>),
+    PromptInfo(super::inputs::PromptUpdate),
+    UpdateAvailable(String),
     ClockTimer,
+    // the process groups which are blocked reading from a terminal
+    Waiting(std::collections::HashSet<nix::unistd::Pid>),
 }
 
 pub fn channel() -> (Writer, Reader) {
@@ -89,14 +100,21 @@ impl InnerReader {
 #[allow(clippy::option_option)]
 #[derive(Default)]
 struct Pending {
-    key: std::collections::VecDeque<textmode::Key>,
+    // keys and pastes, which need to stay in the order they were typed
+    input: std::collections::VecDeque<Event>,
     size: Option<(u16, u16)>,
-    pty_output: bool,
+    focus: Option<bool>,
+    pty_output: std::collections::BTreeSet<usize>,
     child_run_pipeline: std::collections::VecDeque<(usize, (usize, usize))>,
     child_suspend: std::collections::VecDeque<usize>,
+    child_request_input: std::collections::VecDeque<usize>,
+    child_stopped: std::collections::VecDeque<(usize, bool)>,
     child_exit: Option<(usize, super::history::ExitInfo, Option<Env>)>,
-    git_info: Option<Option<super::inputs::GitInfo>>,
+    // only the latest update from each provider matters
+    prompt_info: std::collections::VecDeque<super::inputs::PromptUpdate>,
+    update_available: Option<String>,
     clock_timer: bool,
+    waiting: Option<std::collections::HashSet<nix::unistd::Pid>>,
     done: bool,
 }
 
@@ -109,55 +127,114 @@ impl Pending {
         if self.done {
             return Some(None);
         }
-        if let Some(key) = self.key.pop_front() {
-            return Some(Some(Event::Key(key)));
+        if let Some(input) = self.input.pop_front() {
+            return Some(Some(input));
         }
         if let Some(size) = self.size.take() {
             return Some(Some(Event::Resize(size)));
         }
+        if let Some(focused) = self.focus.take() {
+            return Some(Some(Event::Focus(focused)));
+        }
         if let Some((idx, span)) = self.child_run_pipeline.pop_front() {
             return Some(Some(Event::ChildRunPipeline(idx, span)));
         }
         if let Some(idx) = self.child_suspend.pop_front() {
             return Some(Some(Event::ChildSuspend(idx)));
         }
+        if let Some(idx) = self.child_request_input.pop_front() {
+            return Some(Some(Event::ChildRequestInput(idx)));
+        }
+        if let Some((idx, stopped)) = self.child_stopped.pop_front() {
+            return Some(Some(Event::ChildStopped(idx, stopped)));
+        }
         if let Some((idx, exit_info, env)) = self.child_exit.take() {
             return Some(Some(Event::ChildExit(idx, exit_info, env)));
         }
-        if let Some(info) = self.git_info.take() {
-            return Some(Some(Event::GitInfo(info)));
+        if let Some(update) = self.prompt_info.pop_front() {
+            return Some(Some(Event::PromptInfo(update)));
+        }
+        if let Some(version) = self.update_available.take() {
+            return Some(Some(Event::UpdateAvailable(version)));
         }
         if self.clock_timer {
             self.clock_timer = false;
             return Some(Some(Event::ClockTimer));
         }
+        if let Some(blocked) = self.waiting.take() {
+            return Some(Some(Event::Waiting(blocked)));
+        }
         // process_output should be last because it will often be the case
         // that there is ~always new process output (cat on large files, yes,
         // etc) and that shouldn't prevent other events from happening
-        if self.pty_output {
-            self.pty_output = false;
-            return Some(Some(Event::PtyOutput));
+        if !self.pty_output.is_empty() {
+            return Some(Some(Event::PtyOutputs(std::mem::take(
+                &mut self.pty_output,
+            ))));
         }
         None
     }
 
     fn new_event(&mut self, event: Option<Event>) {
         match event {
-            Some(Event::Key(key)) => self.key.push_back(key),
+            Some(event @ (Event::Key(_) | Event::Paste(_))) => {
+                self.input.push_back(event);
+            }
             Some(Event::Resize(size)) => self.size = Some(size),
-            Some(Event::PtyOutput) => self.pty_output = true,
+            Some(Event::Focus(focused)) => self.focus = Some(focused),
+            Some(Event::PtyOutput(idx)) => {
+                self.pty_output.insert(idx);
+            }
+            Some(Event::PtyOutputs(idxs)) => self.pty_output.extend(idxs),
             Some(Event::ChildRunPipeline(idx, span)) => {
                 self.child_run_pipeline.push_back((idx, span));
             }
             Some(Event::ChildSuspend(idx)) => {
                 self.child_suspend.push_back(idx);
             }
+            Some(Event::ChildRequestInput(idx)) => {
+                self.child_request_input.push_back(idx);
+            }
+            Some(Event::ChildStopped(idx, stopped)) => {
+                self.child_stopped.push_back((idx, stopped));
+            }
             Some(Event::ChildExit(idx, exit_info, env)) => {
                 self.child_exit = Some((idx, exit_info, env));
             }
-            Some(Event::GitInfo(info)) => self.git_info = Some(info),
+            Some(Event::PromptInfo(update)) => {
+                self.prompt_info.retain(|pending| {
+                    std::mem::discriminant(pending)
+                        != std::mem::discriminant(&update)
+                });
+                self.prompt_info.push_back(update);
+            }
+            Some(Event::UpdateAvailable(version)) => {
+                self.update_available = Some(version);
+            }
             Some(Event::ClockTimer) => self.clock_timer = true,
+            Some(Event::Waiting(blocked)) => self.waiting = Some(blocked),
             None => self.done = true,
         }
     }
 }
+
+#[test]
+fn test_pty_output_coalescing() {
+    let mut pending = Pending::new();
+    for i in 0..1000 {
+        pending.new_event(Some(Event::PtyOutput(i % 3)));
+    }
+    pending.new_event(Some(Event::ClockTimer));
+
+    let mut events = vec![];
+    while let Some(Some(event)) = pending.get_event() {
+        events.push(event);
+    }
+    assert_eq!(events.len(), 2);
+    assert!(matches!(events[0], Event::ClockTimer));
+    if let Event::PtyOutputs(idxs) = &events[1] {
+        assert_eq!(idxs.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+    } else {
+        panic!("expected PtyOutputs, got {:?}", events[1]);
+    }
+}
@@ -1,10 +1,44 @@
 use crate::shell::prelude::*;
 
+use unicode_width::UnicodeWidthStr as _;
+
 pub struct Entry {
     cmdline: String,
+    // how the command line was parsed, for the inspector. None if it failed
+    // to parse, in which case the runner will report the error.
+    ast: Option<crate::parse::ast::Commands>,
+    // the git commit checked out when the command was started, if any
+    git_commit: Option<String>,
     env: Env,
+    // the environment that the runner reported when it exited, which isn't
+    // available for entries that were killed or restored from a session
+    exit_env: Option<Env>,
+    pwd: std::sync::Arc<std::sync::Mutex<std::path::PathBuf>>,
+    timings:
+        std::sync::Arc<std::sync::Mutex<Vec<Vec<crate::runner::Timing>>>>,
     pty: super::pty::Pty,
+    // the process group of the command currently in the foreground, once
+    // the runner has reported it
+    pg: std::sync::Arc<std::sync::Mutex<Option<nix::unistd::Pid>>>,
+    // the runner process itself, which isn't part of the process group
+    runner_pid: Option<nix::unistd::Pid>,
     fullscreen: Option<bool>,
+    stopped: bool,
+    suspended: bool,
+    killed: bool,
+    // blocked reading from the terminal, as far as we can tell
+    waiting: bool,
+    collapsed: bool,
+    // the index of the link selected with escape u, if any
+    link: Option<usize>,
+    // restored from a saved session rather than run in this one
+    restored: bool,
+    // run from the profile or rc file rather than typed in
+    startup: bool,
+    // earlier runs of this entry which were replaced by rerunning it, oldest
+    // first
+    archived: Vec<super::session::Entry>,
+    clock: crate::shell::clock::Shared,
     start_instant: std::time::Instant,
     start_time: time::OffsetDateTime,
     state: State,
@@ -13,27 +47,221 @@ pub struct Entry {
 impl Entry {
     pub fn new(
         cmdline: String,
+        git_commit: Option<String>,
         env: Env,
         size: (u16, u16),
+        scrollback: usize,
+        clock: crate::shell::clock::Shared,
         event_w: crate::shell::event::Writer,
-    ) -> Result<Self> {
-        let start_instant = std::time::Instant::now();
-        let start_time = time::OffsetDateTime::now_utc();
+    ) -> Self {
+        let start_instant = clock.instant();
+        let start_time = clock.time();
 
-        let (pty, pts) = super::pty::Pty::new(size, event_w.clone()).unwrap();
-        let (child, fh) = Self::spawn_command(&cmdline, &env, &pts)?;
-        tokio::spawn(Self::task(child, fh, env.idx(), event_w));
-        Ok(Self {
+        let pwd = std::sync::Arc::new(std::sync::Mutex::new(
+            env.pwd().to_path_buf(),
+        ));
+        let timings = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let pg = std::sync::Arc::new(std::sync::Mutex::new(None));
+
+        let (pty, pts) = super::pty::Pty::new(
+            env.idx(),
+            size,
+            scrollback,
+            event_w.clone(),
+        )
+        .unwrap();
+        let mut runner_pid = None;
+        let (cpus, command) = split_cpus(split_title(&cmdline).1);
+        let ast = crate::parse::ast::Commands::parse(command).ok();
+        match Self::spawn_command(command, cpus, &env, &pts) {
+            Ok((child, fh)) => {
+                runner_pid = child.id().map(|id| {
+                    nix::unistd::Pid::from_raw(id.try_into().unwrap())
+                });
+                tokio::spawn(Self::task(
+                    child,
+                    fh,
+                    env.idx(),
+                    pwd.clone(),
+                    timings.clone(),
+                    pg.clone(),
+                    clock.clone(),
+                    event_w,
+                ));
+            }
+            Err(e) => {
+                // report this as a failed entry rather than bringing down
+                // the whole shell
+                pty.with_vt_mut(|vt| {
+                    vt.process(
+                        format!(
+                            "{}\r\n",
+                            crate::msg::get(
+                                crate::msg::Message::FailedToRun,
+                                &[&e]
+                            )
+                        )
+                        .as_bytes(),
+                    );
+                });
+                event_w.send(Event::ChildExit(
+                    env.idx(),
+                    ExitInfo::new(crate::status::failure(), clock.instant()),
+                    None,
+                ));
+            }
+        }
+        Self {
             cmdline,
+            ast,
+            git_commit,
             env,
+            exit_env: None,
+            pwd,
+            timings,
+            pg,
+            runner_pid,
             pty,
             fullscreen: None,
+            stopped: false,
+            suspended: false,
+            killed: false,
+            waiting: false,
+            collapsed: false,
+            link: None,
+            restored: false,
+            startup: false,
+            archived: vec![],
+            clock,
             start_instant,
             start_time,
             state: State::Running((0, 0)),
+        }
+    }
+
+    pub fn restore(
+        saved: super::session::Entry,
+        idx: usize,
+        clock: crate::shell::clock::Shared,
+    ) -> Result<Self> {
+        let mut env = saved.env;
+        env.set_idx(idx);
+        let pty = super::pty::Pty::restored(saved.size);
+        pty.with_vt_mut(|vt| {
+            vt.process(saved.contents.as_bytes());
+            if let Some(hash) = saved.output_hash {
+                vt.set_output_hash(hash);
+            }
+        });
+        let start_instant = clock.instant();
+        // commands which were still running were killed when the shell that
+        // was running them exited
+        let status = saved.status.map_or_else(
+            || crate::status::signaled(libc::SIGHUP),
+            crate::status::from_raw,
+        );
+        let ast = crate::parse::ast::Commands::parse(
+            split_cpus(split_title(&saved.cmdline).1).1,
+        )
+        .ok();
+        Ok(Self {
+            cmdline: saved.cmdline,
+            ast,
+            git_commit: saved.git_commit,
+            pwd: std::sync::Arc::new(std::sync::Mutex::new(
+                env.pwd().to_path_buf(),
+            )),
+            env,
+            exit_env: None,
+            timings: std::sync::Arc::new(std::sync::Mutex::new(vec![])),
+            pg: std::sync::Arc::new(std::sync::Mutex::new(None)),
+            runner_pid: None,
+            pty,
+            fullscreen: None,
+            stopped: false,
+            suspended: false,
+            killed: false,
+            waiting: false,
+            collapsed: false,
+            link: None,
+            restored: true,
+            startup: false,
+            archived: vec![],
+            clock,
+            start_instant,
+            start_time: time::OffsetDateTime::from_unix_timestamp(
+                saved.start_time,
+            )?,
+            state: State::Exited(ExitInfo::new(
+                status,
+                start_instant + saved.duration,
+            )),
         })
     }
 
+    pub fn save(&self) -> super::session::Entry {
+        self.reload();
+        let (size, contents) = self.pty.with_vt(|vt| {
+            (
+                vt.screen().size(),
+                String::from_utf8_lossy(&vt.screen().contents_formatted())
+                    .into_owned(),
+            )
+        });
+        super::session::Entry {
+            cmdline: self.cmdline.clone(),
+            git_commit: self.git_commit.clone(),
+            env: self.env.clone(),
+            start_time: self.start_time.unix_timestamp(),
+            duration: self.duration(),
+            status: self.exit_status().map(crate::status::to_raw),
+            size,
+            contents,
+            output_hash: self.output_hash(),
+        }
+    }
+
+    pub fn restored(&self) -> bool {
+        self.restored
+    }
+
+    pub fn set_startup(&mut self) {
+        self.startup = true;
+    }
+
+    // whether the entry belongs in the history file
+    pub fn saved(&self) -> bool {
+        !self.restored && !self.startup
+    }
+
+    pub fn waiting(&self) -> bool {
+        self.waiting
+    }
+
+    // returns whether the entry started or stopped waiting
+    pub fn update_waiting(
+        &mut self,
+        blocked: &std::collections::HashSet<nix::unistd::Pid>,
+    ) -> bool {
+        let pg = *self.pg.lock().unwrap();
+        let waiting = self.running()
+            && !self.stopped
+            && pg.map_or(false, |pg| blocked.contains(&pg));
+        let changed = waiting != self.waiting;
+        self.waiting = waiting;
+        changed
+    }
+
+    pub fn archive(&mut self, old: Self) {
+        let saved = old.save();
+        self.archived = old.archived;
+        self.archived.push(saved);
+    }
+
+    pub fn archived(&self) -> &[super::session::Entry] {
+        &self.archived
+    }
+
     pub fn render(
         &self,
         out: &mut impl textmode::Textmode,
@@ -42,15 +270,33 @@ impl Entry {
         focused: bool,
         scrolling: bool,
         offset: time::UtcOffset,
+        theme: crate::shell::Theme,
+        colors: crate::shell::ColorScheme,
+        reproduced: Option<bool>,
     ) {
         let idx = self.env.idx();
         let size = out.screen().size();
         let time = self.state.exit_info().map_or_else(
             || {
-                format!(
-                    "[{}]",
+                // kept up to date by the clock timer
+                let time = format!(
+                    "({}) [{}]",
+                    crate::format::elapsed(
+                        self.clock.instant() - self.start_instant
+                    ),
                     crate::format::time(self.start_time.to_offset(offset))
-                )
+                );
+                // show where the command currently is if it has changed
+                // directories since it started, as long as there is room
+                let pwd = self.pwd();
+                if pwd == self.env.pwd() {
+                    return time;
+                }
+                let pwd = crate::format::path(&pwd);
+                if pwd.width() + time.width() > usize::from(size.1) / 3 {
+                    return time;
+                }
+                format!("{} {}", pwd, time)
             },
             |info| {
                 format!(
@@ -67,7 +313,7 @@ impl Entry {
             out.write(b"\x07");
         }
 
-        Self::set_bgcolor(out, idx, focused);
+        Self::set_bgcolor(out, idx, focused, colors);
         out.set_fgcolor(textmode::color::YELLOW);
         let entry_count_width = format!("{}", entry_count + 1).len();
         let idx_str = format!("{}", idx + 1);
@@ -76,45 +322,106 @@ impl Entry {
         out.write_str(" ");
         out.reset_attributes();
 
-        Self::set_bgcolor(out, idx, focused);
-        if let Some(info) = self.state.exit_info() {
-            if info.status.signal().is_some() {
-                out.set_fgcolor(textmode::color::MAGENTA);
-            } else if info.status.success() {
-                out.set_fgcolor(textmode::color::DARKGREY);
-            } else {
-                out.set_fgcolor(textmode::color::RED);
-            }
-            out.write_str(&crate::format::exit_status(info.status));
+        Self::set_bgcolor(out, idx, focused, colors);
+        let status = if let Some(info) = self.state.exit_info() {
+            Some((
+                crate::shell::theme::Status::from_exit_status(info.status),
+                crate::format::exit_status(info.status),
+            ))
+        } else if self.stopped {
+            Some((crate::shell::theme::Status::Stopped, "STOP ".to_string()))
+        } else if self.suspended {
+            Some((
+                crate::shell::theme::Status::Suspended,
+                "SUSP ".to_string(),
+            ))
+        } else if self.waiting {
+            Some((crate::shell::theme::Status::Waiting, "WAIT ".to_string()))
         } else {
-            out.write_str("     ");
+            None
+        };
+        if let Some((status, text)) = status {
+            out.set_fgcolor(theme.status_color(status));
+            out.write_str(&format!("{} {}", status.glyph(), text));
+        } else {
+            out.write_str("       ");
         }
         out.reset_attributes();
 
+        // both of these are cleared as soon as the entry is focused, so they
+        // only show up for entries that haven't been looked at yet
         if vt.is_bell() {
-            out.set_bgcolor(textmode::Color::Rgb(64, 16, 16));
+            out.set_bgcolor(colors.bell());
+            out.set_fgcolor(textmode::color::YELLOW);
+            out.write_str("● ");
+        } else if vt.is_activity() {
+            Self::set_bgcolor(out, idx, focused, colors);
+            out.set_fgcolor(textmode::color::CYAN);
+            out.write_str("● ");
         } else {
-            Self::set_bgcolor(out, idx, focused);
+            Self::set_bgcolor(out, idx, focused, colors);
+            out.write_str("$ ");
+        }
+        out.reset_attributes();
+        Self::set_bgcolor(out, idx, focused, colors);
+        // whether the output matched the previous run of the same command
+        let marker = reproduced.map(|same| if same { '=' } else { '≠' });
+        let marker_width = if marker.is_some() { 2 } else { 0 };
+        // everything here is measured in columns rather than bytes, since
+        // command lines and directories can contain wide characters
+        let end = usize::from(size.1)
+            .saturating_sub(time.width() + 2 + marker_width);
+        let (name, command) = split_title(&self.cmdline);
+        // the running span is relative to what the runner was given
+        let command = split_cpus(command).1;
+        if let Some(name) = name {
+            let start = usize::from(out.screen().cursor_position().1);
+            // leave at least half of the space for the command itself
+            if name.width() + 2 <= end.saturating_sub(start) / 2 {
+                out.set_bold(true);
+                out.write_str(name);
+                out.set_bold(false);
+                out.write_str(": ");
+            }
         }
-        out.write_str("$ ");
-        Self::set_bgcolor(out, idx, focused);
         let start = usize::from(out.screen().cursor_position().1);
-        let end = usize::from(size.1) - time.len() - 2;
-        let max_len = end - start;
-        let cmd = if self.cmd().len() > max_len {
-            &self.cmd()[..(max_len - 4)]
+        let max_width = end.saturating_sub(start);
+        // programs like vim set the window title to something more
+        // descriptive than the command line, so prefer that while it is
+        // running
+        let title = vt.screen().title();
+        let summary = crate::format::command_line(command);
+        // the span can only be shown if it is within the first line
+        let first_len = command.lines().next().map_or(0, str::len);
+        let (full_cmd, span) = match self.state {
+            State::Running(_) if !title.is_empty() => (title, None),
+            State::Running(span) => (
+                &*summary,
+                Some((span.0.min(first_len), span.1.min(first_len))),
+            ),
+            State::Exited(_) => (&*summary, None),
+        };
+        let truncated = full_cmd.width() > max_width;
+        // the span is in bytes, but truncating only ever removes from the
+        // end, so it still lines up with what is left
+        let cmd = if truncated {
+            crate::shell::readline::truncate(
+                full_cmd,
+                max_width.saturating_sub(4),
+            )
         } else {
-            self.cmd()
+            full_cmd
         };
-        if let State::Running(span) = self.state {
-            let span = (span.0.min(cmd.len()), span.1.min(cmd.len()));
+        if let Some(span) = span {
+            let span =
+                (char_boundary(cmd, span.0), char_boundary(cmd, span.1));
             if !cmd[..span.0].is_empty() {
                 out.write_str(&cmd[..span.0]);
             }
             if !cmd[span.0..span.1].is_empty() {
-                out.set_bgcolor(textmode::Color::Rgb(16, 64, 16));
+                out.set_bgcolor(colors.running());
                 out.write_str(&cmd[span.0..span.1]);
-                Self::set_bgcolor(out, idx, focused);
+                Self::set_bgcolor(out, idx, focused, colors);
             }
             if !cmd[span.1..].is_empty() {
                 out.write_str(&cmd[span.1..]);
@@ -122,16 +429,16 @@ impl Entry {
         } else {
             out.write_str(cmd);
         }
-        if self.cmd().len() > max_len {
-            if let State::Running(span) = self.state {
+        if truncated {
+            if let Some(span) = span {
                 if span.0 < cmd.len() && span.1 > cmd.len() {
-                    out.set_bgcolor(textmode::Color::Rgb(16, 64, 16));
+                    out.set_bgcolor(colors.running());
                 }
             }
             out.write_str(" ");
-            if let State::Running(span) = self.state {
+            if let Some(span) = span {
                 if span.1 > cmd.len() {
-                    out.set_bgcolor(textmode::Color::Rgb(16, 64, 16));
+                    out.set_bgcolor(colors.running());
                 }
             }
             out.set_fgcolor(textmode::color::BLUE);
@@ -139,24 +446,40 @@ impl Entry {
         }
         out.reset_attributes();
 
-        Self::set_bgcolor(out, idx, focused);
+        Self::set_bgcolor(out, idx, focused, colors);
         let cur_pos = out.screen().cursor_position();
-        out.write_str(&" ".repeat(
-            usize::from(size.1) - time.len() - 1 - usize::from(cur_pos.1),
-        ));
+        out.write_str(&" ".repeat(usize::from(size.1).saturating_sub(
+            time.width() + 1 + marker_width + usize::from(cur_pos.1),
+        )));
+        if let (Some(marker), Some(same)) = (marker, reproduced) {
+            out.set_fgcolor(theme.reproduced_color(same));
+            out.write_str(&format!("{} ", marker));
+            out.reset_attributes();
+            Self::set_bgcolor(out, idx, focused, colors);
+        }
         out.write_str(&time);
         out.write_str(" ");
         out.reset_attributes();
 
-        if vt.binary() {
-            let msg = "This appears to be binary data. Fullscreen this entry to view anyway.";
-            let len: u16 = msg.len().try_into().unwrap();
+        if self.collapsed {
+            if focused && !scrolling {
+                out.hide_cursor(true);
+            }
+        } else if vt.evicted() {
+            let msg = crate::msg::get(crate::msg::Message::Evicted, &[]);
+            out.move_to(out.screen().cursor_position().0 + 1, 0);
+            out.set_fgcolor(textmode::color::DARKGREY);
+            out.write_str(&msg);
+            out.hide_cursor(true);
+        } else if vt.binary() {
+            let msg = crate::msg::get(crate::msg::Message::BinaryData, &[]);
+            let len: u16 = msg.width().try_into().unwrap();
             out.move_to(
                 out.screen().cursor_position().0 + 1,
-                (size.1 - len) / 2,
+                size.1.saturating_sub(len) / 2,
             );
             out.set_fgcolor(textmode::color::RED);
-            out.write_str(msg);
+            out.write_str(&msg);
             out.hide_cursor(true);
         } else {
             let last_row =
@@ -172,6 +495,13 @@ impl Entry {
             let mut out_row = out.screen().cursor_position().0 + 1;
             let screen = vt.screen();
             let pos = screen.cursor_position();
+            // finding links requires scanning the whole screen, so only
+            // bother for the entry that can actually select them
+            let links = if focused {
+                super::links::find(screen, &[])
+            } else {
+                vec![]
+            };
             let mut wrapped = false;
             let mut cursor_found = None;
             for (idx, row) in screen
@@ -187,6 +517,19 @@ impl Entry {
                 }
                 out.write(&row);
                 wrapped = screen.row_wrapped(idx);
+                for (i, link) in links.iter().enumerate() {
+                    if let Some(cols) = link.cols(idx) {
+                        Self::render_link(
+                            out,
+                            screen,
+                            (idx, out_row),
+                            cols,
+                            self.link == Some(i),
+                        );
+                        // the cursor is no longer at the end of the row
+                        wrapped = false;
+                    }
+                }
                 if pos.0 == idx {
                     cursor_found = Some(out_row);
                 }
@@ -205,6 +548,31 @@ impl Entry {
         out.reset_attributes();
     }
 
+    fn render_link(
+        out: &mut impl textmode::Textmode,
+        screen: &vt100::Screen,
+        (row, out_row): (u16, u16),
+        cols: std::ops::Range<u16>,
+        selected: bool,
+    ) {
+        out.move_to(out_row, cols.start);
+        out.reset_attributes();
+        out.set_underline(true);
+        if selected {
+            out.set_inverse(true);
+        } else {
+            out.set_fgcolor(textmode::color::BLUE);
+        }
+        for col in cols {
+            if let Some(cell) = screen.cell(row, col) {
+                if !cell.is_wide_continuation() {
+                    out.write_str(&cell.contents());
+                }
+            }
+        }
+        out.reset_attributes();
+    }
+
     pub fn render_fullscreen(&self, out: &mut impl textmode::Textmode) {
         self.pty.with_vt_mut(|vt| {
             out.write(&vt.screen().state_formatted());
@@ -215,18 +583,142 @@ impl Entry {
         });
     }
 
+    // for the split view, where the pty has been resized to only take up
+    // part of the terminal
+    pub fn render_pane(
+        &self,
+        out: &mut impl textmode::Textmode,
+        pos: (u16, u16),
+        focused: bool,
+    ) {
+        self.pty.with_vt_mut(|vt| {
+            if vt.bell(focused) {
+                out.write(b"\x07");
+            }
+            let screen = vt.screen();
+            for (row, contents) in
+                screen.rows_formatted(0, screen.size().1).enumerate()
+            {
+                let row: u16 = row.try_into().unwrap();
+                out.reset_attributes();
+                out.move_to(pos.0 + row, pos.1);
+                out.write(&contents);
+            }
+            out.reset_attributes();
+            if focused {
+                let cursor = screen.cursor_position();
+                out.move_to(pos.0 + cursor.0, pos.1 + cursor.1);
+                out.hide_cursor(screen.hide_cursor());
+            }
+        });
+    }
+
     pub fn input(&self, bytes: Vec<u8>) {
+        // typing into the entry should show where the typing is going
+        self.scroll_down(usize::MAX);
         self.pty.input(bytes);
     }
 
+    pub fn scroll_up(&self, lines: usize) {
+        self.pty.with_vt_mut(|vt| vt.scroll_up(lines));
+    }
+
+    pub fn scroll_down(&self, lines: usize) {
+        self.pty.with_vt_mut(|vt| vt.scroll_down(lines));
+    }
+
     pub fn resize(&self, size: (u16, u16)) {
         self.pty.resize(size);
     }
 
+    // this includes the title, if one was given
     pub fn cmd(&self) -> &str {
         &self.cmdline
     }
 
+    pub fn ast(&self) -> Option<&crate::parse::ast::Commands> {
+        self.ast.as_ref()
+    }
+
+    pub fn git_commit(&self) -> Option<&str> {
+        self.git_commit.as_deref()
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        split_title(&self.cmdline).0
+    }
+
+    // the cpus the command was pinned to, as written in the command line
+    pub fn cpus(&self) -> Option<&str> {
+        split_cpus(split_title(&self.cmdline).1).0
+    }
+
+    pub fn idx(&self) -> usize {
+        self.env.idx()
+    }
+
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub fn exit_env(&self) -> Option<&Env> {
+        self.exit_env.as_ref()
+    }
+
+    // the directory the command is currently running in, which may differ
+    // from the directory it was started in if it has run cd
+    pub fn pwd(&self) -> std::path::PathBuf {
+        self.pwd.lock().unwrap().clone()
+    }
+
+    // one list per pipeline that has finished running, in order
+    pub fn timings(&self) -> Vec<Vec<crate::runner::Timing>> {
+        self.timings.lock().unwrap().clone()
+    }
+
+    pub fn exit_status(&self) -> Option<std::process::ExitStatus> {
+        self.state.exit_info().map(|info| info.status)
+    }
+
+    pub fn duration(&self) -> std::time::Duration {
+        self.state.exit_info().map_or_else(
+            || self.clock.instant() - self.start_instant,
+            |info| info.instant - self.start_instant,
+        )
+    }
+
+    // a hash of all of the output, once the command has finished
+    pub fn output_hash(&self) -> Option<u64> {
+        self.exit_status()?;
+        self.pty.with_vt(super::pty::Vt::output_hash)
+    }
+
+    // whether the running program has asked for pastes to be bracketed
+    pub fn bracketed_paste(&self) -> bool {
+        self.pty.with_vt(|vt| vt.screen().bracketed_paste())
+    }
+
+    pub fn output_text(&self) -> String {
+        self.pty.with_vt(|vt| vt.screen().contents())
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.pty.with_vt(super::pty::Vt::memory_usage)
+    }
+
+    pub fn evicted(&self) -> bool {
+        self.pty.with_vt(super::pty::Vt::evicted)
+    }
+
+    pub fn evict(&self) -> Result<()> {
+        let path = crate::shell::tmp::path(&format!("entry-{}", self.idx()))?;
+        self.pty.with_vt_mut(|vt| vt.evict(path))
+    }
+
+    pub fn reload(&self) {
+        self.pty.with_vt_mut(super::pty::Vt::reload);
+    }
+
     pub fn start_time(&self) -> time::OffsetDateTime {
         self.start_time
     }
@@ -247,11 +739,110 @@ impl Entry {
         self.state.running()
     }
 
-    pub fn exited(&mut self, exit_info: ExitInfo) {
+    pub fn set_stopped(&mut self, stopped: bool) {
+        self.stopped = stopped;
+    }
+
+    // collapsed entries only show their header line
+    pub fn toggle_collapsed(&mut self) {
+        self.collapsed = !self.collapsed;
+    }
+
+    pub fn set_collapsed(&mut self, collapsed: bool) {
+        self.collapsed = collapsed;
+    }
+
+    // urls in the visible output and osc 8 hyperlinks
+    pub fn links(&self) -> Vec<super::links::Link> {
+        self.pty
+            .with_vt(|vt| super::links::find(vt.screen(), vt.hyperlinks()))
+    }
+
+    // moves the selection to the next link (wrapping around), and returns
+    // its url
+    pub fn select_next_link(&mut self) -> Option<String> {
+        let links = self.links();
+        if links.is_empty() {
+            self.link = None;
+            return None;
+        }
+        let next = self.link.map_or(0, |link| (link + 1) % links.len());
+        self.link = Some(next);
+        Some(links[next].url().to_string())
+    }
+
+    // the output can change after the link was selected, so this may not be
+    // the same url as was originally selected
+    pub fn selected_link(&self) -> Option<String> {
+        let links = self.links();
+        self.link
+            .and_then(|link| links.get(link))
+            .map(|link| link.url().to_string())
+    }
+
+    // suspended entries are still running, but have been sent to the
+    // background with ^Z
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    // continues the current pipeline, in case it was stopped
+    pub fn resume(&mut self) {
+        self.suspended = false;
+        if !self.running() {
+            return;
+        }
+        if let Some(pg) = *self.pg.lock().unwrap() {
+            // the pipeline may have already finished, which is fine
+            #[allow(clippy::let_underscore_drop)]
+            let _ = nix::sys::signal::killpg(
+                pg,
+                nix::sys::signal::Signal::SIGCONT,
+            );
+        }
+    }
+
+    // the first kill asks the command to exit, and killing it again forces
+    // it to
+    pub fn kill(&mut self) {
+        if !self.running() {
+            return;
+        }
+        let signal = if self.killed {
+            nix::sys::signal::Signal::SIGKILL
+        } else {
+            nix::sys::signal::Signal::SIGTERM
+        };
+        self.killed = true;
+        // either of these may have already exited, which is fine
+        if let Some(pg) = *self.pg.lock().unwrap() {
+            #[allow(clippy::let_underscore_drop)]
+            let _ = nix::sys::signal::killpg(pg, signal);
+            // stopped processes won't see the signal until they continue
+            #[allow(clippy::let_underscore_drop)]
+            let _ = nix::sys::signal::killpg(
+                pg,
+                nix::sys::signal::Signal::SIGCONT,
+            );
+        }
+        if let Some(pid) = self.runner_pid {
+            #[allow(clippy::let_underscore_drop)]
+            let _ = nix::sys::signal::kill(pid, signal);
+        }
+    }
+
+    pub fn exited(&mut self, exit_info: ExitInfo, exit_env: Option<Env>) {
+        self.exit_env = exit_env;
+        self.stopped = false;
+        self.suspended = false;
+        self.waiting = false;
         self.state = State::Exited(exit_info);
     }
 
     pub fn lines(&self, entry_count: usize, focused: bool) -> usize {
+        if self.collapsed {
+            return 1;
+        }
         let running = self.running();
         1 + std::cmp::min(
             self.pty.with_vt(|vt| vt.output_lines(focused, running)),
@@ -285,24 +876,29 @@ impl Entry {
         out: &mut impl textmode::Textmode,
         idx: usize,
         focus: bool,
+        colors: crate::shell::ColorScheme,
     ) {
         if focus {
-            out.set_bgcolor(textmode::Color::Rgb(0x56, 0x1b, 0x8b));
+            out.set_bgcolor(colors.highlight());
         } else if idx % 2 == 0 {
-            out.set_bgcolor(textmode::Color::Rgb(0x24, 0x21, 0x00));
+            out.set_bgcolor(colors.alternate());
         } else {
-            out.set_bgcolor(textmode::Color::Rgb(0x20, 0x20, 0x20));
+            out.set_bgcolor(colors.background());
         }
     }
 
     fn spawn_command(
         cmdline: &str,
+        cpus: Option<&str>,
         env: &Env,
         pts: &pty_process::Pts,
     ) -> Result<(tokio::process::Child, std::fs::File)> {
+        let cpus = cpus.map(cpu_set).transpose()?;
         let mut cmd = pty_process::Command::new(crate::info::current_exe()?);
         cmd.args(&["-c", cmdline, "--status-fd", "3"]);
         env.apply(&mut cmd);
+        // numbered the same way as in the entry header
+        cmd.env("NBSH_ENTRY", (env.idx() + 1).to_string());
         let (from_r, from_w) =
             nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)?;
         // Safety: from_r was just opened above and is not used anywhere else
@@ -311,6 +907,13 @@ impl Entry {
         unsafe {
             cmd.pre_exec(move || {
                 nix::unistd::dup2(from_w, 3)?;
+                // everything the runner starts inherits this
+                if let Some(cpus) = &cpus {
+                    nix::sched::sched_setaffinity(
+                        nix::unistd::Pid::from_raw(0),
+                        cpus,
+                    )?;
+                }
                 Ok(())
             });
         }
@@ -323,6 +926,12 @@ impl Entry {
         mut child: tokio::process::Child,
         fh: std::fs::File,
         idx: usize,
+        pwd: std::sync::Arc<std::sync::Mutex<std::path::PathBuf>>,
+        timings: std::sync::Arc<
+            std::sync::Mutex<Vec<Vec<crate::runner::Timing>>>,
+        >,
+        pg: std::sync::Arc<std::sync::Mutex<Option<nix::unistd::Pid>>>,
+        clock: crate::shell::clock::Shared,
         event_w: crate::shell::event::Writer,
     ) {
         enum Res {
@@ -378,7 +987,30 @@ impl Entry {
                     crate::runner::Event::Suspend => {
                         event_w.send(Event::ChildSuspend(idx));
                     }
+                    crate::runner::Event::RequestInput => {
+                        event_w.send(Event::ChildRequestInput(idx));
+                    }
+                    crate::runner::Event::Stopped => {
+                        event_w.send(Event::ChildStopped(idx, true));
+                    }
+                    crate::runner::Event::Continued => {
+                        event_w.send(Event::ChildStopped(idx, false));
+                    }
+                    crate::runner::Event::ProcessGroup(new_pg) => {
+                        let new_pg = nix::unistd::Pid::from_raw(new_pg);
+                        *pg.lock().unwrap() = Some(new_pg);
+                        // only used for kill %N, so not worth reporting
+                        #[allow(clippy::let_underscore_drop)]
+                        let _ = crate::shell::tmp::set_job(idx, new_pg);
+                    }
+                    crate::runner::Event::Chdir(new_pwd) => {
+                        *pwd.lock().unwrap() = new_pwd;
+                    }
+                    crate::runner::Event::Timings(new_timings) => {
+                        timings.lock().unwrap().push(new_timings);
+                    }
                     crate::runner::Event::Exit(env) => {
+                        *pwd.lock().unwrap() = env.pwd().to_path_buf();
                         new_env = Some(env);
                     }
                 },
@@ -387,14 +1019,114 @@ impl Entry {
                 }
             }
         }
+        let exit_status = exit_status.unwrap();
+        #[allow(clippy::let_underscore_drop)]
+        let _ = crate::shell::tmp::finish_job(idx, exit_status);
         event_w.send(Event::ChildExit(
             idx,
-            ExitInfo::new(exit_status.unwrap()),
+            ExitInfo::new(exit_status, clock.instant()),
             new_env,
         ));
     }
 }
 
+// the closest char boundary at or before idx, so that a byte offset from
+// somewhere else can be used to slice s
+fn char_boundary(s: &str, idx: usize) -> usize {
+    (0..=idx.min(s.len()))
+        .rev()
+        .find(|&i| s.is_char_boundary(i))
+        .unwrap_or(0)
+}
+
+// a command line can start with #[some title] to give the entry a name.
+// since # starts a comment, this has to be removed before the command is
+// actually run.
+fn split_title(cmdline: &str) -> (Option<&str>, &str) {
+    cmdline
+        .strip_prefix("#[")
+        .and_then(|rest| rest.split_once(']'))
+        .map_or((None, cmdline), |(title, command)| {
+            (Some(title.trim()), command.trim_start())
+        })
+}
+
+// after the title (if any), a command line can start with @cpus(0-3,6) to
+// pin the command to those cpus, which is useful for benchmarking
+fn split_cpus(cmdline: &str) -> (Option<&str>, &str) {
+    cmdline
+        .strip_prefix("@cpus(")
+        .and_then(|rest| rest.split_once(')'))
+        .map_or((None, cmdline), |(cpus, command)| {
+            (Some(cpus.trim()), command.trim_start())
+        })
+}
+
+fn cpu_set(spec: &str) -> Result<nix::sched::CpuSet> {
+    let mut set = nix::sched::CpuSet::new();
+    for cpu in parse_cpus(spec)? {
+        set.set(cpu)
+            .map_err(|_| anyhow!("cpu {} is out of range", cpu))?;
+    }
+    Ok(set)
+}
+
+fn parse_cpus(spec: &str) -> Result<Vec<usize>> {
+    let mut cpus = vec![];
+    for part in spec.split(',').map(str::trim) {
+        if let Some((start, end)) = part.split_once('-') {
+            let start: usize = start.trim().parse()?;
+            let end: usize = end.trim().parse()?;
+            if start > end {
+                return Err(anyhow!("invalid cpu range {}", part));
+            }
+            // checked before building the list, since it could otherwise be
+            // arbitrarily large
+            if end >= nix::sched::CpuSet::count() {
+                return Err(anyhow!("cpu {} is out of range", end));
+            }
+            cpus.extend(start..=end);
+        } else {
+            cpus.push(part.parse()?);
+        }
+    }
+    Ok(cpus)
+}
+
+#[test]
+fn test_split_cpus() {
+    assert_eq!(split_cpus("make"), (None, "make"));
+    assert_eq!(
+        split_cpus("@cpus(0-3) cargo bench"),
+        (Some("0-3"), "cargo bench")
+    );
+    assert_eq!(parse_cpus("0-3,6").unwrap(), vec![0, 1, 2, 3, 6]);
+    assert_eq!(parse_cpus(" 2 ").unwrap(), vec![2]);
+    assert!(parse_cpus("3-1").is_err());
+    assert!(parse_cpus("0-18446744073709551615").is_err());
+    assert!(parse_cpus("a").is_err());
+}
+
+#[test]
+fn test_char_boundary() {
+    assert_eq!(char_boundary("echo", 2), 2);
+    assert_eq!(char_boundary("echo", 10), 4);
+    assert_eq!(char_boundary("éé", 3), 2);
+    assert_eq!(char_boundary("éé", 1), 0);
+}
+
+#[test]
+fn test_split_title() {
+    assert_eq!(split_title("ls"), (None, "ls"));
+    assert_eq!(
+        split_title("#[deploy prod] ./deploy.sh"),
+        (Some("deploy prod"), "./deploy.sh")
+    );
+    assert_eq!(split_title("#[foo]bar"), (Some("foo"), "bar"));
+    assert_eq!(split_title("#[unterminated"), (None, "#[unterminated"));
+    assert_eq!(split_title("# comment"), (None, "# comment"));
+}
+
 enum State {
     Running((usize, usize)),
     Exited(ExitInfo),
@@ -420,10 +1152,14 @@ pub struct ExitInfo {
 }
 
 impl ExitInfo {
-    fn new(status: std::process::ExitStatus) -> Self {
-        Self {
-            status,
-            instant: std::time::Instant::now(),
-        }
+    fn new(
+        status: std::process::ExitStatus,
+        instant: std::time::Instant,
+    ) -> Self {
+        Self { status, instant }
+    }
+
+    pub fn success(&self) -> bool {
+        self.status.success()
     }
 }
@@ -0,0 +1,281 @@
+use crate::shell::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    pub fn from_path(path: &std::path::Path) -> Result<Self> {
+        match path.extension().and_then(std::ffi::OsStr::to_str) {
+            Some("md" | "markdown") => Ok(Self::Markdown),
+            Some("html" | "htm") => Ok(Self::Html),
+            _ => Err(anyhow!(
+                "couldn't tell the export format for {} (expected .md or \
+                 .html)",
+                path.display()
+            )),
+        }
+    }
+}
+
+pub fn export(
+    entries: &[super::session::Entry],
+    format: Format,
+    offset: time::UtcOffset,
+) -> String {
+    match format {
+        Format::Markdown => markdown(entries, offset),
+        Format::Html => html(entries, offset),
+    }
+}
+
+fn markdown(
+    entries: &[super::session::Entry],
+    offset: time::UtcOffset,
+) -> String {
+    let mut out = String::from("# nbsh\n");
+    for (idx, entry) in entries.iter().enumerate() {
+        let output = parser(entry).screen().contents();
+        let output = output.trim_end_matches('\n');
+        writeln!(out, "\n## [{}] {}\n", idx + 1, summary(entry, offset))
+            .unwrap();
+        let fence = fence(&entry.cmdline);
+        writeln!(out, "{}sh\n{}\n{}", fence, entry.cmdline, fence).unwrap();
+        if !output.is_empty() {
+            let fence = fence(output);
+            writeln!(out, "\n{}\n{}\n{}", fence, output, fence).unwrap();
+        }
+    }
+    out
+}
+
+fn html(
+    entries: &[super::session::Entry],
+    offset: time::UtcOffset,
+) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>nbsh</title>\n<style>\n\
+         body { background: #111; color: #ccc; font-family: sans-serif; }\n\
+         pre { background: #000; color: #ddd; padding: 0.5em; }\n\
+         pre.cmd { color: #fff; font-weight: bold; }\n\
+         .status.failure { color: #c00; }\n\
+         </style>\n</head>\n<body>\n",
+    );
+    for (idx, entry) in entries.iter().enumerate() {
+        let success = entry.status.map_or(false, |status| {
+            crate::status::from_raw(status).success()
+        });
+        writeln!(
+            out,
+            "<section>\n<h2 class=\"status {}\">[{}] {}</h2>\n\
+             <pre class=\"cmd\">{}</pre>",
+            if success { "success" } else { "failure" },
+            idx + 1,
+            escape(&summary(entry, offset)),
+            escape(&entry.cmdline),
+        )
+        .unwrap();
+        let output = html_screen(parser(entry).screen());
+        if !output.is_empty() {
+            writeln!(out, "<pre class=\"output\">{}</pre>", output).unwrap();
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+// the start time, exit status, and duration of an entry
+fn summary(entry: &super::session::Entry, offset: time::UtcOffset) -> String {
+    let format = time::format_description::parse(
+        "[year]-[month]-[day] [hour]:[minute]:[second]",
+    )
+    .unwrap();
+    let start = time::OffsetDateTime::from_unix_timestamp(entry.start_time)
+        .map_or_else(
+            |_| "unknown time".to_string(),
+            |time| time.to_offset(offset).format(&format).unwrap(),
+        );
+    let status = entry.status.map_or_else(
+        || "still running".to_string(),
+        |status| {
+            crate::format::exit_status_description(crate::status::from_raw(
+                status,
+            ))
+        },
+    );
+    format!(
+        "{}, {} after {}",
+        start,
+        status,
+        crate::format::duration(entry.duration)
+    )
+}
+
+fn parser(entry: &super::session::Entry) -> vt100::Parser {
+    let mut parser = vt100::Parser::new(entry.size.0, entry.size.1, 0);
+    parser.process(entry.contents.as_bytes());
+    parser
+}
+
+// a code fence which is longer than any run of backticks in the text, so
+// that the text can't end the block early
+fn fence(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn html_screen(screen: &vt100::Screen) -> String {
+    let (rows, cols) = screen.size();
+    let mut lines = vec![];
+    for row in 0..rows {
+        // trailing blank cells would just show up as trailing whitespace
+        let width = (0..cols)
+            .rev()
+            .find(|&col| {
+                screen.cell(row, col).map_or(false, |cell| {
+                    cell.has_contents()
+                        || cell.bgcolor() != vt100::Color::Default
+                        || cell.inverse()
+                })
+            })
+            .map_or(0, |col| col + 1);
+        let mut line = String::new();
+        let mut current: Option<String> = None;
+        for col in 0..width {
+            let cell = if let Some(cell) = screen.cell(row, col) {
+                cell
+            } else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let style = style(cell);
+            if style != current {
+                if current.is_some() {
+                    line.push_str("</span>");
+                }
+                if let Some(style) = &style {
+                    write!(line, "<span style=\"{}\">", style).unwrap();
+                }
+                current = style;
+            }
+            if cell.has_contents() {
+                line.push_str(&escape(&cell.contents()));
+            } else {
+                line.push(' ');
+            }
+        }
+        if current.is_some() {
+            line.push_str("</span>");
+        }
+        lines.push(line);
+    }
+    while lines.last().map_or(false, String::is_empty) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+// the css for a cell, or None if it uses the default attributes
+fn style(cell: &vt100::Cell) -> Option<String> {
+    let (fg, bg) = (color(cell.fgcolor()), color(cell.bgcolor()));
+    let (fg, bg) = if cell.inverse() {
+        (
+            Some(bg.unwrap_or_else(|| "#000".to_string())),
+            Some(fg.unwrap_or_else(|| "#ddd".to_string())),
+        )
+    } else {
+        (fg, bg)
+    };
+    let mut style = vec![];
+    if let Some(fg) = fg {
+        style.push(format!("color: {}", fg));
+    }
+    if let Some(bg) = bg {
+        style.push(format!("background: {}", bg));
+    }
+    if cell.bold() {
+        style.push("font-weight: bold".to_string());
+    }
+    if cell.italic() {
+        style.push("font-style: italic".to_string());
+    }
+    if cell.underline() {
+        style.push("text-decoration: underline".to_string());
+    }
+    (!style.is_empty()).then(|| style.join("; "))
+}
+
+fn color(color: vt100::Color) -> Option<String> {
+    // the xterm defaults for the first 16 colors
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+    let (r, g, b) = match color {
+        vt100::Color::Default => return None,
+        vt100::Color::Rgb(r, g, b) => (r, g, b),
+        vt100::Color::Idx(idx @ 0..=15) => ANSI[usize::from(idx)],
+        vt100::Color::Idx(idx @ 16..=231) => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let idx = idx - 16;
+            (level(idx / 36), level((idx / 6) % 6), level(idx % 6))
+        }
+        vt100::Color::Idx(idx) => {
+            let level = 8 + (idx - 232) * 10;
+            (level, level, level)
+        }
+    };
+    Some(format!("#{:02x}{:02x}{:02x}", r, g, b))
+}
+
+#[test]
+fn test_html_screen() {
+    let mut parser = vt100::Parser::new(4, 20, 0);
+    parser.process(b"a <b>\r\n\x1b[31mred\x1b[m \x1b[1;44mbold\x1b[m\r\n");
+    assert_eq!(
+        html_screen(parser.screen()),
+        "a &lt;b&gt;\n\
+         <span style=\"color: #cd0000\">red</span> \
+         <span style=\"background: #0000ee; font-weight: bold\">bold</span>"
+    );
+}
+
+#[test]
+fn test_fence() {
+    assert_eq!(fence("echo foo"), "```");
+    assert_eq!(fence("a ```` b"), "`````");
+}
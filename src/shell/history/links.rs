@@ -0,0 +1,237 @@
+const SCHEMES: &[&str] = &["https://", "http://", "ftp://", "file://"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    url: String,
+    // the rows and ranges of columns on the screen that the url covers (more
+    // than one if it was wrapped). hyperlinks only have a target, so this is
+    // empty for them.
+    pos: Vec<(u16, std::ops::Range<u16>)>,
+}
+
+impl Link {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    // the columns of the given row which are part of this link
+    pub fn cols(&self, row: u16) -> Option<std::ops::Range<u16>> {
+        self.pos
+            .iter()
+            .find(|(link_row, _)| *link_row == row)
+            .map(|(_, cols)| cols.clone())
+    }
+}
+
+// urls that appear in the visible part of the screen (joining rows which
+// were wrapped), followed by the targets of any osc 8 hyperlinks which don't
+// also show up as text
+pub fn find(screen: &vt100::Screen, hyperlinks: &[String]) -> Vec<Link> {
+    let (rows, cols) = screen.size();
+    let mut links = vec![];
+    let mut line = String::new();
+    // the screen position of each byte in the line
+    let mut positions = vec![];
+    for row in 0..rows {
+        for col in 0..cols {
+            let cell = if let Some(cell) = screen.cell(row, col) {
+                cell
+            } else {
+                continue;
+            };
+            if cell.is_wide_continuation() {
+                continue;
+            }
+            let contents = if cell.has_contents() {
+                cell.contents()
+            } else {
+                " ".to_string()
+            };
+            positions
+                .extend(std::iter::repeat((row, col)).take(contents.len()));
+            line.push_str(&contents);
+        }
+        if !screen.row_wrapped(row) || row == rows - 1 {
+            find_in_line(&line, &positions, &mut links);
+            line.clear();
+            positions.clear();
+        }
+    }
+    for url in hyperlinks {
+        if !links.iter().any(|link| &link.url == url) {
+            links.push(Link {
+                url: url.clone(),
+                pos: vec![],
+            });
+        }
+    }
+    links
+}
+
+fn find_in_line(line: &str, positions: &[(u16, u16)], links: &mut Vec<Link>) {
+    let mut offset = 0;
+    while let Some((start, scheme)) = SCHEMES
+        .iter()
+        .filter_map(|scheme| {
+            line[offset..].find(scheme).map(|i| (offset + i, scheme))
+        })
+        .min()
+    {
+        let len = url_len(&line[start..]);
+        offset = start + len.max(scheme.len());
+        if len <= scheme.len() {
+            continue;
+        }
+        let mut pos = vec![];
+        let mut i = start;
+        while i < start + len {
+            let row = positions[i].0;
+            let mut end = i;
+            while end < start + len && positions[end].0 == row {
+                end += 1;
+            }
+            pos.push((row, positions[i].1..positions[end - 1].1 + 1));
+            i = end;
+        }
+        links.push(Link {
+            url: line[start..start + len].to_string(),
+            pos,
+        });
+    }
+}
+
+// the length of the url at the start of the text, without any trailing
+// punctuation that is more likely to be part of the surrounding sentence
+fn url_len(text: &str) -> usize {
+    let mut len = text
+        .find(|c: char| {
+            c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | '`')
+        })
+        .unwrap_or_else(|| text.len());
+    loop {
+        let url = &text[..len];
+        let trim = match url.chars().last() {
+            Some('.' | ',' | ';' | ':' | '!' | '?') => true,
+            // parentheses are allowed in urls (wikipedia uses them a lot),
+            // but only if they are balanced
+            Some(')') => url.matches('(').count() < url.matches(')').count(),
+            Some(']') => url.matches('[').count() < url.matches(']').count(),
+            _ => false,
+        };
+        if !trim {
+            return len;
+        }
+        len -= 1;
+    }
+}
+
+// vt100 doesn't keep track of osc 8 hyperlinks, so this picks their targets
+// out of the raw output. sequences can be split across reads, so any
+// partial sequence is held onto until the rest of it arrives.
+#[derive(Default)]
+pub struct HyperlinkScanner {
+    partial: Vec<u8>,
+}
+
+impl HyperlinkScanner {
+    // sequences longer than this are almost certainly not real hyperlinks,
+    // and shouldn't be able to make us buffer unbounded amounts of output
+    const MAX_LEN: usize = 4096;
+
+    // returns the targets of the hyperlinks which were started
+    pub fn scan(&mut self, bytes: &[u8]) -> Vec<String> {
+        let mut links: Vec<String> = vec![];
+        let mut buf = std::mem::take(&mut self.partial);
+        buf.extend_from_slice(bytes);
+        let mut rest = &buf[..];
+        while let Some(start) = find_bytes(rest, b"\x1b]8;") {
+            let seq = &rest[start + 4..];
+            let end = seq.iter().enumerate().find(|&(i, &c)| {
+                c == b'\x07' || seq[i..].starts_with(b"\x1b\\")
+            });
+            let (end, term_len) = if let Some((end, &c)) = end {
+                (end, if c == b'\x07' { 1 } else { 2 })
+            } else {
+                if seq.len() < Self::MAX_LEN {
+                    self.partial = rest[start..].to_vec();
+                }
+                return links;
+            };
+            // the format is ESC ] 8 ; params ; uri ST, and an empty uri ends
+            // the link
+            if let Some(sep) = seq[..end].iter().position(|&c| c == b';') {
+                let uri = String::from_utf8_lossy(&seq[sep + 1..end]);
+                if !uri.is_empty()
+                    && links.last().map(String::as_str) != Some(&*uri)
+                {
+                    links.push(uri.to_string());
+                }
+            }
+            rest = &seq[end + term_len..];
+        }
+        // keep anything which could be the start of the next sequence
+        for len in (1..4).rev() {
+            if rest.ends_with(&b"\x1b]8;"[..len]) {
+                self.partial = b"\x1b]8;"[..len].to_vec();
+                break;
+            }
+        }
+        links
+    }
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[test]
+fn test_url_len() {
+    assert_eq!(url_len("https://example.com"), 19);
+    assert_eq!(url_len("https://example.com."), 19);
+    assert_eq!(url_len("https://example.com/a b"), 21);
+    assert_eq!(
+        url_len("https://en.wikipedia.org/wiki/Rust_(language))"),
+        45
+    );
+    assert_eq!(url_len("https://example.com>"), 19);
+}
+
+#[test]
+fn test_find() {
+    let mut parser = vt100::Parser::new(4, 20, 0);
+    parser.process(b"see http://a.io/x.\r\nhttps://example.com/long");
+    let links = find(parser.screen(), &["https://b.io".to_string()]);
+    assert_eq!(
+        links,
+        vec![
+            Link {
+                url: "http://a.io/x".to_string(),
+                pos: vec![(0, 4..17)],
+            },
+            Link {
+                url: "https://example.com/long".to_string(),
+                pos: vec![(1, 0..20), (2, 0..4)],
+            },
+            Link {
+                url: "https://b.io".to_string(),
+                pos: vec![],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_hyperlink_scanner() {
+    let mut scanner = HyperlinkScanner::default();
+    assert_eq!(
+        scanner.scan(b"a \x1b]8;;https://a.io\x07a\x1b]8;;\x07 b \x1b]"),
+        vec!["https://a.io"]
+    );
+    assert!(scanner.scan(b"8;id=1;https://b").is_empty());
+    assert_eq!(
+        scanner.scan(b".io\x1b\\b\x1b]8;;\x1b\\"),
+        vec!["https://b.io"]
+    );
+}
@@ -2,18 +2,32 @@ use crate::shell::prelude::*;
 
 mod entry;
 pub use entry::{Entry, ExitInfo};
+mod export;
+mod links;
+pub use export::Format as ExportFormat;
+mod proc;
+pub use proc::blocked_groups;
 mod pty;
+mod session;
+mod subst;
+pub use subst::substitute;
 
 pub struct History {
     size: (u16, u16),
+    scrollback: usize,
+    theme: crate::shell::Theme,
+    clock: crate::shell::clock::Shared,
     entries: Vec<Entry>,
     scroll_pos: usize,
 }
 
 impl History {
-    pub fn new() -> Self {
+    pub fn new(clock: crate::shell::clock::Shared) -> Self {
         Self {
-            size: (24, 80),
+            size: crate::info::default_size(),
+            scrollback: 0,
+            theme: crate::shell::Theme::default(),
+            clock,
             entries: vec![],
             scroll_pos: 0,
         }
@@ -26,6 +40,7 @@ impl History {
         focus: Option<usize>,
         scrolling: bool,
         offset: time::UtcOffset,
+        colors: crate::shell::ColorScheme,
     ) {
         let mut cursor = None;
         for (idx, used_lines, mut vt) in
@@ -43,6 +58,9 @@ impl History {
                 focused,
                 scrolling,
                 offset,
+                self.theme,
+                colors,
+                self.reproduced(idx),
             );
             if focused && !scrolling {
                 cursor = Some((
@@ -65,6 +83,23 @@ impl History {
         &mut self.entries[idx]
     }
 
+    pub fn size(&self) -> (u16, u16) {
+        self.size
+    }
+
+    // only applies to entries started after this is called
+    pub fn set_scrollback(&mut self, scrollback: usize) {
+        self.scrollback = scrollback;
+    }
+
+    pub fn theme(&self) -> crate::shell::Theme {
+        self.theme
+    }
+
+    pub fn set_theme(&mut self, theme: crate::shell::Theme) {
+        self.theme = theme;
+    }
+
     pub fn resize(&mut self, size: (u16, u16)) {
         self.size = size;
         for entry in &self.entries {
@@ -72,14 +107,104 @@ impl History {
         }
     }
 
+    // evicts the output of the oldest finished entries until the total
+    // memory used by the remaining ones fits within the limit
+    pub fn enforce_memory_limit(&self, limit: usize) {
+        let mut total: usize =
+            self.entries.iter().map(Entry::memory_usage).sum();
+        for entry in &self.entries {
+            if total <= limit {
+                break;
+            }
+            if entry.running() || entry.evicted() {
+                continue;
+            }
+            let usage = entry.memory_usage();
+            if entry.evict().is_ok() {
+                total = total - usage + entry.memory_usage();
+            }
+        }
+    }
+
     pub fn run(
         &mut self,
         cmdline: String,
         env: Env,
+        git_commit: Option<String>,
+        event_w: crate::shell::event::Writer,
+    ) {
+        self.entries.push(Entry::new(
+            cmdline,
+            git_commit,
+            env,
+            self.size,
+            self.scrollback,
+            self.clock.clone(),
+            event_w,
+        ));
+    }
+
+    // the startup files aren't something that was typed in, so they are
+    // kept out of the history file
+    pub fn run_startup(
+        &mut self,
+        cmdline: String,
+        env: Env,
+        event_w: crate::shell::event::Writer,
+    ) {
+        self.run(cmdline, env, None, event_w);
+        self.entries.last_mut().unwrap().set_startup();
+    }
+
+    // the entries which are written to the history file, in order
+    pub fn saved_entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|entry| entry.saved())
+    }
+
+    // runs a new command in place of an existing entry, which keeps the
+    // output of the old one around for reference
+    pub fn rerun(
+        &mut self,
+        idx: usize,
+        cmdline: String,
+        mut env: Env,
+        git_commit: Option<String>,
         event_w: crate::shell::event::Writer,
     ) {
+        env.set_idx(idx);
+        let entry = Entry::new(
+            cmdline,
+            git_commit,
+            env,
+            self.size,
+            self.scrollback,
+            self.clock.clone(),
+            event_w,
+        );
+        let old = std::mem::replace(&mut self.entries[idx], entry);
+        self.entries[idx].archive(old);
+    }
+
+    // returns the entries which started or stopped waiting for input
+    pub fn update_waiting(
+        &mut self,
+        blocked: &std::collections::HashSet<nix::unistd::Pid>,
+    ) -> Vec<usize> {
         self.entries
-            .push(Entry::new(cmdline, env, self.size, event_w).unwrap());
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                entry.update_waiting(blocked).then(|| idx)
+            })
+            .collect()
+    }
+
+    pub fn kill(&mut self, idx: usize) {
+        self.entries[idx].kill();
+    }
+
+    pub fn running_count(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.running()).count()
     }
 
     pub fn entry_count(&self) -> usize {
@@ -123,6 +248,54 @@ impl History {
         }
     }
 
+    pub fn save_session(&self, name: &str) -> Result<()> {
+        session::Session::new(self.entries.iter().map(Entry::save).collect())
+            .save(name)
+    }
+
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        offset: time::UtcOffset,
+    ) -> String {
+        let entries: Vec<_> = self.entries.iter().map(Entry::save).collect();
+        export::export(&entries, format, offset)
+    }
+
+    // restored entries are only for viewing, so this should happen before
+    // anything else is run
+    pub fn restore_session(&mut self, name: &str) -> Result<()> {
+        for saved in session::Session::load(name)?.into_entries() {
+            self.entries.push(Entry::restore(
+                saved,
+                self.entries.len(),
+                self.clock.clone(),
+            )?);
+        }
+        Ok(())
+    }
+
+    // whether the entry's output matched the last time its command was run,
+    // either in an earlier run that this one replaced or in an earlier entry.
+    // None if either of them hasn't finished.
+    pub fn reproduced(&self, idx: usize) -> Option<bool> {
+        let entry = &self.entries[idx];
+        let hash = entry.output_hash()?;
+        let previous = if let Some(archived) = entry.archived().last() {
+            archived.output_hash
+        } else {
+            let cmd = crate::shell::old_history::normalize(entry.cmd());
+            self.entries[..idx]
+                .iter()
+                .rev()
+                .find(|entry| {
+                    crate::shell::old_history::normalize(entry.cmd()) == cmd
+                })
+                .and_then(Entry::output_hash)
+        }?;
+        Some(previous == hash)
+    }
+
     pub async fn save(&self) {
         // TODO: we'll probably want some amount of flock or something here
         let mut fh = tokio::fs::OpenOptions::new()
@@ -130,12 +303,30 @@ impl History {
             .open(crate::dirs::history_file())
             .await
             .unwrap();
-        for entry in &self.entries {
+        for entry in self.saved_entries() {
+            // the exit status is an extension to the zsh history format, and
+            // is left off for commands that are still running
+            let (duration, status) = entry.exit_status().map_or_else(
+                || ("0".to_string(), "".to_string()),
+                |status| {
+                    let duration = entry.duration();
+                    (
+                        format!(
+                            "{}.{:09}",
+                            duration.as_secs(),
+                            duration.subsec_nanos()
+                        ),
+                        format!(":{}", crate::status::code(status)),
+                    )
+                },
+            );
             fh.write_all(
                 format!(
-                    ": {}:0;{}\n",
+                    ": {}:{}{};{}\n",
                     entry.start_time().unix_timestamp(),
-                    entry.cmd()
+                    duration,
+                    status,
+                    entry.cmd().replace('\n', "\\\n")
                 )
                 .as_bytes(),
             )
@@ -167,6 +358,18 @@ impl History {
     }
 }
 
+// renders a saved session without starting the shell
+pub fn export_session(
+    name: &str,
+    path: &std::path::Path,
+    offset: time::UtcOffset,
+) -> Result<()> {
+    let format = ExportFormat::from_path(path)?;
+    let entries = session::Session::load(name)?.into_entries();
+    std::fs::write(path, export::export(&entries, format, offset))?;
+    Ok(())
+}
+
 struct VisibleEntries<'a> {
     entries: std::collections::VecDeque<(
         usize,
@@ -0,0 +1,67 @@
+// the process groups which have a process blocked reading from a terminal.
+// this is only a heuristic, based on the syscall that the kernel reports
+// each process is currently in. it is collected for every process group in
+// a single pass, since scanning /proc isn't cheap.
+pub fn blocked_groups() -> std::collections::HashSet<nix::unistd::Pid> {
+    let mut blocked = std::collections::HashSet::new();
+    let dir = if let Ok(dir) = std::fs::read_dir("/proc") {
+        dir
+    } else {
+        return blocked;
+    };
+    for pid in dir
+        .filter_map(Result::ok)
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+    {
+        if let Some(pg) = process_group(pid) {
+            let pg = nix::unistd::Pid::from_raw(pg);
+            if !blocked.contains(&pg) && reading_tty(pid) {
+                blocked.insert(pg);
+            }
+        }
+    }
+    blocked
+}
+
+fn process_group(pid: i32) -> Option<i32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the command name can contain spaces and parens, but it is always
+    // followed by the last ')' in the line
+    let rest = stat.get(stat.rfind(')')? + 2..)?;
+    // state, ppid, pgrp
+    rest.split(' ').nth(2)?.parse().ok()
+}
+
+fn reading_tty(pid: i32) -> bool {
+    let syscall = if let Ok(syscall) =
+        std::fs::read_to_string(format!("/proc/{}/syscall", pid))
+    {
+        syscall
+    } else {
+        return false;
+    };
+    let mut fields = syscall.split_whitespace();
+    if fields.next().and_then(|nr| nr.parse().ok()) != Some(libc::SYS_read) {
+        return false;
+    }
+    fields
+        .next()
+        .and_then(|fd| {
+            i64::from_str_radix(fd.trim_start_matches("0x"), 16).ok()
+        })
+        .and_then(|fd| {
+            std::fs::read_link(format!("/proc/{}/fd/{}", pid, fd)).ok()
+        })
+        .map_or(false, |path| {
+            path.starts_with("/dev/pts")
+                || path == std::path::Path::new("/dev/tty")
+        })
+}
+
+#[test]
+fn test_process_group() {
+    assert_eq!(
+        process_group(nix::unistd::getpid().as_raw()),
+        Some(nix::unistd::getpgrp().as_raw())
+    );
+}
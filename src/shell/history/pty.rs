@@ -1,5 +1,12 @@
 use crate::shell::prelude::*;
 
+// the most reads to handle before publishing a new snapshot, so
+// that constant output still shows up
+const MAX_BATCH: usize = 64;
+// only the most recent hyperlinks are kept, so that programs which print
+// lots of them don't use up unbounded memory
+const MAX_HYPERLINKS: usize = 256;
+
 #[derive(Debug)]
 enum Request {
     Input(Vec<u8>),
@@ -7,13 +14,16 @@ enum Request {
 }
 
 pub struct Pty {
+    id: u64,
     vt: std::sync::Arc<std::sync::Mutex<Vt>>,
     request_w: tokio::sync::mpsc::UnboundedSender<Request>,
 }
 
 impl Pty {
     pub fn new(
+        idx: usize,
         size: (u16, u16),
+        scrollback: usize,
         event_w: crate::shell::event::Writer,
     ) -> Result<(Self, pty_process::Pts)> {
         let (request_w, request_r) = tokio::sync::mpsc::unbounded_channel();
@@ -24,14 +34,32 @@ impl Pty {
 
         let vt = std::sync::Arc::new(std::sync::Mutex::new(Vt::new(size)));
 
-        tokio::spawn(Self::task(
-            pty,
-            std::sync::Arc::clone(&vt),
-            request_r,
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let (pty_r, pty_w) = pty.into_split();
+        reactor().send(Message::Register(Reader {
+            id,
+            idx,
+            parser: vt100::Parser::new(size.0, size.1, scrollback),
+            hyperlinks: super::links::HyperlinkScanner::default(),
+            vt: std::sync::Arc::clone(&vt),
             event_w,
-        ));
+            stream: Some(pty_r),
+            output: false,
+        }));
+        tokio::spawn(Self::write_task(pty_w, request_r));
+
+        Ok((Self { id, vt, request_w }, pts))
+    }
 
-        Ok((Self { vt, request_w }, pts))
+    // for entries restored from a saved session, which only ever display
+    // their saved contents
+    pub fn restored(size: (u16, u16)) -> Self {
+        let (request_w, _) = tokio::sync::mpsc::unbounded_channel();
+        Self {
+            id: NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::SeqCst),
+            vt: std::sync::Arc::new(std::sync::Mutex::new(Vt::new(size))),
+            request_w,
+        }
     }
 
     pub fn with_vt<T>(&self, f: impl FnOnce(&Vt) -> T) -> T {
@@ -60,82 +88,255 @@ impl Pty {
     pub fn resize(&self, size: (u16, u16)) {
         #[allow(clippy::let_underscore_drop)]
         let _ = self.request_w.send(Request::Resize(size.0, size.1));
+        reactor().send(Message::Resize(self.id, size));
+    }
+
+    // writing input can block if the process isn't reading it, so this
+    // gets a task of its own rather than going through the reactor. it only
+    // wakes up for typing and resizes, though, not for output.
+    async fn write_task(
+        mut pty_w: pty_process::OwnedWritePty,
+        mut request_r: tokio::sync::mpsc::UnboundedReceiver<Request>,
+    ) {
+        while let Some(request) = request_r.recv().await {
+            let ok = match request {
+                Request::Input(bytes) => {
+                    pty_w.write_all(&bytes).await.is_ok()
+                }
+                Request::Resize(row, col) => {
+                    pty_w.resize(pty_process::Size::new(row, col)).is_ok()
+                }
+            };
+            // the process has exited and closed the pts
+            if !ok {
+                break;
+            }
+        }
+    }
+}
+
+static NEXT_ID: std::sync::atomic::AtomicU64 =
+    std::sync::atomic::AtomicU64::new(0);
+
+static REACTOR: once_cell::sync::OnceCell<Reactor> =
+    once_cell::sync::OnceCell::new();
+
+fn reactor() -> &'static Reactor {
+    REACTOR.get_or_init(Reactor::new)
+}
+
+enum Message {
+    Register(Reader),
+    Resize(u64, (u16, u16)),
+}
+
+// all of the output from every entry is read and parsed by a single task,
+// so that lots of entries running in the background at once don't mean
+// lots of separate tasks being woken up constantly
+struct Reactor {
+    message_w: tokio::sync::mpsc::UnboundedSender<Message>,
+}
+
+impl Reactor {
+    fn new() -> Self {
+        let (message_w, message_r) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(Self::task(message_r));
+        Self { message_w }
+    }
+
+    fn send(&self, message: Message) {
+        #[allow(clippy::let_underscore_drop)]
+        let _ = self.message_w.send(message);
     }
 
     async fn task(
-        pty: pty_process::Pty,
-        vt: std::sync::Arc<std::sync::Mutex<Vt>>,
-        request_r: tokio::sync::mpsc::UnboundedReceiver<Request>,
-        event_w: crate::shell::event::Writer,
+        mut message_r: tokio::sync::mpsc::UnboundedReceiver<Message>,
     ) {
-        enum Res {
-            Read(Result<bytes::Bytes, std::io::Error>),
-            Request(Request),
-        }
-
-        let (pty_r, mut pty_w) = pty.into_split();
-        let mut stream: futures_util::stream::SelectAll<_> = [
-            tokio_util::io::ReaderStream::new(pty_r)
-                .map(Res::Read)
-                .boxed(),
-            tokio_stream::wrappers::UnboundedReceiverStream::new(request_r)
-                .map(Res::Request)
-                .boxed(),
-        ]
-        .into_iter()
-        .collect();
-        while let Some(res) = stream.next().await {
-            match res {
-                Res::Read(res) => match res {
-                    Ok(bytes) => {
-                        vt.lock().unwrap().process(&bytes);
-                        event_w.send(Event::PtyOutput);
+        let mut readers = std::collections::HashMap::new();
+        let mut streams = tokio_stream::StreamMap::new();
+        loop {
+            let mut dirty = std::collections::HashSet::new();
+            tokio::select! {
+                message = message_r.recv() => match message {
+                    Some(Message::Register(mut reader)) => {
+                        // the stream map drops streams which end without
+                        // saying anything, so mark the end explicitly in
+                        // order to clean up the reader too
+                        streams.insert(
+                            reader.id,
+                            tokio_util::io::ReaderStream::new(
+                                reader.stream.take().unwrap(),
+                            )
+                            .map(Some)
+                            .chain(tokio_stream::once(None)),
+                        );
+                        readers.insert(reader.id, reader);
                     }
-                    Err(e) => {
-                        // this means that there are no longer any open pts
-                        // fds. we could alternately signal this through an
-                        // explicit channel at ChildExit time, but this seems
-                        // reliable enough.
-                        if e.raw_os_error() == Some(libc::EIO) {
-                            return;
+                    Some(Message::Resize(id, size)) => {
+                        if let Some(reader) = readers.get_mut(&id) {
+                            reader.parser.set_size(size.0, size.1);
+                            dirty.insert(id);
                         }
-                        panic!("pty read failed: {:?}", e);
                     }
+                    None => break,
                 },
-                Res::Request(Request::Input(bytes)) => {
-                    pty_w.write(&bytes).await.unwrap();
+                Some((id, res)) = streams.next(), if !streams.is_empty() => {
+                    let mut res = Some((id, res));
+                    let mut batch = 0;
+                    // handle everything that is already available before
+                    // publishing new snapshots, since cloning the screen
+                    // isn't free
+                    while let Some((id, next)) = res {
+                        let reader: &mut Reader =
+                            readers.get_mut(&id).unwrap();
+                        match next {
+                            Some(Ok(bytes)) => {
+                                reader.process(&bytes);
+                                dirty.insert(id);
+                            }
+                            end => {
+                                // EIO means that there are no longer any
+                                // open pts fds. we could alternately signal
+                                // this through an explicit channel at
+                                // ChildExit time, but this seems reliable
+                                // enough. any other error only ends this
+                                // entry's output, so it is shown there
+                                // rather than taking down every other entry
+                                // along with it.
+                                if let Some(Err(e)) = end {
+                                    if e.raw_os_error() != Some(libc::EIO) {
+                                        reader.error(&e);
+                                    }
+                                }
+                                dirty.remove(&id);
+                                reader.finish();
+                                reader.publish();
+                                streams.remove(&id);
+                                readers.remove(&id);
+                            }
+                        }
+                        batch += 1;
+                        if batch >= MAX_BATCH {
+                            break;
+                        }
+                        res = streams.next().now_or_never().flatten();
+                    }
                 }
-                Res::Request(Request::Resize(row, col)) => {
-                    pty_w.resize(pty_process::Size::new(row, col)).unwrap();
-                    vt.lock().unwrap().set_size((row, col));
+            }
+            for id in dirty {
+                if let Some(reader) = readers.get_mut(&id) {
+                    reader.publish();
                 }
             }
         }
     }
 }
 
+// the parser lives entirely within the reactor, and the renderer only ever
+// sees snapshots of its screen, so that heavy output doesn't hold the vt
+// lock while rendering is trying to use it
+struct Reader {
+    id: u64,
+    idx: usize,
+    parser: vt100::Parser,
+    hyperlinks: super::links::HyperlinkScanner,
+    vt: std::sync::Arc<std::sync::Mutex<Vt>>,
+    event_w: crate::shell::event::Writer,
+    // moved into the reactor's stream map once registered
+    stream: Option<pty_process::OwnedReadPty>,
+    // whether anything was read since the last snapshot, as opposed to it
+    // only being published for a resize
+    output: bool,
+}
+
+impl Reader {
+    fn process(&mut self, bytes: &[u8]) {
+        self.parser.process(bytes);
+        self.output = true;
+        let links = self.hyperlinks.scan(bytes);
+        if !links.is_empty() {
+            self.vt.lock().unwrap().add_hyperlinks(links);
+        }
+    }
+
+    fn error(&mut self, e: &std::io::Error) {
+        let msg = crate::msg::get(
+            crate::msg::Message::Error,
+            &[&format!("pty read failed: {}", crate::format::io_error(e))],
+        );
+        self.parser.process(format!("\r\n{}\r\n", msg).as_bytes());
+    }
+
+    // the output can't change once the process is gone, so this is when it
+    // gets fingerprinted
+    fn finish(&mut self) {
+        let hash = output_hash(&full_contents(&mut self.parser));
+        self.vt.lock().unwrap().set_output_hash(hash);
+    }
+
+    fn publish(&mut self) {
+        self.vt
+            .lock()
+            .unwrap()
+            .update(self.parser.screen(), std::mem::take(&mut self.output));
+        self.event_w.send(Event::PtyOutput(self.idx));
+    }
+}
+
 pub struct Vt {
-    vt: vt100::Parser,
+    screen: vt100::Screen,
     bell_state: usize,
     bell: bool,
     real_bell_pending: bool,
+    // new output that hasn't been seen yet because the entry wasn't focused
+    activity: bool,
+    evicted: Option<(std::path::PathBuf, (u16, u16))>,
+    // how many lines back into the scrollback the view currently is
+    scroll: usize,
+    // the targets of osc 8 hyperlinks, which vt100 doesn't track itself
+    hyperlinks: Vec<String>,
+    // set once all of the output has been read
+    output_hash: Option<u64>,
 }
 
 impl Vt {
     pub fn new(size: (u16, u16)) -> Self {
         Self {
-            vt: vt100::Parser::new(size.0, size.1, 0),
+            screen: vt100::Parser::new(size.0, size.1, 0).screen().clone(),
             bell_state: 0,
             bell: false,
             real_bell_pending: false,
+            activity: false,
+            evicted: None,
+            scroll: 0,
+            hyperlinks: vec![],
+            output_hash: None,
         }
     }
 
+    // this is slow, since the parser state has to be reconstructed from the
+    // current snapshot. output from the process itself goes through the pty
+    // task instead, this is only for messages from nbsh itself.
     pub fn process(&mut self, bytes: &[u8]) {
-        self.vt.process(bytes);
-        let screen = self.vt.screen();
+        let (rows, cols) = self.screen.size();
+        let mut parser = vt100::Parser::new(rows, cols, 0);
+        parser.process(&self.screen.state_formatted());
+        parser.process(bytes);
+        // messages from nbsh itself aren't output from the entry
+        self.update(parser.screen(), false);
+    }
+
+    fn update(&mut self, screen: &vt100::Screen, output: bool) {
+        self.screen = screen.clone();
+        if output {
+            self.activity = true;
+        }
+        // the snapshot always comes from the bottom of the scrollback, so
+        // reapply the current position. the scrollback only moves relative
+        // to the bottom, so new output will shift the view.
+        self.screen.set_scrollback(self.scroll);
 
-        let new_bell_state = screen.audible_bell_count();
+        let new_bell_state = self.screen.audible_bell_count();
         if new_bell_state != self.bell_state {
             self.bell = true;
             self.real_bell_pending = true;
@@ -144,17 +345,52 @@ impl Vt {
     }
 
     pub fn screen(&self) -> &vt100::Screen {
-        self.vt.screen()
+        &self.screen
+    }
+
+    pub fn output_hash(&self) -> Option<u64> {
+        self.output_hash
     }
 
-    pub fn set_size(&mut self, size: (u16, u16)) {
-        self.vt.set_size(size.0, size.1);
+    pub fn set_output_hash(&mut self, hash: u64) {
+        self.output_hash = Some(hash);
+    }
+
+    pub fn hyperlinks(&self) -> &[String] {
+        &self.hyperlinks
+    }
+
+    fn add_hyperlinks(&mut self, links: Vec<String>) {
+        for link in links {
+            if !self.hyperlinks.contains(&link) {
+                self.hyperlinks.push(link);
+            }
+        }
+        let excess = self.hyperlinks.len().saturating_sub(MAX_HYPERLINKS);
+        self.hyperlinks.drain(..excess);
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        self.screen
+            .set_scrollback(self.scroll.saturating_add(lines));
+        // this is clamped to the amount of scrollback actually available
+        self.scroll = self.screen.scrollback();
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        self.screen
+            .set_scrollback(self.scroll.saturating_sub(lines));
+        self.scroll = self.screen.scrollback();
     }
 
     pub fn is_bell(&self) -> bool {
         self.bell
     }
 
+    pub fn is_activity(&self) -> bool {
+        self.activity
+    }
+
     pub fn bell(&mut self, focused: bool) -> bool {
         let mut should = false;
         if self.real_bell_pending {
@@ -165,20 +401,73 @@ impl Vt {
         }
         if focused {
             self.bell = false;
+            self.activity = false;
         }
         should
     }
 
+    // an approximation, but the cells make up the bulk of it
+    pub fn memory_usage(&self) -> usize {
+        let (rows, cols) = self.screen.size();
+        usize::from(rows)
+            * usize::from(cols)
+            * std::mem::size_of::<vt100::Cell>()
+    }
+
+    pub fn evicted(&self) -> bool {
+        self.evicted.is_some()
+    }
+
+    // writes the screen contents out to disk and replaces the parser with a
+    // minimal one. this should only be done once the process has exited,
+    // since any further output would be lost.
+    pub fn evict(&mut self, path: std::path::PathBuf) -> Result<()> {
+        if self.evicted() {
+            return Ok(());
+        }
+        std::fs::write(&path, self.screen.contents_formatted())?;
+        self.evicted = Some((path, self.screen.size()));
+        self.screen = vt100::Parser::new(1, 1, 0).screen().clone();
+        Ok(())
+    }
+
+    pub fn reload(&mut self) {
+        let (path, size) = if let Some(evicted) = self.evicted.take() {
+            evicted
+        } else {
+            return;
+        };
+        let mut vt = vt100::Parser::new(size.0, size.1, 0);
+        match std::fs::read(&path) {
+            Ok(contents) => vt.process(&contents),
+            Err(e) => vt.process(
+                format!(
+                    "{}\r\n",
+                    crate::msg::get(
+                        crate::msg::Message::FailedToReload,
+                        &[&e]
+                    )
+                )
+                .as_bytes(),
+            ),
+        }
+        #[allow(clippy::let_underscore_drop)]
+        let _ = std::fs::remove_file(&path);
+        self.screen = vt.screen().clone();
+        // processing the saved contents shouldn't count as a new bell
+        self.bell_state = self.screen.audible_bell_count();
+    }
+
     pub fn binary(&self) -> bool {
-        self.vt.screen().errors() > 5
+        self.screen.errors() > 5
     }
 
     pub fn output_lines(&self, focused: bool, running: bool) -> usize {
-        if self.binary() {
+        if self.binary() || self.evicted() {
             return 1;
         }
 
-        let screen = self.vt.screen();
+        let screen = &self.screen;
         let mut last_row = 0;
         for (idx, row) in screen.rows(0, screen.size().1).enumerate() {
             if !row.is_empty() {
@@ -194,3 +483,62 @@ impl Vt {
         last_row
     }
 }
+
+// every line of output, including what has scrolled off of the top of the
+// screen (as far back as the scrollback goes)
+fn full_contents(parser: &mut vt100::Parser) -> String {
+    let (rows, cols) = parser.screen().size();
+    parser.set_scrollback(usize::MAX);
+    let total = parser.screen().scrollback();
+    let mut lines = vec![];
+    // pages can overlap at the end, so only take the rows that haven't been
+    // seen yet
+    let mut offset = total;
+    loop {
+        parser.set_scrollback(offset);
+        for (row, line) in parser.screen().rows(0, cols).enumerate() {
+            if total - offset + row == lines.len() {
+                lines.push(line.trim_end().to_string());
+            }
+        }
+        if offset == 0 {
+            break;
+        }
+        offset = offset.saturating_sub(usize::from(rows));
+    }
+    parser.set_scrollback(0);
+    let mut contents = lines.join("\n");
+    contents.truncate(contents.trim_end().len());
+    contents
+}
+
+// fnv-1a, which is stable across builds (unlike the std hasher), so hashes
+// can be saved and compared later
+fn output_hash(contents: &str) -> u64 {
+    contents.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[test]
+fn test_full_contents() {
+    let mut parser = vt100::Parser::new(3, 10, 100);
+    for i in 0..8 {
+        parser.process(format!("line {}\r\n", i).as_bytes());
+    }
+    assert_eq!(
+        full_contents(&mut parser),
+        (0..8)
+            .map(|i| format!("line {}", i))
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+    assert_eq!(parser.screen().scrollback(), 0);
+}
+
+#[test]
+fn test_output_hash() {
+    assert_eq!(output_hash(""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(output_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    assert_ne!(output_hash("foo"), output_hash("bar"));
+}
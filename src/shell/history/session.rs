@@ -0,0 +1,71 @@
+use crate::shell::prelude::*;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Session {
+    entries: Vec<Entry>,
+}
+
+impl Session {
+    pub fn new(entries: Vec<Entry>) -> Self {
+        Self { entries }
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let file = path(name)?;
+        let contents = std::fs::read(&file).map_err(|e| {
+            anyhow!(
+                "couldn't read session {}: {}",
+                name,
+                crate::format::io_error(&e)
+            )
+        })?;
+        Ok(serde_json::from_slice(&contents)?)
+    }
+
+    pub fn save(&self, name: &str) -> Result<()> {
+        let file = path(name)?;
+        std::fs::create_dir_all(file.parent().unwrap())?;
+        std::fs::write(&file, serde_json::to_vec(self)?)?;
+        Ok(())
+    }
+
+    pub fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+}
+
+// everything needed to show an entry again after restarting, but not to
+// rerun it
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct Entry {
+    pub cmdline: String,
+    pub git_commit: Option<String>,
+    pub env: Env,
+    pub start_time: i64,
+    pub duration: std::time::Duration,
+    // None if the command was still running when the session was saved
+    pub status: Option<i32>,
+    pub size: (u16, u16),
+    pub contents: String,
+    // older sessions were saved without this
+    #[serde(default)]
+    pub output_hash: Option<u64>,
+}
+
+// the name is only a name, not a path, so it can't end up outside of the
+// sessions directory
+fn path(name: &str) -> Result<std::path::PathBuf> {
+    if name.is_empty() || name.contains('/') || name.contains("..") {
+        return Err(anyhow!("invalid session name: {}", name));
+    }
+    Ok(crate::dirs::sessions_dir().join(format!("{}.json", name)))
+}
+
+#[test]
+fn test_path() {
+    assert!(path("work").is_ok());
+    assert!(path("").is_err());
+    assert!(path("../x").is_err());
+    assert!(path("a/b").is_err());
+    assert!(path("..").is_err());
+}
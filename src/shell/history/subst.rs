@@ -0,0 +1,146 @@
+use crate::shell::prelude::*;
+
+// handles ^old^new, !!:s/old/new/, and !! or !N within the line - returns
+// None if cmdline doesn't use history substitution at all. nth looks up a
+// command by the number that the history builtin shows for it.
+pub fn substitute(
+    cmdline: &str,
+    prev: Option<&str>,
+    nth: impl Fn(usize) -> Option<String>,
+) -> Option<Result<String>> {
+    let (old, new) = if let Some(rest) = cmdline.strip_prefix('^') {
+        split_subst(rest, '^')?
+    } else if let Some(rest) = cmdline.strip_prefix("!!:s") {
+        let mut chars = rest.chars();
+        let delim = chars.next()?;
+        split_subst(chars.as_str(), delim)?
+    } else {
+        return expand(cmdline, prev, nth);
+    };
+
+    let prev = if let Some(prev) = prev {
+        prev
+    } else {
+        return Some(Err(anyhow!("no previous command")));
+    };
+    if old.is_empty() || !prev.contains(old) {
+        return Some(Err(anyhow!("substitution failed")));
+    }
+    Some(Ok(prev.replacen(old, new, 1)))
+}
+
+// !! and !N are only recognized at the start of a word, and not inside of
+// single quotes or after a backslash, so that they don't get in the way of
+// using ! for other things
+fn expand(
+    cmdline: &str,
+    prev: Option<&str>,
+    nth: impl Fn(usize) -> Option<String>,
+) -> Option<Result<String>> {
+    let mut expanded = String::new();
+    let mut found = false;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut word_start = true;
+    let mut chars = cmdline.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if c == '!' && word_start && !quoted && !escaped {
+            let rest = &cmdline[i + 1..];
+            let digits: String =
+                rest.chars().take_while(char::is_ascii_digit).collect();
+            let (cmd, len) = if rest.starts_with('!') {
+                if let Some(prev) = prev {
+                    (prev.to_string(), 1)
+                } else {
+                    return Some(Err(anyhow!("no previous command")));
+                }
+            } else if digits.is_empty() {
+                (c.to_string(), 0)
+            } else if let Some(cmd) = digits.parse().ok().and_then(&nth) {
+                (cmd, digits.len())
+            } else {
+                return Some(Err(anyhow!(
+                    "no such history entry: {}",
+                    digits
+                )));
+            };
+            found |= len > 0;
+            expanded.push_str(&cmd);
+            for _ in 0..len {
+                chars.next();
+            }
+            word_start = false;
+            continue;
+        }
+        match c {
+            '\\' if !escaped && !quoted => {
+                escaped = true;
+            }
+            '\'' if !escaped => {
+                quoted = !quoted;
+                escaped = false;
+            }
+            _ => {
+                escaped = false;
+            }
+        }
+        expanded.push(c);
+        word_start = c.is_whitespace();
+    }
+    found.then(|| Ok(expanded))
+}
+
+fn split_subst(s: &str, delim: char) -> Option<(&str, &str)> {
+    let (old, new) = s.split_once(delim)?;
+    Some((old, new.strip_suffix(delim).unwrap_or(new)))
+}
+
+#[test]
+fn test_substitute() {
+    let prev = Some("git comit -m foo");
+    let nth = |_| None;
+    assert_eq!(
+        substitute("^comit^commit", prev, nth).unwrap().unwrap(),
+        "git commit -m foo"
+    );
+    assert_eq!(
+        substitute("^comit^commit^", prev, nth).unwrap().unwrap(),
+        "git commit -m foo"
+    );
+    assert_eq!(
+        substitute("!!:s/comit/commit/", prev, nth)
+            .unwrap()
+            .unwrap(),
+        "git commit -m foo"
+    );
+    assert_eq!(
+        substitute("!!:s|foo|bar", prev, nth).unwrap().unwrap(),
+        "git comit -m bar"
+    );
+    assert_eq!(
+        substitute("^o^0", Some("foo"), nth).unwrap().unwrap(),
+        "f0o"
+    );
+    assert!(substitute("^nope^yes", prev, nth).unwrap().is_err());
+    assert!(substitute("^comit^commit", None, nth).unwrap().is_err());
+    assert!(substitute("git commit", prev, nth).is_none());
+    assert!(substitute("^comit", prev, nth).is_none());
+}
+
+#[test]
+fn test_expand() {
+    let prev = Some("make test");
+    let nth = |n| (n == 3).then(|| "cargo build".to_string());
+    assert_eq!(substitute("!!", prev, nth).unwrap().unwrap(), "make test");
+    assert_eq!(
+        substitute("sudo !! && !3", prev, nth).unwrap().unwrap(),
+        "sudo make test && cargo build"
+    );
+    assert_eq!(
+        substitute("echo '!!' \\!! !!", prev, nth).unwrap().unwrap(),
+        "echo '!!' \\!! make test"
+    );
+    assert!(substitute("echo hi!! '!3' ! !x", prev, nth).is_none());
+    assert!(substitute("!4", prev, nth).unwrap().is_err());
+    assert!(substitute("!!", None, nth).unwrap().is_err());
+}
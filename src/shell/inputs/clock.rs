@@ -25,3 +25,14 @@ impl Handler {
         }
     }
 }
+
+#[tokio::test(start_paused = true)]
+async fn test_clock_timer() {
+    let (event_w, event_r) = crate::shell::event::channel();
+    let _handler = Handler::new(event_w);
+    // with time paused, tokio skips ahead whenever everything is idle, so
+    // this doesn't actually wait
+    for _ in 0..3 {
+        assert!(matches!(event_r.recv().await, Some(Event::ClockTimer)));
+    }
+}
@@ -46,32 +46,36 @@ impl Handler {
                     }
                 });
                 let event_w = event_w.clone();
+                let dir = dir.clone();
                 tokio::spawn(async move {
                     while watch_r.recv().await.is_some() {
                         let repo = git2::Repository::discover(&dir).ok();
-                        let info = tokio::task::spawn_blocking(|| {
-                            repo.map(|repo| Info::new(&repo))
+                        let dir = dir.clone();
+                        let info = tokio::task::spawn_blocking(move || {
+                            repo.map(|repo| Info::load(&dir, &repo, true))
                         })
                         .await
                         .unwrap();
-                        event_w.send(Event::GitInfo(info));
+                        event_w.send(Event::PromptInfo(
+                            super::prompt::Update::Git(info),
+                        ));
                     }
                 });
                 _active_watcher = Some(watcher);
             } else {
                 _active_watcher = None;
             }
-            let info = tokio::task::spawn_blocking(|| {
-                repo.map(|repo| Info::new(&repo))
+            let info = tokio::task::spawn_blocking(move || {
+                repo.map(|repo| Info::load(&dir, &repo, false))
             })
             .await
             .unwrap();
-            event_w.send(Event::GitInfo(info));
+            event_w.send(Event::PromptInfo(super::prompt::Update::Git(info)));
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct Info {
     modified_files: bool,
     staged_files: bool,
@@ -79,7 +83,20 @@ pub struct Info {
     commits: bool,
     active_operation: ActiveOperation,
     branch: Option<String>,
+    head: Option<String>,
+    // commits ahead of and behind the upstream branch
     remote_branch_diff: Option<(usize, usize)>,
+    stashes: usize,
+}
+
+// the pieces of the summary, which the prompt colors separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Part {
+    Status,
+    Ahead,
+    Behind,
+    Stash,
+    Operation,
 }
 
 const MODIFIED: git2::Status = git2::Status::WT_DELETED
@@ -95,6 +112,18 @@ const STAGED: git2::Status = git2::Status::INDEX_DELETED
 const NEW: git2::Status = git2::Status::WT_NEW;
 
 impl Info {
+    // other nbsh instances in the same repository will often have already
+    // computed this, so check the cache daemon first
+    fn load(
+        dir: &std::path::Path,
+        git: &git2::Repository,
+        refresh: bool,
+    ) -> Self {
+        crate::shell::cache::git_info(dir, refresh)
+            .flatten()
+            .unwrap_or_else(|| Self::new(git))
+    }
+
     pub fn new(git: &git2::Repository) -> Self {
         let mut status_options = git2::StatusOptions::new();
         status_options.include_untracked(true);
@@ -122,38 +151,23 @@ impl Info {
         let head = git.head();
         let mut commits = false;
         let mut branch = None;
+        let mut head_id = None;
         let mut remote_branch_diff = None;
 
         if let Ok(head) = head {
             commits = true;
+            head_id = head
+                .resolve()
+                .ok()
+                .and_then(|head| head.target())
+                .map(|oid| oid.to_string());
             if head.is_branch() {
                 branch = head.shorthand().map(ToString::to_string);
-                remote_branch_diff =
-                    head.resolve()
-                        .ok()
-                        .map(|head| {
-                            (
-                                head.target(),
-                                head.shorthand().map(ToString::to_string),
-                            )
-                        })
-                        .and_then(|(head_id, name)| {
-                            head_id.and_then(|head_id| {
-                                name.and_then(|name| {
-                                    git.refname_to_id(&format!(
-                                        "refs/remotes/origin/{}",
-                                        name
-                                    ))
-                                    .ok()
-                                    .and_then(|remote_id| {
-                                        git.graph_ahead_behind(
-                                            head_id, remote_id,
-                                        )
-                                        .ok()
-                                    })
-                                })
-                            })
-                        });
+                remote_branch_diff = head.resolve().ok().and_then(|head| {
+                    let head_id = head.target()?;
+                    let upstream_id = upstream(git, head.shorthand()?)?;
+                    git.graph_ahead_behind(head_id, upstream_id).ok()
+                });
             } else {
                 branch =
                     head.resolve().ok().and_then(|head| head.target()).map(
@@ -185,9 +199,16 @@ impl Info {
             git2::RepositoryState::Rebase
             | git2::RepositoryState::RebaseInteractive
             | git2::RepositoryState::RebaseMerge => ActiveOperation::Rebase,
+            git2::RepositoryState::ApplyMailbox
+            | git2::RepositoryState::ApplyMailboxOrRebase => {
+                ActiveOperation::ApplyMailbox
+            }
             _ => ActiveOperation::None,
         };
 
+        // each stash is an entry in the reflog of refs/stash
+        let stashes = git.reflog("refs/stash").map_or(0, |log| log.len());
+
         Self {
             modified_files,
             staged_files,
@@ -195,27 +216,40 @@ impl Info {
             commits,
             active_operation,
             branch,
+            head: head_id,
             remote_branch_diff,
+            stashes,
         }
     }
-}
 
-impl std::fmt::Display for Info {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "g")?;
+    // the full id of the commit that is currently checked out
+    pub fn head(&self) -> Option<&str> {
+        self.head.as_deref()
+    }
+
+    // the short commit id if head is detached
+    pub fn branch(&self) -> Option<&str> {
+        self.branch.as_deref()
+    }
 
+    pub fn dirty(&self) -> bool {
+        self.modified_files || self.staged_files
+    }
+
+    pub fn parts(&self) -> Vec<(Part, String)> {
+        let mut status = "g".to_string();
         if self.modified_files {
-            write!(f, "*")?;
+            status.push('*');
         }
         if self.staged_files {
-            write!(f, "+")?;
+            status.push('+');
         }
         if self.new_files {
-            write!(f, "?")?;
+            status.push('?');
         }
         if !self.commits {
-            write!(f, "!")?;
-            return Ok(());
+            status.push('!');
+            return vec![(Part::Status, status)];
         }
 
         let branch = self.branch.as_ref().map_or("???", |branch| {
@@ -226,31 +260,64 @@ impl std::fmt::Display for Info {
             }
         });
         if !branch.is_empty() {
-            write!(f, ":")?;
+            status.push(':');
         }
-        write!(f, "{}", branch)?;
+        status.push_str(branch);
 
+        let mut parts = vec![];
         if let Some((local, remote)) = self.remote_branch_diff {
             if local > 0 || remote > 0 {
-                write!(f, ":")?;
+                status.push(':');
             }
+            parts.push((Part::Status, status));
             if local > 0 {
-                write!(f, "+{}", local)?;
+                parts.push((Part::Ahead, format!("+{}", local)));
             }
             if remote > 0 {
-                write!(f, "-{}", remote)?;
+                parts.push((Part::Behind, format!("-{}", remote)));
             }
         } else {
-            write!(f, ":-")?;
+            status.push_str(":-");
+            parts.push((Part::Status, status));
         }
 
-        write!(f, "{}", self.active_operation)?;
+        // like stash@{n}
+        if self.stashes > 0 {
+            parts.push((Part::Stash, format!("{{{}}}", self.stashes)));
+        }
 
+        let operation = self.active_operation.to_string();
+        if !operation.is_empty() {
+            parts.push((Part::Operation, operation));
+        }
+
+        parts
+    }
+}
+
+// the configured upstream of the branch, or the branch of the same name on
+// origin if there isn't one
+fn upstream(git: &git2::Repository, branch: &str) -> Option<git2::Oid> {
+    git.find_branch(branch, git2::BranchType::Local)
+        .ok()
+        .and_then(|branch| branch.upstream().ok())
+        .and_then(|upstream| upstream.get().target())
+        .or_else(|| {
+            git.refname_to_id(&format!("refs/remotes/origin/{}", branch))
+                .ok()
+        })
+}
+
+impl std::fmt::Display for Info {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (_, part) in self.parts() {
+            write!(f, "{}", part)?;
+        }
         Ok(())
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone)]
 pub enum ActiveOperation {
     None,
     Merge,
@@ -258,6 +325,7 @@ pub enum ActiveOperation {
     CherryPick,
     Bisect,
     Rebase,
+    ApplyMailbox,
 }
 
 impl std::fmt::Display for ActiveOperation {
@@ -269,6 +337,7 @@ impl std::fmt::Display for ActiveOperation {
             ActiveOperation::CherryPick => write!(f, "(c)"),
             ActiveOperation::Bisect => write!(f, "(b)"),
             ActiveOperation::Rebase => write!(f, "(r)"),
+            ActiveOperation::ApplyMailbox => write!(f, "(a)"),
         }
     }
 }
@@ -2,31 +2,59 @@ use crate::shell::prelude::*;
 
 mod clock;
 mod git;
-pub use git::Info as GitInfo;
+pub use git::{Info as GitInfo, Part as GitPart};
+mod prompt;
+pub use prompt::{
+    Context as PromptContext, Info as PromptInfo, Update as PromptUpdate,
+};
 mod signals;
 mod stdin;
+mod waiting;
 
 pub struct Handler {
-    _clock: clock::Handler,
-    git: git::Handler,
+    _clock: Option<clock::Handler>,
+    git: Option<git::Handler>,
+    prompt: Vec<prompt::Handler>,
     _signals: signals::Handler,
     _stdin: stdin::Handler,
+    _waiting: waiting::Handler,
 }
 
 impl Handler {
     pub fn new(
         input: textmode::blocking::Input,
         event_w: crate::shell::event::Writer,
+        clock: bool,
+        providers: bool,
     ) -> Result<Self> {
+        // the hostname is cheap and doesn't touch the filesystem, so it is
+        // always available
+        let mut prompt = vec![prompt::Handler::host(event_w.clone())];
+        if providers {
+            prompt.push(prompt::Handler::kube(event_w.clone()));
+            prompt.push(prompt::Handler::venv(event_w.clone()));
+        }
         Ok(Self {
-            _clock: clock::Handler::new(event_w.clone()),
-            git: git::Handler::new(event_w.clone()),
+            _clock: clock.then(|| clock::Handler::new(event_w.clone())),
+            git: providers.then(|| git::Handler::new(event_w.clone())),
+            prompt,
             _signals: signals::Handler::new(event_w.clone())?,
-            _stdin: stdin::Handler::new(input, event_w),
+            _stdin: stdin::Handler::new(input, event_w.clone()),
+            _waiting: waiting::Handler::new(event_w),
         })
     }
 
     pub fn new_dir(&self, path: std::path::PathBuf) {
-        self.git.new_dir(path);
+        if let Some(git) = &self.git {
+            git.new_dir(path);
+        }
+    }
+
+    // the other prompt providers can also depend on the environment, so
+    // they are updated separately
+    pub fn new_context(&self, context: PromptContext) {
+        for handler in &self.prompt {
+            handler.new_context(context.clone());
+        }
     }
 }
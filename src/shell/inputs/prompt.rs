@@ -0,0 +1,281 @@
+use crate::shell::prelude::*;
+
+// kubernetes contexts can be changed by other programs at any time, but
+// reading a small config file every so often is cheap
+const KUBE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+// new information from one of the prompt providers
+#[derive(Debug)]
+pub enum Update {
+    Git(Option<super::GitInfo>),
+    Kube(Option<String>),
+    Venv(Option<String>),
+    Host(Option<String>),
+}
+
+// the most recent values from each provider, which are what the prompt is
+// rendered from
+#[derive(Default)]
+pub struct Info {
+    pub git: Option<super::GitInfo>,
+    pub kube: Option<String>,
+    pub venv: Option<String>,
+    pub host: Option<String>,
+}
+
+impl Info {
+    pub fn update(&mut self, update: Update) {
+        match update {
+            Update::Git(git) => self.git = git,
+            Update::Kube(kube) => self.kube = kube,
+            Update::Venv(venv) => self.venv = venv,
+            Update::Host(host) => self.host = host,
+        }
+    }
+}
+
+// the variables that the providers look at. exports in the shell only
+// change the shell's own environment rather than the process environment,
+// so these are passed along with the directory.
+const VARS: &[&str] = &["HOME", "KUBECONFIG", "VIRTUAL_ENV"];
+
+// what the providers are loaded for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    dir: std::path::PathBuf,
+    vars: Vec<Option<String>>,
+}
+
+impl Context {
+    pub fn new(env: &Env) -> Self {
+        Self {
+            dir: env.pwd().to_path_buf(),
+            vars: VARS.iter().map(|k| env.var(k)).collect(),
+        }
+    }
+
+    fn var(&self, k: &str) -> Option<&str> {
+        let i = VARS.iter().position(|var| *var == k).unwrap();
+        self.vars[i].as_deref()
+    }
+}
+
+// a source of information for the prompt. each provider runs on its own
+// task, and is reloaded whenever the current directory or one of the
+// variables it uses changes (and every interval, if it has one), so that
+// slow providers never hold up rendering.
+// git has a handler of its own, since it needs to watch the repository.
+trait Provider: Send + Sync + 'static {
+    fn interval(&self) -> Option<std::time::Duration> {
+        None
+    }
+
+    // this runs in a blocking task, so it can do whatever io it needs
+    fn load(&self, context: &Context) -> Option<String>;
+
+    fn update(&self, value: Option<String>) -> Update;
+}
+
+pub struct Handler {
+    context_w: tokio::sync::mpsc::UnboundedSender<Context>,
+}
+
+impl Handler {
+    fn new(
+        provider: impl Provider,
+        event_w: crate::shell::event::Writer,
+    ) -> Self {
+        let (context_w, context_r) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(Self::task(
+            std::sync::Arc::new(provider),
+            context_r,
+            event_w,
+        ));
+        Self { context_w }
+    }
+
+    pub fn kube(event_w: crate::shell::event::Writer) -> Self {
+        Self::new(Kube, event_w)
+    }
+
+    pub fn venv(event_w: crate::shell::event::Writer) -> Self {
+        Self::new(Venv, event_w)
+    }
+
+    pub fn host(event_w: crate::shell::event::Writer) -> Self {
+        Self::new(Host, event_w)
+    }
+
+    pub fn new_context(&self, context: Context) {
+        self.context_w.send(context).unwrap();
+    }
+
+    async fn task(
+        provider: std::sync::Arc<impl Provider>,
+        mut context_r: tokio::sync::mpsc::UnboundedReceiver<Context>,
+        event_w: crate::shell::event::Writer,
+    ) {
+        let mut context = if let Some(context) = context_r.recv().await {
+            context
+        } else {
+            return;
+        };
+        let mut last = None;
+        loop {
+            while let Ok(newer_context) = context_r.try_recv() {
+                context = newer_context;
+            }
+            let value = {
+                let provider = provider.clone();
+                let context = context.clone();
+                tokio::task::spawn_blocking(move || provider.load(&context))
+                    .await
+                    .unwrap()
+            };
+            // polled providers usually haven't changed, and there's no
+            // reason to redraw in that case
+            if last.as_ref() != Some(&value) {
+                event_w
+                    .send(Event::PromptInfo(provider.update(value.clone())));
+                last = Some(value);
+            }
+
+            let interval = provider.interval();
+            tokio::select! {
+                new_context = context_r.recv() => match new_context {
+                    Some(new_context) => context = new_context,
+                    None => break,
+                },
+                _ = tokio::time::sleep(interval.unwrap_or_default()),
+                    if interval.is_some() => {}
+            }
+        }
+    }
+}
+
+// the current-context from the kubectl config
+struct Kube;
+
+impl Provider for Kube {
+    fn interval(&self) -> Option<std::time::Duration> {
+        Some(KUBE_INTERVAL)
+    }
+
+    fn load(&self, context: &Context) -> Option<String> {
+        let paths: Vec<_> = context.var("KUBECONFIG").map_or_else(
+            || {
+                context
+                    .var("HOME")
+                    .map(|home| {
+                        std::path::Path::new(home).join(".kube/config")
+                    })
+                    .into_iter()
+                    .collect()
+            },
+            |paths| std::env::split_paths(paths).collect(),
+        );
+        // when there are several config files, kubectl uses the first one
+        // which sets a context
+        paths.iter().find_map(|path| {
+            current_context(&std::fs::read_to_string(path).ok()?)
+        })
+    }
+
+    fn update(&self, value: Option<String>) -> Update {
+        Update::Kube(value)
+    }
+}
+
+// this is a top level key, so there's no need to pull in a full yaml parser
+fn current_context(config: &str) -> Option<String> {
+    config.lines().find_map(|line| {
+        let context = line.strip_prefix("current-context:")?.trim();
+        let context = context
+            .strip_prefix('"')
+            .and_then(|context| context.strip_suffix('"'))
+            .or_else(|| {
+                context
+                    .strip_prefix('\'')
+                    .and_then(|context| context.strip_suffix('\''))
+            })
+            .unwrap_or(context);
+        (!context.is_empty()).then(|| context.to_string())
+    })
+}
+
+// the python virtualenv which is active, or which belongs to the project
+// we're in
+struct Venv;
+
+impl Provider for Venv {
+    fn load(&self, context: &Context) -> Option<String> {
+        let venv = context
+            .var("VIRTUAL_ENV")
+            .map(std::path::PathBuf::from)
+            .or_else(|| project_venv(&context.dir))?;
+        Some(venv_name(&venv))
+    }
+
+    fn update(&self, value: Option<String>) -> Update {
+        Update::Venv(value)
+    }
+}
+
+fn project_venv(dir: &std::path::Path) -> Option<std::path::PathBuf> {
+    dir.ancestors().find_map(|dir| {
+        [".venv", "venv"].into_iter().find_map(|name| {
+            let venv = dir.join(name);
+            venv.join("pyvenv.cfg").exists().then(|| venv)
+        })
+    })
+}
+
+// virtualenvs inside of a project usually have a generic name, so the
+// project name is more useful
+fn venv_name(venv: &std::path::Path) -> String {
+    let name = |path: &std::path::Path| {
+        path.file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    };
+    name(venv)
+        .filter(|name| name != ".venv" && name != "venv")
+        .or_else(|| venv.parent().and_then(name))
+        .unwrap_or_default()
+}
+
+struct Host;
+
+impl Provider for Host {
+    fn load(&self, _context: &Context) -> Option<String> {
+        crate::info::hostname().ok()
+    }
+
+    fn update(&self, value: Option<String>) -> Update {
+        Update::Host(value)
+    }
+}
+
+#[test]
+fn test_current_context() {
+    assert_eq!(
+        current_context(
+            "apiVersion: v1\ncontexts:\n- name: dev\ncurrent-context: dev\n"
+        ),
+        Some("dev".to_string())
+    );
+    assert_eq!(
+        current_context("current-context: \"prod\"\n"),
+        Some("prod".to_string())
+    );
+    assert_eq!(current_context("current-context: ''\n"), None);
+    assert_eq!(current_context("  current-context: nested\n"), None);
+}
+
+#[test]
+fn test_venv_name() {
+    assert_eq!(venv_name(std::path::Path::new("/home/doy/.venvs/ml")), "ml");
+    assert_eq!(
+        venv_name(std::path::Path::new("/src/project/.venv")),
+        "project"
+    );
+}
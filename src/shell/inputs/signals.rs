@@ -1,5 +1,9 @@
 use crate::shell::prelude::*;
 
+// how long to wait for the terminal size to settle before resizing
+const RESIZE_DEBOUNCE: std::time::Duration =
+    std::time::Duration::from_millis(50);
+
 pub struct Handler;
 
 impl Handler {
@@ -15,16 +19,29 @@ impl Handler {
         mut signals: tokio::signal::unix::Signal,
         event_w: crate::shell::event::Writer,
     ) {
-        event_w.send(resize_event());
+        let mut size = current_size();
+        event_w.send(Event::Resize(size));
         while signals.recv().await.is_some() {
-            event_w.send(resize_event());
+            // dragging the corner of a terminal window sends a constant
+            // stream of SIGWINCH, and resizing every pty and redrawing the
+            // whole screen for each one makes things very sluggish, so only
+            // handle the final size
+            while matches!(
+                tokio::time::timeout(RESIZE_DEBOUNCE, signals.recv()).await,
+                Ok(Some(()))
+            ) {}
+            let new_size = current_size();
+            if new_size != size {
+                size = new_size;
+                event_w.send(Event::Resize(size));
+            }
         }
     }
 }
 
-fn resize_event() -> Event {
-    Event::Resize(terminal_size::terminal_size().map_or(
-        (24, 80),
+fn current_size() -> (u16, u16) {
+    terminal_size::terminal_size().map_or_else(
+        crate::info::default_size,
         |(terminal_size::Width(w), terminal_size::Height(h))| (h, w),
-    ))
+    )
 }
@@ -8,10 +8,125 @@ impl Handler {
         event_w: crate::shell::event::Writer,
     ) -> Self {
         std::thread::spawn(move || {
+            // the contents of a bracketed paste which is still arriving
+            let mut paste: Option<Vec<u8>> = None;
+            // the start of a multi-byte utf8 sequence whose remaining bytes
+            // haven't been read yet
+            let mut partial = vec![];
             while let Some(key) = input.read_key().unwrap() {
-                event_w.send(Event::Key(key));
+                if let Some(bytes) = &mut paste {
+                    if key == textmode::Key::Bytes(b"\x1b[201~".to_vec()) {
+                        event_w.send(Event::Paste(paste.take().unwrap()));
+                    } else {
+                        bytes.extend(key.into_bytes());
+                    }
+                    continue;
+                }
+                let key = match key {
+                    textmode::Key::Bytes(bytes) => {
+                        match decode(&mut partial, bytes) {
+                            Ok(keys) => {
+                                for key in keys {
+                                    event_w.send(Event::Key(key));
+                                }
+                                continue;
+                            }
+                            Err(bytes) => textmode::Key::Bytes(bytes),
+                        }
+                    }
+                    key => key,
+                };
+                // a character which was cut off by something else is passed
+                // along as is
+                if !partial.is_empty() {
+                    event_w.send(Event::Key(textmode::Key::Bytes(
+                        std::mem::take(&mut partial),
+                    )));
+                }
+                // focus reporting and bracketed paste sequences aren't keys
+                // that textmode knows about, so they come through as raw
+                // bytes
+                let event = match key {
+                    textmode::Key::Bytes(ref bytes)
+                        if bytes == b"\x1b[200~" =>
+                    {
+                        paste = Some(vec![]);
+                        continue;
+                    }
+                    textmode::Key::Bytes(ref bytes) if bytes == b"\x1b[I" => {
+                        Event::Focus(true)
+                    }
+                    textmode::Key::Bytes(ref bytes) if bytes == b"\x1b[O" => {
+                        Event::Focus(false)
+                    }
+                    key => Event::Key(key),
+                };
+                event_w.send(event);
             }
         });
         Self
     }
 }
+
+// terminals (and input methods in particular) can send a multi-byte
+// character in pieces, which textmode passes through as raw bytes. those
+// pieces are held onto until they form complete characters. anything which
+// doesn't look like utf8 is given back unchanged.
+fn decode(
+    partial: &mut Vec<u8>,
+    bytes: Vec<u8>,
+) -> std::result::Result<Vec<textmode::Key>, Vec<u8>> {
+    if bytes.first().map_or(true, u8::is_ascii) {
+        return Err(bytes);
+    }
+    partial.extend(bytes);
+    match std::str::from_utf8(partial) {
+        Ok(s) => {
+            let keys = s.chars().map(textmode::Key::Char).collect();
+            partial.clear();
+            Ok(keys)
+        }
+        Err(e) if e.error_len().is_none() => {
+            // the rest of the character hasn't arrived yet, but whatever
+            // came before it is complete
+            let valid = partial.drain(..e.valid_up_to()).collect::<Vec<_>>();
+            Ok(std::str::from_utf8(&valid)
+                .unwrap()
+                .chars()
+                .map(textmode::Key::Char)
+                .collect())
+        }
+        Err(_) => Err(std::mem::take(partial)),
+    }
+}
+
+#[test]
+fn test_decode() {
+    let mut partial = vec![];
+    assert_eq!(
+        decode(&mut partial, b"\x1b[I".to_vec()),
+        Err(b"\x1b[I".to_vec())
+    );
+    assert_eq!(decode(&mut partial, b"\xe6\x97".to_vec()), Ok(vec![]));
+    assert_eq!(
+        decode(&mut partial, b"\xa5\xe6".to_vec()),
+        Ok(vec![textmode::Key::Char('\u{65e5}')])
+    );
+    assert_eq!(
+        decode(&mut partial, b"\x9c\xac".to_vec()),
+        Ok(vec![textmode::Key::Char('\u{672c}')])
+    );
+    assert!(partial.is_empty());
+    assert_eq!(decode(&mut partial, b"\xe6".to_vec()), Ok(vec![]));
+    assert_eq!(
+        decode(&mut partial, b"\x1b[O".to_vec()),
+        Err(b"\x1b[O".to_vec())
+    );
+    assert_eq!(partial, b"\xe6");
+    partial.clear();
+    assert_eq!(
+        decode(&mut partial, b"\xff".to_vec()),
+        Err(b"\xff".to_vec())
+    );
+    assert!(partial.is_empty());
+}
@@ -0,0 +1,28 @@
+use crate::shell::prelude::*;
+
+// how often to check whether running entries are stuck waiting for input
+const INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+pub struct Handler;
+
+impl Handler {
+    pub fn new(event_w: crate::shell::event::Writer) -> Self {
+        tokio::spawn(Self::task(event_w));
+        Self
+    }
+
+    async fn task(event_w: crate::shell::event::Writer) {
+        let mut interval = tokio::time::interval(INTERVAL);
+        loop {
+            interval.tick().await;
+            // this reads through all of /proc, so keep it off of the main
+            // loop
+            let blocked = tokio::task::spawn_blocking(
+                crate::shell::history::blocked_groups,
+            )
+            .await
+            .unwrap();
+            event_w.send(Event::Waiting(blocked));
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use crate::shell::prelude::*;
+
+use unicode_width::UnicodeWidthStr as _;
+
+// how many previous runs of the same command to show in the sparkline
+const SPARKLINE_RUNS: usize = 40;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+pub struct Inspector {
+    idx: usize,
+}
+
+impl Inspector {
+    pub fn new(idx: usize) -> Self {
+        Self { idx }
+    }
+
+    pub fn idx(&self) -> usize {
+        self.idx
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        entry: &super::history::Entry,
+        runs: &[super::old_history::Run],
+        offset: time::UtcOffset,
+        theme: super::theme::Theme,
+        reproduced: Option<bool>,
+    ) {
+        let size = out.screen().size();
+        let mut lines = vec![];
+
+        if let Some(title) = entry.title() {
+            lines.push(("title", title.to_string()));
+        }
+        lines.push(("directory", crate::format::path(&entry.pwd())));
+        if let Some(commit) = entry.git_commit() {
+            lines.push(("commit", commit.to_string()));
+        }
+        if let Some(last) = entry.archived().last() {
+            lines.push((
+                "replaced",
+                format!(
+                    "{} earlier run{}, last was: {}",
+                    entry.archived().len(),
+                    if entry.archived().len() == 1 { "" } else { "s" },
+                    crate::format::command_line(&last.cmdline)
+                ),
+            ));
+        }
+        if let Some(hash) = entry.output_hash() {
+            lines.push((
+                "output",
+                match reproduced {
+                    Some(true) => {
+                        format!("{:016x}, same as the previous run", hash)
+                    }
+                    Some(false) => {
+                        format!(
+                            "{:016x}, differs from the previous run",
+                            hash
+                        )
+                    }
+                    None => format!("{:016x}", hash),
+                },
+            ));
+        }
+        if let Some(cpus) = entry.cpus() {
+            lines.push(("cpus", cpus.to_string()));
+        }
+        lines.push((
+            "started",
+            crate::format::time(entry.start_time().to_offset(offset)),
+        ));
+        lines.push(("duration", crate::format::duration(entry.duration())));
+        lines.push((
+            "status",
+            entry.exit_status().map_or_else(
+                || "running".to_string(),
+                crate::format::exit_status_description,
+            ),
+        ));
+
+        out.move_to(0, 0);
+        out.set_fgcolor(textmode::color::YELLOW);
+        write_clamped(
+            out,
+            &format!(
+                "{}: {}",
+                entry.idx() + 1,
+                crate::format::command_line(entry.cmd())
+            ),
+            size.1,
+        );
+        out.reset_attributes();
+
+        let mut row = 2;
+        for (name, value) in lines {
+            out.move_to(row, 0);
+            out.set_fgcolor(textmode::color::DARKGREY);
+            out.write_str(&format!("{:10} ", name));
+            out.reset_attributes();
+            write_clamped(out, &value, size.1.saturating_sub(11));
+            row += 1;
+        }
+        if !runs.is_empty() {
+            out.move_to(row, 0);
+            out.set_fgcolor(textmode::color::DARKGREY);
+            out.write_str(&format!("{:10} ", "history"));
+            out.reset_attributes();
+            render_sparkline(out, runs, theme);
+            row += 1;
+        }
+
+        if let Some(ast) = entry.ast() {
+            row += 1;
+            render_tree(out, &ast.tree(), &mut row);
+        }
+
+        for timings in entry.timings() {
+            row += 1;
+            if row >= size.0 {
+                break;
+            }
+            render_evaluated(out, &timings, &mut row);
+            render_timings(out, &timings, &mut row);
+        }
+        out.hide_cursor(true);
+    }
+}
+
+// one character per run, with the height showing how long it took and the
+// color showing whether it succeeded
+fn render_sparkline(
+    out: &mut impl textmode::Textmode,
+    runs: &[super::old_history::Run],
+    theme: super::theme::Theme,
+) {
+    let runs = &runs[runs.len().saturating_sub(SPARKLINE_RUNS)..];
+    let max = runs
+        .iter()
+        .filter_map(super::old_history::Run::duration)
+        .max()
+        .unwrap_or_default();
+    for run in runs {
+        match run.success() {
+            Some(true) => out.set_fgcolor(theme.success_color()),
+            Some(false) => out.set_fgcolor(
+                theme.status_color(super::theme::Status::Failure),
+            ),
+            None => out.set_fgcolor(textmode::color::DARKGREY),
+        }
+        out.write_str(&spark(run.duration(), max).to_string());
+    }
+    out.reset_attributes();
+    let failed = runs
+        .iter()
+        .filter(|run| run.success() == Some(false))
+        .count();
+    out.set_fgcolor(textmode::color::DARKGREY);
+    out.write_str(&format!(" {} runs, {} failed", runs.len(), failed));
+    out.reset_attributes();
+}
+
+fn spark(
+    duration: Option<std::time::Duration>,
+    max: std::time::Duration,
+) -> char {
+    let duration = if let Some(duration) = duration {
+        duration
+    } else {
+        return '·';
+    };
+    if max.is_zero() {
+        return SPARKS[0];
+    }
+    let idx = duration.min(max).as_nanos()
+        * u128::try_from(SPARKS.len() - 1).unwrap()
+        / max.as_nanos();
+    SPARKS[usize::try_from(idx).unwrap()]
+}
+
+// how nbsh parsed the command line, to make it easier to tell why it did
+// something unexpected
+fn render_tree(
+    out: &mut impl textmode::Textmode,
+    tree: &[String],
+    row: &mut u16,
+) {
+    let size = out.screen().size();
+    if *row >= size.0 {
+        return;
+    }
+    out.move_to(*row, 0);
+    out.set_fgcolor(textmode::color::DARKGREY);
+    out.write_str("parsed as");
+    out.reset_attributes();
+    *row += 1;
+    for line in tree {
+        if *row >= size.0 {
+            break;
+        }
+        out.move_to(*row, 2);
+        write_clamped(out, line, size.1.saturating_sub(2));
+        *row += 1;
+    }
+}
+
+// the commands in the pipeline as they were actually run, after expansion,
+// aliases, and corrections
+fn render_evaluated(
+    out: &mut impl textmode::Textmode,
+    timings: &[crate::runner::Timing],
+    row: &mut u16,
+) {
+    let size = out.screen().size();
+    for timing in timings {
+        if *row >= size.0 {
+            break;
+        }
+        let words: Vec<_> = std::iter::once(quote(timing.exe()))
+            .chain(timing.args().iter().map(String::as_str).map(quote))
+            .chain(timing.redirects().iter().map(Into::into))
+            .collect();
+        out.move_to(*row, 0);
+        out.set_fgcolor(textmode::color::DARKGREY);
+        out.write_str("$ ");
+        out.reset_attributes();
+        write_clamped(out, &words.join(" "), size.1.saturating_sub(2));
+        *row += 1;
+    }
+}
+
+fn quote(s: &str) -> std::borrow::Cow<str> {
+    if s.is_empty()
+        || s.contains(|c: char| c.is_whitespace() || "'\"\\$".contains(c))
+    {
+        std::borrow::Cow::Owned(format!("{:?}", s))
+    } else {
+        std::borrow::Cow::Borrowed(s)
+    }
+}
+
+// draws one row per command in the pipeline, with a bar showing when it was
+// running relative to the rest of the pipeline
+fn render_timings(
+    out: &mut impl textmode::Textmode,
+    timings: &[crate::runner::Timing],
+    row: &mut u16,
+) {
+    let size = out.screen().size();
+    let total = timings
+        .iter()
+        .map(|timing| timing.end().unwrap_or_else(|| timing.start()))
+        .max()
+        .unwrap_or_default();
+    let cols = usize::from(size.1);
+    let name_width = timings
+        .iter()
+        .map(|timing| timing.exe().width())
+        .max()
+        .unwrap_or(0)
+        .min(cols / 4);
+    let duration_width = 10;
+    let bar_width = cols.saturating_sub(name_width + duration_width + 2);
+
+    for timing in timings {
+        if *row >= size.0 {
+            break;
+        }
+        out.move_to(*row, 0);
+        let name = timing.exe();
+        let name = super::readline::truncate(name, name_width);
+        out.write_str(name);
+        out.write_str(&" ".repeat(name_width - name.width() + 1));
+
+        let (start, end) =
+            bar(timing.start(), timing.end(), total, bar_width);
+        out.write_str(&" ".repeat(start));
+        if timing.end().is_some() {
+            out.set_bgcolor(textmode::color::GREEN);
+        } else {
+            out.set_bgcolor(textmode::color::DARKGREY);
+        }
+        out.write_str(&" ".repeat(end - start));
+        out.reset_attributes();
+        out.write_str(&" ".repeat(bar_width - end + 1));
+
+        out.set_fgcolor(textmode::color::DARKGREY);
+        out.write_str(&timing.end().map_or_else(
+            || "?".to_string(),
+            |end| crate::format::duration(end - timing.start()),
+        ));
+        out.reset_attributes();
+        *row += 1;
+    }
+}
+
+// the columns of a bar of the given width which the command was running for.
+// commands that never finished are shown as running until the end.
+fn bar(
+    start: std::time::Duration,
+    end: Option<std::time::Duration>,
+    total: std::time::Duration,
+    width: usize,
+) -> (usize, usize) {
+    if total.is_zero() {
+        return (0, width);
+    }
+    let scale = |time: std::time::Duration| {
+        usize::try_from(
+            time.min(total).as_nanos() * u128::try_from(width).unwrap()
+                / total.as_nanos(),
+        )
+        .unwrap()
+    };
+    let start = scale(start).min(width.saturating_sub(1));
+    let end = end.map_or(width, scale);
+    // always show at least something, even for very short commands
+    (start, end.max(start + 1).min(width))
+}
+
+fn write_clamped(out: &mut impl textmode::Textmode, s: &str, width: u16) {
+    let width = usize::from(width);
+    if s.len() > width {
+        let mut end = width;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.write_str(&s[..end]);
+    } else {
+        out.write_str(s);
+    }
+}
+
+#[test]
+fn test_spark() {
+    let secs = std::time::Duration::from_secs;
+    assert_eq!(spark(None, secs(10)), '·');
+    assert_eq!(spark(Some(secs(0)), secs(10)), '▁');
+    assert_eq!(spark(Some(secs(10)), secs(10)), '█');
+    assert_eq!(spark(Some(secs(5)), secs(10)), '▄');
+    assert_eq!(spark(Some(secs(5)), secs(0)), '▁');
+}
+
+#[test]
+fn test_quote() {
+    assert_eq!(quote("foo"), "foo");
+    assert_eq!(quote(""), "\"\"");
+    assert_eq!(quote("a b"), "\"a b\"");
+    assert_eq!(quote("$x"), "\"$x\"");
+}
+
+#[test]
+fn test_bar() {
+    let secs = std::time::Duration::from_secs;
+    assert_eq!(bar(secs(0), Some(secs(5)), secs(10), 20), (0, 10));
+    assert_eq!(bar(secs(0), Some(secs(10)), secs(10), 20), (0, 20));
+    assert_eq!(bar(secs(5), Some(secs(10)), secs(10), 20), (10, 20));
+    assert_eq!(bar(secs(0), None, secs(10), 20), (0, 20));
+    assert_eq!(
+        bar(
+            secs(0),
+            Some(std::time::Duration::from_millis(1)),
+            secs(10),
+            20
+        ),
+        (0, 1)
+    );
+    assert_eq!(bar(secs(0), Some(secs(0)), secs(0), 20), (0, 20));
+}
@@ -0,0 +1,109 @@
+use crate::shell::prelude::*;
+
+use unicode_width::UnicodeWidthStr as _;
+
+// picks a directory to cd into out of the ones that have been visited
+// before, narrowing them down as fragments of the path are typed
+pub struct Jump {
+    dirs: super::dirs::Dirs,
+    now: i64,
+    query: String,
+    selected: usize,
+}
+
+impl Jump {
+    pub fn new(mut dirs: super::dirs::Dirs) -> Self {
+        dirs.retain_existing();
+        Self {
+            dirs,
+            now: super::dirs::now(),
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn add_input(&mut self, s: &str) {
+        self.query.push_str(s);
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> Option<std::path::PathBuf> {
+        self.matches()
+            .into_iter()
+            .nth(self.selected)
+            .map(|(path, _)| path)
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        colors: crate::shell::ColorScheme,
+    ) {
+        let size = out.screen().size();
+        let width = usize::from(size.1);
+        let matches = self.matches();
+
+        // like the history search, the best match is drawn closest to the
+        // input line
+        let rows = usize::from(size.0).saturating_sub(1);
+        let start = (self.selected + 1).saturating_sub(rows);
+        for (i, (path, _)) in
+            matches.iter().enumerate().skip(start).take(rows)
+        {
+            out.move_to((rows - 1 - (i - start)).try_into().unwrap(), 0);
+            out.reset_attributes();
+            if i == self.selected {
+                out.set_bgcolor(colors.highlight());
+            } else {
+                out.set_bgcolor(colors.background());
+            }
+            out.write(b"\x1b[K");
+            out.write_str(super::readline::truncate(
+                &crate::format::path(path),
+                width,
+            ));
+        }
+
+        out.move_to(size.0 - 1, 0);
+        out.reset_attributes();
+        let prompt = crate::msg::get(
+            if matches.is_empty() {
+                crate::msg::Message::FailedJump
+            } else {
+                crate::msg::Message::Jump
+            },
+            &[],
+        );
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&prompt);
+        out.reset_attributes();
+        let query = super::readline::truncate(
+            &self.query,
+            width.saturating_sub(prompt.width()),
+        );
+        out.write_str(query);
+        out.move_to(
+            size.0 - 1,
+            (prompt.width() + query.width()).try_into().unwrap(),
+        );
+    }
+
+    fn matches(&self) -> Vec<(std::path::PathBuf, f64)> {
+        self.dirs.matches(&self.query, self.now)
+    }
+}
@@ -2,14 +2,60 @@ use crate::shell::prelude::*;
 
 use textmode::Textmode as _;
 
+mod cache;
+mod clock;
+mod completion;
+mod diff;
+mod dir_env;
+pub mod dirs;
+mod env_diff;
+mod env_editor;
 mod event;
 mod history;
 mod inputs;
-mod old_history;
+mod inspector;
+mod jump;
+mod notify;
+pub use notify::NotifyMethod;
+pub mod old_history;
+mod palette;
+mod paste;
 mod prelude;
+mod prompt;
 mod readline;
+mod split;
+mod sync;
+mod theme;
+pub use theme::{ColorScheme, Theme};
+mod tmp;
+mod tutorial;
+
+// while the terminal isn't focused, output doesn't need to be drawn as
+// quickly
+const UNFOCUSED_FRAME_DURATION: std::time::Duration =
+    std::time::Duration::from_millis(500);
+
+pub async fn main(opt: crate::Opt) -> Result<i32> {
+    if opt.cache_daemon {
+        return cache::daemon().await;
+    }
+
+    if let Some(path) = &opt.export {
+        // clap ensures that --restore was also given
+        history::export_session(
+            opt.restore.as_deref().unwrap(),
+            path,
+            crate::info::get_offset(),
+        )?;
+        return Ok(0);
+    }
+
+    // login(1) indicates a login shell by starting the name with a -
+    let login = opt.login
+        || std::env::args_os()
+            .next()
+            .map_or(false, |arg0| arg0.as_bytes().starts_with(b"-"));
 
-pub async fn main() -> Result<i32> {
     let mut input = textmode::blocking::Input::new()?;
     let mut output = textmode::Output::new().await?;
 
@@ -18,43 +64,276 @@ pub async fn main() -> Result<i32> {
     let _input_guard = input.take_raw_guard();
     let _output_guard = output.take_screen_guard();
 
+    crate::info::record_startup_exe();
+    let config = crate::config::Config::load()?;
+    crate::msg::init(&config);
+    let time_format_err = crate::format::init(&config).err();
+    config.export_named_dirs();
+    if config.cache_daemon() && !opt.safe {
+        cache::enable();
+    }
+    if let Some(runner) = config.runner() {
+        crate::info::set_runner(runner.to_path_buf())?;
+    }
+
     let (event_w, event_r) = event::channel();
 
-    let inputs = inputs::Handler::new(input, event_w.clone()).unwrap();
+    // when running inside another nbsh, the outer instance is already
+    // redrawing its clock every second, and our own clock redraws would just
+    // cause the outer entry to constantly churn
+    let nested = crate::info::depth() > 0;
+    // safe mode avoids anything that would touch the filesystem or network
+    // in the background, beyond running the commands that were asked for
+    let inputs =
+        inputs::Handler::new(input, event_w.clone(), !nested, !opt.safe)
+            .unwrap();
+
+    if config.check_for_updates() && !opt.safe {
+        let event_w = event_w.clone();
+        tokio::spawn(async move {
+            if let Ok(Some(version)) = crate::info::update::check().await {
+                event_w.send(Event::UpdateAvailable(version));
+            }
+        });
+    }
+
+    // nested shells share the history file with the outer one, so only the
+    // outermost shell needs to sync it
+    let sync = config
+        .history_sync()
+        .filter(|_| !opt.safe && !nested)
+        .map(sync::Sync::new);
+    // this has to happen before the history is loaded
+    let sync_error = if let Some(sync) = &sync {
+        sync.pull().await.err()
+    } else {
+        None
+    };
 
-    let mut shell = Shell::new(crate::info::get_offset())?;
+    let mut shell = Shell::new(
+        crate::info::get_offset(),
+        opt.read_only,
+        std::sync::Arc::new(clock::System),
+    )?;
+    if let Some(e) = sync_error {
+        shell.readline.set_hint(Some(crate::msg::get(
+            crate::msg::Message::SyncFailed,
+            &[&e],
+        )));
+    }
+    if opt.tutorial {
+        shell.tutorial = Some(tutorial::Tutorial::new());
+    } else if !opt.no_greeting {
+        if let Some(greeting) = config.greeting() {
+            shell.greeting = Some(greeting.map_or_else(
+                Shell::default_greeting,
+                std::string::ToString::to_string,
+            ));
+        }
+    }
+    // evicting output means writing it out to disk
+    let _tmp_guard = if opt.safe {
+        None
+    } else {
+        if let Err(e) = tmp::collect_garbage() {
+            shell.readline.set_hint(Some(crate::msg::get(
+                crate::msg::Message::CleanupFailed,
+                &[&e],
+            )));
+        }
+        shell.max_output_memory = config.max_output_memory();
+        Some(tmp::Guard)
+    };
+    shell.readline.set_placeholder(&config.placeholder());
+    shell.status_bar = config.status_bar();
+    shell.safe = opt.safe;
+    shell.notifier = config.notify_after().map(|after| {
+        notify::Notifier::new(after, config.notify_method(), opt.safe)
+    });
+    if let Some(e) = time_format_err {
+        shell.readline.set_hint(Some(crate::msg::get(
+            crate::msg::Message::InvalidTimeFormat,
+            &[&e],
+        )));
+    }
+    match prompt::Prompt::new(config.prompt_left(), config.prompt_right()) {
+        Ok(prompt) => shell.readline.set_prompt(prompt),
+        Err(e) => shell.readline.set_hint(Some(crate::msg::get(
+            crate::msg::Message::InvalidPrompt,
+            &[&e],
+        ))),
+    }
+    shell.history.set_scrollback(config.scrollback());
+    shell.history.set_theme(config.theme());
+    shell.env.set_color_scheme(config.color_scheme());
+    if let Some(name) = &opt.restore {
+        shell.history.restore_session(name)?;
+        shell.env.set_idx(shell.history.entry_count());
+    }
+    // the startup files run as an ordinary entry, so that their output and
+    // any errors in them can be seen, and so that what they set up is picked
+    // up by the shell in the same way as for anything else
+    let startup: Vec<_> = login
+        .then(crate::dirs::profile_file)
+        .into_iter()
+        .chain((!opt.norc).then(crate::dirs::rc_file))
+        .filter(|file| file.exists())
+        .map(|file| {
+            format!("source {}", completion::escape(&file.to_string_lossy()))
+        })
+        .collect();
+    if !startup.is_empty() && !opt.read_only && opt.restore.is_none() {
+        shell.history.run_startup(
+            startup.join("\n"),
+            shell.env.clone(),
+            event_w.clone(),
+        );
+        let idx = shell.history.entry_count() - 1;
+        shell.set_focus(Focus::History(idx));
+        shell.hide_readline = true;
+    }
     let mut prev_dir = shell.env.pwd().to_path_buf();
     inputs.new_dir(prev_dir.clone());
-    while let Some(event) = event_r.recv().await {
-        match shell.handle_event(event, &event_w) {
-            Some(Action::Refresh) => {
-                shell.render(&mut output)?;
-                output.refresh().await?;
-            }
-            Some(Action::HardRefresh) => {
-                shell.render(&mut output)?;
-                output.hard_refresh().await?;
-            }
-            Some(Action::Resize(rows, cols)) => {
-                output.set_size(rows, cols);
-                shell.render(&mut output)?;
-                output.hard_refresh().await?;
+    let mut prev_context = inputs::PromptContext::new(&shell.env);
+    inputs.new_context(prev_context.clone());
+    if !opt.safe {
+        shell.update_dir_env().await;
+    }
+    // the terminal tells us when it gains or loses focus once this is
+    // enabled, which we use to avoid doing unnecessary work in the
+    // background. pastes are also bracketed, so that they can be confirmed
+    // before anything in them gets run.
+    std::io::stdout().write_all(b"\x1b[?1004h\x1b[?2004h")?;
+    std::io::stdout().flush()?;
+    let focused_frame = config.frame_duration();
+    let mut last_frame = tokio::time::Instant::now();
+    let mut pending: Option<Action> = None;
+    loop {
+        let frame = if shell.terminal_focused {
+            focused_frame
+        } else {
+            focused_frame.max(UNFOCUSED_FRAME_DURATION)
+        };
+        // if something needs to be drawn, wait for either the next frame or
+        // the next event, whichever comes first
+        let event = if let Some(action) = pending {
+            tokio::select! {
+                event = event_r.recv() => event,
+                _ = tokio::time::sleep_until(last_frame + frame) => {
+                    draw(&mut shell, &mut output, action).await?;
+                    last_frame = tokio::time::Instant::now();
+                    pending = None;
+                    continue;
+                }
             }
+        } else {
+            event_r.recv().await
+        };
+        let event = if let Some(event) = event {
+            event
+        } else {
+            break;
+        };
+        // keypresses are drawn immediately so that typing always feels
+        // responsive, everything else waits for the next frame
+        let immediate = matches!(event, Event::Key(_));
+        let mut action = shell.handle_event(event, &event_w);
+        // anything can finish a step of the tutorial
+        if shell.update_tutorial() {
+            action = Some(action.map_or(Action::Refresh, |action| {
+                action.merge(Action::Refresh)
+            }));
+        }
+        match action {
             Some(Action::Quit) => break,
+            Some(action) => {
+                pending = Some(
+                    pending.map_or(action, |pending| pending.merge(action)),
+                );
+            }
             None => {}
         }
+        if let Some(action) = pending {
+            if immediate || last_frame.elapsed() >= frame {
+                draw(&mut shell, &mut output, action).await?;
+                last_frame = tokio::time::Instant::now();
+                pending = None;
+            }
+        }
         let dir = shell.env().pwd();
-        if dir != prev_dir {
+        let dir_changed = dir != prev_dir;
+        if dir_changed {
             prev_dir = dir.to_path_buf();
             inputs.new_dir(dir.to_path_buf());
+            // the jump list is only a convenience, so there's nothing
+            // worth interrupting anything for if it can't be updated
+            if !opt.safe {
+                #[allow(clippy::let_underscore_drop)]
+                let _ = dirs::Dirs::record(&prev_dir);
+            }
+        }
+        if !opt.safe && (dir_changed || shell.dir_env.reload_requested()) {
+            shell.update_dir_env().await;
+            pending = Some(pending.map_or(Action::Refresh, |pending| {
+                pending.merge(Action::Refresh)
+            }));
+        }
+        let context = inputs::PromptContext::new(shell.env());
+        if context != prev_context {
+            inputs.new_context(context.clone());
+            prev_context = context;
         }
     }
 
     shell.history.save().await;
+    if let Some(name) = &opt.session {
+        shell.history.save_session(name)?;
+    }
+    std::io::stdout().write_all(b"\x1b[?1004l\x1b[?2004l")?;
+    std::io::stdout().flush()?;
+    if let Some(sync) = &sync {
+        sync.push().await?;
+    }
 
     Ok(0)
 }
 
+async fn draw(
+    shell: &mut Shell,
+    output: &mut textmode::Output,
+    action: Action,
+) -> Result<()> {
+    match action {
+        Action::Refresh => {
+            shell.render(output)?;
+            output.refresh().await?;
+        }
+        Action::HardRefresh => {
+            shell.render(output)?;
+            output.hard_refresh().await?;
+        }
+        Action::Resize(rows, cols) => {
+            output.set_size(rows, cols);
+            shell.render(output)?;
+            output.hard_refresh().await?;
+        }
+        Action::Quit => unreachable!(),
+    }
+    Ok(())
+}
+
+// xdg-open can take a while to hand the url off to the browser, so don't
+// wait for it. tokio takes care of reaping it once it exits.
+fn open_url(url: &str) -> std::io::Result<()> {
+    tokio::process::Command::new("xdg-open")
+        .arg(url)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
 #[derive(Copy, Clone, Debug)]
 enum Focus {
     Readline,
@@ -66,8 +345,17 @@ enum Focus {
 enum Scene {
     Readline,
     Fullscreen,
+    Split,
+    Diff,
+    Inspector,
+    Paste,
+    Jump,
+    Palette,
+    EnvEditor,
+    EnvDiff,
 }
 
+#[derive(Copy, Clone, Debug)]
 pub enum Action {
     Refresh,
     HardRefresh,
@@ -75,34 +363,114 @@ pub enum Action {
     Quit,
 }
 
+impl Action {
+    // combines two actions that happened within the same frame into the
+    // single action that covers both of them
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Quit, _) | (_, Self::Quit) => Self::Quit,
+            (_, Self::Resize(rows, cols)) | (Self::Resize(rows, cols), _) => {
+                Self::Resize(rows, cols)
+            }
+            (Self::HardRefresh, _) | (_, Self::HardRefresh) => {
+                Self::HardRefresh
+            }
+            (Self::Refresh, Self::Refresh) => Self::Refresh,
+        }
+    }
+}
+
 pub struct Shell {
     readline: readline::Readline,
     history: history::History,
     old_history: old_history::History,
     env: Env,
-    git: Option<inputs::GitInfo>,
+    prompt_info: inputs::PromptInfo,
+    update: Option<String>,
+    greeting: Option<String>,
+    max_output_memory: Option<usize>,
+    notifier: Option<notify::Notifier>,
+    diff_mark: Option<usize>,
+    split_mark: Option<usize>,
+    split: Option<split::Split>,
+    diff: Option<diff::Diff>,
+    inspector: Option<inspector::Inspector>,
+    paste: Option<paste::Paste>,
+    jump: Option<jump::Jump>,
+    palette: Option<palette::Palette>,
+    env_editor: Option<env_editor::EnvEditor>,
+    env_diff: Option<env_diff::EnvDiff>,
+    dir_env: dir_env::DirEnv,
+    tutorial: Option<tutorial::Tutorial>,
+    count: Option<usize>,
+    // the count only came from ctrl-u so far, so typing a digit starts a new
+    // one rather than adding to it
+    count_universal: bool,
+    terminal_focused: bool,
+    finished_while_away: usize,
+    // the most recent entry noticed waiting for input in the background
+    waiting: Option<usize>,
     focus: Focus,
     scene: Scene,
     escape: bool,
     hide_readline: bool,
+    status_bar: bool,
+    read_only: bool,
+    // started with --read-only, in which case it can't be turned off
+    forced_read_only: bool,
+    // started with --safe, so nothing else is started in the background
+    safe: bool,
     offset: time::UtcOffset,
 }
 
 impl Shell {
-    pub fn new(offset: time::UtcOffset) -> Result<Self> {
+    pub fn new(
+        offset: time::UtcOffset,
+        read_only: bool,
+        clock: clock::Shared,
+    ) -> Result<Self> {
         let mut env = Env::new()?;
         env.set_var("SHELL", std::env::current_exe()?);
         env.set_var("TERM", "screen");
+        env.set_var("NBSH_DEPTH", (crate::info::depth() + 1).to_string());
+        // lets tools running inside an entry tell which nbsh they are
+        // running under
+        env.set_var("NBSH_SESSION", crate::info::pid());
         Ok(Self {
             readline: readline::Readline::new(),
-            history: history::History::new(),
+            history: history::History::new(clock),
             old_history: old_history::History::new(),
             env,
-            git: None,
+            prompt_info: inputs::PromptInfo::default(),
+            update: None,
+            greeting: None,
+            max_output_memory: None,
+            notifier: None,
+            diff_mark: None,
+            split_mark: None,
+            split: None,
+            diff: None,
+            inspector: None,
+            paste: None,
+            jump: None,
+            palette: None,
+            env_editor: None,
+            env_diff: None,
+            dir_env: dir_env::DirEnv::default(),
+            tutorial: None,
+            count: None,
+            count_universal: false,
+            terminal_focused: true,
+            finished_while_away: 0,
+            waiting: None,
             focus: Focus::Readline,
             scene: Scene::Readline,
             escape: false,
             hide_readline: false,
+            status_bar: false,
+            read_only,
+            forced_read_only: read_only,
+            safe: false,
             offset,
         })
     }
@@ -111,80 +479,370 @@ impl Shell {
         out.clear();
         out.write(&vt100::Parser::default().screen().input_mode_formatted());
         match self.scene {
-            Scene::Readline => match self.focus {
-                Focus::Readline => {
-                    self.history.render(
-                        out,
-                        self.readline.lines(),
-                        None,
-                        false,
-                        self.offset,
-                    );
-                    self.readline.render(
-                        out,
-                        &self.env,
-                        self.git.as_ref(),
-                        true,
-                        self.offset,
-                    )?;
-                }
-                Focus::History(idx) => {
-                    if self.hide_readline {
+            Scene::Readline => {
+                match self.focus {
+                    Focus::Readline => {
                         self.history.render(
                             out,
-                            0,
-                            Some(idx),
+                            self.reserved_lines(),
+                            None,
                             false,
                             self.offset,
+                            self.env.color_scheme(),
                         );
-                    } else {
+                        self.render_greeting(out);
+                        self.readline.render(
+                            out,
+                            &self.env,
+                            &self.prompt_info,
+                            true,
+                            self.offset,
+                        )?;
+                        self.render_update_notice(out);
+                    }
+                    Focus::History(idx) => {
+                        if self.hide_readline {
+                            self.history.render(
+                                out,
+                                usize::from(self.status_bar),
+                                Some(idx),
+                                false,
+                                self.offset,
+                                self.env.color_scheme(),
+                            );
+                        } else {
+                            self.history.render(
+                                out,
+                                self.reserved_lines(),
+                                Some(idx),
+                                false,
+                                self.offset,
+                                self.env.color_scheme(),
+                            );
+                            let pos = out.screen().cursor_position();
+                            self.readline.render(
+                                out,
+                                &self.env,
+                                &self.prompt_info,
+                                false,
+                                self.offset,
+                            )?;
+                            out.move_to(pos.0, pos.1);
+                        }
+                    }
+                    Focus::Scrolling(idx) => {
                         self.history.render(
                             out,
-                            self.readline.lines(),
-                            Some(idx),
-                            false,
+                            self.reserved_lines(),
+                            idx,
+                            true,
                             self.offset,
+                            self.env.color_scheme(),
                         );
-                        let pos = out.screen().cursor_position();
                         self.readline.render(
                             out,
                             &self.env,
-                            self.git.as_ref(),
-                            false,
+                            &self.prompt_info,
+                            idx.is_none(),
                             self.offset,
                         )?;
-                        out.move_to(pos.0, pos.1);
+                        out.hide_cursor(true);
                     }
                 }
-                Focus::Scrolling(idx) => {
-                    self.history.render(
+                self.render_status_bar(out)?;
+            }
+            Scene::Fullscreen => {
+                if let Focus::History(idx) = self.focus {
+                    self.history.entry(idx).render_fullscreen(out);
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::Split => {
+                if let (Some(split), Focus::History(idx)) =
+                    (&self.split, self.focus)
+                {
+                    split.render(out, &self.history, idx);
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::Diff => {
+                if let Some(diff) = &self.diff {
+                    diff.render(out);
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::Inspector => {
+                if let Some(inspector) = &self.inspector {
+                    inspector.render(
                         out,
-                        self.readline.lines(),
-                        idx,
-                        true,
+                        self.history.entry(inspector.idx()),
+                        &self.previous_runs(inspector.idx()),
                         self.offset,
+                        self.history.theme(),
+                        self.history.reproduced(inspector.idx()),
                     );
-                    self.readline.render(
-                        out,
-                        &self.env,
-                        self.git.as_ref(),
-                        idx.is_none(),
-                        self.offset,
-                    )?;
-                    out.hide_cursor(true);
+                } else {
+                    unreachable!();
                 }
-            },
-            Scene::Fullscreen => {
-                if let Focus::History(idx) = self.focus {
-                    self.history.entry(idx).render_fullscreen(out);
+            }
+            Scene::Paste => {
+                if let Some(paste) = &self.paste {
+                    let entry = match paste.target() {
+                        paste::Target::Readline => None,
+                        paste::Target::Entry(idx) => {
+                            Some(self.history.entry(idx))
+                        }
+                    };
+                    paste.render(out, entry);
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::Jump => {
+                if let Some(jump) = &self.jump {
+                    jump.render(out, self.env.color_scheme());
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::Palette => {
+                if let Some(palette) = &self.palette {
+                    palette.render(out, self.env.color_scheme());
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::EnvEditor => {
+                if let Some(env_editor) = &self.env_editor {
+                    env_editor.render(out, &self.env);
+                } else {
+                    unreachable!();
+                }
+            }
+            Scene::EnvDiff => {
+                if let Some(env_diff) = &self.env_diff {
+                    env_diff.render(out);
                 } else {
                     unreachable!();
                 }
             }
         }
+        if let (Scene::Readline, Some(tutorial)) =
+            (self.scene, &self.tutorial)
+        {
+            tutorial.render(out, self.env.color_scheme());
+        }
         Ok(())
     }
 
+    // every finished run of the same command line, from both the persistent
+    // history and this session, up to and including the given entry
+    fn previous_runs(&self, idx: usize) -> Vec<old_history::Run> {
+        let cmd = old_history::normalize(self.history.entry(idx).cmd());
+        let mut runs = self.old_history.runs(&cmd);
+        runs.extend(
+            (0..=idx)
+                .map(|idx| self.history.entry(idx))
+                .filter(|entry| old_history::normalize(entry.cmd()) == cmd)
+                .filter_map(|entry| {
+                    entry.exit_status().map(|status| {
+                        old_history::Run::new(
+                            Some(crate::status::code(status)),
+                            Some(entry.duration()),
+                        )
+                    })
+                }),
+        );
+        runs
+    }
+
+    // returns whether the tutorial moved on to a new step
+    fn update_tutorial(&mut self) -> bool {
+        let mut advanced = false;
+        while let Some(goal) =
+            self.tutorial.as_ref().and_then(tutorial::Tutorial::goal)
+        {
+            let reached = match goal {
+                tutorial::Goal::RunCommand => (0..self.history.entry_count())
+                    .any(|idx| !self.history.entry(idx).running()),
+                tutorial::Goal::EnterEscape => self.escape,
+                tutorial::Goal::Scroll => {
+                    matches!(self.focus, Focus::Scrolling(Some(_)))
+                }
+                tutorial::Goal::Fullscreen => {
+                    matches!(self.scene, Scene::Fullscreen)
+                }
+                tutorial::Goal::Inspect => self.inspector.is_some(),
+                tutorial::Goal::Return => {
+                    matches!(self.focus, Focus::Readline) && !self.escape
+                }
+            };
+            if !reached {
+                break;
+            }
+            self.tutorial.as_mut().unwrap().advance();
+            advanced = true;
+        }
+        if advanced
+            && self
+                .tutorial
+                .as_ref()
+                .and_then(tutorial::Tutorial::goal)
+                .is_none()
+        {
+            self.tutorial = None;
+            self.readline.set_hint(Some(crate::msg::get(
+                crate::msg::Message::TutorialDone,
+                &[],
+            )));
+        }
+        advanced
+    }
+
+    fn default_greeting() -> String {
+        crate::msg::get(
+            crate::msg::Message::Greeting,
+            &[&crate::info::update::current_version()],
+        )
+    }
+
+    // only shown until the first command is run, since the history takes
+    // over the screen at that point
+    fn render_greeting(&self, out: &mut impl textmode::Textmode) {
+        let greeting = if let Some(greeting) = &self.greeting {
+            greeting
+        } else {
+            return;
+        };
+        if self.history.entry_count() > 0 {
+            return;
+        }
+        let size = out.screen().size();
+        let max_lines =
+            usize::from(size.0).saturating_sub(self.reserved_lines());
+        let mut lines: Vec<String> = greeting
+            .lines()
+            .take(max_lines)
+            .map(std::string::ToString::to_string)
+            .collect();
+        let old_entries = self.old_history.entry_count();
+        if old_entries > 0 && lines.len() < max_lines {
+            lines.push(format!("{} commands in history", old_entries));
+        }
+        out.set_fgcolor(textmode::color::DARKGREY);
+        for (row, line) in lines.iter().enumerate() {
+            out.move_to(row.try_into().unwrap(), 0);
+            out.write_str(readline::truncate(line, usize::from(size.1)));
+        }
+        out.reset_attributes();
+    }
+
+    // the history leaves a row free for it just above the readline
+    fn render_status_bar(
+        &self,
+        out: &mut impl textmode::Textmode,
+    ) -> Result<()> {
+        if !self.status_bar {
+            return Ok(());
+        }
+        let size = out.screen().size();
+        let readline_lines = if self.hide_readline
+            && matches!(self.focus, Focus::History(_))
+        {
+            0
+        } else {
+            self.readline.lines()
+        };
+        let row = usize::from(size.0).saturating_sub(readline_lines + 1);
+        let pos = out.screen().cursor_position();
+        let hide = out.screen().hide_cursor();
+
+        let mut left = vec![];
+        if self.escape {
+            left.push(crate::msg::get(
+                crate::msg::Message::StatusEscape,
+                &[],
+            ));
+        }
+        if let Some(count) = self.count {
+            left.push(count.to_string());
+        }
+        if matches!(self.focus, Focus::Scrolling(_)) {
+            left.push(crate::msg::get(
+                crate::msg::Message::StatusScrolling,
+                &[],
+            ));
+        }
+        if let Some(idx) = self.focus_idx() {
+            left.push(crate::msg::get(
+                crate::msg::Message::StatusFocus,
+                &[&(idx + 1)],
+            ));
+        }
+        let running = self.history.running_count();
+        if running > 0 {
+            left.push(crate::msg::get(
+                crate::msg::Message::StatusRunning,
+                &[&running],
+            ));
+        }
+        let left = left.join("  ");
+        let time = crate::info::time(self.offset)?;
+
+        out.move_to(row.try_into().unwrap(), 0);
+        out.set_bgcolor(self.env.color_scheme().background());
+        out.write(b"\x1b[K");
+        out.write_str(readline::truncate(&left, usize::from(size.1)));
+        // the last status and the clock go on the right, if there is room
+        let right_width = time.len() + 3;
+        if unicode_width::UnicodeWidthStr::width(left.as_str())
+            + right_width
+            + 2
+            <= usize::from(size.1)
+        {
+            let theme = self.history.theme();
+            let status =
+                theme::Status::from_exit_status(self.env.latest_status());
+            out.move_to(
+                row.try_into().unwrap(),
+                (usize::from(size.1) - right_width).try_into().unwrap(),
+            );
+            out.set_fgcolor(theme.status_color(status));
+            out.write_str(&status.glyph().to_string());
+            out.reset_attributes();
+            out.set_bgcolor(self.env.color_scheme().background());
+            out.write_str(&format!(" {} ", time));
+        }
+        out.reset_attributes();
+        out.move_to(pos.0, pos.1);
+        out.hide_cursor(hide);
+        Ok(())
+    }
+
+    fn render_update_notice(&self, out: &mut impl textmode::Textmode) {
+        let version = if let Some(version) = &self.update {
+            version
+        } else {
+            return;
+        };
+        let notice =
+            crate::msg::get(crate::msg::Message::UpdateAvailable, &[version]);
+        let size = out.screen().size();
+        let pos = out.screen().cursor_position();
+        let len: u16 = notice.len().try_into().unwrap();
+        // only show this if it won't overlap with the input
+        if pos.1 + 2 + len < size.1 {
+            out.move_to(pos.0, size.1 - len - 1);
+            out.set_fgcolor(textmode::color::DARKGREY);
+            out.write_str(&notice);
+            out.reset_attributes();
+            out.move_to(pos.0, pos.1);
+        }
+    }
+
     pub fn handle_event(
         &mut self,
         event: Event,
@@ -192,44 +850,104 @@ impl Shell {
     ) -> Option<Action> {
         match event {
             Event::Key(key) => {
-                return if self.escape {
-                    self.escape = false;
-                    self.handle_key_escape(&key, event_w.clone())
-                } else if key == textmode::Key::Ctrl(b'e') {
-                    self.escape = true;
-                    None
-                } else {
-                    match self.focus {
-                        Focus::Readline => {
-                            self.handle_key_readline(&key, event_w.clone())
-                        }
-                        Focus::History(idx) => {
-                            self.handle_key_history(key, idx);
-                            None
-                        }
-                        Focus::Scrolling(_) => {
-                            self.handle_key_escape(&key, event_w.clone())
-                        }
-                    }
-                };
+                // like emacs, each ctrl-u multiplies the count by four
+                if self.universal_argument(&key) {
+                    self.count = Some(
+                        self.count.map_or(4, |count| count.saturating_mul(4)),
+                    );
+                    self.count_universal = true;
+                    return None;
+                }
+                if let Some(digit) = self.count_digit(&key) {
+                    let count = if std::mem::take(&mut self.count_universal) {
+                        0
+                    } else {
+                        self.count.unwrap_or(0)
+                    };
+                    self.count = Some(count.saturating_mul(10) + digit);
+                    return None;
+                }
+                self.count_universal = false;
+                let count = self.count.take().unwrap_or(1);
+                let count = if self.repeatable(&key) { count } else { 1 };
+                let mut action: Option<Action> = None;
+                for _ in 0..count {
+                    let new = self.handle_key(key.clone(), event_w);
+                    action = match (action, new) {
+                        (Some(action), Some(new)) => Some(action.merge(new)),
+                        (action, new) => action.or(new),
+                    };
+                }
+                return action;
             }
+            Event::Paste(bytes) => return self.handle_paste(bytes, event_w),
             Event::Resize(new_size) => {
                 self.readline.resize(new_size);
                 self.history.resize(new_size);
+                if let Some(split) = &self.split {
+                    split.resize(&self.history);
+                }
                 return Some(Action::Resize(new_size.0, new_size.1));
             }
-            Event::PtyOutput => {
+            Event::Focus(focused) => {
+                self.terminal_focused = focused;
+                if focused && self.finished_while_away > 0 {
+                    let count = std::mem::take(&mut self.finished_while_away);
+                    self.readline.set_hint(Some(crate::msg::get(
+                        if count == 1 {
+                            crate::msg::Message::FinishedWhileAwayOne
+                        } else {
+                            crate::msg::Message::FinishedWhileAway
+                        },
+                        &[&count],
+                    )));
+                }
+            }
+            Event::PtyOutput(_) => unreachable!(),
+            Event::PtyOutputs(idxs) => {
                 // the number of visible lines may have changed, so make sure
                 // the focus is still visible
                 self.history.make_focus_visible(
-                    self.readline.lines(),
+                    self.reserved_lines(),
                     self.focus_idx(),
                     matches!(self.focus, Focus::Scrolling(_)),
                 );
-                self.scene = self.default_scene(self.focus);
+                // only the focused entry can switch into or out of
+                // fullscreen
+                if self.focus_idx().map_or(false, |idx| idxs.contains(&idx)) {
+                    self.scene = self.default_scene(self.focus);
+                }
             }
             Event::ChildExit(idx, exit_info, env) => {
-                self.history.entry_mut(idx).exited(exit_info);
+                if !self.terminal_focused {
+                    self.finished_while_away += 1;
+                }
+                if idx + 1 == self.history.entry_count() {
+                    self.readline.set_hint((!exit_info.success()).then(
+                        || {
+                            crate::msg::get(
+                                crate::msg::Message::LastCommandFailed,
+                                &[],
+                            )
+                        },
+                    ));
+                }
+                self.history.entry_mut(idx).exited(exit_info, env.clone());
+                if let (Some(notifier), false) =
+                    (&self.notifier, self.terminal_focused)
+                {
+                    let entry = self.history.entry(idx);
+                    if let Some(status) = entry.exit_status() {
+                        notifier.finished(
+                            entry.cmd(),
+                            status,
+                            entry.duration(),
+                        );
+                    }
+                }
+                if let Some(limit) = self.max_output_memory {
+                    self.history.enforce_memory_limit(limit);
+                }
                 if self.focus_idx() == Some(idx) {
                     if let Some(env) = env {
                         if self.hide_readline {
@@ -248,24 +966,180 @@ impl Shell {
             Event::ChildRunPipeline(idx, span) => {
                 self.history.entry_mut(idx).set_span(span);
             }
+            Event::ChildStopped(idx, stopped) => {
+                self.history.entry_mut(idx).set_stopped(stopped);
+            }
             Event::ChildSuspend(idx) => {
+                self.history.entry_mut(idx).set_suspended(true);
                 if self.focus_idx() == Some(idx) {
                     self.set_focus(Focus::Readline);
                 }
             }
-            Event::GitInfo(info) => {
-                self.git = info;
+            Event::ChildRequestInput(idx) => {
+                // don't steal focus if the user is in the middle of doing
+                // something else
+                if matches!(self.focus, Focus::Readline)
+                    && self.readline.input().is_empty()
+                {
+                    self.set_focus(Focus::History(idx));
+                }
+            }
+            Event::PromptInfo(update) => {
+                self.prompt_info.update(update);
+            }
+            Event::UpdateAvailable(version) => {
+                self.update = Some(version);
+            }
+            Event::ClockTimer => {
+                // nobody is looking at the clock
+                if !self.terminal_focused {
+                    return None;
+                }
+            }
+            Event::Waiting(blocked) => {
+                let changed = self.history.update_waiting(&blocked);
+                if changed.is_empty() {
+                    return None;
+                }
+                // the entry being typed into is expected to read input
+                let typing = match self.focus {
+                    Focus::History(idx) => Some(idx),
+                    _ => None,
+                };
+                if let Some(idx) = changed.into_iter().rev().find(|&idx| {
+                    Some(idx) != typing && self.history.entry(idx).waiting()
+                }) {
+                    self.waiting = Some(idx);
+                    self.readline.set_hint(Some(crate::msg::get(
+                        crate::msg::Message::WaitingForInput,
+                        &[&(idx + 1)],
+                    )));
+                }
             }
-            Event::ClockTimer => {}
         };
         Some(Action::Refresh)
     }
 
+    fn handle_key(
+        &mut self,
+        key: textmode::Key,
+        event_w: &crate::shell::event::Writer,
+    ) -> Option<Action> {
+        if self.paste.is_some() {
+            self.handle_key_paste(&key, event_w)
+        } else if self.jump.is_some() {
+            self.handle_key_jump(&key, event_w.clone())
+        } else if self.palette.is_some() {
+            self.handle_key_palette(&key, event_w)
+        } else if self.env_editor.is_some() {
+            self.handle_key_env_editor(&key)
+        } else if self.diff.is_some() {
+            self.handle_key_diff(&key)
+        } else if self.env_diff.is_some() {
+            self.handle_key_env_diff(&key)
+        } else if self.inspector.is_some() {
+            self.handle_key_inspector(&key)
+        } else if self.escape {
+            self.escape = false;
+            self.handle_key_escape(&key, event_w.clone())
+        } else if key == textmode::Key::Ctrl(b'e') {
+            self.escape = true;
+            None
+        } else {
+            match self.focus {
+                Focus::Readline => {
+                    self.handle_key_readline(&key, event_w.clone())
+                }
+                Focus::History(idx) => {
+                    if !self.read_only {
+                        self.handle_key_history(key, idx);
+                    }
+                    None
+                }
+                Focus::Scrolling(_) => {
+                    self.handle_key_escape(&key, event_w.clone())
+                }
+            }
+        }
+    }
+
+    // emacs-style numeric arguments: alt-digits in the readline, or plain
+    // digits in escape mode
+    // overlays take their keys directly, without counts
+    fn counts_allowed(&self) -> bool {
+        self.diff.is_none()
+            && self.inspector.is_none()
+            && self.paste.is_none()
+            && self.jump.is_none()
+            && self.palette.is_none()
+            && self.env_editor.is_none()
+            && self.env_diff.is_none()
+    }
+
+    fn count_digit(&self, key: &textmode::Key) -> Option<usize> {
+        if !self.counts_allowed() {
+            return None;
+        }
+        let digit = match (key, self.focus) {
+            (textmode::Key::Meta(c), Focus::Readline) if !self.escape => {
+                char::from(*c).to_digit(10)
+            }
+            (textmode::Key::Char(c), _)
+                if self.escape
+                    || matches!(self.focus, Focus::Scrolling(_)) =>
+            {
+                c.to_digit(10)
+            }
+            _ => None,
+        }?;
+        Some(usize::try_from(digit).unwrap())
+    }
+
+    // ctrl-u already clears the line in the readline, like in bash, so it
+    // only starts a count in escape mode and while scrolling
+    fn universal_argument(&self, key: &textmode::Key) -> bool {
+        *key == textmode::Key::Ctrl(b'u')
+            && (self.escape || matches!(self.focus, Focus::Scrolling(_)))
+            && self.counts_allowed()
+    }
+
+    // only movement and editing keys can be repeated, since repeating
+    // anything that runs a command would be surprising
+    fn repeatable(&self, key: &textmode::Key) -> bool {
+        if self.escape || matches!(self.focus, Focus::Scrolling(_)) {
+            matches!(
+                key,
+                textmode::Key::Char('j' | 'k')
+                    | textmode::Key::Up
+                    | textmode::Key::Down
+                    | textmode::Key::PageUp
+                    | textmode::Key::PageDown
+            )
+        } else if matches!(self.focus, Focus::Readline) {
+            matches!(
+                key,
+                textmode::Key::Char(_)
+                    | textmode::Key::Ctrl(b'w' | b'y')
+                    | textmode::Key::Meta(b'b' | b'f')
+                    | textmode::Key::Backspace
+                    | textmode::Key::Left
+                    | textmode::Key::Right
+                    | textmode::Key::Up
+                    | textmode::Key::Down
+            )
+        } else {
+            false
+        }
+    }
+
     fn handle_key_escape(
         &mut self,
         key: &textmode::Key,
         event_w: crate::shell::event::Writer,
     ) -> Option<Action> {
+        if self.read_only && Self::key_modifies(key) {
+            return None;
+        }
         match key {
             textmode::Key::Ctrl(b'd') => {
                 return Some(Action::Quit);
@@ -278,10 +1152,23 @@ impl Shell {
             }
             textmode::Key::Ctrl(b'm') => {
                 if let Some(idx) = self.focus_idx() {
+                    let entry = self.history.entry(idx);
+                    if entry.evicted() {
+                        entry.reload();
+                        return Some(Action::Refresh);
+                    }
                     self.readline.clear_input();
+                    self.readline.set_hint(None);
+                    let cmdline = self.history.entry(idx).cmd().to_string();
+                    self.write_history_list(
+                        self.history.entry_count(),
+                        &cmdline,
+                    );
+                    let git_commit = self.git_commit();
                     self.history.run(
-                        self.history.entry(idx).cmd().to_string(),
+                        cmdline,
                         self.env.clone(),
+                        git_commit,
                         event_w,
                     );
                     let idx = self.history.entry_count() - 1;
@@ -292,6 +1179,11 @@ impl Shell {
                     self.set_focus(Focus::Readline);
                 }
             }
+            textmode::Key::Char(':') => {
+                self.palette =
+                    Some(palette::Palette::new(self.focus_idx().is_some()));
+                self.scene = self.default_scene(self.focus);
+            }
             textmode::Key::Char(' ') => {
                 if let Some(idx) = self.focus_idx() {
                     if self.history.entry(idx).running() {
@@ -301,6 +1193,51 @@ impl Shell {
                     self.set_focus(Focus::Readline);
                 }
             }
+            textmode::Key::Char('A') => {
+                if let Err(e) = self.dir_env.allow() {
+                    self.readline.set_hint(Some(crate::msg::get(
+                        crate::msg::Message::Error,
+                        &[&e],
+                    )));
+                }
+            }
+            textmode::Key::Char('C') => {
+                let colors = self.env.color_scheme().next();
+                self.env.set_color_scheme(colors);
+                self.readline.set_hint(Some(crate::msg::get(
+                    crate::msg::Message::ColorScheme,
+                    &[&colors.name()],
+                )));
+            }
+            textmode::Key::Char('c') => {
+                if let Some(idx) = self.focus_idx() {
+                    let entry = self.history.entry_mut(idx);
+                    if entry.running() {
+                        entry.resume();
+                        self.set_focus(Focus::History(idx));
+                    }
+                }
+            }
+            textmode::Key::Char('d') => {
+                if let Some(idx) = self.focus_idx() {
+                    match self.diff_mark {
+                        Some(mark) if mark != idx => {
+                            self.diff = Some(diff::Diff::new(
+                                self.history.entry(mark),
+                                self.history.entry(idx),
+                            ));
+                            self.diff_mark = None;
+                            self.scene = self.default_scene(self.focus);
+                        }
+                        Some(_) => {
+                            self.diff_mark = None;
+                        }
+                        None => {
+                            self.diff_mark = Some(idx);
+                        }
+                    }
+                }
+            }
             textmode::Key::Char('e') => {
                 if let Focus::History(idx) = self.focus {
                     self.handle_key_history(textmode::Key::Ctrl(b'e'), idx);
@@ -325,9 +1262,16 @@ impl Shell {
                 if let Some(idx) = self.focus_idx() {
                     self.readline
                         .set_input(self.history.entry(idx).cmd().to_string());
+                    self.readline.set_editing(Some(idx));
                     self.set_focus(Focus::Readline);
                 }
             }
+            textmode::Key::Char('I') => {
+                if let Some(idx) = self.focus_idx() {
+                    self.inspector = Some(inspector::Inspector::new(idx));
+                    self.scene = self.default_scene(self.focus);
+                }
+            }
             textmode::Key::Char('j') | textmode::Key::Down => {
                 self.set_focus(Focus::Scrolling(self.scroll_down()));
             }
@@ -337,12 +1281,150 @@ impl Shell {
             textmode::Key::Char('n') => {
                 self.set_focus(self.next_running());
             }
+            textmode::Key::Char('o') => {
+                let path = self
+                    .env
+                    .pwd()
+                    .join(format!("nbsh-{}.html", crate::info::pid()));
+                let contents = self
+                    .history
+                    .export(history::ExportFormat::Html, self.offset);
+                self.readline.set_hint(Some(
+                    match std::fs::write(&path, contents) {
+                        Ok(()) => crate::msg::get(
+                            crate::msg::Message::Exported,
+                            &[&crate::format::path(&path)],
+                        ),
+                        Err(e) => crate::msg::get(
+                            crate::msg::Message::ExportFailed,
+                            &[&crate::format::io_error(&e)],
+                        ),
+                    },
+                ));
+            }
             textmode::Key::Char('p') => {
                 self.set_focus(self.prev_running());
             }
             textmode::Key::Char('r') => {
                 self.set_focus(Focus::Readline);
             }
+            textmode::Key::PageUp => {
+                if let Some(idx) = self.focus_idx() {
+                    self.history
+                        .entry(idx)
+                        .scroll_up(usize::from(self.history.size().0) / 2);
+                }
+            }
+            textmode::Key::PageDown => {
+                if let Some(idx) = self.focus_idx() {
+                    self.history
+                        .entry(idx)
+                        .scroll_down(usize::from(self.history.size().0) / 2);
+                }
+            }
+            textmode::Key::Char('R') => {
+                self.read_only = !self.read_only || self.forced_read_only;
+            }
+            textmode::Key::Char('s') => {
+                if let Some(split) = self.split.take() {
+                    split.close(&self.history);
+                    self.scene = self.default_scene(self.focus);
+                } else if let Some(idx) = self.focus_idx() {
+                    // like diffing, the first entry is marked and the split
+                    // is shown once a second one is picked
+                    match self.split_mark {
+                        Some(mark) if mark != idx => {
+                            let split = split::Split::new(mark, idx);
+                            split.resize(&self.history);
+                            self.split = Some(split);
+                            self.split_mark = None;
+                            self.set_focus(Focus::History(idx));
+                        }
+                        Some(_) => {
+                            self.split_mark = None;
+                        }
+                        None => {
+                            self.split_mark = Some(idx);
+                        }
+                    }
+                }
+            }
+            textmode::Key::Char('S') => {
+                if let Some(split) = &mut self.split {
+                    split.toggle_orientation(&self.history);
+                }
+            }
+            textmode::Key::Ctrl(b'i') => {
+                if let (Some(split), Focus::History(idx)) =
+                    (&self.split, self.focus)
+                {
+                    let other = split.other(idx);
+                    self.set_focus(Focus::History(other));
+                }
+            }
+            textmode::Key::Char('u') => {
+                if let Some(idx) = self.focus_idx() {
+                    let url = self.history.entry_mut(idx).select_next_link();
+                    self.readline.set_hint(Some(url.map_or_else(
+                        || crate::msg::get(crate::msg::Message::NoLinks, &[]),
+                        |url| {
+                            crate::msg::get(
+                                crate::msg::Message::SelectedLink,
+                                &[&url],
+                            )
+                        },
+                    )));
+                }
+            }
+            textmode::Key::Char('U') => {
+                if let Some(url) = self
+                    .focus_idx()
+                    .and_then(|idx| self.history.entry(idx).selected_link())
+                {
+                    if self.safe {
+                        self.readline.set_hint(Some(crate::msg::get(
+                            crate::msg::Message::SafeMode,
+                            &[],
+                        )));
+                    } else if let Err(e) = open_url(&url) {
+                        self.readline.set_hint(Some(crate::msg::get(
+                            crate::msg::Message::OpenFailed,
+                            &[&url, &crate::format::io_error(&e)],
+                        )));
+                    }
+                }
+            }
+            textmode::Key::Char('v') => {
+                self.env_editor = Some(env_editor::EnvEditor::default());
+                self.scene = self.default_scene(self.focus);
+            }
+            textmode::Key::Char('V') => {
+                if let Some(idx) = self.focus_idx() {
+                    self.env_diff =
+                        Some(env_diff::EnvDiff::new(self.history.entry(idx)));
+                    self.scene = self.default_scene(self.focus);
+                }
+            }
+            textmode::Key::Char('w') => {
+                if let Some(idx) = self.waiting.take() {
+                    if self.history.entry(idx).running() {
+                        self.set_focus(Focus::History(idx));
+                    }
+                }
+            }
+            textmode::Key::Char('x') => {
+                if let Some(idx) = self.focus_idx() {
+                    self.history.kill(idx);
+                }
+            }
+            textmode::Key::Char('z') => {
+                if let Some(idx) = self.focus_idx() {
+                    self.history.entry_mut(idx).toggle_collapsed();
+                    // the entry can't stay focused for typing while it is
+                    // collapsed
+                    self.set_focus(Focus::Scrolling(Some(idx)));
+                }
+            }
             _ => {
                 return None;
             }
@@ -350,11 +1432,50 @@ impl Shell {
         Some(Action::Refresh)
     }
 
+    // keys in escape mode which can run commands or send input to them,
+    // start other programs, or write files
+    fn key_modifies(key: &textmode::Key) -> bool {
+        matches!(
+            key,
+            textmode::Key::Ctrl(b'm')
+                | textmode::Key::Char('A')
+                | textmode::Key::Char('U')
+                | textmode::Key::Char('c')
+                | textmode::Key::Char('e')
+                | textmode::Key::Char('i')
+                | textmode::Key::Char('o')
+                | textmode::Key::Char('v')
+                | textmode::Key::Char('x')
+        )
+    }
+
     fn handle_key_readline(
         &mut self,
         key: &textmode::Key,
         event_w: crate::shell::event::Writer,
     ) -> Option<Action> {
+        if self.read_only {
+            return match key {
+                textmode::Key::Ctrl(b'd') => Some(Action::Quit),
+                textmode::Key::Ctrl(b'l') => Some(Action::HardRefresh),
+                textmode::Key::Up => {
+                    let entry_count = self.history.entry_count();
+                    if entry_count > 0 {
+                        self.set_focus(Focus::Scrolling(Some(
+                            entry_count - 1,
+                        )));
+                    }
+                    Some(Action::Refresh)
+                }
+                _ => None,
+            };
+        }
+        if self.readline.searching() {
+            return self.handle_key_search(key);
+        }
+        if *key != textmode::Key::Ctrl(b'i') {
+            self.readline.clear_completions();
+        }
         match key {
             textmode::Key::Char(c) => {
                 self.readline.add_input(&c.to_string());
@@ -367,42 +1488,499 @@ impl Shell {
                 return Some(Action::HardRefresh);
             }
             textmode::Key::Ctrl(b'm') => {
+                if self.readline.continue_line() {
+                    return Some(Action::Refresh);
+                }
                 let input = self.readline.input();
-                if !input.is_empty() {
-                    self.history.run(
-                        input.to_string(),
-                        self.env.clone(),
-                        event_w,
+                let prev = self.history_cmd(
+                    self.old_history.entry_count()
+                        + self.history.saved_entries().count(),
+                );
+                // show the expanded command rather than running it directly,
+                // so that it can be checked first
+                match history::substitute(input, prev.as_deref(), |n| {
+                    self.history_cmd(n)
+                }) {
+                    Some(Ok(expanded)) => self.readline.set_input(expanded),
+                    // leave the input alone so that it can be fixed
+                    Some(Err(e)) => {
+                        self.readline.set_hint(Some(crate::msg::get(
+                            crate::msg::Message::Error,
+                            &[&e],
+                        )));
+                    }
+                    None => {
+                        if !input.is_empty() {
+                            self.write_history_list(
+                                self.history.entry_count(),
+                                input,
+                            );
+                            let git_commit = self.git_commit();
+                            self.history.run(
+                                input.to_string(),
+                                self.env.clone(),
+                                git_commit,
+                                event_w,
+                            );
+                            let idx = self.history.entry_count() - 1;
+                            self.set_focus(Focus::History(idx));
+                            self.hide_readline = true;
+                            self.env.set_idx(idx + 1);
+                            self.readline.clear_input();
+                            self.readline.set_hint(None);
+                        }
+                    }
+                }
+            }
+            textmode::Key::Meta(b'\r') => {
+                let input = self.readline.input();
+                if let Some(idx) = self.readline.editing() {
+                    if self.history.entry(idx).running() {
+                        self.readline.set_hint(Some(crate::msg::get(
+                            crate::msg::Message::StillRunning,
+                            &[&(idx + 1)],
+                        )));
+                    } else if !input.is_empty() {
+                        self.write_history_list(idx, input);
+                        let git_commit = self.git_commit();
+                        self.history.rerun(
+                            idx,
+                            input.to_string(),
+                            self.env.clone(),
+                            git_commit,
+                            event_w,
+                        );
+                        self.set_focus(Focus::History(idx));
+                        self.hide_readline = true;
+                        self.readline.clear_input();
+                        self.readline.set_hint(None);
+                    }
+                }
+            }
+            textmode::Key::Ctrl(b'i') => {
+                if self.readline.completing() {
+                    self.readline.next_completion();
+                } else {
+                    let completions = completion::complete(
+                        self.readline.input_before_cursor(),
+                        &self.env,
                     );
-                    let idx = self.history.entry_count() - 1;
-                    self.set_focus(Focus::History(idx));
-                    self.hide_readline = true;
-                    self.env.set_idx(idx + 1);
-                    self.readline.clear_input();
+                    self.readline.complete(completions);
                 }
             }
+            textmode::Key::Ctrl(b'r') => {
+                let candidates = (0..self.history.entry_count())
+                    .rev()
+                    .map(|idx| self.history.entry(idx).cmd().to_string())
+                    .chain(self.old_history.cmds().into_iter().rev())
+                    .collect();
+                self.readline.start_search(candidates);
+            }
+            textmode::Key::Meta(b'j') => {
+                self.jump = Some(jump::Jump::new(dirs::Dirs::load()));
+                self.scene = self.default_scene(self.focus);
+            }
             textmode::Key::Ctrl(b'u') => self.readline.clear_backwards(),
+            textmode::Key::Ctrl(b'w') => self.readline.kill_word_backwards(),
+            textmode::Key::Ctrl(b'k') => self.readline.kill_to_end(),
+            textmode::Key::Ctrl(b'y') => self.readline.yank(),
+            // ctrl-e is already used to enter escape mode
+            textmode::Key::Ctrl(b'a') | textmode::Key::Home => {
+                self.readline.cursor_home();
+            }
+            textmode::Key::End => self.readline.cursor_end(),
+            textmode::Key::Meta(b'b') => self.readline.word_left(),
+            textmode::Key::Meta(b'f') => self.readline.word_right(),
             textmode::Key::Backspace => self.readline.backspace(),
             textmode::Key::Left => self.readline.cursor_left(),
             textmode::Key::Right => self.readline.cursor_right(),
             textmode::Key::Up => {
                 let entry_count = self.history.entry_count();
-                if entry_count > 0 {
+                if !self.readline.cursor_up() && entry_count > 0 {
                     self.set_focus(Focus::Scrolling(Some(entry_count - 1)));
                 }
             }
+            textmode::Key::Down => {
+                self.readline.cursor_down();
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_search(&mut self, key: &textmode::Key) -> Option<Action> {
+        match key {
+            textmode::Key::Char(c) => {
+                self.readline.search_add_input(&c.to_string());
+            }
+            textmode::Key::Backspace => self.readline.search_backspace(),
+            textmode::Key::Ctrl(b'r') | textmode::Key::Up => {
+                self.readline.search_older();
+            }
+            textmode::Key::Down => self.readline.search_newer(),
+            textmode::Key::Ctrl(b'm') => self.readline.accept_search(),
+            textmode::Key::Ctrl(b'c' | b'g') | textmode::Key::Escape => {
+                self.readline.cancel_search();
+            }
             _ => return None,
         }
         Some(Action::Refresh)
     }
 
+    fn handle_key_diff(&mut self, key: &textmode::Key) -> Option<Action> {
+        let diff = self.diff.as_mut().unwrap();
+        match key {
+            textmode::Key::Char('j') | textmode::Key::Down => {
+                diff.scroll_down(1);
+            }
+            textmode::Key::Char('k') | textmode::Key::Up => {
+                diff.scroll_up(1);
+            }
+            textmode::Key::Char(' ') | textmode::Key::PageDown => {
+                diff.scroll_down(usize::from(self.history.size().0) / 2);
+            }
+            textmode::Key::PageUp => {
+                diff.scroll_up(usize::from(self.history.size().0) / 2);
+            }
+            textmode::Key::Char('q') | textmode::Key::Escape => {
+                self.diff = None;
+                self.scene = self.default_scene(self.focus);
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_env_diff(&mut self, key: &textmode::Key) -> Option<Action> {
+        let env_diff = self.env_diff.as_mut().unwrap();
+        match key {
+            textmode::Key::Char('j') | textmode::Key::Down => {
+                env_diff.scroll_down(1);
+            }
+            textmode::Key::Char('k') | textmode::Key::Up => {
+                env_diff.scroll_up(1);
+            }
+            textmode::Key::Char(' ') | textmode::Key::PageDown => {
+                env_diff.scroll_down(usize::from(self.history.size().0) / 2);
+            }
+            textmode::Key::PageUp => {
+                env_diff.scroll_up(usize::from(self.history.size().0) / 2);
+            }
+            textmode::Key::Char('q' | 'V') | textmode::Key::Escape => {
+                self.env_diff = None;
+                self.scene = self.default_scene(self.focus);
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_inspector(
+        &mut self,
+        key: &textmode::Key,
+    ) -> Option<Action> {
+        match key {
+            textmode::Key::Char('q' | 'I') | textmode::Key::Escape => {
+                self.inspector = None;
+                self.scene = self.default_scene(self.focus);
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_jump(
+        &mut self,
+        key: &textmode::Key,
+        event_w: crate::shell::event::Writer,
+    ) -> Option<Action> {
+        let jump = self.jump.as_mut().unwrap();
+        match key {
+            textmode::Key::Char(c) => jump.add_input(&c.to_string()),
+            textmode::Key::Backspace => jump.backspace(),
+            textmode::Key::Ctrl(b'p') | textmode::Key::Up => {
+                jump.select_next();
+            }
+            textmode::Key::Ctrl(b'n') | textmode::Key::Down => {
+                jump.select_prev();
+            }
+            textmode::Key::Ctrl(b'm') => {
+                if let Some(dir) = jump.selected() {
+                    self.jump = None;
+                    // this goes through the normal cd builtin so that it
+                    // shows up in the history like any other command,
+                    // without disturbing anything already typed
+                    let cmdline = format!(
+                        "cd {}",
+                        completion::escape(&dir.to_string_lossy())
+                    );
+                    self.write_history_list(
+                        self.history.entry_count(),
+                        &cmdline,
+                    );
+                    let git_commit = self.git_commit();
+                    self.history.run(
+                        cmdline,
+                        self.env.clone(),
+                        git_commit,
+                        event_w,
+                    );
+                    let idx = self.history.entry_count() - 1;
+                    self.set_focus(Focus::History(idx));
+                    self.hide_readline = true;
+                    self.env.set_idx(idx + 1);
+                    self.readline.set_hint(None);
+                }
+            }
+            textmode::Key::Ctrl(b'c' | b'g') | textmode::Key::Escape => {
+                self.jump = None;
+                self.scene = self.default_scene(self.focus);
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_palette(
+        &mut self,
+        key: &textmode::Key,
+        event_w: &crate::shell::event::Writer,
+    ) -> Option<Action> {
+        let palette = self.palette.as_mut().unwrap();
+        match key {
+            textmode::Key::Char(c) => palette.add_input(&c.to_string()),
+            textmode::Key::Backspace => palette.backspace(),
+            textmode::Key::Ctrl(b'p') | textmode::Key::Up => {
+                palette.select_next();
+            }
+            textmode::Key::Ctrl(b'n') | textmode::Key::Down => {
+                palette.select_prev();
+            }
+            textmode::Key::Ctrl(b'm') => {
+                let command = palette.selected();
+                self.palette = None;
+                self.scene = self.default_scene(self.focus);
+                match command {
+                    // this runs a command, so it is blocked in read-only
+                    // mode like the key in the readline is
+                    Some(palette::Command::Jump) if !self.read_only => {
+                        self.jump = Some(jump::Jump::new(dirs::Dirs::load()));
+                        self.scene = self.default_scene(self.focus);
+                    }
+                    // everything else goes through escape mode, so that
+                    // read-only mode applies in the same way
+                    Some(command) => {
+                        if let Some(key) = command.escape_key() {
+                            return self
+                                .handle_key_escape(&key, event_w.clone());
+                        }
+                    }
+                    None => {}
+                }
+            }
+            textmode::Key::Ctrl(b'c' | b'g') | textmode::Key::Escape => {
+                self.palette = None;
+                self.scene = self.default_scene(self.focus);
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_env_editor(
+        &mut self,
+        key: &textmode::Key,
+    ) -> Option<Action> {
+        let env_editor = self.env_editor.as_mut().unwrap();
+        match key {
+            textmode::Key::Char(c) => env_editor.add_input(&c.to_string()),
+            textmode::Key::Backspace => env_editor.backspace(),
+            textmode::Key::Ctrl(b'p') | textmode::Key::Up => {
+                env_editor.select_prev();
+            }
+            textmode::Key::Ctrl(b'n') | textmode::Key::Down => {
+                env_editor.select_next(&self.env);
+            }
+            textmode::Key::Ctrl(b'm') => {
+                if env_editor.editing() {
+                    env_editor.finish_editing(&mut self.env);
+                } else {
+                    env_editor.start_editing(&self.env);
+                }
+            }
+            textmode::Key::Ctrl(b'd') => env_editor.delete(&mut self.env),
+            textmode::Key::Ctrl(b'c' | b'g') | textmode::Key::Escape => {
+                if env_editor.editing() {
+                    env_editor.cancel_editing();
+                } else {
+                    self.env_editor = None;
+                    self.scene = self.default_scene(self.focus);
+                }
+            }
+            _ => return None,
+        }
+        Some(Action::Refresh)
+    }
+
+    fn handle_key_paste(
+        &mut self,
+        key: &textmode::Key,
+        event_w: &crate::shell::event::Writer,
+    ) -> Option<Action> {
+        let target = self.paste.as_ref().unwrap().target();
+        let action = match key {
+            textmode::Key::Ctrl(b'm') | textmode::Key::Char('y') => {
+                let bytes = self.paste.take().unwrap().into_bytes();
+                self.send_paste(bytes, target, event_w)
+            }
+            textmode::Key::Char('j') => {
+                let joined = self.paste.take().unwrap().joined();
+                self.send_paste(joined.into_bytes(), target, event_w)
+            }
+            textmode::Key::Char('e') if target == paste::Target::Readline => {
+                let editable = self.paste.take().unwrap().editable();
+                self.readline.add_input(&editable);
+                None
+            }
+            textmode::Key::Char('q')
+            | textmode::Key::Escape
+            | textmode::Key::Ctrl(b'c') => {
+                self.paste = None;
+                None
+            }
+            _ => return None,
+        };
+        self.scene = self.default_scene(self.focus);
+        Some(
+            action.map_or(Action::Refresh, |action| {
+                action.merge(Action::Refresh)
+            }),
+        )
+    }
+
+    fn handle_paste(
+        &mut self,
+        bytes: Vec<u8>,
+        event_w: &crate::shell::event::Writer,
+    ) -> Option<Action> {
+        if self.read_only
+            || self.paste.is_some()
+            || self.diff.is_some()
+            || self.inspector.is_some()
+            || self.jump.is_some()
+            || self.palette.is_some()
+            || self.env_editor.is_some()
+            || self.env_diff.is_some()
+            || self.escape
+        {
+            return None;
+        }
+        let target = match self.focus {
+            Focus::Readline => paste::Target::Readline,
+            Focus::History(idx) => {
+                // programs which ask for bracketed paste themselves can
+                // decide what to do with it on their own
+                let entry = self.history.entry(idx);
+                if entry.bracketed_paste() {
+                    entry.input(
+                        [&b"\x1b[200~"[..], &bytes, &b"\x1b[201~"[..]]
+                            .concat(),
+                    );
+                    return None;
+                }
+                paste::Target::Entry(idx)
+            }
+            Focus::Scrolling(_) => return None,
+        };
+        if paste::needs_confirm(&bytes) {
+            self.paste = Some(paste::Paste::new(bytes, target));
+            self.scene = self.default_scene(self.focus);
+            return Some(Action::Refresh);
+        }
+        self.send_paste(bytes, target, event_w)
+    }
+
+    fn send_paste(
+        &mut self,
+        bytes: Vec<u8>,
+        target: paste::Target,
+        event_w: &crate::shell::event::Writer,
+    ) -> Option<Action> {
+        match target {
+            paste::Target::Readline => {
+                let mut action: Option<Action> = Some(Action::Refresh);
+                for key in paste::keys(&String::from_utf8_lossy(&bytes)) {
+                    let new = self.handle_key_readline(&key, event_w.clone());
+                    action = match (action, new) {
+                        (Some(action), Some(new)) => Some(action.merge(new)),
+                        (action, new) => action.or(new),
+                    };
+                }
+                action
+            }
+            paste::Target::Entry(idx) => {
+                self.history.entry(idx).input(bytes);
+                None
+            }
+        }
+    }
+
     fn handle_key_history(&mut self, key: textmode::Key, idx: usize) {
         self.history.entry(idx).input(key.into_bytes());
     }
 
+    // applies the .nbsh-env file for the current directory, if it changed
+    async fn update_dir_env(&mut self) {
+        let hint = match self.dir_env.update(&mut self.env).await {
+            Ok(None) => return,
+            Ok(Some(dir_env::Update::Loaded(file))) => crate::msg::get(
+                crate::msg::Message::DirEnvLoaded,
+                &[&crate::format::path(&file)],
+            ),
+            Ok(Some(dir_env::Update::Blocked(file))) => crate::msg::get(
+                crate::msg::Message::DirEnvBlocked,
+                &[&crate::format::path(&file)],
+            ),
+            Err(e) => crate::msg::get(
+                crate::msg::Message::DirEnvFailed,
+                &[&dir_env::FILE_NAME, &e],
+            ),
+        };
+        self.readline.set_hint(Some(hint));
+    }
+
     fn default_scene(&self, focus: Focus) -> Scene {
+        if self.paste.is_some() {
+            return Scene::Paste;
+        }
+        if self.jump.is_some() {
+            return Scene::Jump;
+        }
+        if self.palette.is_some() {
+            return Scene::Palette;
+        }
+        if self.env_editor.is_some() {
+            return Scene::EnvEditor;
+        }
+        if self.diff.is_some() {
+            return Scene::Diff;
+        }
+        if self.env_diff.is_some() {
+            return Scene::EnvDiff;
+        }
+        if self.inspector.is_some() {
+            return Scene::Inspector;
+        }
         match focus {
             Focus::Readline | Focus::Scrolling(_) => Scene::Readline,
+            Focus::History(idx)
+                if self
+                    .split
+                    .as_ref()
+                    .map_or(false, |split| split.contains(idx)) =>
+            {
+                Scene::Split
+            }
             Focus::History(idx) => {
                 if self.history.entry(idx).should_fullscreen() {
                     Scene::Fullscreen
@@ -414,20 +1992,91 @@ impl Shell {
     }
 
     fn set_focus(&mut self, new_focus: Focus) {
+        // focusing a suspended entry brings it back to the foreground, and
+        // there is no point in focusing an entry without seeing its output
+        if let Focus::History(idx) = new_focus {
+            let entry = self.history.entry_mut(idx);
+            entry.set_suspended(false);
+            entry.set_collapsed(false);
+        }
+        // the split only lasts as long as one of its entries is focused
+        if let Some(split) = self.split.take() {
+            if matches!(new_focus, Focus::History(idx) if split.contains(idx))
+            {
+                self.split = Some(split);
+            } else {
+                split.close(&self.history);
+            }
+        }
         self.focus = new_focus;
         self.hide_readline = false;
         self.scene = self.default_scene(new_focus);
         self.history.make_focus_visible(
-            self.readline.lines(),
+            self.reserved_lines(),
             self.focus_idx(),
             matches!(self.focus, Focus::Scrolling(_)),
         );
     }
 
+    // numbered from 1, with the persistent history first (matching the
+    // numbers shown by the history builtin) followed by this session
+    // the history builtin runs in a separate process, so it is given the
+    // same list that !N refers to, including the command about to be run as
+    // entry idx
+    fn write_history_list(&self, idx: usize, cmdline: &str) {
+        let mut cmds = self.old_history.cmds();
+        for i in 0..self.history.entry_count() {
+            let entry = self.history.entry(i);
+            if i == idx {
+                cmds.push(cmdline.to_string());
+            } else if entry.saved() {
+                cmds.push(entry.cmd().to_string());
+            }
+        }
+        if idx == self.history.entry_count() {
+            cmds.push(cmdline.to_string());
+        }
+        // the builtin falls back to the history file, so this isn't worth
+        // interrupting anything for
+        #[allow(clippy::let_underscore_drop)]
+        let _ = tmp::set_history(&cmds);
+    }
+
+    fn history_cmd(&self, n: usize) -> Option<String> {
+        let idx = n.checked_sub(1)?;
+        let old = self.old_history.entry_count();
+        if idx < old {
+            self.old_history.cmd(idx)
+        } else {
+            self.history
+                .saved_entries()
+                .nth(idx - old)
+                .map(|entry| entry.cmd().to_string())
+        }
+    }
+
+    // recorded on each entry, so that its results can be tied back to the
+    // code they came from
+    fn git_commit(&self) -> Option<String> {
+        let git = self.prompt_info.git.as_ref()?;
+        git.head().map(|head| {
+            if git.dirty() {
+                format!("{} (with uncommitted changes)", head)
+            } else {
+                head.to_string()
+            }
+        })
+    }
+
     fn env(&self) -> &Env {
         &self.env
     }
 
+    // the rows at the bottom of the screen which the history can't use
+    fn reserved_lines(&self) -> usize {
+        self.readline.lines() + usize::from(self.status_bar)
+    }
+
     fn focus_idx(&self) -> Option<usize> {
         match self.focus {
             Focus::History(idx) => Some(idx),
@@ -0,0 +1,104 @@
+use crate::shell::prelude::*;
+
+// how to let the user know that a long command finished while the terminal
+// didn't have focus
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyMethod {
+    // the escape sequence understood by iterm2, kitty, windows terminal, and
+    // others
+    Osc9,
+    // the escape sequence understood by urxvt, foot, and others, which has
+    // a separate title
+    Osc777,
+    // for terminals which don't support either of those
+    NotifySend,
+}
+
+impl Default for NotifyMethod {
+    fn default() -> Self {
+        Self::Osc9
+    }
+}
+
+pub struct Notifier {
+    after: std::time::Duration,
+    method: NotifyMethod,
+    // safe mode doesn't start anything in the background, but the escape
+    // sequences are just output
+    safe: bool,
+}
+
+impl Notifier {
+    pub fn new(
+        after: std::time::Duration,
+        method: NotifyMethod,
+        safe: bool,
+    ) -> Self {
+        Self {
+            after,
+            method,
+            safe,
+        }
+    }
+
+    // notifications are best effort, there's nowhere useful to report it if
+    // one can't be shown
+    pub fn finished(
+        &self,
+        cmd: &str,
+        status: std::process::ExitStatus,
+        duration: std::time::Duration,
+    ) {
+        if duration < self.after {
+            return;
+        }
+        let body = sanitize(&crate::msg::get(
+            crate::msg::Message::CommandFinished,
+            &[
+                &crate::format::command_line(cmd),
+                &crate::format::exit_status_description(status),
+                &crate::format::duration(duration),
+            ],
+        ));
+        #[allow(clippy::let_underscore_drop)]
+        let _ = match self.method {
+            NotifyMethod::Osc9 => write_osc(&format!("9;{}", body)),
+            NotifyMethod::Osc777 => {
+                write_osc(&format!("777;notify;nbsh;{}", body))
+            }
+            NotifyMethod::NotifySend if !self.safe => notify_send(&body),
+            NotifyMethod::NotifySend => Ok(()),
+        };
+    }
+}
+
+fn write_osc(osc: &str) -> std::io::Result<()> {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]{}\x07", osc)?;
+    stdout.flush()
+}
+
+// like open_url, this doesn't wait for it to finish
+fn notify_send(body: &str) -> std::io::Result<()> {
+    tokio::process::Command::new("notify-send")
+        .args(["nbsh", body])
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(drop)
+}
+
+// control characters would end the escape sequence early, and semicolons
+// separate its fields
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_control() || c == ';' { ' ' } else { c })
+        .collect()
+}
+
+#[test]
+fn test_sanitize() {
+    assert_eq!(sanitize("make; echo\x07done"), "make  echo done");
+}
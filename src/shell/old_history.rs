@@ -3,6 +3,7 @@ use crate::shell::prelude::*;
 use tokio::io::AsyncBufReadExt as _;
 
 use pest::Parser as _;
+use unicode_width::UnicodeWidthStr as _;
 
 #[derive(pest_derive::Parser)]
 #[grammar = "history.pest"]
@@ -23,6 +24,36 @@ impl History {
         self.entries.lock().unwrap().len()
     }
 
+    // idx is 0-based, unlike the numbers shown by the history builtin
+    pub fn cmd(&self, idx: usize) -> Option<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(idx)
+            .map(|entry| entry.cmd().to_string())
+    }
+
+    pub fn cmds(&self) -> Vec<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| entry.cmd().to_string())
+            .collect()
+    }
+
+    // previous runs of the given command line, oldest first
+    pub fn runs(&self, cmdline: &str) -> Vec<Run> {
+        let cmdline = normalize(cmdline);
+        self.entries
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|entry| normalize(&entry.cmdline) == cmdline)
+            .map(|entry| Run::new(entry.status, entry.duration))
+            .collect()
+    }
+
     async fn task(entries: std::sync::Arc<std::sync::Mutex<Vec<Entry>>>) {
         // TODO: we should actually read this in reverse order, because we
         // want to populate the most recent entries first
@@ -34,12 +65,19 @@ impl History {
             )
             .lines(),
         );
+        let mut continued = String::new();
         while let Some(line) = stream.next().await {
             let line = if let Ok(line) = line {
                 line
             } else {
                 continue;
             };
+            let line =
+                if let Some(line) = join_continued(&mut continued, line) {
+                    line
+                } else {
+                    continue;
+                };
             let entry = if let Ok(entry) = line.parse() {
                 entry
             } else {
@@ -50,10 +88,54 @@ impl History {
     }
 }
 
+// the commands in the history file, oldest first, for when there is no
+// shell around to have loaded it already
+pub fn read_cmds() -> std::io::Result<Vec<String>> {
+    let contents = match std::fs::read_to_string(crate::dirs::history_file())
+    {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(vec![]);
+        }
+        Err(e) => return Err(e),
+    };
+    let mut continued = String::new();
+    Ok(contents
+        .lines()
+        .filter_map(|line| join_continued(&mut continued, line.to_string()))
+        .filter_map(|line| line.parse::<Entry>().ok())
+        .map(|entry| entry.cmdline)
+        .collect())
+}
+
+// the history file is left in place (rather than removed) since it is
+// expected to exist when the shell appends to it on exit
+pub fn clear() -> std::io::Result<()> {
+    std::fs::File::create(crate::dirs::history_file()).map(drop)
+}
+
+// multi-line commands are saved with a backslash at the end of each line but
+// the last, like zsh does. this returns the full line once the last part of
+// it has been seen.
+fn join_continued(continued: &mut String, line: String) -> Option<String> {
+    if let Some(line) = line.strip_suffix('\\') {
+        continued.push_str(line);
+        continued.push('\n');
+        return None;
+    }
+    if continued.is_empty() {
+        Some(line)
+    } else {
+        continued.push_str(&line);
+        Some(std::mem::take(continued))
+    }
+}
+
 pub struct Entry {
     cmdline: String,
     start_time: Option<time::OffsetDateTime>,
     duration: Option<std::time::Duration>,
+    status: Option<i32>,
 }
 
 impl Entry {
@@ -61,6 +143,7 @@ impl Entry {
         &self,
         out: &mut impl textmode::Textmode,
         offset: time::UtcOffset,
+        colors: crate::shell::ColorScheme,
     ) {
         let size = out.screen().size();
         let mut time = "".to_string();
@@ -73,26 +156,35 @@ impl Entry {
 
         out.write_str("       $ ");
         let start = usize::from(out.screen().cursor_position().1);
-        let end = usize::from(size.1) - time.len() - 2;
-        let max_len = end - start;
-        let cmd = if self.cmdline.len() > max_len {
-            &self.cmdline[..(max_len - 4)]
+        let end = usize::from(size.1).saturating_sub(time.width() + 2);
+        let max_width = end.saturating_sub(start);
+        let cmdline = crate::format::command_line(&self.cmdline);
+        let truncated = cmdline.width() > max_width;
+        let cmd = if truncated {
+            crate::shell::readline::truncate(
+                &cmdline,
+                max_width.saturating_sub(4),
+            )
         } else {
-            &self.cmdline
+            &cmdline
         };
         out.write_str(cmd);
-        if self.cmdline.len() > max_len {
+        if truncated {
             out.write_str(" ");
             out.set_fgcolor(textmode::color::BLUE);
             out.write_str("...");
         }
         out.reset_attributes();
 
-        out.set_bgcolor(textmode::Color::Rgb(0x20, 0x20, 0x20));
+        out.set_bgcolor(colors.background());
         let cur_pos = out.screen().cursor_position();
-        out.write_str(&" ".repeat(
-            usize::from(size.1) - time.len() - 1 - usize::from(cur_pos.1),
-        ));
+        out.write_str(
+            &" ".repeat(
+                usize::from(size.1).saturating_sub(
+                    time.width() + 1 + usize::from(cur_pos.1),
+                ),
+            ),
+        );
         out.write_str(&time);
         out.write_str(" ");
         out.reset_attributes();
@@ -114,6 +206,7 @@ impl std::str::FromStr for Entry {
 
         let mut start_time = None;
         let mut duration = None;
+        let mut status = None;
         let mut cmdline = None;
         for part in line.into_inner() {
             match part.as_rule() {
@@ -140,6 +233,9 @@ impl std::str::FromStr for Entry {
                                 )),
                     ));
                 }
+                Rule::status => {
+                    status = Some(part.as_str().parse()?);
+                }
                 Rule::command => {
                     cmdline = Some(part.as_str().to_string());
                 }
@@ -152,10 +248,41 @@ impl std::str::FromStr for Entry {
             cmdline: cmdline.unwrap(),
             start_time,
             duration,
+            status,
         })
     }
 }
 
+// the exit status and duration of a single run of a command, for showing
+// trends across repeated runs
+pub struct Run {
+    status: Option<i32>,
+    duration: Option<std::time::Duration>,
+}
+
+impl Run {
+    pub fn new(
+        status: Option<i32>,
+        duration: Option<std::time::Duration>,
+    ) -> Self {
+        Self { status, duration }
+    }
+
+    pub fn success(&self) -> Option<bool> {
+        self.status.map(|status| status == 0)
+    }
+
+    pub fn duration(&self) -> Option<std::time::Duration> {
+        self.duration
+    }
+}
+
+// command lines which only differ in whitespace are considered the same
+// command
+pub fn normalize(cmdline: &str) -> String {
+    cmdline.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
 #[test]
 fn test_parse() {
     let entry: Entry =
@@ -177,9 +304,43 @@ fn test_parse() {
         entry.start_time,
         Some(time::macros::datetime!(2022-03-08 22:50:48).assume_utc())
     );
+    assert_eq!(entry.status, None);
+
+    let entry: Entry = ": 1646779848:1.5:130;make test".parse().unwrap();
+    assert_eq!(entry.cmdline, "make test");
+    assert_eq!(entry.duration, Some(std::time::Duration::from_millis(1500)));
+    assert_eq!(entry.status, Some(130));
 
     let entry: Entry = "vim ~/.zsh_history".parse().unwrap();
     assert_eq!(entry.cmdline, "vim ~/.zsh_history");
     assert_eq!(entry.duration, None);
     assert_eq!(entry.start_time, None);
 }
+
+#[test]
+fn test_join_continued() {
+    let mut continued = String::new();
+    assert_eq!(
+        join_continued(&mut continued, "ls".to_string()),
+        Some("ls".to_string())
+    );
+    assert_eq!(
+        join_continued(&mut continued, "for i in a\\".to_string()),
+        None
+    );
+    assert_eq!(
+        join_continued(&mut continued, "  echo $i\\".to_string()),
+        None
+    );
+    assert_eq!(
+        join_continued(&mut continued, "end".to_string()),
+        Some("for i in a\n  echo $i\nend".to_string())
+    );
+    assert!(continued.is_empty());
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize("ls"), "ls");
+    assert_eq!(normalize("  ls   -l\t/tmp "), "ls -l /tmp");
+}
@@ -0,0 +1,238 @@
+use crate::shell::prelude::*;
+
+use unicode_width::UnicodeWidthStr as _;
+
+// lists the things that can be done from escape mode by name, so that they
+// can be found without knowing which key they are bound to
+pub struct Palette {
+    commands: Vec<Command>,
+    query: String,
+    selected: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Rerun,
+    Kill,
+    ToggleFullscreen,
+    Inspect,
+    ExportHtml,
+    Jump,
+    EditEnv,
+    ToggleReadOnly,
+    NextColorScheme,
+}
+
+impl Command {
+    const ALL: &'static [Self] = &[
+        Self::Rerun,
+        Self::Kill,
+        Self::ToggleFullscreen,
+        Self::Inspect,
+        Self::ExportHtml,
+        Self::Jump,
+        Self::EditEnv,
+        Self::ToggleReadOnly,
+        Self::NextColorScheme,
+    ];
+
+    // the escape mode key which does the same thing. jumping is only bound
+    // in the readline, so it is handled separately.
+    pub fn escape_key(self) -> Option<textmode::Key> {
+        match self {
+            Self::Rerun => Some(textmode::Key::Ctrl(b'm')),
+            Self::Kill => Some(textmode::Key::Char('x')),
+            Self::ToggleFullscreen => Some(textmode::Key::Char('f')),
+            Self::Inspect => Some(textmode::Key::Char('I')),
+            Self::ExportHtml => Some(textmode::Key::Char('o')),
+            Self::Jump => None,
+            Self::EditEnv => Some(textmode::Key::Char('v')),
+            Self::ToggleReadOnly => Some(textmode::Key::Char('R')),
+            Self::NextColorScheme => Some(textmode::Key::Char('C')),
+        }
+    }
+
+    fn key_name(self) -> &'static str {
+        match self {
+            Self::Rerun => "^E Enter",
+            Self::Kill => "^E x",
+            Self::ToggleFullscreen => "^E f",
+            Self::Inspect => "^E I",
+            Self::ExportHtml => "^E o",
+            Self::Jump => "M-j",
+            Self::EditEnv => "^E v",
+            Self::ToggleReadOnly => "^E R",
+            Self::NextColorScheme => "^E C",
+        }
+    }
+
+    fn description(self) -> String {
+        crate::msg::get(
+            match self {
+                Self::Rerun => crate::msg::Message::PaletteRerun,
+                Self::Kill => crate::msg::Message::PaletteKill,
+                Self::ToggleFullscreen => {
+                    crate::msg::Message::PaletteFullscreen
+                }
+                Self::Inspect => crate::msg::Message::PaletteInspect,
+                Self::ExportHtml => crate::msg::Message::PaletteExport,
+                Self::Jump => crate::msg::Message::PaletteJump,
+                Self::EditEnv => crate::msg::Message::PaletteEnv,
+                Self::ToggleReadOnly => crate::msg::Message::PaletteReadOnly,
+                Self::NextColorScheme => crate::msg::Message::PaletteColors,
+            },
+            &[],
+        )
+    }
+
+    fn needs_entry(self) -> bool {
+        matches!(
+            self,
+            Self::Rerun | Self::Kill | Self::ToggleFullscreen | Self::Inspect
+        )
+    }
+}
+
+impl Palette {
+    // commands which act on an entry are left out if there isn't one
+    // focused
+    pub fn new(has_entry: bool) -> Self {
+        Self {
+            commands: Command::ALL
+                .iter()
+                .copied()
+                .filter(|command| has_entry || !command.needs_entry())
+                .collect(),
+            query: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn add_input(&mut self, s: &str) {
+        self.query.push_str(s);
+        self.selected = 0;
+    }
+
+    pub fn backspace(&mut self) {
+        self.query.pop();
+        self.selected = 0;
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.matches().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_prev(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn selected(&self) -> Option<Command> {
+        self.matches().into_iter().nth(self.selected)
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        colors: crate::shell::ColorScheme,
+    ) {
+        let size = out.screen().size();
+        let width = usize::from(size.1);
+        let matches = self.matches();
+
+        // like the jump list, the best match is drawn closest to the input
+        // line
+        let rows = usize::from(size.0).saturating_sub(1);
+        let start = (self.selected + 1).saturating_sub(rows);
+        for (i, command) in matches.iter().enumerate().skip(start).take(rows)
+        {
+            out.move_to((rows - 1 - (i - start)).try_into().unwrap(), 0);
+            out.reset_attributes();
+            let bgcolor = if i == self.selected {
+                colors.highlight()
+            } else {
+                colors.background()
+            };
+            out.set_bgcolor(bgcolor);
+            out.write(b"\x1b[K");
+            let description = command.description();
+            out.write_str(super::readline::truncate(&description, width));
+            let key = command.key_name();
+            if description.width() + key.len() + 2 <= width {
+                out.move_to(
+                    (rows - 1 - (i - start)).try_into().unwrap(),
+                    (width - key.len()).try_into().unwrap(),
+                );
+                out.set_fgcolor(textmode::color::DARKGREY);
+                out.write_str(key);
+            }
+        }
+
+        out.move_to(size.0 - 1, 0);
+        out.reset_attributes();
+        let prompt = crate::msg::get(crate::msg::Message::Palette, &[]);
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&prompt);
+        out.reset_attributes();
+        let query = super::readline::truncate(
+            &self.query,
+            width.saturating_sub(prompt.width()),
+        );
+        out.write_str(query);
+        out.move_to(
+            size.0 - 1,
+            (prompt.width() + query.width()).try_into().unwrap(),
+        );
+    }
+
+    fn matches(&self) -> Vec<Command> {
+        let mut matches: Vec<_> = self
+            .commands
+            .iter()
+            .filter_map(|command| {
+                fuzzy_match(&command.description(), &self.query)
+                    .map(|score| (*command, score))
+            })
+            .collect();
+        // stable, so ties stay in the order they are listed in
+        matches.sort_by_key(|(_, score)| *score);
+        matches.into_iter().map(|(command, _)| command).collect()
+    }
+}
+
+// the characters of the query have to appear in order, but not necessarily
+// next to each other. lower scores are better, based on how spread out the
+// matched characters are.
+fn fuzzy_match(s: &str, query: &str) -> Option<usize> {
+    let s = s.to_lowercase();
+    let query = query.to_lowercase();
+    let mut query = query.chars().filter(|c| *c != ' ');
+    let mut next = if let Some(c) = query.next() {
+        c
+    } else {
+        return Some(0);
+    };
+    let mut start = None;
+    for (i, c) in s.chars().enumerate() {
+        if c != next {
+            continue;
+        }
+        let start = *start.get_or_insert(i);
+        if let Some(c) = query.next() {
+            next = c;
+        } else {
+            return Some(i - start);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_fuzzy_match() {
+    assert_eq!(fuzzy_match("kill the focused entry", ""), Some(0));
+    assert_eq!(fuzzy_match("kill the focused entry", "kill"), Some(3));
+    assert_eq!(fuzzy_match("kill the focused entry", "KE"), Some(7));
+    assert_eq!(fuzzy_match("kill the focused entry", "k e"), Some(7));
+    assert_eq!(fuzzy_match("kill the focused entry", "ek"), None);
+}
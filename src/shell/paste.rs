@@ -0,0 +1,190 @@
+use crate::shell::prelude::*;
+
+use unicode_width::UnicodeWidthStr as _;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Readline,
+    Entry(usize),
+}
+
+// a bracketed paste which is waiting to be confirmed, since it contains
+// something (like a newline) that would do more than just insert text
+pub struct Paste {
+    bytes: Vec<u8>,
+    target: Target,
+}
+
+impl Paste {
+    pub fn new(bytes: Vec<u8>, target: Target) -> Self {
+        Self { bytes, target }
+    }
+
+    pub fn target(&self) -> Target {
+        self.target
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    // the pasted text as a single line, for when the newlines weren't
+    // meant to be there
+    pub fn joined(&self) -> String {
+        join(&String::from_utf8_lossy(&self.bytes))
+    }
+
+    // the pasted text with control characters removed but newlines kept,
+    // for editing as a multi-line command
+    pub fn editable(&self) -> String {
+        editable(&String::from_utf8_lossy(&self.bytes))
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        entry: Option<&super::history::Entry>,
+    ) {
+        let size = out.screen().size();
+        let text = String::from_utf8_lossy(&self.bytes).replace("\r\n", "\n");
+
+        out.move_to(0, 0);
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&entry.map_or_else(
+            || "paste into the command line?".to_string(),
+            |entry| {
+                format!(
+                    "paste into {}: {}?",
+                    entry.idx() + 1,
+                    crate::format::command_line(entry.cmd())
+                )
+            },
+        ));
+        out.reset_attributes();
+
+        let lines: Vec<_> = text.split_inclusive(['\r', '\n']).collect();
+        out.move_to(1, 0);
+        out.set_fgcolor(textmode::color::DARKGREY);
+        out.write_str(&format!(
+            "{} line{}, {} byte{}",
+            lines.len(),
+            if lines.len() == 1 { "" } else { "s" },
+            self.bytes.len(),
+            if self.bytes.len() == 1 { "" } else { "s" },
+        ));
+        out.reset_attributes();
+
+        let rows = size.0.saturating_sub(4);
+        for (row, line) in (3..).zip(lines.iter().take(usize::from(rows))) {
+            out.move_to(row, 0);
+            let mut width = 0;
+            for c in line.chars() {
+                // control characters are the whole reason for asking, so
+                // make them visible
+                let (s, control) = if c == '\r' || c == '\n' {
+                    ("⏎".to_string(), true)
+                } else if c.is_control() {
+                    (caret(c), true)
+                } else {
+                    (c.to_string(), false)
+                };
+                width += s.width();
+                if width > usize::from(size.1) {
+                    break;
+                }
+                if control {
+                    out.set_fgcolor(textmode::color::RED);
+                }
+                out.write_str(&s);
+                if control {
+                    out.reset_attributes();
+                }
+            }
+        }
+
+        out.move_to(size.0 - 1, 0);
+        out.set_fgcolor(textmode::color::DARKGREY);
+        let help = if self.target == Target::Readline {
+            "enter: paste  j: join lines  e: edit  q: cancel"
+        } else {
+            "enter: paste  j: join lines  q: cancel"
+        };
+        out.write_str(help);
+        out.reset_attributes();
+        out.hide_cursor(true);
+    }
+}
+
+// anything that could do something other than insert text, like run a
+// command or send a signal
+pub fn needs_confirm(bytes: &[u8]) -> bool {
+    String::from_utf8_lossy(bytes).chars().any(char::is_control)
+}
+
+// the keys that typing the pasted text would have produced, for feeding
+// through the readline
+pub fn keys(text: &str) -> Vec<textmode::Key> {
+    text.chars()
+        .map(|c| match c {
+            '\r' | '\n' => textmode::Key::Ctrl(b'm'),
+            '\x1b' => textmode::Key::Escape,
+            '\x7f' => textmode::Key::Backspace,
+            c if u32::from(c) < 0x20 => {
+                textmode::Key::Ctrl(u8::try_from(c).unwrap() | 0x60)
+            }
+            c => textmode::Key::Char(c),
+        })
+        .collect()
+}
+
+fn join(text: &str) -> String {
+    text.split(['\r', '\n'])
+        .map(strip_control)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn editable(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .trim_end_matches('\n')
+        .split('\n')
+        .map(strip_control)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn strip_control(line: &str) -> String {
+    line.chars().filter(|c| !c.is_control()).collect()
+}
+
+fn caret(c: char) -> String {
+    format!(
+        "^{}",
+        char::from(u8::try_from(c).map_or(b'?', |c| c ^ 0x40))
+    )
+}
+
+#[test]
+fn test_needs_confirm() {
+    assert!(!needs_confirm(b"ls -l"));
+    assert!(!needs_confirm("caf\u{e9}".as_bytes()));
+    assert!(needs_confirm(b"ls\r"));
+    assert!(needs_confirm(b"echo\x03"));
+}
+
+#[test]
+fn test_join() {
+    assert_eq!(join("ls\r\n-l\n\n\x1b[Afoo\n"), "ls -l [Afoo");
+    assert_eq!(editable("a\r\nb\rc\x07\n\n"), "a\nb\nc");
+    assert_eq!(caret('\x1b'), "^[");
+    assert_eq!(
+        keys("a\n\x03"),
+        vec![
+            textmode::Key::Char('a'),
+            textmode::Key::Ctrl(b'm'),
+            textmode::Key::Ctrl(b'c'),
+        ]
+    );
+}
@@ -0,0 +1,418 @@
+use crate::shell::prelude::*;
+
+use unicode_width::{UnicodeWidthChar as _, UnicodeWidthStr as _};
+
+// these reproduce the original hardcoded status line
+const DEFAULT_LEFT: &str = "{index:yellow} ({cwd}{?|{git}})";
+const DEFAULT_RIGHT: &str = "{user}@{host} [{time}]";
+
+// the status line above the input is built from a template on each side.
+// templates are literal text with components like {cwd} substituted in.
+// components can be followed by options, like {cwd:blue:bold:30}, where a
+// number is the maximum width to truncate to. {?...} is a group, which is
+// left out entirely if none of the components in it have a value (so that
+// "{?|{git}}" only shows the separator inside of git repositories). {{ and
+// }} are literal braces. {git} colors its own pieces (ahead and behind
+// counts, stashes, and any operation in progress) unless it is given a
+// color or width.
+pub struct Prompt {
+    left: Vec<Node>,
+    right: Vec<Node>,
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Self::new(None, None).unwrap()
+    }
+}
+
+impl Prompt {
+    pub fn new(left: Option<&str>, right: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            left: parse(left.unwrap_or(DEFAULT_LEFT))?,
+            right: parse(right.unwrap_or(DEFAULT_RIGHT))?,
+        })
+    }
+
+    pub fn left(&self, values: &Values) -> Vec<Span> {
+        render(&self.left, values)
+    }
+
+    pub fn right(&self, values: &Values) -> Vec<Span> {
+        render(&self.right, values)
+    }
+}
+
+// the current value of each component, empty if it doesn't apply
+#[derive(Default)]
+pub struct Values {
+    pub index: String,
+    pub cwd: String,
+    pub git: String,
+    // the same as git, but split up so that the pieces can be colored
+    pub git_parts: Vec<Span>,
+    pub branch: String,
+    pub dirty: String,
+    pub status: String,
+    pub kube: String,
+    pub venv: String,
+    pub time: String,
+    pub user: String,
+    pub host: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub text: String,
+    pub color: Option<textmode::Color>,
+    pub bold: bool,
+}
+
+pub fn width(spans: &[Span]) -> usize {
+    spans.iter().map(|span| span.text.width()).sum()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Index,
+    Cwd,
+    Git,
+    Branch,
+    Dirty,
+    Status,
+    Kube,
+    Venv,
+    Time,
+    User,
+    Host,
+}
+
+impl Kind {
+    fn value(self, values: &Values) -> &str {
+        match self {
+            Self::Index => &values.index,
+            Self::Cwd => &values.cwd,
+            Self::Git => &values.git,
+            Self::Branch => &values.branch,
+            Self::Dirty => &values.dirty,
+            Self::Status => &values.status,
+            Self::Kube => &values.kube,
+            Self::Venv => &values.venv,
+            Self::Time => &values.time,
+            Self::User => &values.user,
+            Self::Host => &values.host,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Component {
+    kind: Kind,
+    color: Option<textmode::Color>,
+    bold: bool,
+    max_width: Option<usize>,
+}
+
+impl std::str::FromStr for Component {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let kind = match parts.next().unwrap() {
+            "index" => Kind::Index,
+            "cwd" => Kind::Cwd,
+            "git" => Kind::Git,
+            "branch" => Kind::Branch,
+            "dirty" => Kind::Dirty,
+            "status" => Kind::Status,
+            "kube" => Kind::Kube,
+            "venv" => Kind::Venv,
+            "time" => Kind::Time,
+            "user" => Kind::User,
+            "host" => Kind::Host,
+            name => return Err(anyhow!("unknown prompt component {}", name)),
+        };
+        let mut component = Self {
+            kind,
+            color: None,
+            bold: false,
+            max_width: None,
+        };
+        for option in parts {
+            match option {
+                "bold" => component.bold = true,
+                "black" => component.color = Some(textmode::color::BLACK),
+                "red" => component.color = Some(textmode::color::RED),
+                "green" => component.color = Some(textmode::color::GREEN),
+                "yellow" => component.color = Some(textmode::color::YELLOW),
+                "blue" => component.color = Some(textmode::color::BLUE),
+                "magenta" => {
+                    component.color = Some(textmode::color::MAGENTA);
+                }
+                "cyan" => component.color = Some(textmode::color::CYAN),
+                "white" => component.color = Some(textmode::color::WHITE),
+                "grey" => component.color = Some(textmode::color::DARKGREY),
+                option => {
+                    component.max_width =
+                        Some(option.parse().map_err(|_| {
+                            anyhow!("unknown prompt option {}", option)
+                        })?);
+                }
+            }
+        }
+        Ok(component)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Component(Component),
+    Group(Vec<Node>),
+}
+
+fn parse(template: &str) -> Result<Vec<Node>> {
+    parse_nodes(&mut template.chars().peekable(), false)
+}
+
+fn parse_nodes(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    in_group: bool,
+) -> Result<Vec<Node>> {
+    let mut nodes = vec![];
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '{' => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(std::mem::take(&mut text)));
+                }
+                if chars.peek() == Some(&'?') {
+                    chars.next();
+                    nodes.push(Node::Group(parse_nodes(chars, true)?));
+                } else {
+                    let mut spec = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => spec.push(c),
+                            None => {
+                                return Err(anyhow!(
+                                    "unterminated prompt component"
+                                ))
+                            }
+                        }
+                    }
+                    nodes.push(Node::Component(spec.parse()?));
+                }
+            }
+            '}' if in_group => {
+                if !text.is_empty() {
+                    nodes.push(Node::Text(text));
+                }
+                return Ok(nodes);
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err(anyhow!("unmatched }} in prompt")),
+            c => text.push(c),
+        }
+    }
+    if in_group {
+        return Err(anyhow!("unterminated prompt group"));
+    }
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    Ok(nodes)
+}
+
+fn render(nodes: &[Node], values: &Values) -> Vec<Span> {
+    let mut spans = vec![];
+    for node in nodes {
+        match node {
+            Node::Text(text) => spans.push(Span {
+                text: text.clone(),
+                color: None,
+                bold: false,
+            }),
+            Node::Component(component)
+                if component.kind == Kind::Git
+                    && component.color.is_none()
+                    && component.max_width.is_none()
+                    && !values.git_parts.is_empty() =>
+            {
+                spans.extend(values.git_parts.iter().map(|span| Span {
+                    bold: span.bold || component.bold,
+                    ..span.clone()
+                }));
+            }
+            Node::Component(component) => {
+                let value = component.kind.value(values);
+                let text = component.max_width.map_or_else(
+                    || value.to_string(),
+                    |max| truncate(value, max, component.kind == Kind::Cwd),
+                );
+                spans.push(Span {
+                    text,
+                    color: component.color,
+                    bold: component.bold,
+                });
+            }
+            Node::Group(nodes) => {
+                if has_value(nodes, values) {
+                    spans.extend(render(nodes, values));
+                }
+            }
+        }
+    }
+    spans
+}
+
+// groups without any components in them are always shown
+fn has_value(nodes: &[Node], values: &Values) -> bool {
+    let mut components = 0;
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Component(component) => {
+                if !component.kind.value(values).is_empty() {
+                    return true;
+                }
+                components += 1;
+            }
+            Node::Group(nodes) => {
+                if has_value(nodes, values) {
+                    return true;
+                }
+                components += 1;
+            }
+        }
+    }
+    components == 0
+}
+
+// paths are more useful with the end kept, since that is where you are
+fn truncate(s: &str, max: usize, keep_end: bool) -> String {
+    if s.width() <= max {
+        return s.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut width = 1;
+    let mut chars = vec![];
+    let mut add = |c: char| {
+        let c_width = c.width().unwrap_or(0);
+        if width + c_width > max {
+            return false;
+        }
+        width += c_width;
+        chars.push(c);
+        true
+    };
+    if keep_end {
+        for c in s.chars().rev() {
+            if !add(c) {
+                break;
+            }
+        }
+        chars.push('…');
+        chars.into_iter().rev().collect()
+    } else {
+        for c in s.chars() {
+            if !add(c) {
+                break;
+            }
+        }
+        chars.push('…');
+        chars.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+fn text(spans: &[Span]) -> String {
+    spans.iter().map(|span| span.text.as_str()).collect()
+}
+
+#[test]
+fn test_default() {
+    let prompt = Prompt::default();
+    let mut values = Values {
+        index: "3".to_string(),
+        cwd: "~/src".to_string(),
+        time: "12:00:00".to_string(),
+        user: "doy".to_string(),
+        host: "host".to_string(),
+        ..Values::default()
+    };
+    assert_eq!(text(&prompt.left(&values)), "3 (~/src)");
+    assert_eq!(text(&prompt.right(&values)), "doy@host [12:00:00]");
+    values.git = "g:main".to_string();
+    assert_eq!(text(&prompt.left(&values)), "3 (~/src|g:main)");
+    values.git = "g:main:+2{1}".to_string();
+    values.git_parts = vec![
+        Span {
+            text: "g:main:".to_string(),
+            color: None,
+            bold: false,
+        },
+        Span {
+            text: "+2".to_string(),
+            color: Some(textmode::color::GREEN),
+            bold: false,
+        },
+        Span {
+            text: "{1}".to_string(),
+            color: Some(textmode::color::CYAN),
+            bold: false,
+        },
+    ];
+    assert_eq!(text(&prompt.left(&values)), "3 (~/src|g:main:+2{1})");
+    assert_eq!(prompt.left(&values)[4].color, Some(textmode::color::GREEN));
+    let plain = Prompt::new(Some("{git:white}"), None).unwrap();
+    assert_eq!(plain.left(&values).len(), 1);
+    assert_eq!(
+        prompt.left(&values)[0],
+        Span {
+            text: "3".to_string(),
+            color: Some(textmode::color::YELLOW),
+            bold: false,
+        }
+    );
+}
+
+#[test]
+fn test_parse() {
+    let prompt =
+        Prompt::new(Some("{{{cwd:6}}} {?x {status:red:bold}}"), None)
+            .unwrap();
+    let mut values = Values {
+        cwd: "~/src/nbsh".to_string(),
+        ..Values::default()
+    };
+    assert_eq!(text(&prompt.left(&values)), "{…/nbsh} ");
+    values.status = "1".to_string();
+    assert_eq!(text(&prompt.left(&values)), "{…/nbsh} x 1");
+
+    assert!(Prompt::new(Some("{kube:cyan} {venv}"), None).is_ok());
+    assert!(Prompt::new(Some("{nope}"), None).is_err());
+    assert!(Prompt::new(Some("{cwd:chartreuse}"), None).is_err());
+    assert!(Prompt::new(Some("{?{cwd}"), None).is_err());
+    assert!(Prompt::new(Some("{cwd"), None).is_err());
+    assert!(Prompt::new(Some("}"), None).is_err());
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("abcdef", 10, false), "abcdef");
+    assert_eq!(truncate("abcdef", 4, false), "abc…");
+    assert_eq!(truncate("abcdef", 4, true), "…def");
+    assert_eq!(truncate("abcdef", 0, true), "");
+}
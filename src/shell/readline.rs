@@ -1,21 +1,49 @@
 use crate::shell::prelude::*;
 
+use unicode_segmentation::UnicodeSegmentation as _;
 use unicode_width::{UnicodeWidthChar as _, UnicodeWidthStr as _};
 
+// the maximum number of search matches to show at once
+const SEARCH_LINES: usize = 5;
+
+// the maximum number of input lines to show at once, so that multi-line
+// input doesn't push the history entirely off of the screen
+const INPUT_LINES: usize = 5;
+
+// the number of killed pieces of text to remember
+const KILL_RING_SIZE: usize = 16;
+
 pub struct Readline {
     size: (u16, u16),
     input_line: String,
     scroll: usize,
     pos: usize,
+    search: Option<Search>,
+    completions: Option<CompletionMenu>,
+    placeholder: String,
+    prompt: super::prompt::Prompt,
+    hint: Option<String>,
+    // the entry that the input was copied from, which alt-enter will rerun
+    // in place
+    editing: Option<usize>,
+    // text removed by the kill commands, most recent last
+    kill_ring: Vec<String>,
 }
 
 impl Readline {
     pub fn new() -> Self {
         Self {
-            size: (24, 80),
+            size: crate::info::default_size(),
             input_line: "".into(),
             scroll: 0,
             pos: 0,
+            search: None,
+            completions: None,
+            placeholder: "".into(),
+            prompt: super::prompt::Prompt::default(),
+            hint: None,
+            editing: None,
+            kill_ring: vec![],
         }
     }
 
@@ -23,74 +51,292 @@ impl Readline {
         &self,
         out: &mut impl textmode::Textmode,
         env: &Env,
-        git: Option<&super::inputs::GitInfo>,
+        info: &super::inputs::PromptInfo,
         focus: bool,
         offset: time::UtcOffset,
     ) -> Result<()> {
-        let pwd = env.pwd();
-        let user = crate::info::user()?;
-        let hostname = crate::info::hostname()?;
-        let time = crate::info::time(offset)?;
         let prompt_char = crate::info::prompt_char()?;
-
-        let id = format!("{}@{}", user, hostname);
-        let idlen: u16 = id.len().try_into().unwrap();
-        let timelen: u16 = time.len().try_into().unwrap();
-
-        out.move_to(self.size.0 - 2, 0);
-        if focus {
-            out.set_bgcolor(textmode::Color::Rgb(0x56, 0x1b, 0x8b));
+        let status = env.latest_status();
+        let git = info.git.as_ref();
+        let values = super::prompt::Values {
+            index: (env.idx() + 1).to_string(),
+            cwd: crate::format::path(env.pwd()),
+            git: git.map(ToString::to_string).unwrap_or_default(),
+            git_parts: git.map_or_else(Vec::new, |git| {
+                git.parts()
+                    .into_iter()
+                    .map(|(part, text)| super::prompt::Span {
+                        text,
+                        color: match part {
+                            super::inputs::GitPart::Status => None,
+                            super::inputs::GitPart::Ahead => {
+                                Some(textmode::color::GREEN)
+                            }
+                            super::inputs::GitPart::Behind => {
+                                Some(textmode::color::RED)
+                            }
+                            super::inputs::GitPart::Stash => {
+                                Some(textmode::color::CYAN)
+                            }
+                            super::inputs::GitPart::Operation => {
+                                Some(textmode::color::MAGENTA)
+                            }
+                        },
+                        bold: false,
+                    })
+                    .collect()
+            }),
+            branch: git
+                .and_then(super::inputs::GitInfo::branch)
+                .unwrap_or_default()
+                .to_string(),
+            dirty: if git.map_or(false, super::inputs::GitInfo::dirty) {
+                "*".to_string()
+            } else {
+                String::new()
+            },
+            status: if status.success() {
+                String::new()
+            } else {
+                crate::format::exit_status(status).trim().to_string()
+            },
+            time: crate::info::time(offset)?,
+            kube: info.kube.clone().unwrap_or_default(),
+            venv: info.venv.clone().unwrap_or_default(),
+            user: crate::info::user()?,
+            host: info.host.clone().unwrap_or_default(),
+        };
+        let colors = env.color_scheme();
+        let bgcolor = if focus {
+            colors.highlight()
         } else if env.idx() % 2 == 0 {
-            out.set_bgcolor(textmode::Color::Rgb(0x24, 0x21, 0x00));
+            colors.alternate()
         } else {
-            out.set_bgcolor(textmode::Color::Rgb(0x20, 0x20, 0x20));
-        }
+            colors.background()
+        };
+
+        let status_row = self
+            .size
+            .0
+            .saturating_sub(u16::try_from(self.lines()).unwrap());
+        out.move_to(status_row, 0);
+        out.set_bgcolor(bgcolor);
         out.write(b"\x1b[K");
-        out.set_fgcolor(textmode::color::YELLOW);
-        out.write_str(&format!("{}", env.idx() + 1));
-        out.reset_attributes();
+        let left = self.prompt.left(&values);
+        render_spans(out, &left, bgcolor);
+        let mut left_width = super::prompt::width(&left);
+        if let Some(idx) = self.editing {
+            let editing = format!(
+                " {}",
+                crate::msg::get(crate::msg::Message::Reruns, &[&(idx + 1)])
+            );
+            out.write_str(&editing);
+            left_width += editing.width();
+        }
+        let right = self.prompt.right(&values);
+        let right_width = super::prompt::width(&right);
+        // the left side is more important, so the right side is dropped if
+        // there isn't room for both
+        if left_width + right_width + 2 <= usize::from(self.size.1) {
+            out.move_to(
+                status_row,
+                self.size.1 - u16::try_from(right_width).unwrap() - 1,
+            );
+            render_spans(out, &right, bgcolor);
+        }
+
+        if let Some(search) = &self.search {
+            self.render_search(out, search, colors);
+            if focus {
+                out.hide_cursor(false);
+            }
+            return Ok(());
+        }
+
+        if let Some(completions) = &self.completions {
+            // laid out as if the status line was second from the bottom
+            Self::render_completions(
+                out,
+                completions,
+                (status_row + 2, self.size.1),
+                colors,
+            );
+        }
+
+        let rows = self.lines() - 1;
+        let (cursor_line, _) = self.cursor_line();
+        // keep the line with the cursor visible
+        let first = (cursor_line + 1).saturating_sub(rows);
+        let width = usize::from(self.size.1).saturating_sub(2);
+        for (i, line) in self
+            .input_line
+            .split('\n')
+            .enumerate()
+            .skip(first)
+            .take(rows)
+        {
+            out.move_to(
+                status_row + 1 + u16::try_from(i - first).unwrap(),
+                0,
+            );
+            out.reset_attributes();
+            if i == 0 {
+                out.write_str(&prompt_char);
+            } else {
+                out.set_fgcolor(textmode::color::DARKGREY);
+                out.write_str(">");
+                out.reset_attributes();
+            }
+            out.write_str(" ");
+            out.write(b"\x1b[K");
+            if self.input_line.is_empty() {
+                // a hint about what just happened is more useful than the
+                // generic placeholder
+                let text = self.hint.as_deref().unwrap_or(&self.placeholder);
+                out.set_fgcolor(textmode::color::DARKGREY);
+                out.write_str(truncate(text, width.saturating_sub(1)));
+            } else if i == cursor_line {
+                out.write_str(self.visible_input());
+            } else {
+                out.write_str(truncate(line, width));
+            }
+            out.reset_attributes();
+        }
+        out.move_to(
+            status_row + 1 + u16::try_from(cursor_line - first).unwrap(),
+            2 + self.pos_width(),
+        );
         if focus {
-            out.set_bgcolor(textmode::Color::Rgb(0x56, 0x1b, 0x8b));
-        } else if env.idx() % 2 == 0 {
-            out.set_bgcolor(textmode::Color::Rgb(0x24, 0x21, 0x00));
-        } else {
-            out.set_bgcolor(textmode::Color::Rgb(0x20, 0x20, 0x20));
+            out.hide_cursor(false);
         }
-        out.write_str(" (");
-        out.write_str(&crate::format::path(pwd));
-        if let Some(info) = git {
-            out.write_str(&format!("|{}", info));
+        Ok(())
+    }
+
+    // there is no room below the prompt, so the candidates are laid out in
+    // columns above it instead
+    fn render_completions(
+        out: &mut impl textmode::Textmode,
+        completions: &CompletionMenu,
+        size: (u16, u16),
+        colors: crate::shell::ColorScheme,
+    ) {
+        let candidates = completions.completions.candidates();
+        let col_width = candidates
+            .iter()
+            .map(|candidate| candidate.width())
+            .max()
+            .unwrap_or(0)
+            + 2;
+        let cols = (usize::from(size.1) / col_width).max(1);
+        let rows = ((candidates.len() + cols - 1) / cols)
+            .min(usize::from(size.0).saturating_sub(2));
+        let first_row = usize::from(size.0) - 2 - rows;
+        for row in 0..rows {
+            out.move_to((first_row + row).try_into().unwrap(), 0);
+            out.reset_attributes();
+            out.set_bgcolor(colors.background());
+            out.write(b"\x1b[K");
+            for col in 0..cols {
+                let i = row * cols + col;
+                let candidate = if let Some(candidate) = candidates.get(i) {
+                    candidate
+                } else {
+                    break;
+                };
+                out.move_to(
+                    (first_row + row).try_into().unwrap(),
+                    (col * col_width).try_into().unwrap(),
+                );
+                if completions.selected == Some(i) {
+                    out.set_bgcolor(colors.highlight());
+                }
+                out.write_str(truncate(candidate, usize::from(size.1)));
+                out.set_bgcolor(colors.background());
+            }
+        }
+        out.reset_attributes();
+    }
+
+    fn render_search(
+        &self,
+        out: &mut impl textmode::Textmode,
+        search: &Search,
+        colors: crate::shell::ColorScheme,
+    ) {
+        let width = usize::from(self.size.1);
+        let matches = search.matches();
+        let start = search.selected.saturating_sub(SEARCH_LINES - 1);
+        let visible: Vec<_> = matches
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(SEARCH_LINES)
+            .collect();
+        // the matches are drawn above the prompt, with the most recent one
+        // closest to the input line
+        let mut row = usize::from(self.size.0)
+            .saturating_sub(2)
+            .saturating_sub(visible.len());
+        for (i, cmd) in visible.into_iter().rev() {
+            out.move_to(row.try_into().unwrap(), 0);
+            out.reset_attributes();
+            if i == search.selected {
+                out.set_bgcolor(colors.highlight());
+            } else {
+                out.set_bgcolor(colors.background());
+            }
+            out.write(b"\x1b[K");
+            out.write_str(truncate(cmd, width));
+            row += 1;
         }
-        out.write_str(")");
-        out.move_to(self.size.0 - 2, self.size.1 - 4 - idlen - timelen);
-        out.write_str(&id);
-        out.write_str(" [");
-        out.write_str(&time);
-        out.write_str("]");
 
         out.move_to(self.size.0 - 1, 0);
         out.reset_attributes();
-        out.write_str(&prompt_char);
-        out.write_str(" ");
-        out.reset_attributes();
         out.write(b"\x1b[K");
-        out.write_str(self.visible_input());
+        let prompt = crate::msg::get(
+            if matches.is_empty() {
+                crate::msg::Message::FailedSearch
+            } else {
+                crate::msg::Message::Search
+            },
+            &[],
+        );
+        out.set_fgcolor(textmode::color::YELLOW);
+        out.write_str(&prompt);
         out.reset_attributes();
-        out.move_to(self.size.0 - 1, 2 + self.pos_width());
-        if focus {
-            out.hide_cursor(false);
-        }
-        Ok(())
+        let query =
+            truncate(&search.query, width.saturating_sub(prompt.width()));
+        out.write_str(query);
+        out.move_to(
+            self.size.0 - 1,
+            (prompt.width() + query.width()).try_into().unwrap(),
+        );
     }
 
     pub fn resize(&mut self, size: (u16, u16)) {
         self.size = size;
     }
 
-    // self will be used eventually
-    #[allow(clippy::unused_self)]
+    // the status line plus the input lines. long lines scroll
+    // horizontally rather than wrapping.
     pub fn lines(&self) -> usize {
-        2 // XXX handle wrapping
+        if self.search.is_some() {
+            2
+        } else {
+            1 + (self.input_line.matches('\n').count() + 1).min(INPUT_LINES)
+        }
+    }
+
+    pub fn set_placeholder(&mut self, placeholder: &str) {
+        self.placeholder = placeholder.to_string();
+    }
+
+    pub fn set_prompt(&mut self, prompt: super::prompt::Prompt) {
+        self.prompt = prompt;
+    }
+
+    pub fn set_hint(&mut self, hint: Option<String>) {
+        self.hint = hint;
     }
 
     pub fn input(&self) -> &str {
@@ -107,117 +353,579 @@ impl Readline {
         self.input_line = s;
     }
 
+    // removes the whole grapheme cluster before the cursor, so that a base
+    // character goes away along with any combining characters on it
     pub fn backspace(&mut self) {
-        while self.pos > 0 {
-            self.dec_pos(1);
-            let width =
-                self.input_line.remove(self.byte_pos()).width().unwrap_or(0);
-            if width > 0 {
-                break;
-            }
-        }
+        let start = self.prev_grapheme();
+        self.input_line
+            .replace_range(self.char_to_byte(start)..self.byte_pos(), "");
+        self.set_pos(start);
     }
 
     pub fn clear_input(&mut self) {
         self.input_line.clear();
         self.set_pos(0);
+        self.editing = None;
+    }
+
+    pub fn editing(&self) -> Option<usize> {
+        self.editing
+    }
+
+    pub fn set_editing(&mut self, idx: Option<usize>) {
+        self.editing = idx;
+    }
+
+    // enter starts a new line rather than running the input after a
+    // trailing backslash (which is removed), inside a quote, or inside a
+    // block which hasn't been ended yet. returns whether it did.
+    pub fn continue_line(&mut self) -> bool {
+        if self.input_line.ends_with('\\')
+            && !self.input_line.ends_with("\\\\")
+        {
+            self.input_line.pop();
+        } else if !incomplete(&self.input_line) {
+            return false;
+        }
+        self.set_pos(self.input_line.chars().count());
+        self.add_input("\n");
+        true
+    }
+
+    // returns false if the cursor is already on the first line
+    pub fn cursor_up(&mut self) -> bool {
+        let (line, start) = self.cursor_line();
+        if line == 0 {
+            return false;
+        }
+        let col = self.pos - start;
+        let prev = self.input_line.split('\n').nth(line - 1).unwrap();
+        let prev_len = prev.chars().count();
+        self.set_pos(start - prev_len - 1 + col.min(prev_len));
+        true
+    }
+
+    // returns false if the cursor is already on the last line
+    pub fn cursor_down(&mut self) -> bool {
+        let (line, start) = self.cursor_line();
+        let mut lines = self.input_line.split('\n').skip(line);
+        let cur_len = lines.next().unwrap().chars().count();
+        let next_len = if let Some(next) = lines.next() {
+            next.chars().count()
+        } else {
+            return false;
+        };
+        let col = self.pos - start;
+        self.set_pos(start + cur_len + 1 + col.min(next_len));
+        true
     }
 
     pub fn clear_backwards(&mut self) {
-        self.input_line = self.input_line.chars().skip(self.pos).collect();
-        self.set_pos(0);
+        self.kill(0, self.pos);
+    }
+
+    // removes the whitespace-separated word before the cursor, like
+    // ctrl-w in bash
+    pub fn kill_word_backwards(&mut self) {
+        let chars: Vec<char> = self.input_line.chars().collect();
+        let mut start = self.pos;
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.kill(start, self.pos);
+    }
+
+    // at the end of a line, this joins it with the next line instead
+    pub fn kill_to_end(&mut self) {
+        let mut end =
+            self.cursor_line().1 + self.current_line().chars().count();
+        if end == self.pos && end < self.input_line.chars().count() {
+            end += 1;
+        }
+        self.kill(self.pos, end);
+    }
+
+    pub fn yank(&mut self) {
+        if let Some(text) = self.kill_ring.last().cloned() {
+            self.add_input(&text);
+        }
+    }
+
+    pub fn cursor_home(&mut self) {
+        self.set_pos(self.cursor_line().1);
+    }
+
+    pub fn cursor_end(&mut self) {
+        self.set_pos(
+            self.cursor_line().1 + self.current_line().chars().count(),
+        );
+    }
+
+    pub fn word_left(&mut self) {
+        let chars: Vec<char> = self.input_line.chars().collect();
+        let mut pos = self.pos;
+        while pos > 0 && !chars[pos - 1].is_alphanumeric() {
+            pos -= 1;
+        }
+        while pos > 0 && chars[pos - 1].is_alphanumeric() {
+            pos -= 1;
+        }
+        self.set_pos(pos);
+    }
+
+    pub fn word_right(&mut self) {
+        let chars: Vec<char> = self.input_line.chars().collect();
+        let mut pos = self.pos;
+        while pos < chars.len() && !chars[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        while pos < chars.len() && chars[pos].is_alphanumeric() {
+            pos += 1;
+        }
+        self.set_pos(pos);
     }
 
     pub fn cursor_left(&mut self) {
-        if self.pos == 0 {
-            return;
+        self.set_pos(self.prev_grapheme());
+    }
+
+    pub fn cursor_right(&mut self) {
+        self.set_pos(self.next_grapheme());
+    }
+
+    pub fn input_before_cursor(&self) -> &str {
+        &self.input_line[..self.byte_pos()]
+    }
+
+    pub fn completing(&self) -> bool {
+        self.completions.is_some()
+    }
+
+    // fills in as much of the word as all candidates agree on. if that
+    // doesn't make any progress, the candidates are shown instead, and
+    // further completions cycle through them.
+    pub fn complete(&mut self, completions: super::completion::Completions) {
+        let start = completions.start();
+        match completions.candidates() {
+            [] => {}
+            [candidate] => {
+                let mut candidate = candidate.clone();
+                if !candidate.ends_with('/') {
+                    candidate.push(' ');
+                }
+                self.replace_word(start, &candidate);
+            }
+            _ => {
+                let prefix = completions.common_prefix().to_string();
+                if prefix.len() > self.byte_pos() - start {
+                    self.replace_word(start, &prefix);
+                } else {
+                    self.completions = Some(CompletionMenu {
+                        completions,
+                        selected: None,
+                    });
+                }
+            }
         }
-        self.dec_pos(1);
-        while let Some(c) = self.input_line.chars().nth(self.pos) {
-            if c.width().unwrap_or(0) == 0 {
-                self.dec_pos(1);
+    }
+
+    pub fn next_completion(&mut self) {
+        let (start, candidate) =
+            if let Some(completions) = &mut self.completions {
+                let count = completions.completions.candidates().len();
+                let selected =
+                    completions.selected.map_or(0, |i| (i + 1) % count);
+                completions.selected = Some(selected);
+                (
+                    completions.completions.start(),
+                    completions.completions.candidates()[selected].clone(),
+                )
             } else {
-                break;
+                return;
+            };
+        self.replace_word(start, &candidate);
+    }
+
+    pub fn clear_completions(&mut self) {
+        self.completions = None;
+    }
+
+    pub fn searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    // candidates should be ordered from most to least recent
+    pub fn start_search(&mut self, candidates: Vec<String>) {
+        let mut seen = std::collections::HashSet::new();
+        let candidates = candidates
+            .into_iter()
+            .filter(|cmd| seen.insert(cmd.clone()))
+            .collect();
+        self.search = Some(Search {
+            query: self.input_line.clone(),
+            candidates,
+            selected: 0,
+        });
+    }
+
+    pub fn search_add_input(&mut self, s: &str) {
+        if let Some(search) = &mut self.search {
+            search.query.push_str(s);
+            search.selected = 0;
+        }
+    }
+
+    pub fn search_backspace(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.query.pop();
+            search.selected = 0;
+        }
+    }
+
+    pub fn search_older(&mut self) {
+        if let Some(search) = &mut self.search {
+            if search.selected + 1 < search.matches().len() {
+                search.selected += 1;
             }
         }
     }
 
-    pub fn cursor_right(&mut self) {
-        if self.pos == self.input_line.chars().count() {
-            return;
+    pub fn search_newer(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.selected = search.selected.saturating_sub(1);
         }
-        self.inc_pos(1);
-        while let Some(c) = self.input_line.chars().nth(self.pos) {
-            if c.width().unwrap_or(0) == 0 {
-                self.inc_pos(1);
-            } else {
-                break;
+    }
+
+    pub fn accept_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            if let Some(cmd) = search.matches().get(search.selected) {
+                self.set_input((*cmd).to_string());
             }
         }
     }
 
+    pub fn cancel_search(&mut self) {
+        self.search = None;
+    }
+
+    // removes the text between two char positions and saves it in the kill
+    // ring
+    fn kill(&mut self, start: usize, end: usize) {
+        if start == end {
+            return;
+        }
+        let range = self.char_to_byte(start)..self.char_to_byte(end);
+        let killed = self.input_line[range.clone()].to_string();
+        self.input_line.replace_range(range, "");
+        if self.kill_ring.len() >= KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(killed);
+        self.set_pos(start);
+    }
+
+    // replaces everything from start (a byte offset) to the cursor
+    fn replace_word(&mut self, start: usize, s: &str) {
+        let end = self.byte_pos();
+        self.input_line.replace_range(start..end, s);
+        self.set_pos(self.input_line[..start + s.len()].chars().count());
+    }
+
+    // the horizontal scroll position is relative to the start of the line
+    // that the cursor is on
     fn set_pos(&mut self, pos: usize) {
         self.pos = pos;
-        if self.pos < self.scroll || self.pos_width() > self.size.1 - 2 {
-            self.scroll = self.pos;
+        let col = self.pos - self.cursor_line().1;
+        if col < self.scroll || self.pos_width() > self.size.1 - 2 {
+            self.scroll = col;
             let mut extra_scroll = usize::from(self.size.1) / 2;
             while extra_scroll > 0 && self.scroll > 0 {
                 self.scroll -= 1;
-                extra_scroll -= self
-                    .input_line
-                    .chars()
-                    .nth(self.scroll)
-                    .unwrap()
-                    .width()
-                    .unwrap_or(1);
+                extra_scroll = extra_scroll.saturating_sub(
+                    self.current_line()
+                        .chars()
+                        .nth(self.scroll)
+                        .unwrap()
+                        .width()
+                        .unwrap_or(1),
+                );
             }
         }
     }
 
-    fn inc_pos(&mut self, inc: usize) {
-        self.set_pos(self.pos + inc);
+    // the index of the line that the cursor is on, and the position (in
+    // chars) where that line starts
+    fn cursor_line(&self) -> (usize, usize) {
+        let mut line = 0;
+        let mut start = 0;
+        for (i, c) in self.input_line.chars().take(self.pos).enumerate() {
+            if c == '\n' {
+                line += 1;
+                start = i + 1;
+            }
+        }
+        (line, start)
+    }
+
+    fn current_line(&self) -> &str {
+        let start = self.char_to_byte(self.cursor_line().1);
+        self.input_line[start..].split('\n').next().unwrap()
+    }
+
+    fn char_to_byte(&self, pos: usize) -> usize {
+        self.input_line
+            .char_indices()
+            .nth(pos)
+            .map_or(self.input_line.len(), |(i, _)| i)
+    }
+
+    // the char position of the start of the grapheme cluster before the
+    // cursor
+    fn prev_grapheme(&self) -> usize {
+        let before = &self.input_line[..self.byte_pos()];
+        before
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| before[..i].chars().count())
     }
 
-    fn dec_pos(&mut self, dec: usize) {
-        self.set_pos(self.pos - dec);
+    // the char position of the end of the grapheme cluster after the cursor
+    fn next_grapheme(&self) -> usize {
+        self.input_line[self.byte_pos()..]
+            .graphemes(true)
+            .next()
+            .map_or(self.pos, |g| self.pos + g.chars().count())
+    }
+
+    fn inc_pos(&mut self, inc: usize) {
+        self.set_pos(self.pos + inc);
     }
 
     fn pos_width(&self) -> u16 {
-        let start = self
-            .input_line
+        let line = self.current_line();
+        let col = self.pos - self.cursor_line().1;
+        let start = line
             .char_indices()
             .nth(self.scroll)
-            .map_or(self.input_line.len(), |(i, _)| i);
-        let end = self
-            .input_line
-            .char_indices()
-            .nth(self.pos)
-            .map_or(self.input_line.len(), |(i, _)| i);
-        self.input_line[start..end].width().try_into().unwrap()
+            .map_or(line.len(), |(i, _)| i);
+        let end = line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+        line[start..end].width().try_into().unwrap()
     }
 
     fn byte_pos(&self) -> usize {
-        self.input_line
-            .char_indices()
-            .nth(self.pos)
-            .map_or(self.input_line.len(), |(i, _)| i)
+        self.char_to_byte(self.pos)
     }
 
+    // the part of the line with the cursor which fits on the screen
     fn visible_input(&self) -> &str {
-        let start = self
-            .input_line
+        let line = self.current_line();
+        let start = line
             .char_indices()
             .nth(self.scroll)
-            .map_or(self.input_line.len(), |(i, _)| i);
-        let mut end = self.input_line.len();
+            .map_or(line.len(), |(i, _)| i);
+        let mut end = line.len();
         let mut width = 0;
-        for (i, c) in self.input_line.char_indices().skip(self.scroll) {
-            if width >= usize::from(self.size.1) - 2 {
+        for (i, c) in line.char_indices().skip(self.scroll) {
+            // a wide character which would only half fit is left off
+            // entirely
+            width += c.width().unwrap_or(0);
+            if width > usize::from(self.size.1) - 2 {
                 end = i;
                 break;
             }
-            width += c.width().unwrap_or(1);
         }
-        &self.input_line[start..end]
+        &line[start..end]
+    }
+}
+
+struct CompletionMenu {
+    completions: super::completion::Completions,
+    selected: Option<usize>,
+}
+
+struct Search {
+    query: String,
+    candidates: Vec<String>,
+    selected: usize,
+}
+
+impl Search {
+    fn matches(&self) -> Vec<&str> {
+        self.candidates
+            .iter()
+            .filter(|cmd| cmd.contains(&self.query))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+// whether the input ends inside a quote or an unfinished block
+fn incomplete(input: &str) -> bool {
+    let mut quote = None;
+    let mut escaped = false;
+    let mut comment = false;
+    for c in input.chars() {
+        if comment {
+            comment = c != '\n';
+            continue;
+        }
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (quote, c) {
+            (_, '\\') => escaped = true,
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            // comments run until the end of the line, and can contain
+            // anything
+            (None, '#') => comment = true,
+            _ => {}
+        }
+    }
+    if quote.is_some() {
+        return true;
+    }
+    crate::parse::ast::Commands::parse(input).map_or(false, |commands| {
+        let mut depth = 0_usize;
+        for command in commands.commands() {
+            match command {
+                crate::parse::ast::Command::If(_)
+                | crate::parse::ast::Command::While(_)
+                | crate::parse::ast::Command::For(..) => depth += 1,
+                crate::parse::ast::Command::End => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+        }
+        depth > 0
+    })
+}
+
+fn render_spans(
+    out: &mut impl textmode::Textmode,
+    spans: &[super::prompt::Span],
+    bgcolor: textmode::Color,
+) {
+    for span in spans {
+        out.reset_attributes();
+        out.set_bgcolor(bgcolor);
+        if let Some(color) = span.color {
+            out.set_fgcolor(color);
+        }
+        out.set_bold(span.bold);
+        out.write_str(&span.text);
     }
+    out.reset_attributes();
+    out.set_bgcolor(bgcolor);
+}
+
+pub fn truncate(s: &str, width: usize) -> &str {
+    let mut cur = 0;
+    for (i, c) in s.char_indices() {
+        cur += c.width().unwrap_or(0);
+        if cur > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+#[test]
+fn test_truncate() {
+    assert_eq!(truncate("hello", 3), "hel");
+    assert_eq!(truncate("hello", 10), "hello");
+    assert_eq!(truncate("日本語", 3), "日");
+    assert_eq!(truncate("日本語", 4), "日本");
+}
+
+#[test]
+fn test_incomplete() {
+    assert!(!incomplete("echo foo"));
+    assert!(incomplete("echo 'foo"));
+    assert!(incomplete("echo \"foo 'bar"));
+    assert!(!incomplete("echo \"foo\\\"\""));
+    assert!(incomplete("if true"));
+    assert!(incomplete("for i in a b c; echo $i"));
+    assert!(!incomplete("while true; echo; end"));
+    assert!(!incomplete("echo # don't"));
+    assert!(incomplete("if true # don't"));
+    assert!(incomplete("echo # don't\necho 'foo"));
+}
+
+#[test]
+fn test_graphemes() {
+    let mut readline = Readline::new();
+    readline.resize((24, 80));
+    readline.add_input("cafe\u{301} ");
+    readline.add_input("\u{1f469}\u{200d}\u{1f4bb}");
+    readline.cursor_left();
+    assert_eq!(readline.pos, 6);
+    readline.cursor_left();
+    readline.cursor_left();
+    assert_eq!(readline.pos, 3);
+    readline.cursor_right();
+    assert_eq!(readline.pos, 5);
+    readline.backspace();
+    assert_eq!(readline.input(), "caf \u{1f469}\u{200d}\u{1f4bb}");
+    readline.cursor_right();
+    readline.cursor_right();
+    readline.backspace();
+    assert_eq!(readline.input(), "caf ");
+    readline.add_input("e");
+    readline.add_input("\u{301}");
+    assert_eq!(readline.pos, 6);
+    readline.cursor_left();
+    assert_eq!(readline.pos, 4);
+}
+
+#[test]
+fn test_lines() {
+    let mut readline = Readline::new();
+    readline.resize((24, 80));
+    readline.set_input("if true".to_string());
+    assert!(readline.continue_line());
+    readline.add_input("echo foo");
+    assert_eq!(readline.input(), "if true\necho foo");
+    assert_eq!(readline.lines(), 3);
+    assert!(readline.cursor_up());
+    assert!(!readline.cursor_up());
+    assert_eq!(readline.input_before_cursor(), "if true");
+    assert!(readline.cursor_down());
+    assert!(!readline.cursor_down());
+    assert_eq!(readline.input_before_cursor(), "if true\necho fo");
+    readline.set_input("ls \\".to_string());
+    assert!(readline.continue_line());
+    assert_eq!(readline.input(), "ls \n");
+}
+
+#[test]
+fn test_words() {
+    let mut readline = Readline::new();
+    readline.resize((24, 80));
+    readline.set_input("git commit --amend".to_string());
+    readline.word_left();
+    assert_eq!(readline.input_before_cursor(), "git commit --");
+    readline.word_left();
+    assert_eq!(readline.input_before_cursor(), "git ");
+    readline.word_right();
+    assert_eq!(readline.input_before_cursor(), "git commit");
+    readline.cursor_home();
+    readline.word_right();
+    assert_eq!(readline.input_before_cursor(), "git");
+
+    readline.cursor_end();
+    readline.kill_word_backwards();
+    assert_eq!(readline.input(), "git commit ");
+    readline.cursor_home();
+    readline.kill_to_end();
+    assert_eq!(readline.input(), "");
+    readline.yank();
+    assert_eq!(readline.input(), "git commit ");
+    readline.clear_backwards();
+    assert_eq!(readline.input(), "");
 }
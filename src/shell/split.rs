@@ -0,0 +1,106 @@
+use crate::shell::prelude::*;
+
+// shows two entries at once, each with its pty resized to fit its half of
+// the terminal, so that one can be watched while typing into the other
+pub struct Split {
+    entries: (usize, usize),
+    side_by_side: bool,
+}
+
+impl Split {
+    pub fn new(first: usize, second: usize) -> Self {
+        Self {
+            entries: (first, second),
+            side_by_side: true,
+        }
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.entries.0 == idx || self.entries.1 == idx
+    }
+
+    pub fn other(&self, idx: usize) -> usize {
+        if self.entries.0 == idx {
+            self.entries.1
+        } else {
+            self.entries.0
+        }
+    }
+
+    pub fn toggle_orientation(&mut self, history: &super::history::History) {
+        self.side_by_side = !self.side_by_side;
+        self.resize(history);
+    }
+
+    // needs to be called again whenever the terminal is resized, since the
+    // history resizes every entry to the full terminal
+    pub fn resize(&self, history: &super::history::History) {
+        let [first, second] = self.panes(history.size());
+        history.entry(self.entries.0).resize(first.1);
+        history.entry(self.entries.1).resize(second.1);
+    }
+
+    // puts the entries back to the size that everything else expects
+    pub fn close(self, history: &super::history::History) {
+        history.entry(self.entries.0).resize(history.size());
+        history.entry(self.entries.1).resize(history.size());
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        history: &super::history::History,
+        focus: usize,
+    ) {
+        let size = out.screen().size();
+        let [first, second] = self.panes(size);
+
+        out.set_fgcolor(textmode::color::DARKGREY);
+        if self.side_by_side {
+            for row in 0..size.0 {
+                out.move_to(row, first.1 .1);
+                out.write_str("│");
+            }
+        } else {
+            out.move_to(first.1 .0, 0);
+            out.write_str(&"─".repeat(usize::from(size.1)));
+        }
+        out.reset_attributes();
+
+        // the focused entry goes last, so that it ends up with the cursor
+        let panes = [(self.entries.0, first.0), (self.entries.1, second.0)];
+        for (idx, pos) in panes.iter().filter(|(idx, _)| *idx != focus) {
+            history.entry(*idx).render_pane(out, *pos, false);
+        }
+        for (idx, pos) in panes.iter().filter(|(idx, _)| *idx == focus) {
+            history.entry(*idx).render_pane(out, *pos, true);
+        }
+    }
+
+    // the position and size of each pane, leaving a line between them
+    fn panes(&self, size: (u16, u16)) -> [((u16, u16), (u16, u16)); 2] {
+        if self.side_by_side {
+            let left = size.1.saturating_sub(1) / 2;
+            let right = size.1.saturating_sub(1) - left;
+            [((0, 0), (size.0, left)), ((0, left + 1), (size.0, right))]
+        } else {
+            let top = size.0.saturating_sub(1) / 2;
+            let bottom = size.0.saturating_sub(1) - top;
+            [((0, 0), (top, size.1)), ((top + 1, 0), (bottom, size.1))]
+        }
+    }
+}
+
+#[test]
+fn test_panes() {
+    let mut split = Split::new(0, 1);
+    assert_eq!(
+        split.panes((24, 80)),
+        [((0, 0), (24, 39)), ((0, 40), (24, 40))]
+    );
+    split.side_by_side = false;
+    assert_eq!(
+        split.panes((24, 80)),
+        [((0, 0), (11, 80)), ((12, 0), (12, 80))]
+    );
+}
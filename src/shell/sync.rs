@@ -0,0 +1,181 @@
+use crate::shell::prelude::*;
+
+// pulling happens before the shell starts up, so an unreachable remote
+// shouldn't be able to hold that up for long
+const GIT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+// keeps the history file in sync with a copy in a directory that is shared
+// between machines, either by being a git checkout or by some external tool
+// like rsync or syncthing. the history file is append-only, so the copies
+// can always be merged by taking the union of their entries.
+pub struct Sync {
+    dir: std::path::PathBuf,
+}
+
+impl Sync {
+    pub fn new(dir: &std::path::Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    // brings in entries from other machines, before the history is loaded
+    pub async fn pull(&self) -> Result<()> {
+        if self.is_git() {
+            self.git(&["pull", "--quiet", "--no-rebase"]).await?;
+        }
+        merge_into(&self.shared_file(), &crate::dirs::history_file()).await
+    }
+
+    // publishes entries from this machine, after the history is saved
+    pub async fn push(&self) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        merge_into(&crate::dirs::history_file(), &self.shared_file()).await?;
+        if self.is_git() {
+            // lets git merge concurrent pushes from different machines
+            // without conflicts
+            let attributes = self.dir.join(".gitattributes");
+            if tokio::fs::metadata(&attributes).await.is_err() {
+                tokio::fs::write(&attributes, "history merge=union\n")
+                    .await?;
+            }
+            self.git(&["add", "history", ".gitattributes"]).await?;
+            // committing fails if nothing changed, which is fine
+            #[allow(clippy::let_underscore_drop)]
+            let _ =
+                self.git(&["commit", "--quiet", "-m", "nbsh history"]).await;
+            self.git(&["pull", "--quiet", "--no-rebase"]).await?;
+            self.git(&["push", "--quiet"]).await?;
+        }
+        Ok(())
+    }
+
+    fn shared_file(&self) -> std::path::PathBuf {
+        self.dir.join("history")
+    }
+
+    fn is_git(&self) -> bool {
+        self.dir.join(".git").exists()
+    }
+
+    async fn git(&self, args: &[&str]) -> Result<()> {
+        let status = tokio::process::Command::new("git")
+            .arg("-C")
+            .arg(&self.dir)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true)
+            .status();
+        let status = tokio::time::timeout(GIT_TIMEOUT, status)
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "git {} timed out in {}",
+                    args[0],
+                    crate::format::path(&self.dir)
+                )
+            })??;
+        if !status.success() {
+            return Err(anyhow!(
+                "git {} failed in {}",
+                args[0],
+                crate::format::path(&self.dir)
+            ));
+        }
+        Ok(())
+    }
+}
+
+// adds any entries from `from` that `to` doesn't have yet
+async fn merge_into(
+    from: &std::path::Path,
+    to: &std::path::Path,
+) -> Result<()> {
+    let from = read(from).await?;
+    let existing = read(to).await?;
+    let merged = merge(&existing, &from);
+    if merged != existing {
+        if let Some(parent) = to.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // written alongside and then moved into place, so that the history
+        // is never left half written
+        let mut tmp = to.as_os_str().to_os_string();
+        tmp.push(".tmp");
+        tokio::fs::write(&tmp, merged).await?;
+        tokio::fs::rename(&tmp, to).await?;
+    }
+    Ok(())
+}
+
+async fn read(path: &std::path::Path) -> Result<String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(String::new())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// the union of the entries in both histories, ordered by start time. the
+// order within each history is kept for entries with the same start time.
+fn merge(a: &str, b: &str) -> String {
+    let mut seen = std::collections::HashSet::new();
+    let mut entries: Vec<_> = entries(a)
+        .into_iter()
+        .chain(entries(b))
+        .filter(|entry| seen.insert(*entry))
+        .collect();
+    entries.sort_by_key(|entry| start_time(entry));
+    entries
+        .into_iter()
+        .map(|entry| format!("{}\n", entry))
+        .collect()
+}
+
+// multi-line commands continue onto the next line after a backslash
+fn entries(history: &str) -> Vec<&str> {
+    let mut entries = vec![];
+    let mut start = 0;
+    let mut pos = 0;
+    for line in history.split_inclusive('\n') {
+        pos += line.len();
+        if !line.trim_end_matches('\n').ends_with('\\') {
+            let entry = history[start..pos].trim_end_matches('\n');
+            if !entry.is_empty() {
+                entries.push(entry);
+            }
+            start = pos;
+        }
+    }
+    let rest = history[start..].trim_end_matches('\n');
+    if !rest.is_empty() {
+        entries.push(rest);
+    }
+    entries
+}
+
+// entries in the extended format start with ": <start time>:"
+fn start_time(entry: &str) -> u64 {
+    entry
+        .strip_prefix(": ")
+        .and_then(|rest| rest.split(':').next())
+        .and_then(|time| time.parse().ok())
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_merge() {
+    let a = ": 100:0.5:0;ls\n: 300:1.0:0;make\n";
+    let b = ": 100:0.5:0;ls\n: 200:0.1:1;false\n: 400:0.0:0;echo \\\nfoo\n";
+    assert_eq!(
+        merge(a, b),
+        ": 100:0.5:0;ls\n: 200:0.1:1;false\n: 300:1.0:0;make\n\
+         : 400:0.0:0;echo \\\nfoo\n"
+    );
+    assert_eq!(merge(a, a), a);
+    assert_eq!(merge("", a), a);
+}
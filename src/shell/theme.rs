@@ -0,0 +1,337 @@
+use crate::shell::prelude::*;
+
+// the colors used to show the state of an entry. exit statuses also get a
+// glyph, so that the colors are never the only way to tell them apart.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    // based on the okabe-ito palette, which stays distinguishable with the
+    // common forms of color blindness
+    ColorBlind,
+    // the original red/magenta colors
+    Classic,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::ColorBlind
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Success,
+    Failure,
+    Signaled,
+    Stopped,
+    Suspended,
+    Waiting,
+}
+
+impl Status {
+    pub fn from_exit_status(status: std::process::ExitStatus) -> Self {
+        if status.signal().is_some() {
+            Self::Signaled
+        } else if status.success() {
+            Self::Success
+        } else {
+            Self::Failure
+        }
+    }
+
+    // these need to all be a single column wide, since the status field
+    // has a fixed width
+    pub fn glyph(self) -> char {
+        match self {
+            Self::Success => '✓',
+            Self::Failure => '✗',
+            Self::Signaled => '↯',
+            Self::Stopped | Self::Suspended | Self::Waiting => ' ',
+        }
+    }
+}
+
+impl Theme {
+    pub fn status_color(self, status: Status) -> textmode::Color {
+        match (self, status) {
+            // successful commands are the common case, so they shouldn't
+            // draw attention to themselves
+            (_, Status::Success) => textmode::color::DARKGREY,
+            (Self::ColorBlind, Status::Failure) => rgb(0xe6, 0x9f, 0x00),
+            (Self::ColorBlind, Status::Signaled | Status::Waiting) => {
+                rgb(0xcc, 0x79, 0xa7)
+            }
+            (Self::ColorBlind, Status::Stopped) => rgb(0xf0, 0xe4, 0x42),
+            (Self::ColorBlind, Status::Suspended) => rgb(0x56, 0xb4, 0xe9),
+            (Self::Classic, Status::Failure) => textmode::color::RED,
+            (Self::Classic, Status::Signaled | Status::Waiting) => {
+                textmode::color::MAGENTA
+            }
+            (Self::Classic, Status::Stopped) => textmode::color::YELLOW,
+            (Self::Classic, Status::Suspended) => textmode::color::BLUE,
+        }
+    }
+
+    // for the marker showing whether a rerun's output matched the previous
+    // run. matching is what you'd usually expect, so it stays quiet.
+    pub fn reproduced_color(self, same: bool) -> textmode::Color {
+        if same {
+            textmode::color::DARKGREY
+        } else {
+            self.status_color(Status::Stopped)
+        }
+    }
+
+    // unlike in the entry list, successful runs need to stand out in places
+    // like the sparkline where there is no room for glyphs
+    pub fn success_color(self) -> textmode::Color {
+        match self {
+            Self::ColorBlind => rgb(0x00, 0x72, 0xb2),
+            Self::Classic => textmode::color::GREEN,
+        }
+    }
+}
+
+// the background colors that the ui is drawn with. unlike the theme, this
+// can be changed while the shell is running (with the colors builtin), so
+// it is stored in the environment.
+#[derive(serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    Dark,
+    Light,
+    Solarized,
+}
+
+impl Default for ColorScheme {
+    fn default() -> Self {
+        Self::Dark
+    }
+}
+
+impl std::str::FromStr for ColorScheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|scheme| scheme.name() == s)
+            .ok_or_else(|| anyhow!("unknown color scheme {}", s))
+    }
+}
+
+impl ColorScheme {
+    pub const ALL: &'static [Self] =
+        &[Self::Dark, Self::Light, Self::Solarized];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Dark => "dark",
+            Self::Light => "light",
+            Self::Solarized => "solarized",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx =
+            Self::ALL.iter().position(|scheme| *scheme == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    // the focused entry, and the selected row in lists
+    pub fn highlight(self) -> textmode::Color {
+        match self {
+            Self::Dark => bg((0x56, 0x1b, 0x8b), 5, 5),
+            Self::Light => bg((0xc9, 0xb3, 0xe6), 14, 5),
+            Self::Solarized => bg((0x6c, 0x71, 0xc4), 4, 4),
+        }
+    }
+
+    // every other entry gets this instead of the normal background, so that
+    // it is clear where one ends and the next begins
+    pub fn alternate(self) -> textmode::Color {
+        match self {
+            Self::Dark => bg((0x24, 0x21, 0x00), 0, 4),
+            Self::Light => bg((0xf5, 0xf0, 0xd8), 15, 3),
+            Self::Solarized => bg((0x00, 0x2b, 0x36), 0, 6),
+        }
+    }
+
+    pub fn background(self) -> textmode::Color {
+        match self {
+            Self::Dark => bg((0x20, 0x20, 0x20), 8, 0),
+            Self::Light => bg((0xe8, 0xe8, 0xe8), 7, 7),
+            Self::Solarized => bg((0x07, 0x36, 0x42), 8, 0),
+        }
+    }
+
+    pub fn bell(self) -> textmode::Color {
+        match self {
+            Self::Dark => bg((0x40, 0x10, 0x10), 1, 1),
+            Self::Light => bg((0xf2, 0xc4, 0xc4), 9, 1),
+            Self::Solarized => bg((0x5c, 0x1f, 0x1e), 1, 1),
+        }
+    }
+
+    // the part of a command line that is currently running
+    pub fn running(self) -> textmode::Color {
+        match self {
+            Self::Dark => bg((0x10, 0x40, 0x10), 2, 2),
+            Self::Light => bg((0xc4, 0xe8, 0xc4), 10, 2),
+            Self::Solarized => bg((0x3b, 0x45, 0x00), 2, 2),
+        }
+    }
+}
+
+// how many colors the terminal can show. anything less than truecolor gets
+// the closest color it does have instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Depth {
+    Colors8,
+    Colors16,
+    Colors256,
+    TrueColor,
+}
+
+static DEPTH: once_cell::sync::Lazy<Depth> =
+    once_cell::sync::Lazy::new(|| {
+        depth(
+            std::env::var("COLORTERM").ok().as_deref(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    });
+
+// there isn't a terminfo capability for truecolor, so this goes by the
+// names that terminals conventionally use instead
+fn depth(colorterm: Option<&str>, term: Option<&str>) -> Depth {
+    if matches!(colorterm, Some("truecolor" | "24bit")) {
+        return Depth::TrueColor;
+    }
+    let term = term.unwrap_or("dumb");
+    if term.contains("256color") || term.contains("direct") {
+        Depth::Colors256
+    } else if term.contains("16color")
+        || term.starts_with("xterm")
+        || term.starts_with("rxvt")
+    {
+        Depth::Colors16
+    } else {
+        Depth::Colors8
+    }
+}
+
+// for foreground colors, where the closest match is good enough
+fn rgb(r: u8, g: u8, b: u8) -> textmode::Color {
+    match *DEPTH {
+        Depth::TrueColor => textmode::Color::Rgb(r, g, b),
+        Depth::Colors256 => textmode::Color::Idx(to_256(r, g, b)),
+        Depth::Colors16 => textmode::Color::Idx(to_basic(r, g, b, 16)),
+        Depth::Colors8 => textmode::Color::Idx(to_basic(r, g, b, 8)),
+    }
+}
+
+// the backgrounds are mostly subtle tints which would all end up as black
+// if they were matched in the same way, so each one picks which of the
+// basic colors to use instead. only the first 8 of those work as a
+// background on 8-color terminals, and the bright ones don't map onto
+// those in a way that keeps the roles apart, so those are picked
+// separately.
+fn bg((r, g, b): (u8, u8, u8), basic16: u8, basic8: u8) -> textmode::Color {
+    match *DEPTH {
+        Depth::TrueColor | Depth::Colors256 => rgb(r, g, b),
+        Depth::Colors16 => textmode::Color::Idx(basic16),
+        Depth::Colors8 => textmode::Color::Idx(basic8),
+    }
+}
+
+// the default xterm palette
+const BASIC_COLORS: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+fn to_basic(r: u8, g: u8, b: u8, count: usize) -> u8 {
+    let d = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    let idx = BASIC_COLORS[..count]
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, (r2, g2, b2))| d(r, *r2) + d(g, *g2) + d(b, *b2))
+        .unwrap()
+        .0;
+    idx.try_into().unwrap()
+}
+
+// the 6x6x6 color cube starts at 16, and the grayscale ramp (which is
+// finer grained, so better for the mostly gray backgrounds) at 232
+fn to_256(r: u8, g: u8, b: u8) -> u8 {
+    let cube = |c: u8| (u16::from(c) * 5 + 127) / 255;
+    let cube_level = |c: u16| if c == 0 { 0 } else { c * 40 + 55 };
+    let dist = |(r2, g2, b2): (u16, u16, u16)| {
+        let d = |a: u8, b: u16| u32::from(u16::from(a).abs_diff(b)).pow(2);
+        d(r, r2) + d(g, g2) + d(b, b2)
+    };
+
+    let (cr, cg, cb) = (cube(r), cube(g), cube(b));
+    let cube_idx = 16 + 36 * cr + 6 * cg + cb;
+    let cube_dist = dist((cube_level(cr), cube_level(cg), cube_level(cb)));
+
+    let avg = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    let gray = (avg.saturating_sub(3) / 10).min(23);
+    let gray_level = gray * 10 + 8;
+    let gray_dist = dist((gray_level, gray_level, gray_level));
+
+    if gray_dist < cube_dist {
+        (232 + gray).try_into().unwrap()
+    } else {
+        cube_idx.try_into().unwrap()
+    }
+}
+
+#[test]
+fn test_to_256() {
+    assert_eq!(to_256(0, 0, 0), 16);
+    assert_eq!(to_256(0xff, 0xff, 0xff), 231);
+    assert_eq!(to_256(0xff, 0, 0), 196);
+    assert_eq!(to_256(0x20, 0x20, 0x20), 234);
+}
+
+#[test]
+fn test_to_basic() {
+    assert_eq!(to_basic(0xe6, 0x9f, 0x00, 16), 3);
+    assert_eq!(to_basic(0xcc, 0x79, 0xa7, 16), 8);
+    assert_eq!(to_basic(0xcc, 0x79, 0xa7, 8), 5);
+    assert_eq!(to_basic(0x56, 0xb4, 0xe9, 8), 6);
+}
+
+#[test]
+fn test_depth() {
+    assert_eq!(depth(Some("truecolor"), Some("linux")), Depth::TrueColor);
+    assert_eq!(depth(None, Some("xterm-256color")), Depth::Colors256);
+    assert_eq!(depth(None, Some("xterm")), Depth::Colors16);
+    assert_eq!(depth(None, Some("linux")), Depth::Colors8);
+    assert_eq!(depth(None, None), Depth::Colors8);
+}
+
+#[test]
+fn test_color_scheme_names() {
+    for scheme in ColorScheme::ALL {
+        assert_eq!(scheme.name().parse::<ColorScheme>().unwrap(), *scheme);
+    }
+    assert!("neon".parse::<ColorScheme>().is_err());
+    assert_eq!(ColorScheme::Solarized.next(), ColorScheme::Dark);
+}
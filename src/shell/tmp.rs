@@ -0,0 +1,81 @@
+use crate::shell::prelude::*;
+
+// every file that nbsh writes out temporarily (evicted output, etc) goes
+// into a directory specific to the current shell process, so that it can all
+// be removed at once when the shell exits
+pub fn path(name: &str) -> Result<std::path::PathBuf> {
+    let dir = session_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir.join(name))
+}
+
+fn session_dir() -> std::path::PathBuf {
+    crate::dirs::tmp_dir().join(crate::info::pid())
+}
+
+// records the process group of a running entry, for the kill builtin to find
+pub fn set_job(idx: usize, pg: nix::unistd::Pid) -> std::io::Result<()> {
+    write_job(idx, &pg.as_raw().to_string())
+}
+
+// replaces the process group with the exit status once the entry is done,
+// for the wait builtin to find
+pub fn finish_job(
+    idx: usize,
+    status: std::process::ExitStatus,
+) -> std::io::Result<()> {
+    write_job(idx, &format!("exited {}", crate::status::to_raw(status)))
+}
+
+// the commands numbered the way that !N sees them, for the history builtin
+pub fn set_history(cmds: &[String]) -> Result<()> {
+    let path = crate::dirs::history_list_file(&crate::info::pid());
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, serde_json::to_string(cmds)?)?;
+    Ok(())
+}
+
+fn write_job(idx: usize, contents: &str) -> std::io::Result<()> {
+    let path = crate::dirs::job_file(&crate::info::pid(), idx + 1);
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(path, contents)
+}
+
+// removes the temporary files for this shell when dropped, including when
+// unwinding from a panic
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        #[allow(clippy::let_underscore_drop)]
+        let _ = std::fs::remove_dir_all(session_dir());
+    }
+}
+
+// shells which crashed or were killed don't get a chance to clean up after
+// themselves, so remove the directories belonging to any process which is no
+// longer running. if the pid has since been reused, the directory will just
+// stick around until the next time this runs.
+pub fn collect_garbage() -> Result<()> {
+    let entries = match std::fs::read_dir(crate::dirs::tmp_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let pid = if let Some(pid) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            pid
+        } else {
+            continue;
+        };
+        if !crate::info::running(pid) {
+            std::fs::remove_dir_all(entry.path())?;
+        }
+    }
+    Ok(())
+}
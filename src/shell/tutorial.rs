@@ -0,0 +1,91 @@
+use crate::shell::prelude::*;
+
+// the guided tour started by nbsh --tutorial. each step says what to do
+// next, and moves on once the shell notices that it has been done.
+pub struct Tutorial {
+    step: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    RunCommand,
+    EnterEscape,
+    Scroll,
+    Fullscreen,
+    Inspect,
+    Return,
+}
+
+const STEPS: &[(Goal, &str)] = &[
+    (
+        Goal::RunCommand,
+        "type a command (like ls) and press enter to run it",
+    ),
+    (
+        Goal::EnterEscape,
+        "press ctrl-e to switch to escape mode, where keys act on the \
+         history",
+    ),
+    (Goal::Scroll, "press k or up to select an earlier entry"),
+    (
+        Goal::Fullscreen,
+        "press f to show the selected entry fullscreen, then ctrl-e f to \
+         go back",
+    ),
+    (
+        Goal::Inspect,
+        "press ctrl-e I to inspect the selected entry, and q to close it",
+    ),
+    (Goal::Return, "press r to go back to typing commands"),
+];
+
+impl Tutorial {
+    pub fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    // None once every step is done
+    pub fn goal(&self) -> Option<Goal> {
+        STEPS.get(self.step).map(|(goal, _)| *goal)
+    }
+
+    pub fn advance(&mut self) {
+        self.step += 1;
+    }
+
+    pub fn render(
+        &self,
+        out: &mut impl textmode::Textmode,
+        colors: crate::shell::ColorScheme,
+    ) {
+        let text = if let Some((_, text)) = STEPS.get(self.step) {
+            text
+        } else {
+            return;
+        };
+        let text =
+            format!(" tutorial {}/{}: {}", self.step + 1, STEPS.len(), text);
+        let width = usize::from(out.screen().size().1);
+        let pos = out.screen().cursor_position();
+        out.move_to(0, 0);
+        out.set_bgcolor(colors.highlight());
+        out.set_fgcolor(textmode::color::WHITE);
+        out.write(b"\x1b[K");
+        out.write_str(&text[..text.len().min(width)]);
+        out.reset_attributes();
+        out.move_to(pos.0, pos.1);
+    }
+}
+
+#[test]
+fn test_steps() {
+    let mut tutorial = Tutorial::new();
+    let mut goals = vec![];
+    while let Some(goal) = tutorial.goal() {
+        goals.push(goal);
+        tutorial.advance();
+    }
+    assert_eq!(goals.len(), STEPS.len());
+    assert_eq!(goals.first(), Some(&Goal::RunCommand));
+    assert_eq!(goals.last(), Some(&Goal::Return));
+}
@@ -0,0 +1,76 @@
+use crate::prelude::*;
+
+// std::process::ExitStatus is a raw wait status, which stores the exit code
+// in the second byte and the terminating signal (if any) in the low bits.
+// these helpers are the only place that encoding should need to be known.
+
+pub fn exited(code: i32) -> std::process::ExitStatus {
+    std::process::ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+pub fn signaled(signal: i32) -> std::process::ExitStatus {
+    signaled_core(signal, false)
+}
+
+// whether a signal dumped core is kept in the 0x80 bit
+pub fn signaled_core(
+    signal: i32,
+    core_dumped: bool,
+) -> std::process::ExitStatus {
+    std::process::ExitStatus::from_raw(
+        (signal & 0x7f) | if core_dumped { 0x80 } else { 0 },
+    )
+}
+
+pub fn success() -> std::process::ExitStatus {
+    exited(0)
+}
+
+pub fn failure() -> std::process::ExitStatus {
+    exited(1)
+}
+
+// the inverse of from_raw, for passing statuses around as plain integers
+pub fn to_raw(status: std::process::ExitStatus) -> i32 {
+    status.signal().map_or_else(
+        || exited(status.code().unwrap_or(0)).into_raw(),
+        |signal| signaled_core(signal, status.core_dumped()).into_raw(),
+    )
+}
+
+pub fn from_raw(raw: i32) -> std::process::ExitStatus {
+    std::process::ExitStatus::from_raw(raw)
+}
+
+// the value exposed as $? and used as our own exit code, which follows the
+// usual shell convention of reporting death by signal as 128 + the signal
+// number
+pub fn code(status: std::process::ExitStatus) -> i32 {
+    status
+        .signal()
+        .map_or_else(|| status.code().unwrap_or(0), |signal| signal + 128)
+}
+
+#[test]
+fn test_status() {
+    assert!(success().success());
+    assert_eq!(code(success()), 0);
+    assert_eq!(code(failure()), 1);
+    assert_eq!(code(exited(42)), 42);
+    assert_eq!(exited(42).code(), Some(42));
+    assert_eq!(code(signaled(9)), 137);
+    assert_eq!(signaled(9).signal(), Some(9));
+    assert_eq!(signaled(9).code(), None);
+    assert!(from_raw(0x80 | 11).core_dumped());
+    assert_eq!(signaled_core(11, true), from_raw(0x80 | 11));
+    assert!(!signaled_core(11, false).core_dumped());
+    for status in [
+        success(),
+        failure(),
+        exited(255),
+        signaled(15),
+        from_raw(0x80 | 11),
+    ] {
+        assert_eq!(from_raw(to_raw(status)), status);
+    }
+}